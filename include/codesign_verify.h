@@ -0,0 +1,76 @@
+/*
+ * C interface to codesign-verify, built with the `ffi` feature:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Every function returning a pointer hands over ownership: contexts are released with
+ * csv_context_free and strings with csv_string_free. Strings are UTF-8 and NUL terminated.
+ * Status codes and functions are only ever added, never changed or removed.
+ */
+
+#ifndef CODESIGN_VERIFY_H
+#define CODESIGN_VERIFY_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by the csv_verify_* functions */
+#define CSV_OK 0                  /* The signature is valid */
+#define CSV_ERROR 1               /* Any other failure, see csv_last_error */
+#define CSV_UNSIGNED 2            /* The code isn't signed */
+#define CSV_TAMPERED 3            /* The code was modified after it was signed */
+#define CSV_UNTRUSTED_ROOT 4      /* The certificate chain doesn't end at a trusted root */
+#define CSV_EXPIRED 5             /* A certificate in the chain is expired */
+#define CSV_REVOKED 6             /* A certificate in the chain was revoked */
+#define CSV_REQUIREMENT_FAILED 7  /* The signature is valid, but doesn't satisfy the requirement */
+#define CSV_INVALID_ARGUMENT 8    /* A required pointer was null, or a string wasn't UTF-8 */
+#define CSV_INTERNAL_ERROR 9      /* The library failed internally */
+#define CSV_SIGNATURE_STRIPPED 10 /* The code was signed, but its signature was removed or cut short */
+
+/* Fields of a certificate name, for csv_context_subject and csv_context_issuer */
+#define CSV_NAME_COMMON_NAME 0
+#define CSV_NAME_ORGANIZATION 1
+#define CSV_NAME_ORGANIZATION_UNIT 2
+#define CSV_NAME_COUNTRY 3
+
+/* A verified signature */
+typedef struct csv_context csv_context;
+
+/*
+ * Verify the file or bundle at path, against requirement unless it's NULL.
+ * On success stores a context in *out and returns CSV_OK, otherwise stores NULL.
+ */
+int32_t csv_verify_path(const char *path, const char *requirement, csv_context **out);
+
+/*
+ * Verify the running process with the given PID, against requirement unless it's NULL.
+ * On success stores a context in *out and returns CSV_OK, otherwise stores NULL.
+ */
+int32_t csv_verify_pid(int32_t pid, const char *requirement, csv_context **out);
+
+/* A field of the leaf certificate's subject, NULL when absent */
+char *csv_context_subject(const csv_context *ctx, int32_t field);
+
+/* A field of the leaf certificate's issuer, NULL when absent */
+char *csv_context_issuer(const csv_context *ctx, int32_t field);
+
+/* The sha256 thumbprint of the leaf certificate, in hex */
+char *csv_context_thumbprint(const csv_context *ctx);
+
+/* Release a context, NULL is ignored */
+void csv_context_free(csv_context *ctx);
+
+/* The message of the last failure on the calling thread, NULL if there was none */
+char *csv_last_error(void);
+
+/* Release a string returned by the library, NULL is ignored */
+void csv_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CODESIGN_VERIFY_H */
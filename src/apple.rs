@@ -0,0 +1,166 @@
+//!
+//! Telling the kinds of Apple-issued signing certificates apart.
+//!
+//! Apple's CAs mark each kind of certificate they issue with an extension under
+//! `1.2.840.113635.100.6`, which is what designated requirements such as
+//! `certificate leaf[field.1.2.840.113635.100.6.1.13]` match on. The markers only mean anything
+//! in a chain that ends at one of Apple's roots, anyone can put them in a certificate of their own.
+//!
+
+use crate::{der, digest, AppleMarker, SignerKind};
+
+/// The sha256 thumbprints of the roots Apple issues signing certificates from
+const APPLE_ROOTS: &[&str] = &[
+    "b0b1730ecbc7ff4505142c49f1295e6eda6bcaed7e2c68c5be91b5a11001f024", // Apple Root CA
+    "c2b9b042dd57830e7d117dac55ac8ae19407d38e41d88f3215bc3a890444a050", // Apple Root CA - G2
+    "63343abfb89a6a03ebb57e9b3f5fa7be7c4f5c756f3017b3a8c488c3653e9179", // Apple Root CA - G3
+];
+
+/// The markers of leaf certificates
+const LEAF_MARKERS: &[AppleMarker] = &[
+    AppleMarker::DeveloperIdApplication,
+    AppleMarker::DeveloperIdInstaller,
+    AppleMarker::DeveloperIdKext,
+    AppleMarker::MacAppStore,
+    AppleMarker::MacDevelopment,
+    AppleMarker::IPhoneDevelopment,
+];
+
+/// Whether the certificate carries the extension
+fn has_marker(certificate: &[u8], marker: AppleMarker) -> bool {
+    let oid = der::oid(marker.oid());
+    der::extensions(certificate)
+        .iter()
+        .any(|extension| extension.oid == oid.as_slice())
+}
+
+/// Whether the chain ends at one of Apple's roots
+fn is_apple_anchored(chain: &[Vec<u8>]) -> bool {
+    chain.last().map_or(false, |root| {
+        APPLE_ROOTS.contains(&digest::hex(&digest::sha256(root)).as_str())
+    })
+}
+
+/// The markers in a chain, leaf first, that ends at one of Apple's roots
+pub(crate) fn markers(chain: &[Vec<u8>]) -> Vec<AppleMarker> {
+    if !is_apple_anchored(chain) {
+        return Vec::new();
+    }
+
+    let mut markers = Vec::new();
+    if let Some(leaf) = chain.first() {
+        markers.extend(
+            LEAF_MARKERS
+                .iter()
+                .copied()
+                .filter(|marker| has_marker(leaf, *marker)),
+        );
+    }
+    if let Some(issuer) = chain.get(1) {
+        if has_marker(issuer, AppleMarker::DeveloperIdCa) {
+            markers.push(AppleMarker::DeveloperIdCa);
+        }
+    }
+    markers
+}
+
+/// Classify a macOS signature from its certificate chain, leaf first, and whether it has a
+/// platform identifier
+pub(crate) fn signer_kind(chain: &[Vec<u8>], platform_binary: bool) -> Option<SignerKind> {
+    let leaf = match chain.first() {
+        Some(leaf) => leaf,
+        None => return Some(SignerKind::AdHoc),
+    };
+    if platform_binary {
+        return Some(SignerKind::ApplePlatform);
+    }
+    if !is_apple_anchored(chain) {
+        return None;
+    }
+
+    LEAF_MARKERS
+        .iter()
+        .find(|marker| has_marker(leaf, **marker))
+        .and_then(|marker| match marker {
+            AppleMarker::DeveloperIdApplication
+            | AppleMarker::DeveloperIdInstaller
+            | AppleMarker::DeveloperIdKext => Some(SignerKind::DeveloperId),
+            AppleMarker::MacAppStore => Some(SignerKind::MacAppStore),
+            AppleMarker::MacDevelopment | AppleMarker::IPhoneDevelopment => {
+                Some(SignerKind::AppleDevelopment)
+            }
+            AppleMarker::DeveloperIdCa => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::der::{encode, EXTENSIONS, OBJECT_IDENTIFIER, OCTET_STRING, SEQUENCE};
+
+    /// A certificate that is only well formed enough to carry the extensions
+    fn certificate(markers: &[AppleMarker]) -> Vec<u8> {
+        let extensions: Vec<u8> = markers
+            .iter()
+            .flat_map(|marker| {
+                let fields = [
+                    encode(OBJECT_IDENTIFIER, &der::oid(marker.oid())),
+                    encode(OCTET_STRING, &[0x05, 0x00]),
+                ];
+                encode(SEQUENCE, &fields.concat())
+            })
+            .collect();
+        let tbs = [
+            vec![0x02, 0x01, 0x01],
+            encode(EXTENSIONS, &encode(SEQUENCE, &extensions)),
+        ]
+        .concat();
+        encode(SEQUENCE, &encode(SEQUENCE, &tbs))
+    }
+
+    #[test]
+    fn test_signer_kind() {
+        let leaf = certificate(&[AppleMarker::DeveloperIdApplication]);
+        assert!(has_marker(&leaf, AppleMarker::DeveloperIdApplication));
+        assert!(!has_marker(&leaf, AppleMarker::MacAppStore));
+
+        assert_eq!(signer_kind(&[], false), Some(SignerKind::AdHoc));
+        assert_eq!(
+            signer_kind(&[leaf.clone()], true),
+            Some(SignerKind::ApplePlatform)
+        );
+        // The marker alone isn't enough without an Apple root
+        let chain = [leaf, certificate(&[AppleMarker::DeveloperIdCa])];
+        assert_eq!(signer_kind(&chain, false), None);
+        assert!(markers(&chain).is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_markers() {
+        let ctx = crate::CodeSignVerifier::for_file(crate::signed_system_binary())
+            .unwrap()
+            .verify("anchor apple")
+            .unwrap();
+        let root = ctx.0.certificate_chain().pop().unwrap();
+        assert!(is_apple_anchored(&[root.clone()]));
+
+        let chain = [
+            certificate(&[
+                AppleMarker::DeveloperIdApplication,
+                AppleMarker::DeveloperIdKext,
+            ]),
+            certificate(&[AppleMarker::DeveloperIdCa]),
+            root,
+        ];
+        assert_eq!(
+            markers(&chain),
+            [
+                AppleMarker::DeveloperIdApplication,
+                AppleMarker::DeveloperIdKext,
+                AppleMarker::DeveloperIdCa
+            ]
+        );
+        assert_eq!(signer_kind(&chain, false), Some(SignerKind::DeveloperId));
+    }
+}
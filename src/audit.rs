@@ -0,0 +1,183 @@
+//!
+//! Audit records of verification attempts.
+//!
+//! An `AuditSink` set with `VerificationOptions::audit` receives a record of every
+//! verification made with those options, successful or not: what was verified, the outcome,
+//! the signer and how long it took. Sinks are called on the verifying thread, once
+//! verification has finished, so a slow sink slows verification down.
+//!
+//! Any closure taking an `&AuditRecord` is a sink. With the `json` feature, `JsonLinesSink`
+//! appends each record to a writer as a line of JSON, for compliance trails.
+//!
+
+use crate::Name;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+///
+/// A single verification attempt
+///
+/// # Fields
+///
+/// `time`: When verification started
+///
+/// `target`: The file or bundle verified, the temporary file for data that wasn't in one, or
+/// None when it couldn't be determined
+///
+/// `executable`: The executable verified, such as the image of a process or the main executable
+/// of a bundle, when verification succeeded
+///
+/// `duration`: How long verification took
+///
+/// `error`: Why verification failed, None when the signature is valid
+///
+/// `subject`: The names on the leaf certificate, when verification succeeded
+///
+/// `sha256_thumbprint`: The thumbprint of the leaf certificate, when verification succeeded
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditRecord {
+    pub time: SystemTime,
+    pub target: Option<PathBuf>,
+    pub executable: Option<PathBuf>,
+    pub duration: Duration,
+    pub error: Option<String>,
+    pub subject: Option<Name>,
+    pub sha256_thumbprint: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+///
+/// Receives a record of every verification made with the options it's set on
+///
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord) + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// The sink set on options, which have to stay `Debug` and `Clone`
+#[derive(Clone)]
+pub(crate) struct SharedSink(pub(crate) Arc<dyn AuditSink>);
+
+impl std::fmt::Debug for SharedSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuditSink")
+    }
+}
+
+///
+/// Writes each record as a line of JSON, in the layout of `AuditRecord::to_json`.
+/// Records are written whole and flushed one at a time, so concurrent verifications never
+/// interleave. Write errors are ignored, an audit trail that can't be written mustn't make
+/// verification fail.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::audit::JsonLinesSink;
+/// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+/// use std::sync::Arc;
+///
+/// let sink = JsonLinesSink::append("/var/log/codesign-audit.jsonl").unwrap();
+/// let options = VerificationOptions::new().audit(Arc::new(sink));
+///
+/// CodeSignVerifier::for_file("/sbin/ping").unwrap().verify_with(&options).unwrap();
+/// ```
+///
+#[cfg(feature = "json")]
+pub struct JsonLinesSink<W: std::io::Write + Send>(std::sync::Mutex<W>);
+
+#[cfg(feature = "json")]
+impl<W: std::io::Write + Send> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesSink(std::sync::Mutex::new(writer))
+    }
+
+    /// Take the writer back, e.g. to inspect what was written
+    pub fn into_inner(self) -> W {
+        self.0
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonLinesSink<std::fs::File> {
+    /// Append to the file at the path, creating it if it doesn't exist
+    pub fn append<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(JsonLinesSink::new(file))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<W: std::io::Write + Send> AuditSink for JsonLinesSink<W> {
+    fn record(&self, record: &AuditRecord) {
+        let mut line = record.to_json();
+        line.push('\n');
+
+        let mut writer = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CodeSignVerifier, VerificationOptions};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_audit() {
+        let path = crate::signed_system_binary();
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = records.clone();
+        let options = VerificationOptions::new().audit(Arc::new(move |record: &AuditRecord| {
+            sink.lock().unwrap().push(record.clone())
+        }));
+
+        let verifier = CodeSignVerifier::for_file(path).unwrap();
+        let ctx = verifier.verify_with(&options).unwrap();
+        assert!(verifier
+            .verify_with(&options.clone().leaf_thumbprint(&[0; 32]))
+            .is_err());
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_success());
+        assert_eq!(
+            records[0].target.as_deref(),
+            Some(std::path::Path::new(path))
+        );
+        assert_eq!(
+            records[0].sha256_thumbprint.as_deref(),
+            Some(ctx.sha256_thumbprint().as_str())
+        );
+        assert!(!records[1].is_success());
+        assert_eq!(records[1].subject, None);
+    }
+}
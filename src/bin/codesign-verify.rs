@@ -0,0 +1,497 @@
+//!
+//! Command line front end to the crate, built with the `cli` feature.
+//!
+//! Exits with 0 when every signature checked is valid, 1 when any isn't, and 2 for usage
+//! errors. With `--json` each result is printed as a single line of JSON.
+//!
+
+use clap::{Args, Parser, Subcommand};
+use codesign_verify::scan::{FileFormat, Scanner};
+use codesign_verify::{
+    CodeSignVerifier, Error, Name, PropertyValue, RevocationState, SignatureContext,
+    VerificationOptions,
+};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(name = "codesign-verify", version, about = "Verify code signatures")]
+struct Cli {
+    /// Print results as JSON, one line each
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Verify a file or bundle, and print who signed it
+    Verify {
+        path: PathBuf,
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Print everything known about the signature
+    Info {
+        path: PathBuf,
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the trust and revocation status of the certificate chain
+    Chain {
+        path: PathBuf,
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the entitlements the code was signed with, macOS only
+    Entitlements {
+        path: PathBuf,
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Verify every signable file under a directory
+    Scan {
+        root: PathBuf,
+        /// Only descend this many directories below the root
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Skip files larger than this many bytes
+        #[arg(long)]
+        max_size: Option<u64>,
+        /// Only print files that fail verification
+        #[arg(long)]
+        failures_only: bool,
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+}
+
+#[derive(Args)]
+struct OptionArgs {
+    /// A requirement the signature must satisfy, in the macOS requirement language
+    #[arg(long)]
+    requirement: Option<String>,
+    /// The publisher the leaf certificate must be issued to
+    #[arg(long)]
+    publisher: Option<String>,
+    /// The sha256 thumbprint the leaf certificate must have, in hex
+    #[arg(long, value_parser = parse_hex)]
+    // Spelled out so clap takes a single parsed value, rather than a list of u8
+    thumbprint: Option<::std::vec::Vec<u8>>,
+    /// Only use revocation data already cached, never the network
+    #[arg(long)]
+    offline: bool,
+}
+
+impl OptionArgs {
+    fn options(&self) -> VerificationOptions {
+        let mut options = VerificationOptions::new().offline(self.offline);
+        if let Some(requirement) = &self.requirement {
+            options = options.requirement(requirement.as_str());
+        }
+        if let Some(publisher) = &self.publisher {
+            options = options.publisher(publisher);
+        }
+        if let Some(thumbprint) = &self.thumbprint {
+            options = options.leaf_thumbprint(thumbprint);
+        }
+        options
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex: String = hex.chars().filter(|c| !matches!(c, ':' | ' ')).collect();
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("expected an even number of hex digits".to_string());
+    }
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let valid = match &cli.command {
+        Command::Verify { path, options } => with_context(path, options, cli.json, |ctx| {
+            print_verify(path, ctx, cli.json)
+        }),
+        Command::Info { path, options } => {
+            with_context(path, options, cli.json, |ctx| print_info(ctx, cli.json))
+        }
+        Command::Chain { path, options } => {
+            with_context(path, options, cli.json, |ctx| print_chain(ctx, cli.json))
+        }
+        Command::Entitlements { path, options } => with_context(path, options, cli.json, |ctx| {
+            print_entitlements(ctx, cli.json)
+        }),
+        Command::Scan {
+            root,
+            max_depth,
+            max_size,
+            failures_only,
+            options,
+        } => {
+            let mut scanner = Scanner::new(root).options(options.options());
+            if let Some(depth) = max_depth {
+                scanner = scanner.max_depth(*depth);
+            }
+            if let Some(size) = max_size {
+                scanner = scanner.max_file_size(*size);
+            }
+            scan(&scanner, *failures_only, cli.json)
+        }
+    };
+
+    if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+/// Verify the code at the path and print the context, or the error when it's not valid
+fn with_context<F: FnOnce(&SignatureContext)>(
+    path: &Path,
+    options: &OptionArgs,
+    json: bool,
+    print: F,
+) -> bool {
+    match CodeSignVerifier::for_file(path).and_then(|v| v.verify_with(&options.options())) {
+        Ok(ctx) => {
+            print(&ctx);
+            true
+        }
+        Err(err) => {
+            print_error(path, &err, json);
+            false
+        }
+    }
+}
+
+fn print_error(path: &Path, err: &Error, json: bool) {
+    if json {
+        println!(
+            "{}",
+            json!({
+                "path": path.to_string_lossy(),
+                "valid": false,
+                "error": err.to_string(),
+                "code": err.code(),
+            })
+        );
+    } else {
+        eprintln!("{}: {}", path.display(), err);
+    }
+}
+
+fn print_verify(path: &Path, ctx: &SignatureContext, json: bool) {
+    if json {
+        println!(
+            "{}",
+            json!({
+                "path": path.to_string_lossy(),
+                "valid": true,
+                "subject": name_json(&ctx.subject_name()),
+                "sha256_thumbprint": ctx.sha256_thumbprint(),
+            })
+        );
+    } else {
+        println!("{}: valid", path.display());
+        println!("  signed by {}", name(&ctx.subject_name()));
+        println!("  sha256 {}", ctx.sha256_thumbprint());
+    }
+}
+
+fn print_info(ctx: &SignatureContext, json: bool) {
+    let report = ctx.report();
+    if json {
+        println!("{}", report.to_json());
+        return;
+    }
+
+    println!("Subject:      {}", name(&report.subject));
+    println!("Issuer:       {}", name(&report.issuer));
+    println!("Thumbprint:   {}", report.sha256_thumbprint);
+    if let Some(serial) = &report.serial {
+        println!("Serial:       {}", serial);
+    }
+    if let Some(time) = report.signing_time {
+        println!("Signed:       {}", format_time(time));
+    }
+    if let Some(time) = report.timestamp {
+        println!("Timestamp:    {}", format_time(time));
+    }
+    if let Some((not_before, not_after)) = report.validity_period {
+        println!(
+            "Valid:        {} to {}",
+            format_time(not_before),
+            format_time(not_after)
+        );
+    }
+    println!("Time:         {:?}", report.time_validity);
+    if let Some(version) = report.runtime_version {
+        println!("Runtime:      {}", version);
+    }
+    if let Some(properties) = ctx.additional_properties() {
+        let mut properties: Vec<_> = properties.into_iter().collect();
+        properties.sort();
+        for (key, value) in properties {
+            println!("{:<14}{}", format!("{}:", key), value);
+        }
+    }
+    if let Some(catalog) = ctx.catalog() {
+        println!("Catalog:      {}", catalog.path.display());
+    }
+}
+
+fn print_chain(ctx: &SignatureContext, json: bool) {
+    if json {
+        // The same layout as the chain and revocation keys of the report
+        let report: Value = serde_json::from_str(&ctx.report().to_json()).unwrap();
+        println!(
+            "{}",
+            json!({ "chain": report["chain"], "revocation": report["revocation"] })
+        );
+        return;
+    }
+
+    let status = ctx.chain_status();
+    println!("Error status: {:#010x}", status.error_status);
+    println!("Info status:  {:#010x}", status.info_status);
+    for (failed, what) in [
+        (status.is_expired(), "a certificate is out of date"),
+        (status.is_revoked(), "a certificate is revoked"),
+        (status.is_untrusted_root(), "the root isn't trusted"),
+        (status.is_partial_chain(), "the chain doesn't reach a root"),
+        (status.is_revocation_unknown(), "revocation is unknown"),
+    ] {
+        if failed {
+            println!("  {}", what);
+        }
+    }
+    for detail in &status.details {
+        println!("  {}", detail);
+    }
+
+    for (depth, revocation) in ctx.revocation_status().iter().enumerate() {
+        let state = match revocation.state {
+            RevocationState::Good => "good".to_string(),
+            RevocationState::Revoked => match revocation.revocation_time {
+                Some(time) => format!("revoked at {}", format_time(time)),
+                None => "revoked".to_string(),
+            },
+            RevocationState::Unknown => "unknown".to_string(),
+            RevocationState::NotChecked => "not checked".to_string(),
+        };
+        println!("{} {} {}", depth, revocation.thumbprint, state);
+    }
+}
+
+fn print_entitlements(ctx: &SignatureContext, json: bool) {
+    let entitlements = ctx.entitlements().unwrap_or_default();
+    if json {
+        let entitlements = entitlements
+            .into_iter()
+            .map(|(key, value)| (key, property_json(&value)))
+            .collect::<serde_json::Map<_, _>>();
+        println!("{}", Value::Object(entitlements));
+        return;
+    }
+
+    for (key, value) in &entitlements {
+        println!("{}: {}", key, property_json(value));
+    }
+}
+
+fn scan(scanner: &Scanner, failures_only: bool, json: bool) -> bool {
+    let mut valid = true;
+    for file in scanner.scan() {
+        valid &= file.result.is_ok();
+        if failures_only && file.result.is_ok() {
+            continue;
+        }
+
+        let format = match file.format {
+            FileFormat::Pe => "pe",
+            FileFormat::MachO => "macho",
+            FileFormat::Script => "script",
+        };
+        match (&file.result, json) {
+            (Ok(ctx), true) => println!(
+                "{}",
+                json!({
+                    "path": file.path.to_string_lossy(),
+                    "format": format,
+                    "valid": true,
+                    "subject": name_json(&ctx.subject_name()),
+                    "sha256_thumbprint": ctx.sha256_thumbprint(),
+                })
+            ),
+            (Err(err), true) => println!(
+                "{}",
+                json!({
+                    "path": file.path.to_string_lossy(),
+                    "format": format,
+                    "valid": false,
+                    "error": err.to_string(),
+                    "code": err.code(),
+                })
+            ),
+            (Ok(ctx), false) => println!(
+                "{} ({}): {}",
+                file.path.display(),
+                format,
+                name(&ctx.subject_name())
+            ),
+            (Err(err), false) => println!("{} ({}): {}", file.path.display(), format, err),
+        }
+    }
+    valid
+}
+
+fn name(name: &Name) -> String {
+    let parts = [
+        ("CN", &name.common_name),
+        ("O", &name.organization),
+        ("OU", &name.organization_unit),
+        ("C", &name.country),
+    ];
+    parts
+        .iter()
+        .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}={}", key, value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn name_json(name: &Name) -> Value {
+    json!({
+        "common_name": name.common_name,
+        "organization": name.organization,
+        "organization_unit": name.organization_unit,
+        "country": name.country,
+    })
+}
+
+fn property_json(value: &PropertyValue) -> Value {
+    match value {
+        PropertyValue::Bool(b) => json!(b),
+        PropertyValue::Integer(i) => json!(i),
+        PropertyValue::Real(r) => json!(r),
+        PropertyValue::String(s) => json!(s),
+        PropertyValue::Data(data) => json!(data),
+        PropertyValue::Date(time) => json!(format_time(*time)),
+        PropertyValue::Array(values) => Value::Array(values.iter().map(property_json).collect()),
+        PropertyValue::Dictionary(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), property_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Format a time as RFC 3339 in UTC, e.g. `2024-01-31T12:00:00Z`
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    };
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_arguments() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "codesign-verify",
+            "verify",
+            "app",
+            "--json",
+            "--offline",
+            "--thumbprint",
+            "AB:cd",
+        ])
+        .unwrap();
+        assert!(cli.json);
+        match cli.command {
+            Command::Verify { path, options } => {
+                assert_eq!(path, Path::new("app"));
+                assert!(options.offline);
+                assert_eq!(options.thumbprint, Some(vec![0xab, 0xcd]));
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(Cli::try_parse_from(["codesign-verify", "verify"]).is_err());
+        assert!(
+            Cli::try_parse_from(["codesign-verify", "chain", "app", "--thumbprint", "abc"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("00ff10").unwrap(), [0x00, 0xff, 0x10]);
+        assert_eq!(parse_hex("00 FF:10").unwrap(), [0x00, 0xff, 0x10]);
+        assert!(parse_hex("0").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(1_709_208_000)),
+            "2024-02-29T12:00:00Z"
+        );
+        assert_eq!(
+            format_time(UNIX_EPOCH - Duration::from_secs(1)),
+            "1969-12-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_name() {
+        let subject = Name {
+            common_name: Some("Developer".to_string()),
+            organization: Some("Example".to_string()),
+            organization_unit: None,
+            country: Some("US".to_string()),
+        };
+        assert_eq!(name(&subject), "CN=Developer, O=Example, C=US");
+    }
+}
@@ -0,0 +1,151 @@
+//!
+//! UniFFI bindings for Swift, Kotlin and Python, enabled with the `uniffi` feature.
+//!
+//! Build the library with `cargo rustc --release --features uniffi --crate-type cdylib`, then
+//! generate the bindings for a language from it with
+//! `uniffi-bindgen generate --library <library> --language swift --out-dir <dir>`.
+//!
+//! The interface is a reduced mirror of the Rust one: `Verifier` and `Signature` wrap
+//! `CodeSignVerifier` and `SignatureContext`, and errors are flattened into `VerifyError`
+//! with the message of the underlying error.
+//!
+
+use crate::{CodeSignVerifier, Error, ErrorKind, Name, SignatureContext, VerificationOptions};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+///
+/// Why verification failed, with the message of the underlying error
+///
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum VerifyError {
+    Unsigned(String),
+    SignatureStripped(String),
+    Tampered(String),
+    UntrustedRoot(String),
+    Expired(String),
+    Revoked(String),
+    RequirementFailed(String),
+    Other(String),
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Unsigned(message)
+            | VerifyError::SignatureStripped(message)
+            | VerifyError::Tampered(message)
+            | VerifyError::UntrustedRoot(message)
+            | VerifyError::Expired(message)
+            | VerifyError::Revoked(message)
+            | VerifyError::RequirementFailed(message)
+            | VerifyError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<Error> for VerifyError {
+    fn from(err: Error) -> Self {
+        let message = err.to_string();
+        match err.kind() {
+            ErrorKind::Unsigned => VerifyError::Unsigned(message),
+            ErrorKind::SignatureStripped => VerifyError::SignatureStripped(message),
+            ErrorKind::Tampered => VerifyError::Tampered(message),
+            ErrorKind::UntrustedRoot => VerifyError::UntrustedRoot(message),
+            ErrorKind::Expired => VerifyError::Expired(message),
+            ErrorKind::Revoked => VerifyError::Revoked(message),
+            ErrorKind::RequirementFailed => VerifyError::RequirementFailed(message),
+            ErrorKind::Other => VerifyError::Other(message),
+        }
+    }
+}
+
+/// The code to verify, see `CodeSignVerifier`
+#[derive(uniffi::Object)]
+pub struct Verifier(CodeSignVerifier);
+
+#[uniffi::export]
+impl Verifier {
+    /// A binary or, on macOS, an application bundle at the given path
+    #[uniffi::constructor]
+    pub fn for_file(path: String) -> Result<Arc<Self>, VerifyError> {
+        Ok(Arc::new(Verifier(CodeSignVerifier::for_file(path)?)))
+    }
+
+    /// A binary held in memory
+    #[uniffi::constructor]
+    pub fn for_bytes(data: Vec<u8>) -> Result<Arc<Self>, VerifyError> {
+        Ok(Arc::new(Verifier(CodeSignVerifier::for_bytes(&data)?)))
+    }
+
+    /// A running process
+    #[uniffi::constructor]
+    pub fn for_pid(pid: i32) -> Result<Arc<Self>, VerifyError> {
+        Ok(Arc::new(Verifier(CodeSignVerifier::for_pid(pid)?)))
+    }
+
+    /// Verify the signature, against the requirement when there is one
+    pub fn verify(&self, requirement: Option<String>) -> Result<Arc<Signature>, VerifyError> {
+        let mut options = VerificationOptions::new();
+        if let Some(requirement) = requirement {
+            options = options.requirement(requirement.as_str());
+        }
+        Ok(Arc::new(Signature(self.0.verify_with(&options)?)))
+    }
+
+    /// Verify the signature, and that the leaf certificate was issued to the publisher
+    pub fn verify_publisher(&self, publisher: String) -> Result<Arc<Signature>, VerifyError> {
+        Ok(Arc::new(Signature(self.0.verify_publisher(&publisher)?)))
+    }
+
+    /// The number of signatures on the file, including the primary one
+    pub fn signer_count(&self) -> Result<u32, VerifyError> {
+        Ok(self.0.signer_count()?)
+    }
+}
+
+/// A verified signature, see `SignatureContext`
+#[derive(uniffi::Object)]
+pub struct Signature(SignatureContext);
+
+#[uniffi::export]
+impl Signature {
+    pub fn subject_name(&self) -> Name {
+        self.0.subject_name()
+    }
+
+    pub fn issuer_name(&self) -> Name {
+        self.0.issuer_name()
+    }
+
+    pub fn sha256_thumbprint(&self) -> String {
+        self.0.sha256_thumbprint()
+    }
+
+    pub fn serial(&self) -> Option<String> {
+        self.0.serial()
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.0.timestamp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Verifier, VerifyError};
+
+    #[test]
+    fn test_bindings() {
+        let path = crate::signed_system_binary();
+
+        let verifier = Verifier::for_file(path.to_string()).unwrap();
+        let signature = verifier.verify(None).unwrap();
+        assert_eq!(signature.sha256_thumbprint().len(), 64);
+        assert!(matches!(
+            verifier.verify_publisher("Nobody In Particular".to_string()),
+            Err(VerifyError::Other(_))
+        ));
+    }
+}
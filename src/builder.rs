@@ -0,0 +1,99 @@
+use crate::{Architecture, CodeSignVerifier, Error, Policy, SignatureContext, VerificationOptions};
+use std::path::PathBuf;
+
+/// The code a builder resolves to
+enum Target {
+    Path(PathBuf),
+    Pid(i32),
+    #[cfg(windows)]
+    Handle(std::os::windows::io::RawHandle),
+    CurrentProcess,
+}
+
+///
+/// Collects the code to verify and how to verify it, see `CodeSignVerifier::builder`.
+/// Setting another target replaces the previous one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::{Architecture, CodeSignVerifier, Policy};
+///
+/// let ctx = CodeSignVerifier::builder()
+///     .path("/Applications/Safari.app")
+///     .architecture(Architecture::Native)
+///     .policy(Policy::AppleAnchored)
+///     .verify()
+///     .unwrap();
+/// ```
+///
+#[derive(Default)]
+pub struct CodeSignVerifierBuilder {
+    target: Option<Target>,
+    options: VerificationOptions,
+}
+
+impl CodeSignVerifierBuilder {
+    /// Verify a binary or bundle at a given path, as `CodeSignVerifier::for_file`
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.target = Some(Target::Path(path.into()));
+        self
+    }
+
+    /// Verify a running process, as `CodeSignVerifier::for_pid`
+    pub fn pid(mut self, pid: i32) -> Self {
+        self.target = Some(Target::Pid(pid));
+        self
+    }
+
+    /// Verify an already open file, as `CodeSignVerifier::for_handle`
+    #[cfg(windows)]
+    pub fn handle(mut self, handle: std::os::windows::io::RawHandle) -> Self {
+        self.target = Some(Target::Handle(handle));
+        self
+    }
+
+    /// Verify the calling process, as `CodeSignVerifier::for_current_process`
+    pub fn current_process(mut self) -> Self {
+        self.target = Some(Target::CurrentProcess);
+        self
+    }
+
+    /// Replace all the verification options set so far
+    pub fn options(mut self, options: VerificationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Select which architectures of a universal binary are verified, see
+    /// `VerificationOptions::architecture`
+    pub fn architecture(mut self, architecture: Architecture) -> Self {
+        self.options = self.options.architecture(architecture);
+        self
+    }
+
+    /// Require one of the built-in policies, see `VerificationOptions::policy`
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.options = self.options.policy(policy);
+        self
+    }
+
+    /// Create the verifier for the target, failing with `Error::InvalidPath` if none was set.
+    /// Other than the symlink policy for paths, the options only apply when verifying through
+    /// the builder with `verify`.
+    pub fn build(self) -> Result<CodeSignVerifier, Error> {
+        match self.target.ok_or(Error::InvalidPath)? {
+            Target::Path(path) => CodeSignVerifier::for_file_with(path, &self.options),
+            Target::Pid(pid) => CodeSignVerifier::for_pid(pid),
+            #[cfg(windows)]
+            Target::Handle(handle) => CodeSignVerifier::for_handle(handle),
+            Target::CurrentProcess => CodeSignVerifier::for_current_process(),
+        }
+    }
+
+    /// Create the verifier for the target, and verify it with the collected options
+    pub fn verify(self) -> Result<SignatureContext, Error> {
+        let options = self.options.clone();
+        self.build()?.verify_with(&options)
+    }
+}
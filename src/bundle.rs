@@ -0,0 +1,225 @@
+//!
+//! Per-component verification of app bundles.
+//!
+//! Where `VerificationOptions::nested_code_depth` checks the code a bundle's seal records
+//! against the seal's requirements, `verify_components` finds the components of a bundle by
+//! where they are installed: frameworks, helpers, XPC services, login items, plugins and system
+//! extensions. Each one is verified on its own with the given options, whether or not the
+//! bundle around it is valid, so a report can say exactly which component is broken.
+//!
+
+use crate::{CodeSignVerifier, Error, SignatureContext, VerificationOptions};
+use std::path::{Path, PathBuf};
+
+/// Where components are installed inside a bundle's contents, and what they are
+const LOCATIONS: &[(&str, ComponentKind)] = &[
+    ("Frameworks", ComponentKind::Framework),
+    ("Helpers", ComponentKind::Helper),
+    ("XPCServices", ComponentKind::XpcService),
+    ("Library/LoginItems", ComponentKind::LoginItem),
+    ("Library/LaunchServices", ComponentKind::Helper),
+    ("Library/SystemExtensions", ComponentKind::SystemExtension),
+    ("PlugIns", ComponentKind::PlugIn),
+];
+
+///
+/// What a component of a bundle is
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    App,             // The bundle verification started at, or an app inside it
+    Framework,       // A framework, in Frameworks
+    Library,         // A bare dynamic library, in Frameworks
+    Helper,          // A helper tool or app, in Helpers, Library/LaunchServices or Frameworks
+    XpcService,      // An XPC service, in XPCServices
+    LoginItem,       // A login item, in Library/LoginItems
+    PlugIn,          // A plugin or app extension, in PlugIns
+    SystemExtension, // A system extension, in Library/SystemExtensions
+}
+
+///
+/// A component of a bundle, and the outcome of verifying it
+///
+/// # Fields
+///
+/// `path`: The location of the component
+///
+/// `kind`: What the component is
+///
+/// `result`: The outcome of verifying it on its own
+///
+/// `components`: The components inside this one in turn
+///
+pub struct Component {
+    pub path: PathBuf,
+    pub kind: ComponentKind,
+    pub result: Result<SignatureContext, Error>,
+    pub components: Vec<Component>,
+}
+
+impl Component {
+    /// Whether this component and every component inside it are valid
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok() && self.components.iter().all(Component::is_valid)
+    }
+
+    /// The components of the tree that failed verification, parents before their components
+    pub fn failures(&self) -> Vec<&Component> {
+        let mut failures = Vec::new();
+        if self.result.is_err() {
+            failures.push(self);
+        }
+        for component in &self.components {
+            failures.extend(component.failures());
+        }
+        failures
+    }
+}
+
+/// The directory holding a bundle's components: Contents for apps, the current version for
+/// frameworks, or the bundle itself for shallow bundles
+fn contents(bundle: &Path) -> PathBuf {
+    [bundle.join("Contents"), bundle.join("Versions/Current")]
+        .iter()
+        .find(|contents| contents.is_dir())
+        .cloned()
+        .unwrap_or_else(|| bundle.to_path_buf())
+}
+
+/// What the entry of a component location is, None for entries that aren't code
+fn kind(path: &Path, location: ComponentKind) -> Option<ComponentKind> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match (location, extension) {
+        (ComponentKind::Framework, Some("framework")) => Some(ComponentKind::Framework),
+        (ComponentKind::Framework, Some("dylib")) => Some(ComponentKind::Library),
+        (ComponentKind::Framework, Some("app")) => Some(ComponentKind::Helper),
+        (ComponentKind::Framework, _) => None,
+        (ComponentKind::PlugIn, Some("app")) => Some(ComponentKind::App),
+        (_, Some("app")) | (_, Some("xpc")) | (_, Some("appex")) | (_, Some("bundle")) => {
+            Some(location)
+        }
+        (ComponentKind::PlugIn, Some("plugin")) | (_, Some("systemextension")) => Some(location),
+        // Bare helper tools
+        (ComponentKind::Helper, None) => Some(location),
+        _ => None,
+    }
+}
+
+fn verify(path: PathBuf, kind: ComponentKind, options: &VerificationOptions) -> Component {
+    let result = CodeSignVerifier::for_file(&path).and_then(|v| v.verify_with(options));
+    let components = if path.is_dir() {
+        components(&path, options)
+    } else {
+        Vec::new()
+    };
+
+    Component {
+        path,
+        kind,
+        result,
+        components,
+    }
+}
+
+/// Verify every component installed in the bundle, in path order
+fn components(bundle: &Path, options: &VerificationOptions) -> Vec<Component> {
+    let contents = contents(bundle);
+    let mut components = Vec::new();
+
+    for (location, location_kind) in LOCATIONS {
+        let entries = match std::fs::read_dir(contents.join(location)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok())
+            // Framework versions link back into the bundle, so links aren't followed
+            .filter(|entry| entry.file_type().map_or(false, |t| !t.is_symlink()))
+            .map(|entry| entry.path())
+            .filter_map(|path| kind(&path, *location_kind).map(|kind| (path, kind)))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        components.extend(
+            paths
+                .into_iter()
+                .map(|(path, kind)| verify(path, kind, options)),
+        );
+    }
+
+    components
+}
+
+///
+/// Verify an app bundle and each of its components individually, returning the tree of results.
+/// Fails with `Error::InvalidPath` when the path isn't a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::bundle::verify_components;
+/// use codesign_verify::VerificationOptions;
+///
+/// let app = verify_components("/Applications/Safari.app", &VerificationOptions::new()).unwrap();
+/// for component in app.failures() {
+///     println!("{:?} {} failed verification", component.kind, component.path.display());
+/// }
+/// ```
+///
+pub fn verify_components<P: AsRef<Path>>(
+    bundle: P,
+    options: &VerificationOptions,
+) -> Result<Component, Error> {
+    let bundle = bundle.as_ref();
+    if !bundle.is_dir() {
+        return Err(Error::InvalidPath);
+    }
+    Ok(verify(bundle.to_path_buf(), ComponentKind::App, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_components, ComponentKind};
+    use crate::VerificationOptions;
+
+    #[test]
+    fn test_components() {
+        let root = crate::temp_path().join("Tool.app");
+        for dir in [
+            "Contents/Frameworks/Core.framework/Versions/A/XPCServices/Fetch.xpc",
+            "Contents/Library/LoginItems/Launcher.app",
+            "Contents/Resources/Icon.bundle",
+        ]
+        .iter()
+        {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::os::unix::fs::symlink(
+            "A",
+            root.join("Contents/Frameworks/Core.framework/Versions/Current"),
+        )
+        .unwrap();
+        std::fs::write(root.join("Contents/Frameworks/libz.dylib"), "").unwrap();
+        std::fs::write(root.join("Contents/Frameworks/notes.txt"), "").unwrap();
+
+        let app = verify_components(&root, &VerificationOptions::new()).unwrap();
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+
+        let kinds =
+            |components: &[super::Component]| components.iter().map(|c| c.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds(&app.components),
+            [
+                ComponentKind::Framework,
+                ComponentKind::Library,
+                ComponentKind::LoginItem
+            ]
+        );
+        assert_eq!(
+            kinds(&app.components[0].components),
+            [ComponentKind::XpcService]
+        );
+        assert!(!app.is_valid());
+        assert_eq!(app.failures().len(), 5);
+    }
+}
@@ -0,0 +1,266 @@
+//!
+//! Caching of verification results.
+//!
+//! Verifying the same binary over and over, e.g. for every connection a service accepts,
+//! costs a full signature check each time. The cache remembers successful verifications,
+//! keyed by the canonical path, and reuses them for as long as the file's size and
+//! modification time, and optionally a hash of its contents, stay the same. For bundles,
+//! that's the executables and the resource seal.
+//!
+
+use crate::{CodeSignVerifier, Error, SignatureContext, VerificationOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// What a file looked like when it was verified, a change to any of it means verifying again
+#[derive(PartialEq, Eq)]
+struct Fingerprint {
+    size: u64,
+    modified: Option<SystemTime>,
+    hash: Option<[u8; 32]>,   // Only when hashing contents was asked for
+    sealed: Vec<Fingerprint>, // For bundles, the files a change to the code or its seal shows in
+}
+
+impl Fingerprint {
+    fn of(path: &Path, hash_contents: bool) -> Result<Self, Error> {
+        let metadata = std::fs::metadata(path).map_err(Error::IoError)?;
+        let hash = if hash_contents && metadata.is_file() {
+            Some(crate::digest::sha256_file(path).map_err(Error::IoError)?)
+        } else {
+            None
+        };
+        let sealed = if metadata.is_dir() {
+            sealed_files(path)
+                .iter()
+                .map(|file| Fingerprint::of(file, hash_contents))
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Fingerprint {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            hash,
+            sealed,
+        })
+    }
+}
+
+/// The files of a bundle that change along with its code or signature: the executables
+/// next to the resource seal, in `Contents/MacOS` for applications, and the seal itself.
+/// Resources are only covered by the seal, which doesn't change when they're replaced.
+fn sealed_files(bundle: &Path) -> Vec<PathBuf> {
+    let root = ["Contents", "Versions/Current", ""]
+        .iter()
+        .map(|contents| bundle.join(contents))
+        .find(|root| root.join("_CodeSignature").is_dir());
+    let root = match root {
+        Some(root) => root,
+        None => return Vec::new(),
+    };
+
+    let executables = match root.join("MacOS") {
+        macos if macos.is_dir() => macos,
+        _ => root.clone(),
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(executables)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    let seal = root.join("_CodeSignature/CodeResources");
+    if seal.is_file() {
+        files.push(seal);
+    }
+    files
+}
+
+struct Entry {
+    fingerprint: Fingerprint,
+    context: SignatureContext,
+    verified_at: Instant,
+}
+
+///
+/// Remembers successful verifications, so unchanged files aren't verified again.
+/// Failures aren't cached, they may be transient, like a revocation server being
+/// unreachable, and are rare on the paths worth caching. The cache can be shared between
+/// threads.
+///
+/// Bundles are directories, whose size and modification time don't change when a file deep
+/// inside them does, so their executables and resource seal are compared instead, hashed
+/// along with single files. A resource replaced without re-signing changes neither, so
+/// bundles are only cached with a TTL, and verified again once it passes.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::cache::VerificationCache;
+/// use codesign_verify::VerificationOptions;
+/// use std::time::Duration;
+///
+/// let cache = VerificationCache::new(VerificationOptions::new().requirement("anchor apple"))
+///     .ttl(Duration::from_secs(300));
+///
+/// // Only the first call verifies the signature
+/// for _ in 0..10 {
+///     cache.verify("/sbin/ping").unwrap();
+/// }
+/// ```
+///
+pub struct VerificationCache {
+    options: VerificationOptions,
+    ttl: Option<Duration>,
+    hash_contents: bool,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+impl VerificationCache {
+    /// Cache verifications made with the given options
+    pub fn new(options: VerificationOptions) -> Self {
+        VerificationCache {
+            options,
+            ttl: None,
+            hash_contents: false,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Verify again once a cached result is this old, even if the file didn't change, so
+    /// revoked certificates and replaced bundle resources are noticed eventually. Without
+    /// one, bundles aren't cached at all.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Also compare a sha256 hash of the file's contents, for files that may be modified
+    /// without changing their size or modification time. The file is read in full on every
+    /// lookup, which is still much cheaper than verifying it.
+    pub fn hash_contents(mut self, hash_contents: bool) -> Self {
+        self.hash_contents = hash_contents;
+        self
+    }
+
+    /// Verify the file at the given path, reusing the previous result if it didn't change
+    /// since then
+    pub fn verify<P: AsRef<Path>>(&self, path: P) -> Result<SignatureContext, Error> {
+        let path = std::fs::canonicalize(path).map_err(Error::IoError)?;
+        let fingerprint = Fingerprint::of(&path, self.hash_contents)?;
+
+        if let Some(entry) = self.entries.lock().unwrap().get(&path) {
+            let fresh = self
+                .ttl
+                .map_or(true, |ttl| entry.verified_at.elapsed() < ttl);
+            if fresh && entry.fingerprint == fingerprint {
+                return Ok(entry.context.clone());
+            }
+        }
+
+        // The lock isn't held while verifying, so other files can be looked up meanwhile
+        let verified_at = Instant::now();
+        let result = CodeSignVerifier::for_file(&path).and_then(|v| v.verify_with(&self.options));
+        let cacheable = self.ttl.is_some() || !path.is_dir();
+
+        let mut entries = self.entries.lock().unwrap();
+        match &result {
+            Ok(context) if cacheable => {
+                entries.insert(
+                    path,
+                    Entry {
+                        fingerprint,
+                        context: context.clone(),
+                        verified_at,
+                    },
+                );
+            }
+            _ => {
+                entries.remove(&path);
+            }
+        }
+        result
+    }
+
+    /// Forget the cached result for the file at the given path, e.g. when a file watcher
+    /// reports it changed
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.entries.lock().unwrap().remove(&path);
+    }
+
+    /// Forget the cached results of every file the predicate matches
+    pub fn invalidate_where<F: FnMut(&Path) -> bool>(&self, mut predicate: F) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|path, _| !predicate(path));
+    }
+
+    /// Forget all cached results
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// The number of files with a cached result, including ones that may since have changed
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether no file has a cached result
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sealed_files, Fingerprint, VerificationCache};
+    use crate::VerificationOptions;
+
+    #[test]
+    fn test_cache() {
+        let path = crate::signed_system_binary();
+
+        let cache = VerificationCache::new(VerificationOptions::new()).hash_contents(true);
+        let first = cache.verify(path).unwrap();
+        let second = cache.verify(path).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&first.0, &second.0));
+        assert_eq!(cache.len(), 1);
+
+        cache.invalidate(path);
+        assert!(cache.is_empty());
+        let third = cache.verify(path).unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&first.0, &third.0));
+
+        cache.invalidate_where(|_| true);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_bundle_fingerprint() {
+        let bundle = crate::temp_path();
+        let executable = bundle.join("Contents/MacOS/tool");
+        let seal = bundle.join("Contents/_CodeSignature/CodeResources");
+        std::fs::create_dir_all(bundle.join("Contents/MacOS")).unwrap();
+        std::fs::create_dir_all(bundle.join("Contents/_CodeSignature")).unwrap();
+        std::fs::write(&executable, b"original").unwrap();
+        std::fs::write(&seal, b"seal").unwrap();
+        std::fs::write(bundle.join("Contents/Info.plist"), b"plist").unwrap();
+
+        let files = sealed_files(&bundle);
+        let before = Fingerprint::of(&bundle, false).unwrap();
+        std::fs::write(&executable, b"replaced executable").unwrap();
+        let after = Fingerprint::of(&bundle, false).unwrap();
+        std::fs::remove_dir_all(&bundle).unwrap();
+
+        assert_eq!(files, [executable, seal]);
+        assert!(before != after);
+    }
+}
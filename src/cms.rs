@@ -0,0 +1,549 @@
+//!
+//! The CMS SignedData behind a signature, with the `cms` feature.
+//!
+//! Both platforms sign code with a CMS (PKCS #7) SignedData: Authenticode keeps it in the
+//! `WIN_CERTIFICATE` entries of a PE file's certificate table, and Mach-O code signatures in
+//! the CMS slot of the embedded signature. Verification never exposes it, so it's read from
+//! the file here, for consumers that want to run their own analysis on the signature.
+//! Parsing a blob doesn't verify anything, it's only read as far as the signer infos.
+//!
+//! `verify_detached` checks a detached signature, as `openssl cms -sign -binary -outform DER`
+//! writes for firmware images and configuration bundles, against the file it covers and
+//! the platform trust store.
+//!
+
+use crate::der::{self, Element};
+use crate::digest::{self, FileData};
+#[cfg(target_os = "macos")]
+use crate::macos::detached;
+#[cfg(windows)]
+use crate::windows::detached;
+use crate::{Error, Name, SignatureRemnant};
+use std::convert::TryInto;
+use std::path::Path;
+use std::time::SystemTime;
+
+const SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const CONTENT_TYPE: &str = "1.2.840.113549.1.9.3";
+const MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+const SIGNING_TIME: &str = "1.2.840.113549.1.9.5";
+/// The unsigned attribute Authenticode appends further signatures in
+const NESTED_SIGNATURE: &str = "1.3.6.1.4.1.311.2.4.1";
+
+/// The `WIN_CERTIFICATE` type of a PKCS #7 SignedData
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+/// The slot of the CMS blob in a Mach-O embedded signature
+const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+/// The magic of the blob wrapping the CMS SignedData
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade0b01;
+
+/// The implicitly tagged certificates of a SignedData, and signed attributes of a SignerInfo
+const CONTEXT_0: u8 = 0xa0;
+/// The implicitly tagged unsigned attributes of a SignerInfo
+const CONTEXT_1: u8 = 0xa1;
+const SET: u8 = 0x31;
+const BMP_STRING: u8 = 0x1e;
+
+///
+/// An attribute of a signer info
+///
+/// # Fields
+///
+/// `oid`: The dotted attribute type, e.g. "1.2.840.113549.1.9.5" for the signing time
+///
+/// `values`: The DER encoded values
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    pub oid: String,
+    pub values: Vec<Vec<u8>>,
+}
+
+///
+/// A signer of a SignedData
+///
+/// # Fields
+///
+/// `digest_algorithm`, `signature_algorithm`: The dotted algorithm OIDs
+///
+/// `signature`: The signature over the signed attributes
+///
+/// `signed_attributes`: The attributes the signature covers, in the order they're encoded
+///
+/// `unsigned_attributes`: The attributes added after signing, such as timestamps and, for
+/// Authenticode, further signatures
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignerInfo {
+    pub digest_algorithm: String,
+    pub signature_algorithm: String,
+    pub signature: Vec<u8>,
+    pub signed_attributes: Vec<Attribute>,
+    pub unsigned_attributes: Vec<Attribute>,
+}
+
+///
+/// A CMS SignedData, see the module documentation
+///
+/// # Fields
+///
+/// `der`: The encoded ContentInfo, without the padding that may follow it in the file
+///
+/// `content_type`: The dotted type of the signed content, e.g. "1.3.6.1.4.1.311.2.1.4" for
+/// Authenticode's SpcIndirectDataContent
+///
+/// `certificates`: The DER encoded certificates the signature carries, in no particular order
+///
+/// `signers`: The signer infos
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedData {
+    pub der: Vec<u8>,
+    pub content_type: String,
+    pub certificates: Vec<Vec<u8>>,
+    pub signers: Vec<SignerInfo>,
+}
+
+/// The dotted algorithm OID of an AlgorithmIdentifier
+fn algorithm(element: &Element<'_>) -> Option<String> {
+    let fields = der::children(element.contents)?;
+    let oid = fields
+        .first()
+        .filter(|oid| oid.tag == der::OBJECT_IDENTIFIER)?;
+    Some(der::oid_string(oid.contents))
+}
+
+fn attributes(contents: &[u8]) -> Vec<Attribute> {
+    der::children(contents)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(
+            |attribute| match der::children(attribute.contents)?.as_slice() {
+                [oid, values] if oid.tag == der::OBJECT_IDENTIFIER && values.tag == SET => {
+                    Some(Attribute {
+                        oid: der::oid_string(oid.contents),
+                        values: der::children(values.contents)?
+                            .iter()
+                            .map(|value| value.encoded.to_vec())
+                            .collect(),
+                    })
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+impl SignerInfo {
+    fn parse(element: &Element<'_>) -> Option<SignerInfo> {
+        let fields = der::children(element.contents)?;
+        // The version and the signer's identifier come first
+        let digest_algorithm = algorithm(fields.get(2)?)?;
+        let (signed_attributes, rest) = match fields.get(3..)? {
+            [attributes, rest @ ..] if attributes.tag == CONTEXT_0 => {
+                (self::attributes(attributes.contents), rest)
+            }
+            rest => (Vec::new(), rest),
+        };
+        let signature = rest
+            .get(1)
+            .filter(|signature| signature.tag == der::OCTET_STRING)?;
+
+        Some(SignerInfo {
+            digest_algorithm,
+            signature_algorithm: algorithm(rest.first()?)?,
+            signature: signature.contents.to_vec(),
+            signed_attributes,
+            unsigned_attributes: rest
+                .get(2)
+                .filter(|attributes| attributes.tag == CONTEXT_1)
+                .map(|attributes| self::attributes(attributes.contents))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The attribute with the dotted type, signed attributes first
+    pub fn attribute(&self, oid: &str) -> Option<&Attribute> {
+        self.signed_attributes
+            .iter()
+            .chain(&self.unsigned_attributes)
+            .find(|attribute| attribute.oid == oid)
+    }
+
+    /// The digest of the signed content, from the messageDigest attribute
+    pub fn message_digest(&self) -> Option<&[u8]> {
+        let value = self.attribute(MESSAGE_DIGEST)?.values.first()?;
+        der::parse(value)
+            .filter(|digest| digest.tag == der::OCTET_STRING)
+            .map(|digest| digest.contents)
+    }
+
+    /// The dotted type of the signed content, from the contentType attribute
+    pub fn content_type(&self) -> Option<String> {
+        let value = self.attribute(CONTENT_TYPE)?.values.first()?;
+        der::parse(value)
+            .filter(|oid| oid.tag == der::OBJECT_IDENTIFIER)
+            .map(|oid| der::oid_string(oid.contents))
+    }
+
+    /// The time the signer claims to have signed at, from the signingTime attribute. Unlike
+    /// a timestamp, nothing vouches for it.
+    pub fn signing_time(&self) -> Option<SystemTime> {
+        let value = self.attribute(SIGNING_TIME)?.values.first()?;
+        der::time(&der::parse(value)?)
+    }
+}
+
+impl SignedData {
+    /// Parse a ContentInfo holding a SignedData, ignoring anything that follows it
+    pub fn parse(data: &[u8]) -> Option<SignedData> {
+        let (content_info, _) = der::split(data)?;
+        let signed_data = match der::children(content_info.contents)?.as_slice() {
+            [content_type, content]
+                if content_type.tag == der::OBJECT_IDENTIFIER
+                    && der::oid_string(content_type.contents) == SIGNED_DATA
+                    && content.tag == CONTEXT_0 =>
+            {
+                der::parse(content.contents)?
+            }
+            _ => return None,
+        };
+
+        // The version, digest algorithms and content, the optional certificates and CRLs,
+        // and the signer infos last
+        let fields = der::children(signed_data.contents)?;
+        let content_type = der::children(fields.get(2)?.contents)?
+            .first()
+            .filter(|oid| oid.tag == der::OBJECT_IDENTIFIER)
+            .map(|oid| der::oid_string(oid.contents))?;
+        let certificates = fields
+            .iter()
+            .find(|field| field.tag == CONTEXT_0)
+            .and_then(|certificates| der::children(certificates.contents))
+            .unwrap_or_default()
+            .iter()
+            .map(|certificate| certificate.encoded.to_vec())
+            .collect();
+        let signers = fields
+            .last()
+            .filter(|signers| signers.tag == SET)
+            .and_then(|signers| der::children(signers.contents))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(SignerInfo::parse)
+            .collect();
+
+        Some(SignedData {
+            der: content_info.encoded.to_vec(),
+            content_type,
+            certificates,
+            signers,
+        })
+    }
+
+    /// The further signatures Authenticode nests in the unsigned attributes of the signers,
+    /// such as the sha256 signature dual signed files add to a sha1 one
+    pub fn nested(&self) -> Vec<SignedData> {
+        self.signers
+            .iter()
+            .flat_map(|signer| &signer.unsigned_attributes)
+            .filter(|attribute| attribute.oid == NESTED_SIGNATURE)
+            .flat_map(|attribute| &attribute.values)
+            .filter_map(|value| SignedData::parse(value))
+            .collect()
+    }
+}
+
+/// The SignedData of each `WIN_CERTIFICATE` entry in a PE certificate table
+fn certificate_table(table: &[u8]) -> Vec<SignedData> {
+    let mut signed_data = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = table.get(offset..offset + 8) {
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let kind = u16::from_le_bytes(header[6..].try_into().unwrap());
+        let entry = match table.get(offset + 8..offset + len) {
+            Some(entry) if len >= 8 => entry,
+            _ => break,
+        };
+        if kind == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            signed_data.extend(SignedData::parse(entry));
+        }
+        // Entries are 8 byte aligned
+        offset += (len + 7) & !7;
+    }
+    signed_data
+}
+
+/// Read the SignedData of the signatures a PE or Mach-O file embeds, one for each slice of
+/// a universal binary. Empty for unsigned, catalog signed and ad-hoc signed files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::cms;
+///
+/// for signed_data in cms::signed_data("C:/Windows/System32/ntdll.dll") {
+///     for signer in &signed_data.signers {
+///         println!("{} {:?}", signer.digest_algorithm, signer.message_digest());
+///     }
+/// }
+/// ```
+pub fn signed_data<P: AsRef<Path>>(path: P) -> Vec<SignedData> {
+    crate::remnant::embedded_signatures(path.as_ref())
+        .into_iter()
+        .flat_map(|(remnant, data)| match remnant {
+            SignatureRemnant::CertificateTable => certificate_table(&data),
+            SignatureRemnant::LoadCommand => {
+                crate::remnant::superblob_slot(&data, CSSLOT_SIGNATURESLOT, CSMAGIC_BLOBWRAPPER)
+                    .and_then(SignedData::parse)
+                    .into_iter()
+                    .collect()
+            }
+            SignatureRemnant::BundleSeal => Vec::new(),
+        })
+        .collect()
+}
+
+///
+/// A detached signature that verified, see `verify_detached`
+///
+/// # Fields
+///
+/// `subject`, `issuer`: The names of the signing certificate and its issuer
+///
+/// `sha256_thumbprint`: The hex SHA-256 of the signing certificate
+///
+/// `certificate_chain`: The DER certificates of the chain the platform built, from the
+/// leaf up to the trusted root
+///
+/// `signing_time`: The signer's signingTime attribute, see `SignerInfo::signing_time`
+///
+/// `signed_data`: The signature
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetachedSignature {
+    pub subject: Name,
+    pub issuer: Name,
+    pub sha256_thumbprint: String,
+    pub certificate_chain: Vec<Vec<u8>>,
+    pub signing_time: Option<SystemTime>,
+    pub signed_data: SignedData,
+}
+
+/// A directory string, BMPStrings are UTF-16 and the other string types ASCII compatible
+fn directory_string(value: &Element<'_>) -> String {
+    match value.tag {
+        BMP_STRING => {
+            let units: Vec<u16> = value
+                .contents
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(value.contents).into_owned(),
+    }
+}
+
+/// The attributes `Name` reports from an encoded X.501 Name, the first of each
+fn name(encoded: &Element<'_>) -> Name {
+    let mut name = Name {
+        common_name: None,
+        organization: None,
+        organization_unit: None,
+        country: None,
+    };
+
+    let rdns = der::children(encoded.contents).unwrap_or_default();
+    for rdn in &rdns {
+        for attribute in der::children(rdn.contents).unwrap_or_default() {
+            let (oid, value) = match der::children(attribute.contents).as_deref() {
+                Some([oid, value]) if oid.tag == der::OBJECT_IDENTIFIER => {
+                    (der::oid_string(oid.contents), directory_string(value))
+                }
+                _ => continue,
+            };
+            let field = match oid.as_str() {
+                "2.5.4.3" => &mut name.common_name,
+                "2.5.4.10" => &mut name.organization,
+                "2.5.4.11" => &mut name.organization_unit,
+                "2.5.4.6" => &mut name.country,
+                _ => continue,
+            };
+            field.get_or_insert(value);
+        }
+    }
+    name
+}
+
+/// Verify a detached signature over a file, see `verify_detached_bytes`
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::cms;
+///
+/// let signature = cms::verify_detached("firmware.bin", "firmware.bin.p7s").unwrap();
+/// assert_eq!(signature.subject.organization.as_deref(), Some("Vendor Inc."));
+/// ```
+pub fn verify_detached<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    signature: Q,
+) -> Result<DetachedSignature, Error> {
+    let content = FileData::open(path.as_ref()).map_err(Error::IoError)?;
+    let signature = std::fs::read(signature).map_err(Error::IoError)?;
+    verify_detached_bytes(&content, &signature)
+}
+
+/// Verify a DER encoded detached CMS signature over the content: the first signer's
+/// signature has to match the content, and its certificate chain up to a root the platform
+/// trusts, as of now. Revocation isn't checked, and neither are the key usages of the chain,
+/// so any signer the trust store vouches for is accepted. Compare the signer with the
+/// expected one before relying on the content.
+///
+/// A message that isn't a CMS SignedData fails with `Error::Unsigned`, content that doesn't
+/// match the signature with `Error::Tampered`, and untrusted signers with the chain errors
+/// code signatures fail with.
+pub fn verify_detached_bytes(content: &[u8], signature: &[u8]) -> Result<DetachedSignature, Error> {
+    let signed_data = SignedData::parse(signature).ok_or(Error::Unsigned)?;
+    let certificate_chain = detached::verify(content, &signed_data.der)?;
+    let leaf = certificate_chain.first().ok_or(Error::LeafCertNotFound)?;
+    let (issuer, subject) = der::names(leaf).ok_or(Error::InvalidCertificate)?;
+
+    Ok(DetachedSignature {
+        subject: name(&subject),
+        issuer: name(&issuer),
+        sha256_thumbprint: digest::hex(&digest::sha256(leaf)),
+        signing_time: signed_data
+            .signers
+            .first()
+            .and_then(SignerInfo::signing_time),
+        certificate_chain,
+        signed_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::der::{encode, OBJECT_IDENTIFIER, OCTET_STRING, SEQUENCE};
+
+    fn oid(dotted: &str) -> Vec<u8> {
+        encode(OBJECT_IDENTIFIER, &der::oid(dotted))
+    }
+
+    fn attribute(dotted: &str, value: Vec<u8>) -> Vec<u8> {
+        encode(SEQUENCE, &[oid(dotted), encode(SET, &value)].concat())
+    }
+
+    #[test]
+    fn test_signed_data() {
+        let sha256 = encode(SEQUENCE, &oid("2.16.840.1.101.3.4.2.1"));
+        let signer = encode(
+            SEQUENCE,
+            &[
+                encode(der::INTEGER, &[1]),
+                encode(SEQUENCE, &[]),
+                sha256.clone(),
+                encode(
+                    CONTEXT_0,
+                    &[
+                        attribute(CONTENT_TYPE, oid("1.3.6.1.4.1.311.2.1.4")),
+                        attribute(MESSAGE_DIGEST, encode(OCTET_STRING, &[0xab; 32])),
+                        attribute(SIGNING_TIME, encode(0x17, b"240102030405Z")),
+                    ]
+                    .concat(),
+                ),
+                encode(SEQUENCE, &oid("1.2.840.113549.1.1.1")),
+                encode(OCTET_STRING, b"signature"),
+            ]
+            .concat(),
+        );
+        let signed_data = encode(
+            SEQUENCE,
+            &[
+                encode(der::INTEGER, &[1]),
+                encode(SET, &sha256),
+                encode(SEQUENCE, &oid("1.3.6.1.4.1.311.2.1.4")),
+                encode(CONTEXT_0, &encode(SEQUENCE, b"")),
+                encode(SET, &signer),
+            ]
+            .concat(),
+        );
+        let content_info = encode(
+            SEQUENCE,
+            &[oid(SIGNED_DATA), encode(CONTEXT_0, &signed_data)].concat(),
+        );
+
+        // A certificate table entry, padded to 8 bytes
+        let mut table = Vec::new();
+        table.extend_from_slice(&(8 + content_info.len() as u32).to_le_bytes());
+        table.extend_from_slice(&0x0200u16.to_le_bytes());
+        table.extend_from_slice(&WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+        table.extend_from_slice(&content_info);
+        table.resize((table.len() + 7) & !7, 0);
+
+        let parsed = certificate_table(&table);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].der, content_info);
+        assert_eq!(parsed[0].content_type, "1.3.6.1.4.1.311.2.1.4");
+        assert_eq!(parsed[0].certificates.len(), 1);
+
+        let signer = &parsed[0].signers[0];
+        assert_eq!(signer.digest_algorithm, "2.16.840.1.101.3.4.2.1");
+        assert_eq!(signer.signature_algorithm, "1.2.840.113549.1.1.1");
+        assert_eq!(signer.signature, b"signature");
+        assert_eq!(signer.message_digest(), Some(&[0xab; 32][..]));
+        assert_eq!(
+            signer.content_type().as_deref(),
+            Some("1.3.6.1.4.1.311.2.1.4")
+        );
+        assert_eq!(
+            signer.signing_time(),
+            Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1704164645))
+        );
+        assert!(signer.unsigned_attributes.is_empty());
+        assert!(parsed[0].nested().is_empty());
+    }
+
+    #[test]
+    fn test_name() {
+        let rdn = |dotted: &str, value: Vec<u8>| {
+            encode(SET, &encode(SEQUENCE, &[oid(dotted), value].concat()))
+        };
+        let encoded = encode(
+            SEQUENCE,
+            &[
+                rdn("2.5.4.6", encode(0x13, b"US")),
+                rdn("2.5.4.10", encode(0x0c, b"Vendor Inc.")),
+                rdn("2.5.4.3", encode(BMP_STRING, &[0, b'V', 0, b'1'])),
+                rdn("2.5.4.3", encode(0x0c, b"ignored")),
+            ]
+            .concat(),
+        );
+        let name = name(&der::parse(&encoded).unwrap());
+        assert_eq!(name.common_name.as_deref(), Some("V1"));
+        assert_eq!(name.organization.as_deref(), Some("Vendor Inc."));
+        assert_eq!(name.country.as_deref(), Some("US"));
+        assert_eq!(name.organization_unit, None);
+
+        assert!(matches!(
+            verify_detached_bytes(b"content", b"not a signature"),
+            Err(Error::Unsigned)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_macho_signed_data() {
+        let signed_data = signed_data(crate::signed_system_binary());
+        assert!(!signed_data.is_empty());
+        assert!(signed_data
+            .iter()
+            .all(|signed_data| !signed_data.certificates.is_empty()));
+    }
+}
@@ -0,0 +1,395 @@
+//!
+//! Certificate Transparency for signing certificates.
+//!
+//! Certificate authorities log the certificates they issue to public CT logs, and embed the
+//! logs' signed promises to include them (SCTs) in the certificate itself. Apple requires
+//! them on Developer ID certificates issued since 2024, so a signing certificate without
+//! any is worth flagging.
+//!
+//! The embedded SCTs are always available from `SignatureContext`. Checking their signatures
+//! against a list of known logs requires the `ct` feature.
+//!
+
+use crate::der;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The certificate extension holding the embedded SignedCertificateTimestampList
+const SCT_LIST_OID: &str = "1.3.6.1.4.1.11129.2.4.2";
+
+///
+/// A signed certificate timestamp embedded in the leaf certificate, as defined in RFC 6962
+///
+/// # Fields
+///
+/// `version`: The SCT version, 0 for RFC 6962
+///
+/// `log_id`: The hex encoded sha256 of the key of the log that issued it
+///
+/// `timestamp`: When the log promised to include the certificate
+///
+/// `extensions`: The raw extensions, empty for every log in use today
+///
+/// `hash_algorithm`: The TLS HashAlgorithm of the signature, 4 for sha256
+///
+/// `signature_algorithm`: The TLS SignatureAlgorithm of the signature, 1 for RSA and 3 for ECDSA
+///
+/// `signature`: The log's signature
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedCertificateTimestamp {
+    pub version: u8,
+    pub log_id: String,
+    pub timestamp: SystemTime,
+    pub extensions: Vec<u8>,
+    pub hash_algorithm: u8,
+    pub signature_algorithm: u8,
+    pub signature: Vec<u8>,
+}
+
+/// Reads the TLS encoding the SCT list uses
+struct TlsReader<'a>(&'a [u8]);
+
+impl<'a> TlsReader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn integer(&mut self, len: usize) -> Option<u64> {
+        let bytes = self.bytes(len)?;
+        Some(
+            bytes
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as u64),
+        )
+    }
+
+    /// A vector with a 16 bit length prefix
+    fn vector(&mut self) -> Option<&'a [u8]> {
+        let len = self.integer(2)? as usize;
+        self.bytes(len)
+    }
+}
+
+fn parse_sct(data: &[u8]) -> Option<SignedCertificateTimestamp> {
+    let mut reader = TlsReader(data);
+    let version = reader.integer(1)? as u8;
+    let log_id = reader.bytes(32)?;
+    let timestamp = reader.integer(8)?;
+    let extensions = reader.vector()?;
+    let hash_algorithm = reader.integer(1)? as u8;
+    let signature_algorithm = reader.integer(1)? as u8;
+    let signature = reader.vector()?;
+
+    Some(SignedCertificateTimestamp {
+        version,
+        log_id: crate::digest::hex(log_id),
+        timestamp: UNIX_EPOCH + Duration::from_millis(timestamp),
+        extensions: extensions.to_vec(),
+        hash_algorithm,
+        signature_algorithm,
+        signature: signature.to_vec(),
+    })
+}
+
+/// The SCTs embedded in a DER certificate, skipping any that can't be parsed
+pub(crate) fn embedded_scts(certificate: &[u8]) -> Vec<SignedCertificateTimestamp> {
+    let oid = der::oid(SCT_LIST_OID);
+    let list = der::extensions(certificate)
+        .into_iter()
+        .find(|extension| extension.oid == &oid[..])
+        .and_then(|extension| der::parse(extension.value))
+        .filter(|list| list.tag == der::OCTET_STRING)
+        .and_then(|list| TlsReader(list.contents).vector());
+
+    let mut reader = match list {
+        Some(list) => TlsReader(list),
+        None => return Vec::new(),
+    };
+    let mut scts = Vec::new();
+    while let Some(sct) = reader.vector() {
+        scts.extend(parse_sct(sct));
+    }
+    scts
+}
+
+///
+/// A Certificate Transparency log trusted to issue SCTs. Only ECDSA P-256 logs are
+/// supported, which covers every log currently in Apple's and Chrome's log lists.
+///
+#[cfg(feature = "ct")]
+pub struct CtLog {
+    name: String,
+    id: [u8; 32],
+    key: p256::ecdsa::VerifyingKey,
+}
+
+#[cfg(feature = "ct")]
+impl CtLog {
+    /// A log with the given name and DER encoded SubjectPublicKeyInfo, None if the key
+    /// isn't a P-256 key
+    pub fn new(name: &str, key: &[u8]) -> Option<Self> {
+        use p256::pkcs8::DecodePublicKey;
+
+        Some(CtLog {
+            name: name.to_string(),
+            id: crate::digest::sha256(key),
+            key: p256::ecdsa::VerifyingKey::from_public_key_der(key).ok()?,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The hex encoded log ID, as in `SignedCertificateTimestamp::log_id`
+    pub fn log_id(&self) -> String {
+        crate::digest::hex(&self.id)
+    }
+}
+
+///
+/// The outcome of checking an SCT against the known logs
+///
+#[cfg(feature = "ct")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SctStatus {
+    Valid,                // A known log signed the SCT for this certificate
+    InvalidSignature,     // The signature doesn't verify for this certificate
+    UnknownLog,           // The SCT was issued by a log that isn't in the list
+    UnsupportedAlgorithm, // The SCT isn't signed with ECDSA over sha256
+    MissingIssuer,        // The issuer certificate the SCT commits to isn't in the chain
+}
+
+/// The leaf's TBSCertificate without the SCT list, which is what the log signed
+#[cfg(feature = "ct")]
+fn precertificate_tbs(certificate: &[u8]) -> Option<Vec<u8>> {
+    let oid = der::oid(SCT_LIST_OID);
+    let mut contents = Vec::new();
+
+    for field in der::tbs_fields(certificate)? {
+        if field.tag != der::EXTENSIONS {
+            contents.extend_from_slice(field.encoded);
+            continue;
+        }
+        let extensions: Vec<u8> = der::extensions(certificate)
+            .iter()
+            .filter(|extension| extension.oid != &oid[..])
+            .flat_map(|extension| extension.encoded.iter().copied())
+            .collect();
+        let extensions = der::encode(der::SEQUENCE, &extensions);
+        contents.extend(der::encode(der::EXTENSIONS, &extensions));
+    }
+    Some(der::encode(der::SEQUENCE, &contents))
+}
+
+/// The data a log signs for an SCT embedded in a certificate, per RFC 6962 section 3.2
+#[cfg(feature = "ct")]
+fn signed_data(
+    sct: &SignedCertificateTimestamp,
+    issuer_key_hash: &[u8; 32],
+    tbs: &[u8],
+) -> Vec<u8> {
+    let timestamp = sct
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut data = vec![sct.version, 0]; // The signature type is certificate_timestamp
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(&1u16.to_be_bytes()); // The entry type is precert_entry
+    data.extend_from_slice(issuer_key_hash);
+    data.extend_from_slice(&(tbs.len() as u32).to_be_bytes()[1..]);
+    data.extend_from_slice(tbs);
+    data.extend_from_slice(&(sct.extensions.len() as u16).to_be_bytes());
+    data.extend_from_slice(&sct.extensions);
+    data
+}
+
+/// Check the SCTs embedded in the leaf of a DER certificate chain, leaf first, against the logs
+#[cfg(feature = "ct")]
+pub(crate) fn verify(
+    chain: &[Vec<u8>],
+    logs: &[CtLog],
+) -> Vec<(SignedCertificateTimestamp, SctStatus)> {
+    use p256::ecdsa::signature::Verifier;
+
+    let leaf = match chain.first() {
+        Some(leaf) => leaf,
+        None => return Vec::new(),
+    };
+    let issuer_key_hash = chain
+        .get(1)
+        .and_then(|issuer| der::subject_public_key_info(issuer))
+        .map(crate::digest::sha256);
+    let tbs = precertificate_tbs(leaf);
+
+    embedded_scts(leaf)
+        .into_iter()
+        .map(|sct| {
+            let log = logs.iter().find(|log| log.log_id() == sct.log_id);
+            let status = match (log, &issuer_key_hash, &tbs) {
+                (None, _, _) => SctStatus::UnknownLog,
+                _ if sct.hash_algorithm != 4 || sct.signature_algorithm != 3 => {
+                    SctStatus::UnsupportedAlgorithm
+                }
+                (Some(log), Some(issuer_key_hash), Some(tbs)) => {
+                    let data = signed_data(&sct, issuer_key_hash, tbs);
+                    match p256::ecdsa::Signature::from_der(&sct.signature) {
+                        Ok(signature) if log.key.verify(&data, &signature).is_ok() => {
+                            SctStatus::Valid
+                        }
+                        _ => SctStatus::InvalidSignature,
+                    }
+                }
+                (Some(_), None, _) => SctStatus::MissingIssuer,
+                (Some(_), _, None) => SctStatus::InvalidSignature,
+            };
+            (sct, status)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::der::{encode, EXTENSIONS, OBJECT_IDENTIFIER, OCTET_STRING, SEQUENCE};
+
+    /// A certificate that is only well formed enough to carry the SPKI and extensions
+    fn certificate(spki: &[u8], extensions: &[Vec<u8>]) -> Vec<u8> {
+        let name = encode(SEQUENCE, &[]);
+        let tbs = [
+            encode(0xa0, &[0x02, 0x01, 0x02]),
+            vec![0x02, 0x01, 0x01],
+            encode(SEQUENCE, &[]),
+            name.clone(),
+            encode(SEQUENCE, &[]),
+            name,
+            spki.to_vec(),
+            encode(EXTENSIONS, &encode(SEQUENCE, &extensions.concat())),
+        ]
+        .concat();
+        let certificate = [
+            encode(SEQUENCE, &tbs),
+            encode(SEQUENCE, &[]),
+            vec![0x03, 0x01, 0x00],
+        ]
+        .concat();
+        encode(SEQUENCE, &certificate)
+    }
+
+    fn extension(oid: &str, value: &[u8]) -> Vec<u8> {
+        let fields = [
+            encode(OBJECT_IDENTIFIER, &der::oid(oid)),
+            encode(OCTET_STRING, value),
+        ];
+        encode(SEQUENCE, &fields.concat())
+    }
+
+    fn sct_list(scts: &[Vec<u8>]) -> Vec<u8> {
+        let mut list = Vec::new();
+        for sct in scts {
+            list.extend_from_slice(&(sct.len() as u16).to_be_bytes());
+            list.extend_from_slice(sct);
+        }
+        let mut vector = (list.len() as u16).to_be_bytes().to_vec();
+        vector.extend(list);
+        encode(OCTET_STRING, &vector)
+    }
+
+    fn sct(log_id: &[u8; 32], timestamp: u64, signature: &[u8]) -> Vec<u8> {
+        let mut sct = vec![0];
+        sct.extend_from_slice(log_id);
+        sct.extend_from_slice(&timestamp.to_be_bytes());
+        sct.extend_from_slice(&[0, 0, 4, 3]);
+        sct.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+        sct.extend_from_slice(signature);
+        sct
+    }
+
+    #[test]
+    fn test_embedded_scts() {
+        let list = sct_list(&[sct(&[0x11; 32], 1_700_000_000_000, &[1, 2, 3])]);
+        let leaf = certificate(
+            &encode(SEQUENCE, &[]),
+            &[
+                extension("2.5.29.19", &encode(SEQUENCE, &[])),
+                extension(SCT_LIST_OID, &list),
+            ],
+        );
+
+        let scts = embedded_scts(&leaf);
+        assert_eq!(scts.len(), 1);
+        assert_eq!(scts[0].log_id, "11".repeat(32));
+        assert_eq!(
+            scts[0].timestamp,
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)
+        );
+        assert_eq!(scts[0].signature, [1, 2, 3]);
+
+        let unlogged = certificate(&encode(SEQUENCE, &[]), &[]);
+        assert!(embedded_scts(&unlogged).is_empty());
+    }
+
+    #[cfg(feature = "ct")]
+    #[test]
+    fn test_verify_scts() {
+        use p256::ecdsa::signature::Signer;
+        use p256::pkcs8::EncodePublicKey;
+
+        let log_key = p256::ecdsa::SigningKey::from_slice(&[3u8; 32]).unwrap();
+        let log_spki = log_key.verifying_key().to_public_key_der().unwrap();
+        let log = CtLog::new("test log", log_spki.as_bytes()).unwrap();
+        let log_id = crate::digest::sha256(log_spki.as_bytes());
+
+        let issuer_spki = encode(SEQUENCE, b"issuer key");
+        let issuer = certificate(&issuer_spki, &[]);
+        let basic_constraints = extension("2.5.29.19", &encode(SEQUENCE, &[]));
+        let precertificate = certificate(&encode(SEQUENCE, &[]), &[basic_constraints.clone()]);
+
+        // The log signs the certificate as it was before the SCT list was added
+        let timestamp = 1_700_000_000_000;
+        let unsigned = SignedCertificateTimestamp {
+            version: 0,
+            log_id: crate::digest::hex(&log_id),
+            timestamp: UNIX_EPOCH + Duration::from_millis(timestamp),
+            extensions: Vec::new(),
+            hash_algorithm: 4,
+            signature_algorithm: 3,
+            signature: Vec::new(),
+        };
+        let tbs = precertificate_tbs(&precertificate).unwrap();
+        let data = signed_data(&unsigned, &crate::digest::sha256(&issuer_spki), &tbs);
+        let signature: p256::ecdsa::DerSignature = log_key.sign(&data);
+
+        let list = sct_list(&[
+            sct(&log_id, timestamp, signature.as_bytes()),
+            sct(&log_id, timestamp + 1, signature.as_bytes()),
+            sct(&[0x22; 32], timestamp, signature.as_bytes()),
+        ]);
+        let leaf = certificate(
+            &encode(SEQUENCE, &[]),
+            &[basic_constraints, extension(SCT_LIST_OID, &list)],
+        );
+
+        let statuses: Vec<SctStatus> = verify(&[leaf, issuer], &[log])
+            .into_iter()
+            .map(|(_, status)| status)
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                SctStatus::Valid,
+                SctStatus::InvalidSignature,
+                SctStatus::UnknownLog
+            ]
+        );
+    }
+}
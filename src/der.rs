@@ -0,0 +1,370 @@
+//!
+//! Just enough DER to find things in certificates, shared by both platforms.
+//!
+//! Values are borrowed from the encoding and never validated beyond their lengths, callers
+//! only pick out the fields they need and treat anything unexpected as absent.
+//!
+
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OBJECT_IDENTIFIER: u8 = 0x06;
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+#[cfg(any(windows, feature = "cms"))]
+const UTC_TIME: u8 = 0x17;
+#[cfg(any(windows, feature = "cms"))]
+const GENERALIZED_TIME: u8 = 0x18;
+/// The explicitly tagged extensions of a TBSCertificate
+pub(crate) const EXTENSIONS: u8 = 0xa3;
+/// The explicitly tagged version of a TBSCertificate
+const VERSION: u8 = 0xa0;
+
+/// A single encoded value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Element<'a> {
+    pub tag: u8,
+    pub encoded: &'a [u8],  // The whole value, header included
+    pub contents: &'a [u8], // The value without its header
+}
+
+/// Split the first value off the data, returning it and what follows
+pub(crate) fn split(data: &[u8]) -> Option<(Element<'_>, &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = match first {
+        len if len < 0x80 => (len as usize, rest),
+        0x81..=0x84 => {
+            let count = (first & 0x7f) as usize;
+            if rest.len() < count {
+                return None;
+            }
+            let len = rest[..count]
+                .iter()
+                .fold(0usize, |len, &byte| len << 8 | byte as usize);
+            (len, &rest[count..])
+        }
+        _ => return None, // Indefinite lengths aren't DER
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+    let header = data.len() - rest.len();
+    let element = Element {
+        tag,
+        encoded: &data[..header + len],
+        contents: &rest[..len],
+    };
+    Some((element, &rest[len..]))
+}
+
+/// Parse data that is exactly one value
+pub(crate) fn parse(data: &[u8]) -> Option<Element<'_>> {
+    match split(data)? {
+        (element, []) => Some(element),
+        _ => None,
+    }
+}
+
+/// The values inside a constructed value's contents
+pub(crate) fn children(contents: &[u8]) -> Option<Vec<Element<'_>>> {
+    let mut children = Vec::new();
+    let mut rest = contents;
+    while !rest.is_empty() {
+        let (child, next) = split(rest)?;
+        children.push(child);
+        rest = next;
+    }
+    Some(children)
+}
+
+/// Encode a value with the tag and contents
+pub(crate) fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let len = contents.len();
+    let mut encoded = vec![tag];
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+        encoded.push(0x80 | (bytes.len() - skip) as u8);
+        encoded.extend_from_slice(&bytes[skip..]);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// The fields of a certificate's TBSCertificate
+pub(crate) fn tbs_fields(certificate: &[u8]) -> Option<Vec<Element<'_>>> {
+    let certificate = parse(certificate)?;
+    let (tbs, _) = split(certificate.contents)?;
+    children(tbs.contents)
+}
+
+/// The encoded TBSCertificate of a certificate, the part its issuer signed
+#[cfg(windows)]
+pub(crate) fn tbs_certificate(certificate: &[u8]) -> Option<&[u8]> {
+    let certificate = parse(certificate)?;
+    let (tbs, _) = split(certificate.contents)?;
+    Some(tbs.encoded)
+}
+
+/// The contents of a certificate's serialNumber INTEGER, big endian and including any padding
+pub(crate) fn serial_number(certificate: &[u8]) -> Option<&[u8]> {
+    let fields = tbs_fields(certificate)?;
+    let skip = if fields.first()?.tag == VERSION { 1 } else { 0 };
+    fields
+        .get(skip)
+        .filter(|serial| serial.tag == INTEGER)
+        .map(|serial| serial.contents)
+}
+
+/// The issuer and subject Names of a certificate
+#[cfg(feature = "cms")]
+pub(crate) fn names(certificate: &[u8]) -> Option<(Element<'_>, Element<'_>)> {
+    let fields = tbs_fields(certificate)?;
+    // serialNumber, signature, issuer, validity, then subject
+    let skip = if fields.first()?.tag == VERSION { 1 } else { 0 };
+    Some((*fields.get(skip + 2)?, *fields.get(skip + 4)?))
+}
+
+/// The encoded SubjectPublicKeyInfo of a certificate
+#[cfg(feature = "ct")]
+pub(crate) fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let fields = tbs_fields(certificate)?;
+    // serialNumber, signature, issuer, validity, subject, then subjectPublicKeyInfo
+    let skip = if fields.first()?.tag == VERSION { 6 } else { 5 };
+    Some(fields.get(skip)?.encoded)
+}
+
+/// A certificate extension
+#[cfg_attr(not(feature = "ct"), allow(dead_code))]
+pub(crate) struct Extension<'a> {
+    pub oid: &'a [u8], // The encoded object identifier, without its header
+    pub encoded: &'a [u8],
+    pub value: &'a [u8], // The contents of the extnValue octet string
+}
+
+/// The extensions of a certificate
+pub(crate) fn extensions(certificate: &[u8]) -> Vec<Extension<'_>> {
+    let fields = tbs_fields(certificate).unwrap_or_default();
+    let extensions = fields
+        .iter()
+        .find(|field| field.tag == EXTENSIONS)
+        .and_then(|field| parse(field.contents))
+        .and_then(|sequence| children(sequence.contents))
+        .unwrap_or_default();
+
+    extensions
+        .into_iter()
+        .filter_map(|extension| {
+            let fields = children(extension.contents)?;
+            let oid = fields.first().filter(|oid| oid.tag == OBJECT_IDENTIFIER)?;
+            let value = fields.last().filter(|value| value.tag == OCTET_STRING)?;
+            Some(Extension {
+                oid: oid.contents,
+                encoded: extension.encoded,
+                value: value.contents,
+            })
+        })
+        .collect()
+}
+
+/// Encode a dotted object identifier the way it appears in `Extension::oid`
+pub(crate) fn oid(dotted: &str) -> Vec<u8> {
+    let arcs: Vec<u64> = dotted
+        .split('.')
+        .filter_map(|arc| arc.parse().ok())
+        .collect();
+    let mut encoded = Vec::new();
+    let first = arcs.first().copied().unwrap_or(0) * 40 + arcs.get(1).copied().unwrap_or(0);
+
+    for arc in std::iter::once(first).chain(arcs.iter().skip(2).copied()) {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            bytes.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        encoded.extend(bytes.iter().rev());
+    }
+    encoded
+}
+
+/// Decode an object identifier's contents to its dotted form, the inverse of `oid`
+#[cfg(feature = "cms")]
+pub(crate) fn oid_string(encoded: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &byte in encoded {
+        arc = arc << 7 | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+
+    let mut dotted = match arcs.first() {
+        Some(&first) if first < 80 => format!("{}.{}", first / 40, first % 40),
+        Some(&first) => format!("2.{}", first - 80),
+        None => return String::new(),
+    };
+    for arc in &arcs[1..] {
+        dotted.push_str(&format!(".{}", arc));
+    }
+    dotted
+}
+
+/// Decode a UTCTime or GeneralizedTime, which DER requires in UTC and without fractions
+#[cfg(any(windows, feature = "cms"))]
+pub(crate) fn time(element: &Element<'_>) -> Option<std::time::SystemTime> {
+    let text = std::str::from_utf8(element.contents)
+        .ok()?
+        .strip_suffix('Z')?;
+    let (year, rest) = match element.tag {
+        // Two digit years are 1950 to 2049
+        UTC_TIME if text.len() == 12 => match text[..2].parse::<i64>().ok()? {
+            year if year < 50 => (2000 + year, &text[2..]),
+            year => (1900 + year, &text[2..]),
+        },
+        GENERALIZED_TIME if text.len() == 14 => (text[..4].parse::<i64>().ok()?, &text[4..]),
+        _ => return None,
+    };
+    let field = |at: usize| rest.get(at..at + 2)?.parse::<i64>().ok();
+    let (month, day) = (field(0)?, field(2)?);
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+
+    // Days since the epoch in the proleptic Gregorian calendar, with years starting in March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let secs = days * 86400 + field(4)? * 3600 + field(6)? * 60 + field(8)?;
+    if secs < 0 {
+        return None;
+    }
+    Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs as u64))
+}
+
+/// When the responder signed an encoded OCSPResponse, from the producedAt of its
+/// BasicOCSPResponse
+#[cfg(windows)]
+pub(crate) fn ocsp_produced_at(response: &[u8]) -> Option<std::time::SystemTime> {
+    let fields = children(parse(response)?.contents)?;
+    // The responseBytes follow the responseStatus, explicitly tagged
+    let response_bytes = fields.get(1).filter(|field| field.tag == 0xa0)?;
+    let response_bytes = children(parse(response_bytes.contents)?.contents)?;
+    let basic = response_bytes
+        .get(1)
+        .filter(|basic| basic.tag == OCTET_STRING)?;
+    let (response_data, _) = split(parse(basic.contents)?.contents)?;
+    // The optional version and the responder come before producedAt
+    let fields = children(response_data.contents)?;
+    let produced_at = fields.iter().find(|field| field.tag == GENERALIZED_TIME)?;
+    time(produced_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der() {
+        let long = vec![0xab; 300];
+        let encoded = encode(OCTET_STRING, &long);
+        assert_eq!(&encoded[..4], &[OCTET_STRING, 0x82, 0x01, 0x2c]);
+        let element = parse(&encoded).unwrap();
+        assert_eq!(element.contents, &long[..]);
+        assert!(parse(&encoded[..encoded.len() - 1]).is_none());
+
+        let sequence = encode(
+            SEQUENCE,
+            &[encode(BOOLEAN, &[0xff]), encode(OCTET_STRING, b"x")].concat(),
+        );
+        let fields = children(parse(&sequence).unwrap().contents).unwrap();
+        assert_eq!(fields.len(), 2);
+
+        #[cfg(feature = "cms")]
+        assert_eq!(
+            oid_string(&oid("1.2.840.113549.1.7.2")),
+            "1.2.840.113549.1.7.2"
+        );
+        assert_eq!(fields[1].contents, b"x");
+
+        #[cfg(any(windows, feature = "cms"))]
+        {
+            let utc = encode(UTC_TIME, b"240102030405Z");
+            assert_eq!(
+                time(&parse(&utc).unwrap()),
+                Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1704164645))
+            );
+            let generalized = encode(GENERALIZED_TIME, b"19700101000000Z");
+            assert_eq!(
+                time(&parse(&generalized).unwrap()),
+                Some(std::time::UNIX_EPOCH)
+            );
+            assert_eq!(
+                time(&parse(&encode(UTC_TIME, b"241302030405Z")).unwrap()),
+                None
+            );
+        }
+
+        #[cfg(windows)]
+        {
+            let response_data = encode(
+                SEQUENCE,
+                &[
+                    encode(0xa2, &encode(OCTET_STRING, &[0xab; 20])),
+                    encode(GENERALIZED_TIME, b"20240102030405Z"),
+                    encode(SEQUENCE, &[]),
+                ]
+                .concat(),
+            );
+            let basic = encode(SEQUENCE, &response_data);
+            let response_bytes = encode(
+                SEQUENCE,
+                &[
+                    encode(OBJECT_IDENTIFIER, &oid("1.3.6.1.5.5.7.48.1.1")),
+                    encode(OCTET_STRING, &basic),
+                ]
+                .concat(),
+            );
+            let response = encode(
+                SEQUENCE,
+                &[encode(0x0a, &[0]), encode(0xa0, &response_bytes)].concat(),
+            );
+            assert_eq!(
+                ocsp_produced_at(&response),
+                Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1704164645))
+            );
+            assert_eq!(
+                ocsp_produced_at(&encode(SEQUENCE, &encode(0x0a, &[6]))),
+                None
+            );
+        }
+
+        let serial = encode(INTEGER, &[0x00, 0x80, 0x01]);
+        let version = encode(VERSION, &encode(INTEGER, &[0x02]));
+        let tbs = encode(SEQUENCE, &[version, serial.clone()].concat());
+        assert_eq!(
+            serial_number(&encode(SEQUENCE, &tbs)),
+            Some(&[0x00, 0x80, 0x01][..])
+        );
+        let tbs = encode(SEQUENCE, &serial);
+        assert_eq!(
+            serial_number(&encode(SEQUENCE, &tbs)),
+            Some(&[0x00, 0x80, 0x01][..])
+        );
+
+        assert_eq!(
+            oid("1.3.6.1.4.1.11129.2.4.2"),
+            [0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02]
+        );
+    }
+}
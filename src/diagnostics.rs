@@ -0,0 +1,330 @@
+//!
+//! Structured explanations of verification failures.
+//!
+//! `Error::explain` breaks an error down into the checks that failed, in the order the
+//! platforms run them: the signature itself, the code it covers, the resource seal and nested
+//! code, then the certificate chain, its time validity and revocation, and finally the
+//! requirement and any pins. A rejected chain usually fails several checks at once, each gets
+//! its own entry. The `Display` output lists one check per line, like `codesign -vvv`.
+//!
+
+use crate::{ChainStatus, Error, NestedCode, SignatureRemnant};
+
+///
+/// A check verification runs, in the order they're run
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Check {
+    Input,          // Opening the code: the path, the file, and anything passed in with it
+    Signature,      // Finding and reading the signature
+    CodeIntegrity,  // The code still matches the hashes the signature covers
+    ResourceSeal,   // The bundle resources still match their seal, macOS only
+    NestedCode,     // The code nested in the bundle verifies on its own, macOS only
+    ChainBuilding,  // The certificate chain is complete, well formed and ends at a trusted root
+    TimeValidity,   // The certificates are valid now, or at the timestamp
+    Revocation,     // No certificate in the chain was revoked
+    Requirement,    // The requirement or policy the signature must satisfy
+    Pinning,        // The pinned thumbprint or publisher
+    RunningProcess, // The running process still matches its signed code
+    Other,          // Anything else, such as an OS error without a check of its own
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Check::Input => "input",
+            Check::Signature => "signature",
+            Check::CodeIntegrity => "code integrity",
+            Check::ResourceSeal => "resource seal",
+            Check::NestedCode => "nested code",
+            Check::ChainBuilding => "chain building",
+            Check::TimeValidity => "time validity",
+            Check::Revocation => "revocation",
+            Check::Requirement => "requirement",
+            Check::Pinning => "pinning",
+            Check::RunningProcess => "running process",
+            Check::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///
+/// A single check that failed
+///
+/// # Fields
+///
+/// `check`: Which check failed
+///
+/// `detail`: What about it failed
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FailedCheck {
+    pub check: Check,
+    pub detail: String,
+}
+
+///
+/// Every check an error failed, see `Error::explain`
+///
+/// # Fields
+///
+/// `summary`: The error's own message
+///
+/// `failures`: The failed checks, in the order they're run
+///
+/// `code`: The OS status code behind the error, if any
+///
+/// `native_message`: The description the OS gave the failure, if any
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostics {
+    pub summary: String,
+    pub failures: Vec<FailedCheck>,
+    pub code: Option<i32>,
+    pub native_message: Option<String>,
+}
+
+impl Diagnostics {
+    /// Whether the given check failed
+    pub fn failed(&self, check: Check) -> bool {
+        self.failures.iter().any(|failure| failure.check == check)
+    }
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.summary)?;
+        for failure in &self.failures {
+            writeln!(f, "  {}: {}", failure.check, failure.detail)?;
+        }
+        if let Some(code) = self.code {
+            writeln!(f, "  status: {}", code)?;
+        }
+        Ok(())
+    }
+}
+
+/// The checks a rejected chain failed, from its status bits
+fn chain_failures(status: &ChainStatus, failures: &mut Vec<FailedCheck>) {
+    let checks = [
+        (
+            ChainStatus::IS_PARTIAL_CHAIN,
+            Check::ChainBuilding,
+            "the chain couldn't be built up to a root",
+        ),
+        (
+            ChainStatus::IS_UNTRUSTED_ROOT,
+            Check::ChainBuilding,
+            "the chain ends at a root that isn't trusted",
+        ),
+        (
+            ChainStatus::IS_NOT_SIGNATURE_VALID,
+            Check::ChainBuilding,
+            "a certificate in the chain has an invalid signature",
+        ),
+        (
+            ChainStatus::IS_NOT_VALID_FOR_USAGE,
+            Check::ChainBuilding,
+            "a certificate in the chain isn't valid for code signing",
+        ),
+        (
+            ChainStatus::IS_NOT_TIME_VALID,
+            Check::TimeValidity,
+            "a certificate in the chain is expired, or not yet valid",
+        ),
+        (
+            ChainStatus::IS_REVOKED,
+            Check::Revocation,
+            "a certificate in the chain was revoked",
+        ),
+        (
+            ChainStatus::REVOCATION_STATUS_UNKNOWN | ChainStatus::IS_OFFLINE_REVOCATION,
+            Check::Revocation,
+            "revocation couldn't be determined",
+        ),
+    ];
+
+    for (bits, check, detail) in checks.iter() {
+        if status.error_status & bits != 0 {
+            failures.push(FailedCheck {
+                check: *check,
+                detail: detail.to_string(),
+            });
+        }
+    }
+    // The SecTrust checks behind the bits, macOS only
+    failures.extend(status.details.iter().map(|key| FailedCheck {
+        check: Check::ChainBuilding,
+        detail: format!("the trust evaluation failed {}", key),
+    }));
+}
+
+/// One entry for each nested code item that failed, nested items after their parent
+fn nested_failures(nested: &[NestedCode], failures: &mut Vec<FailedCheck>) {
+    for item in nested {
+        if let Err(err) = &item.result {
+            failures.push(FailedCheck {
+                check: Check::NestedCode,
+                detail: format!("{}: {}", item.path.display(), err),
+            });
+        }
+        nested_failures(&item.nested, failures);
+    }
+}
+
+pub(crate) fn explain(err: &Error) -> Diagnostics {
+    let mut failures = Vec::new();
+    let mut fail = |check, detail: &str| {
+        failures.push(FailedCheck {
+            check,
+            detail: detail.to_string(),
+        })
+    };
+
+    match err {
+        Error::Unsigned => fail(Check::Signature, "the code carries no signature"),
+        Error::SignatureStripped(remnant) => fail(
+            Check::Signature,
+            match remnant {
+                SignatureRemnant::CertificateTable => {
+                    "the PE header records a certificate table the file no longer holds in full"
+                }
+                SignatureRemnant::LoadCommand => {
+                    "a Mach-O load command records a signature the file no longer holds in full"
+                }
+                SignatureRemnant::BundleSeal => "the bundle is sealed, but its code isn't signed",
+            },
+        ),
+        Error::SignerNotFound => fail(Check::Signature, "the requested signature isn't present"),
+        Error::AdHocSigned(signature) => fail(
+            Check::ChainBuilding,
+            &format!(
+                "the signature is ad-hoc, without any certificate, cdhash {}",
+                signature.cd_hash
+            ),
+        ),
+        Error::LeafCertNotFound => fail(
+            Check::ChainBuilding,
+            "the signing certificate couldn't be read",
+        ),
+        Error::Tampered { .. } => fail(
+            Check::CodeIntegrity,
+            "the code no longer matches the hashes its signature covers",
+        ),
+        Error::ResourceModified { .. } => fail(
+            Check::ResourceSeal,
+            "a resource sealed by the signature was modified, added or removed",
+        ),
+        Error::NestedCode(nested) => {
+            nested_failures(nested, &mut failures);
+        }
+        Error::Expired { status, .. }
+        | Error::Revoked { status, .. }
+        | Error::UntrustedRoot { status, .. }
+        | Error::ChainFailed { status, .. } => {
+            chain_failures(status, &mut failures);
+            if failures.is_empty() {
+                failures.push(FailedCheck {
+                    check: Check::ChainBuilding,
+                    detail: "the certificate chain was rejected".to_string(),
+                });
+            }
+        }
+        Error::RequirementFailed => fail(
+            Check::Requirement,
+            "the signature doesn't satisfy the requirement",
+        ),
+        #[cfg(windows)]
+        Error::DriverPolicy(_) => fail(
+            Check::Requirement,
+            "the signature doesn't satisfy kernel-mode signing policy",
+        ),
+        #[cfg(target_os = "macos")]
+        Error::InvalidRequirement(_) => fail(Check::Requirement, &err.to_string()),
+        Error::ThumbprintMismatch => fail(
+            Check::Pinning,
+            "the signing certificate isn't the pinned certificate",
+        ),
+        Error::PublisherMismatch => fail(
+            Check::Pinning,
+            "the signing certificate isn't from the pinned publisher",
+        ),
+        Error::TimestampRequired => fail(
+            Check::TimeValidity,
+            "the short-lived signing certificate is only valid with an RFC 3161 timestamp",
+        ),
+        Error::Invalidated | Error::ImageMismatch => fail(Check::RunningProcess, &err.to_string()),
+        Error::InvalidPath
+        | Error::FileChanged
+        | Error::SymlinkRejected(_)
+        | Error::InvalidCertificate
+        | Error::IoError(_) => fail(Check::Input, &err.to_string()),
+        #[cfg(feature = "sigstore")]
+        Error::Sigstore(failure) => fail(Check::Signature, &failure.to_string()),
+        _ => fail(Check::Other, &err.to_string()),
+    }
+
+    // Stable, so checks of the same kind keep the order they were found in
+    failures.sort_by_key(|failure| failure.check);
+
+    Diagnostics {
+        summary: err.to_string(),
+        failures,
+        code: err.code(),
+        native_message: err.native_message().map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Check;
+    use crate::{ChainStatus, Error, NestedCode};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_explain() {
+        let err = Error::chain_failed(
+            -1,
+            ChainStatus::from_error_status(
+                ChainStatus::IS_REVOKED
+                    | ChainStatus::IS_NOT_TIME_VALID
+                    | ChainStatus::IS_PARTIAL_CHAIN,
+            ),
+            None,
+        );
+        let checks = err
+            .explain()
+            .failures
+            .iter()
+            .map(|failure| failure.check)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            [Check::ChainBuilding, Check::TimeValidity, Check::Revocation]
+        );
+
+        let err = Error::NestedCode(vec![NestedCode {
+            path: PathBuf::from("Helper.app"),
+            result: Ok(()),
+            nested: vec![NestedCode {
+                path: PathBuf::from("Helper.app/Contents/MacOS/helper"),
+                result: Err(Error::Unsigned),
+                nested: Vec::new(),
+            }],
+        }]);
+        let diagnostics = err.explain();
+        assert_eq!(diagnostics.failures.len(), 1);
+        assert!(diagnostics.failures[0].detail.contains("helper"));
+        assert!(diagnostics.failed(Check::NestedCode));
+        assert!(diagnostics.to_string().starts_with(&err.to_string()));
+
+        assert!(Error::Unsigned.explain().failed(Check::Signature));
+        assert!(Error::TimestampRequired
+            .explain()
+            .failed(Check::TimeValidity));
+    }
+}
@@ -0,0 +1,220 @@
+//!
+//! Hex encoding and sha256 hashing, shared by both platforms.
+//!
+//! Thumbprints and hashes are formatted for every file a scan touches, so encoding goes
+//! through a lookup table rather than a `format!` per byte, and files are hashed straight
+//! from a memory mapping when the `mmap` feature is enabled.
+//!
+
+use sha2::Digest;
+use std::path::Path;
+
+const HEX_LOWER: &[u8; 16] = b"0123456789abcdef";
+const HEX_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+fn encode(bytes: &[u8], digits: &[u8; 16]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(digits[(byte >> 4) as usize] as char);
+        hex.push(digits[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Lowercase hex, as thumbprints and Windows hashes are formatted
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    encode(bytes, HEX_LOWER)
+}
+
+/// Uppercase hex, as `codesign` formats code directory hashes
+#[cfg(target_os = "macos")]
+pub(crate) fn hex_upper(bytes: &[u8]) -> String {
+    encode(bytes, HEX_UPPER)
+}
+
+///
+/// How serial numbers and thumbprints are written as hex. The default is what `serial` and
+/// `sha256_thumbprint` return on every platform: lowercase digits without separators, and
+/// serials without the leading zero bytes DER pads them with.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::{CodeSignVerifier, HexFormat};
+///
+/// let ctx = CodeSignVerifier::for_file("/sbin/ping").unwrap().verify("").unwrap();
+/// // As Keychain Access shows it, e.g. "5B 49 83 27 ..."
+/// let serial = ctx.serial_with(&HexFormat::new().uppercase().separator(' '));
+/// ```
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HexFormat {
+    uppercase: bool,
+    separator: Option<char>,
+    leading_zeros: bool,
+}
+
+impl HexFormat {
+    pub fn new() -> Self {
+        HexFormat::default()
+    }
+
+    /// Use uppercase digits, as certutil and the Windows certificate dialog do
+    pub fn uppercase(mut self) -> Self {
+        self.uppercase = true;
+        self
+    }
+
+    /// Put the separator between bytes, e.g. ':' like openssl or ' ' like Keychain Access
+    pub fn separator(mut self, separator: char) -> Self {
+        self.separator = Some(separator);
+        self
+    }
+
+    /// Keep the leading zero byte DER puts before serials whose first byte has its high bit
+    /// set, as Windows shows them. Thumbprints always keep every byte.
+    pub fn leading_zeros(mut self) -> Self {
+        self.leading_zeros = true;
+        self
+    }
+
+    /// Write the bytes as hex
+    pub fn format(&self, bytes: &[u8]) -> String {
+        let digits = if self.uppercase { HEX_UPPER } else { HEX_LOWER };
+        match self.separator {
+            None => encode(bytes, digits),
+            Some(separator) => {
+                let mut hex = String::with_capacity(bytes.len() * 3);
+                for (i, byte) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        hex.push(separator);
+                    }
+                    hex.push_str(&encode(&[*byte], digits));
+                }
+                hex
+            }
+        }
+    }
+
+    /// Write a serial number, as encoded in the certificate, as hex
+    pub(crate) fn format_serial(&self, serial: &[u8]) -> String {
+        let start = if self.leading_zeros {
+            0
+        } else {
+            // A zero serial still keeps its one byte
+            serial
+                .iter()
+                .position(|byte| *byte != 0)
+                .unwrap_or(serial.len().saturating_sub(1))
+        };
+        self.format(&serial[start..])
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    sha2::Sha256::digest(data).into()
+}
+
+/// The contents of a file, mapped into memory when the `mmap` feature is enabled and read
+/// into a buffer otherwise
+pub(crate) struct FileData {
+    #[cfg(feature = "mmap")]
+    map: Option<memmap2::Mmap>, // Empty files can't be mapped
+    #[cfg(not(feature = "mmap"))]
+    data: Vec<u8>,
+}
+
+impl FileData {
+    #[cfg(feature = "mmap")]
+    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let map = match file.metadata()?.len() {
+            0 => None,
+            // The mapping is only read, a file truncated meanwhile fails the read like any I/O
+            _ => Some(unsafe { memmap2::Mmap::map(&file)? }),
+        };
+        Ok(FileData { map })
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(FileData {
+            data: std::fs::read(path)?,
+        })
+    }
+}
+
+impl std::ops::Deref for FileData {
+    type Target = [u8];
+
+    #[cfg(feature = "mmap")]
+    fn deref(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or(&[])
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Hash a file's contents, mapped into memory with the `mmap` feature and streamed otherwise
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    if cfg!(feature = "mmap") {
+        return Ok(sha256(&FileData::open(path)?));
+    }
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut file, &mut HashWriter(&mut hasher))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Feeds written bytes to the hasher, so files can be streamed into it with `io::copy`
+struct HashWriter<'a>(&'a mut sha2::Sha256);
+
+impl std::io::Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hex, sha256, HexFormat};
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(&[0x00, 0x0f, 0xa5, 0xff]), "000fa5ff");
+        assert_eq!(hex(&[]), "");
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_hex_format() {
+        let serial = [0x00, 0x9a, 0x0f];
+        assert_eq!(HexFormat::new().format_serial(&serial), "9a0f");
+        assert_eq!(
+            HexFormat::new().leading_zeros().format_serial(&serial),
+            "009a0f"
+        );
+        assert_eq!(
+            HexFormat::new()
+                .uppercase()
+                .separator(':')
+                .format_serial(&serial),
+            "9A:0F"
+        );
+        assert_eq!(HexFormat::new().format_serial(&[0x00]), "00");
+        assert_eq!(HexFormat::new().format(&[0x00, 0x01]), "0001");
+        assert_eq!(HexFormat::new().separator(' ').format(&[]), "");
+    }
+}
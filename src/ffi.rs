@@ -0,0 +1,315 @@
+//!
+//! A C interface to the crate, enabled with the `ffi` feature.
+//!
+//! The declarations are in `include/codesign_verify.h`. Build the library for linking with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Every function returning a pointer hands over ownership: contexts are released with
+//! `csv_context_free` and strings with `csv_string_free`. Strings are UTF-8 and NUL
+//! terminated. The status codes and function signatures only ever get added to, so a library
+//! built from a newer release can replace an older one.
+//!
+
+use crate::{CodeSignVerifier, Error, ErrorKind, Name, SignatureContext, VerificationOptions};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// The signature is valid
+pub const CSV_OK: i32 = 0;
+/// Verification failed for a reason without a status of its own, see `csv_last_error`
+pub const CSV_ERROR: i32 = 1;
+/// The code isn't signed
+pub const CSV_UNSIGNED: i32 = 2;
+/// The code was modified after it was signed
+pub const CSV_TAMPERED: i32 = 3;
+/// The certificate chain doesn't end at a trusted root
+pub const CSV_UNTRUSTED_ROOT: i32 = 4;
+/// A certificate in the chain is expired
+pub const CSV_EXPIRED: i32 = 5;
+/// A certificate in the chain was revoked
+pub const CSV_REVOKED: i32 = 6;
+/// The signature is valid, but doesn't satisfy the requirement
+pub const CSV_REQUIREMENT_FAILED: i32 = 7;
+/// A required pointer was null, or a string wasn't valid UTF-8
+pub const CSV_INVALID_ARGUMENT: i32 = 8;
+/// The library failed internally, which is a bug
+pub const CSV_INTERNAL_ERROR: i32 = 9;
+/// The code was signed, but its signature was removed or cut short
+pub const CSV_SIGNATURE_STRIPPED: i32 = 10;
+
+/// The common name of a certificate name, for `csv_context_subject` and `csv_context_issuer`
+pub const CSV_NAME_COMMON_NAME: i32 = 0;
+/// The organization of a certificate name
+pub const CSV_NAME_ORGANIZATION: i32 = 1;
+/// The organizational unit of a certificate name
+pub const CSV_NAME_ORGANIZATION_UNIT: i32 = 2;
+/// The country of a certificate name
+pub const CSV_NAME_COUNTRY: i32 = 3;
+
+/// A verified signature, opaque to C
+#[allow(non_camel_case_types)]
+pub struct csv_context(SignatureContext);
+
+thread_local! {
+    // The message of the last failure on this thread, for csv_last_error
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+fn status(err: &Error) -> i32 {
+    match err.kind() {
+        ErrorKind::Unsigned => CSV_UNSIGNED,
+        ErrorKind::SignatureStripped => CSV_SIGNATURE_STRIPPED,
+        ErrorKind::Tampered => CSV_TAMPERED,
+        ErrorKind::UntrustedRoot => CSV_UNTRUSTED_ROOT,
+        ErrorKind::Expired => CSV_EXPIRED,
+        ErrorKind::Revoked => CSV_REVOKED,
+        ErrorKind::RequirementFailed => CSV_REQUIREMENT_FAILED,
+        ErrorKind::Other => CSV_ERROR,
+    }
+}
+
+/// Read an optional C string argument, None for a null pointer
+unsafe fn optional_str<'a>(s: *const c_char) -> Result<Option<&'a str>, i32> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s).to_str().map(Some).map_err(|_| {
+        set_last_error("string argument isn't valid UTF-8".to_string());
+        CSV_INVALID_ARGUMENT
+    })
+}
+
+/// Check the out argument and clear it, before any other argument can fail
+unsafe fn clear_out(out: *mut *mut csv_context) -> Result<(), i32> {
+    if out.is_null() {
+        set_last_error("out is null".to_string());
+        return Err(CSV_INVALID_ARGUMENT);
+    }
+    *out = std::ptr::null_mut();
+    Ok(())
+}
+
+/// Run a verification, storing the context in `out` and returning its status
+unsafe fn verify(
+    out: *mut *mut csv_context,
+    requirement: *const c_char,
+    verifier: impl FnOnce() -> Result<CodeSignVerifier, Error>,
+) -> i32 {
+    if let Err(status) = clear_out(out) {
+        return status;
+    }
+
+    let requirement = match optional_str(requirement) {
+        Ok(requirement) => requirement,
+        Err(status) => return status,
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut options = VerificationOptions::new();
+        if let Some(requirement) = requirement {
+            options = options.requirement(requirement);
+        }
+        verifier().and_then(|verifier| verifier.verify_with(&options))
+    }));
+
+    match result {
+        Ok(Ok(ctx)) => {
+            *out = Box::into_raw(Box::new(csv_context(ctx)));
+            CSV_OK
+        }
+        Ok(Err(err)) => {
+            set_last_error(err.to_string());
+            status(&err)
+        }
+        Err(_) => {
+            set_last_error("verification panicked".to_string());
+            CSV_INTERNAL_ERROR
+        }
+    }
+}
+
+/// Hand a string over to the caller, null for None
+fn into_c_string(s: Option<String>) -> *mut c_char {
+    s.and_then(|s| CString::new(s).ok())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+fn name_field(name: Name, field: i32) -> Option<String> {
+    match field {
+        CSV_NAME_COMMON_NAME => name.common_name,
+        CSV_NAME_ORGANIZATION => name.organization,
+        CSV_NAME_ORGANIZATION_UNIT => name.organization_unit,
+        CSV_NAME_COUNTRY => name.country,
+        _ => None,
+    }
+}
+
+/// Verify the file or bundle at `path`, against `requirement` if it isn't null.
+/// On success stores a context in `out` and returns `CSV_OK`, otherwise stores null.
+///
+/// # Safety
+///
+/// `path` and `requirement` must be null or NUL terminated strings, and `out` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn csv_verify_path(
+    path: *const c_char,
+    requirement: *const c_char,
+    out: *mut *mut csv_context,
+) -> i32 {
+    if let Err(status) = clear_out(out) {
+        return status;
+    }
+    let path = match optional_str(path) {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            set_last_error("path is null".to_string());
+            return CSV_INVALID_ARGUMENT;
+        }
+        Err(status) => return status,
+    };
+    verify(out, requirement, || CodeSignVerifier::for_file(path))
+}
+
+/// Verify the running process with the given PID, against `requirement` if it isn't null.
+/// On success stores a context in `out` and returns `CSV_OK`, otherwise stores null.
+///
+/// # Safety
+///
+/// `requirement` must be null or a NUL terminated string, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn csv_verify_pid(
+    pid: i32,
+    requirement: *const c_char,
+    out: *mut *mut csv_context,
+) -> i32 {
+    verify(out, requirement, || CodeSignVerifier::for_pid(pid))
+}
+
+/// A field of the leaf certificate's subject, one of the `CSV_NAME_*` constants.
+/// Returns null when the name has no such field. Free with `csv_string_free`.
+///
+/// # Safety
+///
+/// `ctx` must be null or a context returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv_context_subject(ctx: *const csv_context, field: i32) -> *mut c_char {
+    match ctx.as_ref() {
+        Some(ctx) => into_c_string(name_field(ctx.0.subject_name(), field)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// A field of the leaf certificate's issuer, one of the `CSV_NAME_*` constants.
+/// Returns null when the name has no such field. Free with `csv_string_free`.
+///
+/// # Safety
+///
+/// `ctx` must be null or a context returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv_context_issuer(ctx: *const csv_context, field: i32) -> *mut c_char {
+    match ctx.as_ref() {
+        Some(ctx) => into_c_string(name_field(ctx.0.issuer_name(), field)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// The sha256 thumbprint of the leaf certificate, in hex. Free with `csv_string_free`.
+///
+/// # Safety
+///
+/// `ctx` must be null or a context returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv_context_thumbprint(ctx: *const csv_context) -> *mut c_char {
+    match ctx.as_ref() {
+        Some(ctx) => into_c_string(Some(ctx.0.sha256_thumbprint())),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Release a context. Does nothing for null.
+///
+/// # Safety
+///
+/// `ctx` must be null or a context returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv_context_free(ctx: *mut csv_context) {
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+/// The message of the last failure on the calling thread, or null if there was none.
+/// Free with `csv_string_free`.
+#[no_mangle]
+pub extern "C" fn csv_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null_mut(), |message| message.clone().into_raw())
+    })
+}
+
+/// Release a string returned by this library. Does nothing for null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        let path = CString::new(crate::signed_system_binary()).unwrap();
+
+        unsafe {
+            let mut ctx = std::ptr::null_mut();
+            assert_eq!(
+                csv_verify_path(path.as_ptr(), std::ptr::null(), &mut ctx),
+                CSV_OK
+            );
+            assert!(!ctx.is_null());
+
+            let thumbprint = csv_context_thumbprint(ctx);
+            assert_eq!(CStr::from_ptr(thumbprint).to_bytes().len(), 64);
+            csv_string_free(thumbprint);
+            csv_string_free(csv_context_subject(ctx, CSV_NAME_COMMON_NAME));
+            assert!(csv_context_subject(ctx, 42).is_null());
+            csv_context_free(ctx);
+
+            let missing = CString::new("/nonexistent/file").unwrap();
+            assert_ne!(
+                csv_verify_path(missing.as_ptr(), std::ptr::null(), &mut ctx),
+                CSV_OK
+            );
+            assert!(ctx.is_null());
+            let message = csv_last_error();
+            assert!(!message.is_null());
+            csv_string_free(message);
+
+            // Cleared even when an argument is rejected
+            ctx = std::ptr::NonNull::dangling().as_ptr();
+            assert_eq!(
+                csv_verify_path(std::ptr::null(), std::ptr::null(), &mut ctx),
+                CSV_INVALID_ARGUMENT
+            );
+            assert!(ctx.is_null());
+        }
+    }
+}
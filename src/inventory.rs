@@ -0,0 +1,323 @@
+//!
+//! Signer inventories of scanned files.
+//!
+//! An `Inventory` collects the results of a scan, or of any batch verification, by the
+//! certificate each file was signed with. Its signers can then be grouped by publisher, team ID
+//! or thumbprint, summarized, and turned into a starting allowlist of one rule per group.
+//!
+
+use crate::policy::{Action, Condition, Rule, Ruleset};
+use crate::scan::ScannedFile;
+use crate::{Error, Name, PlatformProperties};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+///
+/// A signing certificate, and the files found signed with it
+///
+/// # Fields
+///
+/// `sha256_thumbprint`: The thumbprint of the leaf certificate
+///
+/// `subject`, `issuer`: The names on the leaf certificate
+///
+/// `team_id`: The team identifier of the signatures, macOS only
+///
+/// `files`: The files signed with the certificate, in the order they were added
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signer {
+    pub sha256_thumbprint: String,
+    pub subject: Name,
+    pub issuer: Name,
+    pub team_id: Option<String>,
+    pub files: Vec<PathBuf>,
+}
+
+impl Signer {
+    /// The organization the certificate was issued to, or its common name without one
+    pub fn publisher(&self) -> Option<&str> {
+        self.subject
+            .organization
+            .as_deref()
+            .or(self.subject.common_name.as_deref())
+    }
+}
+
+///
+/// What signers are grouped by
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Publisher,  // The organization, or common name, the certificate was issued to
+    TeamId,     // The team identifier, macOS only
+    Thumbprint, // The leaf certificate itself
+}
+
+///
+/// Signers sharing a publisher, team ID or thumbprint
+///
+/// # Fields
+///
+/// `key`: The publisher, team ID or thumbprint the signers share
+///
+/// `thumbprints`: The thumbprints of the certificates in the group
+///
+/// `files`: The files signed by any of them
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignerGroup {
+    pub key: String,
+    pub thumbprints: Vec<String>,
+    pub files: Vec<PathBuf>,
+}
+
+///
+/// The counts of an inventory
+///
+/// # Fields
+///
+/// `files`: Every file added
+///
+/// `signed`, `unsigned`, `failed`: The files with a valid signature, without any signature,
+/// and with one that failed verification
+///
+/// `signers`, `publishers`, `team_ids`: The distinct certificates, publishers and team IDs
+/// the signed files were signed by
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InventorySummary {
+    pub files: usize,
+    pub signed: usize,
+    pub unsigned: usize,
+    pub failed: usize,
+    pub signers: usize,
+    pub publishers: usize,
+    pub team_ids: usize,
+}
+
+impl std::fmt::Display for InventorySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files: {} signed by {} certificates from {} publishers, {} unsigned, {} failed",
+            self.files, self.signed, self.signers, self.publishers, self.unsigned, self.failed
+        )
+    }
+}
+
+///
+/// The files of a scan, by the certificate they were signed with
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::inventory::{GroupBy, Inventory};
+/// use codesign_verify::scan::Scanner;
+///
+/// let inventory = Scanner::new("/Applications").scan().collect::<Inventory>();
+/// println!("{}", inventory.summary());
+/// for group in inventory.group_by(GroupBy::Publisher) {
+///     println!("{}: {} files", group.key, group.files.len());
+/// }
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct Inventory {
+    signers: Vec<Signer>,
+    by_thumbprint: HashMap<String, usize>, // Index of each thumbprint's signer
+    unsigned: Vec<PathBuf>,
+    failed: Vec<(PathBuf, Error)>,
+}
+
+impl Inventory {
+    /// An empty inventory, see also `collect`
+    pub fn new() -> Self {
+        Inventory::default()
+    }
+
+    /// Add a scanned file to the inventory
+    pub fn add(&mut self, file: ScannedFile) {
+        let ctx = match file.result {
+            Ok(ctx) => ctx,
+            Err(Error::Unsigned) => return self.unsigned.push(file.path),
+            Err(err) => return self.failed.push((file.path, err)),
+        };
+
+        let thumbprint = ctx.sha256_thumbprint();
+        if let Some(&index) = self.by_thumbprint.get(&thumbprint) {
+            return self.signers[index].files.push(file.path);
+        }
+
+        let report = ctx.report();
+        let team_id = match report.platform {
+            PlatformProperties::Mac(mac) => mac.team_id,
+            PlatformProperties::Windows(_) => None,
+        };
+        self.by_thumbprint
+            .insert(thumbprint.clone(), self.signers.len());
+        self.signers.push(Signer {
+            sha256_thumbprint: thumbprint,
+            subject: report.subject,
+            issuer: report.issuer,
+            team_id,
+            files: vec![file.path],
+        });
+    }
+
+    /// Each signing certificate, in the order it was first seen
+    pub fn signers(&self) -> &[Signer] {
+        &self.signers
+    }
+
+    /// The files without any signature
+    pub fn unsigned(&self) -> &[PathBuf] {
+        &self.unsigned
+    }
+
+    /// The files whose signature failed verification, and why
+    pub fn failed(&self) -> &[(PathBuf, Error)] {
+        &self.failed
+    }
+
+    /// Group the signers by the key, largest group first. Signers without the key, such as
+    /// any signer when grouping by team ID on Windows, are left out.
+    pub fn group_by(&self, by: GroupBy) -> Vec<SignerGroup> {
+        let mut groups: BTreeMap<&str, SignerGroup> = BTreeMap::new();
+
+        for signer in &self.signers {
+            let key = match by {
+                GroupBy::Publisher => signer.publisher(),
+                GroupBy::TeamId => signer.team_id.as_deref(),
+                GroupBy::Thumbprint => Some(signer.sha256_thumbprint.as_str()),
+            };
+            let key = match key {
+                Some(key) => key,
+                None => continue,
+            };
+
+            let group = groups.entry(key).or_insert_with(|| SignerGroup {
+                key: key.to_string(),
+                thumbprints: Vec::new(),
+                files: Vec::new(),
+            });
+            group.thumbprints.push(signer.sha256_thumbprint.clone());
+            group.files.extend(signer.files.iter().cloned());
+        }
+
+        // The map keeps ties in key order, and the sort is stable
+        let mut groups = groups.into_values().collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()));
+        groups
+    }
+
+    pub fn summary(&self) -> InventorySummary {
+        let signed = self.signers.iter().map(|signer| signer.files.len()).sum();
+
+        InventorySummary {
+            files: signed + self.unsigned.len() + self.failed.len(),
+            signed,
+            unsigned: self.unsigned.len(),
+            failed: self.failed.len(),
+            signers: self.signers.len(),
+            publishers: self.group_by(GroupBy::Publisher).len(),
+            team_ids: self.group_by(GroupBy::TeamId).len(),
+        }
+    }
+
+    /// A ruleset allowing each group and denying everything else, as a starting allowlist.
+    /// Rules are named after their group's key, and publishers are matched by organization,
+    /// or by common name for certificates without one.
+    pub fn allowlist(&self, by: GroupBy) -> Ruleset {
+        self.group_by(by)
+            .into_iter()
+            .fold(Ruleset::new(Action::Deny), |rules, group| {
+                let condition = match by {
+                    GroupBy::Publisher => {
+                        let signer = &self.signers[self.by_thumbprint[&group.thumbprints[0]]];
+                        match signer.subject.organization {
+                            Some(_) => Condition::Organization(group.key.clone()),
+                            None => Condition::CommonName(group.key.clone()),
+                        }
+                    }
+                    GroupBy::TeamId => Condition::TeamId(group.key.clone()),
+                    GroupBy::Thumbprint => Condition::Thumbprint(group.key.clone()),
+                };
+                rules.rule(Rule::allow(&group.key, condition))
+            })
+    }
+}
+
+impl Extend<ScannedFile> for Inventory {
+    fn extend<I: IntoIterator<Item = ScannedFile>>(&mut self, files: I) {
+        for file in files {
+            self.add(file);
+        }
+    }
+}
+
+impl std::iter::FromIterator<ScannedFile> for Inventory {
+    fn from_iter<I: IntoIterator<Item = ScannedFile>>(files: I) -> Self {
+        let mut inventory = Inventory::new();
+        inventory.extend(files);
+        inventory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupBy, Inventory};
+    use crate::scan::{FileFormat, ScannedFile};
+    use crate::{CodeSignVerifier, Error};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_inventory() {
+        let path = crate::signed_system_binary();
+
+        let ctx = CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify("")
+            .unwrap();
+        let verified = |path: &str| ScannedFile {
+            path: PathBuf::from(path),
+            format: FileFormat::Pe,
+            result: Ok(ctx.clone()),
+        };
+        let inventory = vec![
+            verified(path),
+            verified(path),
+            ScannedFile {
+                path: PathBuf::from("unsigned.sh"),
+                format: FileFormat::Script,
+                result: Err(Error::Unsigned),
+            },
+        ]
+        .into_iter()
+        .collect::<Inventory>();
+
+        let summary = inventory.summary();
+        assert_eq!(
+            (
+                summary.files,
+                summary.signed,
+                summary.unsigned,
+                summary.signers
+            ),
+            (3, 2, 1, 1)
+        );
+        assert_eq!(inventory.unsigned(), &[PathBuf::from("unsigned.sh")]);
+
+        let signer = &inventory.signers()[0];
+        let groups = inventory.group_by(GroupBy::Thumbprint);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].key, signer.sha256_thumbprint);
+        assert_eq!(groups[0].files.len(), 2);
+
+        let rules = inventory.allowlist(GroupBy::Thumbprint);
+        assert!(rules.evaluate(&ctx.report(), None).is_allowed());
+    }
+}
@@ -0,0 +1,355 @@
+//!
+//! JSON serialization of verification reports.
+//!
+//! The layout is written out by hand rather than derived, so it stays the same across
+//! platforms and releases regardless of how the Rust types evolve. See
+//! `VerificationReport::to_json` for the schema.
+//!
+
+use crate::audit::AuditRecord;
+use crate::{
+    ApplicationManifest, ChainStatus, ExecutionLevel, Name, PeMachine, PlatformProperties,
+    RevocationSource, RevocationState, RevocationStatus, TimeValidity, VerificationReport,
+};
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever a key is removed or changes meaning, adding keys doesn't change it
+const SCHEMA_VERSION: u32 = 1;
+
+/// Whole seconds since the Unix epoch, negative for earlier times
+fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    }
+}
+
+fn name(name: &Name) -> Value {
+    json!({
+        "common_name": name.common_name,
+        "organization": name.organization,
+        "organization_unit": name.organization_unit,
+        "country": name.country,
+    })
+}
+
+fn chain(status: &ChainStatus) -> Value {
+    json!({
+        "error_status": status.error_status,
+        "info_status": status.info_status,
+        "details": status.details,
+        "expired": status.is_expired(),
+        "revoked": status.is_revoked(),
+        "untrusted_root": status.is_untrusted_root(),
+        "partial_chain": status.is_partial_chain(),
+        "revocation_unknown": status.is_revocation_unknown(),
+    })
+}
+
+fn revocation(status: &RevocationStatus) -> Value {
+    let state = match status.state {
+        RevocationState::Good => "good",
+        RevocationState::Revoked => "revoked",
+        RevocationState::Unknown => "unknown",
+        RevocationState::NotChecked => "not_checked",
+    };
+    let source = status.source.map(|source| match source {
+        RevocationSource::Crl => "crl",
+        RevocationSource::Ocsp => "ocsp",
+    });
+
+    json!({
+        "thumbprint": status.thumbprint,
+        "state": state,
+        "revocation_time": status.revocation_time.map(unix_time),
+        "source": source,
+        "this_update": status.this_update.map(unix_time),
+        "next_update": status.next_update.map(unix_time),
+        "produced_at": status.produced_at.map(unix_time),
+    })
+}
+
+fn manifest_json(manifest: &ApplicationManifest) -> Value {
+    let execution_level = manifest.execution_level.map(|level| match level {
+        ExecutionLevel::AsInvoker => "asInvoker",
+        ExecutionLevel::HighestAvailable => "highestAvailable",
+        ExecutionLevel::RequireAdministrator => "requireAdministrator",
+    });
+    let identity = manifest.assembly_identity.as_ref().map(|identity| {
+        json!({
+            "name": identity.name,
+            "version": identity.version,
+            "processor_architecture": identity.processor_architecture,
+            "type": identity.kind,
+        })
+    });
+
+    json!({
+        "execution_level": execution_level,
+        "ui_access": manifest.ui_access,
+        "assembly_identity": identity,
+    })
+}
+
+fn machine(machine: PeMachine) -> Value {
+    match machine {
+        PeMachine::X86 => json!("x86"),
+        PeMachine::X64 => json!("x64"),
+        PeMachine::Arm => json!("arm"),
+        PeMachine::Arm64 => json!("arm64"),
+        PeMachine::Arm64EC => json!("arm64ec"),
+        PeMachine::Arm64X => json!("arm64x"),
+        PeMachine::Other(machine) => json!(machine),
+    }
+}
+
+impl VerificationReport {
+    #[cfg(windows)]
+    fn driver_policy_json(&self) -> Value {
+        let signing = self.driver_policy.signing.map(|signing| match signing {
+            crate::DriverSigning::Whql => "whql",
+            crate::DriverSigning::Attestation => "attestation",
+            crate::DriverSigning::WindowsComponent => "windows_component",
+        });
+        json!({
+            "microsoft_root": self.driver_policy.microsoft_root,
+            "signing": signing,
+        })
+    }
+
+    #[cfg(not(windows))]
+    fn driver_policy_json(&self) -> Value {
+        Value::Null
+    }
+
+    /// Serialize the report as a single line of JSON with a stable layout, for log pipelines
+    /// and SIEM ingestion. Every key is present on both platforms, `null` when it doesn't
+    /// apply. Times are whole seconds since the Unix epoch.
+    ///
+    /// ```text
+    /// {
+    ///   "schema_version": 1,
+    ///   "platform": "macos" | "windows",
+    ///   "subject": { "common_name", "organization", "organization_unit", "country" },
+    ///   "issuer": { same as subject },
+    ///   "sha256_thumbprint": string,
+    ///   "serial": string | null,
+    ///   "signing_time": int | null,
+    ///   "timestamp": int | null,
+    ///   "not_before": int | null,
+    ///   "not_after": int | null,
+    ///   "time_validity": "current" | "timestamped" | "expired",
+    ///   "chain": { "error_status": int, "info_status": int, "details": [string],
+    ///              "expired", "revoked", "untrusted_root", "partial_chain",
+    ///              "revocation_unknown": bool },
+    ///   "revocation": [{ "thumbprint": string,
+    ///                    "state": "good" | "revoked" | "unknown" | "not_checked",
+    ///                    "revocation_time": int | null, "source": "crl" | "ocsp" | null,
+    ///                    "this_update", "next_update", "produced_at": int | null }],
+    ///   "runtime_version": "major.minor.patch" | null,
+    ///   "macos": { "cd_hash", "team_id", "identifier", "bundle_id", "short_version",
+    ///              "bundle_version": string | null, "platform_id": int | null } | null,
+    ///   "windows": { "enhanced_key_usages": [string], "microsoft_root": bool,
+    ///                "store_signed": bool,
+    ///                "catalog": { "path": string, "member_tag": string } | null,
+    ///                "product_name", "file_version", "company_name",
+    ///                "original_filename": string | null,
+    ///                "manifest": { "execution_level": "asInvoker" | "highestAvailable"
+    ///                                                 | "requireAdministrator" | null,
+    ///                              "ui_access": bool,
+    ///                              "assembly_identity": { "name", "version",
+    ///                                                     "processor_architecture",
+    ///                                                     "type": string | null } | null } | null,
+    ///                "machine": "x86" | "x64" | "arm" | "arm64" | "arm64ec" | "arm64x"
+    ///                           | int | null,
+    ///                "driver_policy": { "microsoft_root": bool,
+    ///                                   "signing": "whql" | "attestation"
+    ///                                              | "windows_component" | null } } | null
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let time_validity = match self.time_validity {
+            TimeValidity::Current => "current",
+            TimeValidity::Timestamped => "timestamped",
+            TimeValidity::Expired => "expired",
+        };
+        let (platform, macos, windows) = match &self.platform {
+            PlatformProperties::Mac(properties) => (
+                "macos",
+                json!({
+                    "cd_hash": properties.cd_hash,
+                    "team_id": properties.team_id,
+                    "identifier": properties.identifier,
+                    "bundle_id": properties.bundle_id,
+                    "short_version": properties.short_version,
+                    "bundle_version": properties.bundle_version,
+                    "platform_id": properties.platform_id,
+                }),
+                Value::Null,
+            ),
+            PlatformProperties::Windows(properties) => (
+                "windows",
+                Value::Null,
+                json!({
+                    "enhanced_key_usages": properties.enhanced_key_usages,
+                    "microsoft_root": properties.microsoft_root,
+                    "store_signed": properties.store_signed,
+                    "catalog": properties.catalog.as_ref().map(|catalog| json!({
+                        "path": catalog.path.to_string_lossy(),
+                        "member_tag": catalog.member_tag,
+                    })),
+                    "product_name": properties.product_name,
+                    "file_version": properties.file_version,
+                    "company_name": properties.company_name,
+                    "original_filename": properties.original_filename,
+                    "manifest": properties.manifest.as_ref().map(manifest_json),
+                    "machine": properties.machine.map(machine),
+                    "driver_policy": self.driver_policy_json(),
+                }),
+            ),
+        };
+
+        json!({
+            "schema_version": SCHEMA_VERSION,
+            "platform": platform,
+            "subject": name(&self.subject),
+            "issuer": name(&self.issuer),
+            "sha256_thumbprint": self.sha256_thumbprint,
+            "serial": self.serial,
+            "signing_time": self.signing_time.map(unix_time),
+            "timestamp": self.timestamp.map(unix_time),
+            "not_before": self.validity_period.map(|(not_before, _)| unix_time(not_before)),
+            "not_after": self.validity_period.map(|(_, not_after)| unix_time(not_after)),
+            "time_validity": time_validity,
+            "chain": chain(&self.chain_status),
+            "revocation": self.revocation.iter().map(revocation).collect::<Vec<_>>(),
+            "runtime_version": self.runtime_version.map(|version| version.to_string()),
+            "macos": macos,
+            "windows": windows,
+        })
+        .to_string()
+    }
+}
+
+impl AuditRecord {
+    /// Serialize the record as a single line of JSON, as `JsonLinesSink` writes it.
+    /// Times are whole seconds since the Unix epoch.
+    ///
+    /// ```text
+    /// {
+    ///   "schema_version": 1,
+    ///   "time": int,
+    ///   "target": string | null,
+    ///   "executable": string | null,
+    ///   "duration_ms": int,
+    ///   "outcome": "valid" | "failed",
+    ///   "error": string | null,
+    ///   "subject": { "common_name", "organization", "organization_unit", "country" } | null,
+    ///   "sha256_thumbprint": string | null
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        json!({
+            "schema_version": SCHEMA_VERSION,
+            "time": unix_time(self.time),
+            "target": self.target.as_ref().map(|target| target.to_string_lossy()),
+            "executable": self.executable.as_ref().map(|executable| executable.to_string_lossy()),
+            "duration_ms": self.duration.as_millis() as u64,
+            "outcome": if self.is_success() { "valid" } else { "failed" },
+            "error": self.error,
+            "subject": self.subject.as_ref().map(name),
+            "sha256_thumbprint": self.sha256_thumbprint,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ChainStatus, MacProperties, Name, PlatformProperties, RevocationSource, RevocationState,
+        RevocationStatus, TimeValidity, VerificationReport,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_to_json() {
+        let report = VerificationReport {
+            subject: Name {
+                common_name: Some("Developer ID Application: Example (ABC123)".to_string()),
+                organization: Some("Example".to_string()),
+                organization_unit: Some("ABC123".to_string()),
+                country: Some("US".to_string()),
+            },
+            issuer: Name {
+                common_name: Some("Developer ID Certification Authority".to_string()),
+                organization: Some("Apple Inc.".to_string()),
+                organization_unit: None,
+                country: None,
+            },
+            sha256_thumbprint: "AB".repeat(32),
+            serial: None,
+            signing_time: None,
+            timestamp: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            validity_period: None,
+            time_validity: TimeValidity::Timestamped,
+            chain_status: ChainStatus::default(),
+            revocation: vec![RevocationStatus {
+                thumbprint: "CD".repeat(32),
+                state: RevocationState::NotChecked,
+                revocation_time: None,
+                source: Some(RevocationSource::Ocsp),
+                this_update: None,
+                next_update: Some(UNIX_EPOCH + Duration::from_secs(1_700_086_400)),
+                produced_at: None,
+            }],
+            runtime_version: None,
+            platform: PlatformProperties::Mac(MacProperties {
+                team_id: Some("ABC123".to_string()),
+                ..MacProperties::default()
+            }),
+            #[cfg(windows)]
+            driver_policy: crate::DriverPolicy {
+                microsoft_root: false,
+                signing: None,
+            },
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["platform"], "macos");
+        assert_eq!(json["subject"]["organization_unit"], "ABC123");
+        assert_eq!(json["issuer"]["country"], serde_json::Value::Null);
+        assert_eq!(json["timestamp"], 1_700_000_000);
+        assert_eq!(json["time_validity"], "timestamped");
+        assert_eq!(json["chain"]["revoked"], false);
+        assert_eq!(json["revocation"][0]["state"], "not_checked");
+        assert_eq!(json["revocation"][0]["source"], "ocsp");
+        assert_eq!(json["revocation"][0]["next_update"], 1_700_086_400);
+        assert!(json["revocation"][0]["produced_at"].is_null());
+        assert_eq!(json["macos"]["team_id"], "ABC123");
+        assert_eq!(json["windows"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_audit_json() {
+        let record = crate::audit::AuditRecord {
+            time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            target: Some("/Applications/Example.app".into()),
+            executable: None,
+            duration: Duration::from_millis(1500),
+            error: Some("the code is not signed".to_string()),
+            subject: None,
+            sha256_thumbprint: None,
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(json["time"], 1_700_000_000);
+        assert_eq!(json["target"], "/Applications/Example.app");
+        assert_eq!(json["executable"], serde_json::Value::Null);
+        assert_eq!(json["duration_ms"], 1500);
+        assert_eq!(json["outcome"], "failed");
+        assert_eq!(json["subject"], serde_json::Value::Null);
+    }
+}
@@ -3,24 +3,106 @@ mod macos;
 #[cfg(windows)]
 mod windows;
 
+mod apple;
+pub mod audit;
+#[cfg(feature = "uniffi")]
+pub mod bindings;
+mod builder;
 #[cfg(target_os = "macos")]
-pub use macos::errSecCSBadResource;
+pub mod bundle;
+pub mod cache;
+#[cfg(feature = "cms")]
+pub mod cms;
+pub mod ct;
+mod der;
+pub mod diagnostics;
+mod digest;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod inventory;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "notarization")]
+pub mod notarization;
+mod options;
+pub mod plugin;
+pub mod policy;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
 #[cfg(target_os = "macos")]
-use macos::{Context, Verifier};
+pub mod quarantine;
+mod remnant;
+mod requirement;
+pub mod revocation;
+pub mod scan;
+pub mod session;
+#[cfg(feature = "sigstore")]
+pub mod sigstore;
+pub mod status;
+#[cfg(any(target_os = "macos", windows))]
+pub mod watch;
+#[cfg(windows)]
+pub mod wdac;
+#[cfg(windows)]
+mod xml;
+
+pub use builder::CodeSignVerifierBuilder;
+pub use digest::HexFormat;
+pub use options::{
+    Architecture, CancellationToken, Policy, RevocationMode, SymlinkPolicy, TimestampPolicy,
+    ValidationMode, VerificationOptions,
+};
+pub use requirement::{Anchor, Requirement, RequirementSyntax};
+
+#[cfg(target_os = "macos")]
+use macos::{describe_os_error, os_error_message, Context, Verifier};
+#[cfg(target_os = "macos")]
+pub use status::errSecCSBadResource;
 
 use std::collections::HashMap;
 #[cfg(windows)]
-use windows::{Context, Verifier};
+use windows::{describe_os_error, os_error_message, Context, Verifier};
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
 
 ///
 /// Used to verify the validity of a code signature
 ///
 pub struct CodeSignVerifier(Verifier);
 
+/// A path in the temporary directory no other verification uses, for data that isn't in a file
+pub(crate) fn temp_path() -> std::path::PathBuf {
+    static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    std::env::temp_dir().join(format!("codesign-verify-{}-{}", std::process::id(), count))
+}
+
+/// A binary shipped signed with every install of the OS, for tests
+#[cfg(test)]
+pub(crate) fn signed_system_binary() -> &'static str {
+    #[cfg(target_os = "macos")]
+    return "/sbin/ping";
+    #[cfg(windows)]
+    return "c:\\windows\\explorer.exe";
+}
+
+/// The context of `signed_system_binary`, verified without a requirement
+#[cfg(test)]
+pub(crate) fn verified_system_binary() -> SignatureContext {
+    CodeSignVerifier::for_file(signed_system_binary())
+        .unwrap()
+        .verify("")
+        .unwrap()
+}
+
 ///
-/// Used to extract additional information from the signing leaf certificate
+/// Used to extract additional information from the signing leaf certificate.
+/// Clones are cheap and share the same signing information.
 ///
-pub struct SignatureContext(Context);
+#[derive(Clone)]
+pub struct SignatureContext(std::sync::Arc<Context>);
 
 ///
 /// Represents an Issuer or Subject name with the following fields:
@@ -35,7 +117,9 @@ pub struct SignatureContext(Context);
 ///
 /// `country`: OID 2.5.4.6
 ///
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 pub struct Name {
     pub common_name: Option<String>,       // 2.5.4.3
     pub organization: Option<String>,      // 2.5.4.10
@@ -43,157 +127,3344 @@ pub struct Name {
     pub country: Option<String>,           // 2.5.4.6
 }
 
-#[derive(Debug)]
-pub enum Error {
-    Unsigned,         // The binary file didn't have any singature
-    OsError(i32),     // Warps an inner provider error code
-    InvalidPath,      // The provided path was malformed
-    LeafCertNotFound, // Unable to fetch certificate information
-    #[cfg(target_os = "macos")]
-    CFError(core_foundation::error::CFError),
-    #[cfg(windows)]
-    IoError(std::io::Error),
+///
+/// The trust status of the leaf certificate chain, explaining why a chain was rejected.
+///
+/// The status bits use the `CERT_TRUST_STATUS` encoding on both platforms. On macOS they are
+/// translated from the SecTrust result details, whose raw keys are kept in `details`.
+///
+/// # Fields
+///
+/// `error_status`: The `CERT_TRUST_*` error bits, e.g. `ChainStatus::IS_REVOKED`
+///
+/// `info_status`: The `CERT_TRUST_*` informational bits, only reported on Windows
+///
+/// `details`: The SecTrust checks that failed for any certificate in the chain, only reported on macOS
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChainStatus {
+    pub error_status: u32,
+    pub info_status: u32,
+    pub details: Vec<String>,
 }
 
-impl CodeSignVerifier {
-    /// Create a verifier for a binary at a given path.
-    /// On macOS it can be either a binary or an application package.
-    pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
-        Verifier::for_file(path).map(|v| CodeSignVerifier(v))
+impl ChainStatus {
+    pub const IS_NOT_TIME_VALID: u32 = 0x0000_0001;
+    pub const IS_REVOKED: u32 = 0x0000_0004;
+    pub const IS_NOT_SIGNATURE_VALID: u32 = 0x0000_0008;
+    pub const IS_NOT_VALID_FOR_USAGE: u32 = 0x0000_0010;
+    pub const IS_UNTRUSTED_ROOT: u32 = 0x0000_0020;
+    pub const REVOCATION_STATUS_UNKNOWN: u32 = 0x0000_0040;
+    pub const IS_PARTIAL_CHAIN: u32 = 0x0001_0000;
+    pub const IS_OFFLINE_REVOCATION: u32 = 0x0100_0000;
+
+    /// A status with just the given error bits, for failures reported without a chain
+    pub(crate) fn from_error_status(error_status: u32) -> Self {
+        ChainStatus {
+            error_status,
+            ..ChainStatus::default()
+        }
     }
 
-    /// Create a verifier for a running application by PID.
-    /// On Windows it will get the full path to the running application first.
-    /// This can be used for e.g. verifying the app on the other end of a pipe.
-    pub fn for_pid(pid: i32) -> Result<Self, Error> {
-        Verifier::for_pid(pid).map(|v| CodeSignVerifier(v))
+    /// Whether any certificate in the chain failed a check
+    pub fn has_errors(&self) -> bool {
+        self.error_status != 0
     }
 
-    /// Perform the verification itself.
-    /// On macOS the verification uses the Security framework with "anchor trusted" as the requirement.
-    /// On Windows the verification uses WinTrust and the `WINTRUST_ACTION_GENERIC_VERIFY_V2` action.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use codesign_verify::CodeSignVerifier;
-    ///
-    /// CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
-    /// ```
-    pub fn verify(self, requirement: &str) -> Result<SignatureContext, Error> {
-        self.0.verify(requirement).map(|c| SignatureContext(c))
+    /// A certificate in the chain is expired, or not yet valid
+    pub fn is_expired(&self) -> bool {
+        self.error_status & Self::IS_NOT_TIME_VALID != 0
+    }
+
+    /// A certificate in the chain was revoked
+    pub fn is_revoked(&self) -> bool {
+        self.error_status & Self::IS_REVOKED != 0
+    }
+
+    /// The chain ends at a root that isn't trusted
+    pub fn is_untrusted_root(&self) -> bool {
+        self.error_status & Self::IS_UNTRUSTED_ROOT != 0
+    }
+
+    /// The chain could not be built up to a root
+    pub fn is_partial_chain(&self) -> bool {
+        self.error_status & Self::IS_PARTIAL_CHAIN != 0
+    }
+
+    /// Revocation could not be determined, e.g. because the responder was unreachable
+    pub fn is_revocation_unknown(&self) -> bool {
+        self.error_status & (Self::REVOCATION_STATUS_UNKNOWN | Self::IS_OFFLINE_REVOCATION) != 0
     }
 }
 
-impl SignatureContext {
-    /// Retrieve the subject name on the leaf certificate
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use codesign_verify::CodeSignVerifier;
-    ///
-    /// let ctx = CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
-    /// assert_eq!(
-    ///    ctx.subject_name().organization.as_deref(),
-    ///    Some("Microsoft Corporation")
-    /// );
-    ///
-    /// ```
-    pub fn subject_name(&self) -> Name {
-        self.0.subject_name()
+///
+/// The outcome of checking a single certificate for revocation
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RevocationState {
+    Good,       // Revocation was checked and the certificate isn't revoked
+    Revoked,    // The certificate was revoked
+    Unknown, // Revocation was checked, but couldn't be determined, e.g. the responder was unreachable
+    NotChecked, // The certificate wasn't checked for revocation
+}
+
+///
+/// Where revocation information came from
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RevocationSource {
+    Crl,  // A certificate revocation list
+    Ocsp, // An OCSP responder
+}
+
+///
+/// The revocation status of a single certificate in the signing chain
+///
+/// # Fields
+///
+/// `thumbprint`: The sha256 thumbprint of the certificate
+///
+/// `state`: Whether the certificate was revoked
+///
+/// `revocation_time`: When the certificate was revoked, only reported on Windows
+///
+/// `source`: Where the revocation information came from, only reported on Windows
+///
+/// `this_update`: When the CRL or OCSP response the state was decided with was issued, only
+/// reported on Windows
+///
+/// `next_update`: When that revocation information expires, None if it was never checked or
+/// its issuer didn't say
+///
+/// `produced_at`: When the OCSP responder signed its response, only reported on Windows
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RevocationStatus {
+    pub thumbprint: String,
+    pub state: RevocationState,
+    pub revocation_time: Option<std::time::SystemTime>,
+    pub source: Option<RevocationSource>,
+    pub this_update: Option<std::time::SystemTime>,
+    pub next_update: Option<std::time::SystemTime>,
+    pub produced_at: Option<std::time::SystemTime>,
+}
+
+impl RevocationStatus {
+    /// Whether the revocation information was current at the time: issued before it, and not
+    /// yet due for an update. False without an expiry to go by.
+    pub fn is_current_at(&self, time: std::time::SystemTime) -> bool {
+        self.this_update
+            .map_or(true, |this_update| this_update <= time)
+            && self
+                .next_update
+                .map_or(false, |next_update| time < next_update)
     }
+}
 
-    /// Retrieve the issuer name on the leaf certificate
-    pub fn issuer_name(&self) -> Name {
-        self.0.issuer_name()
+///
+/// Why the signing certificate is considered valid in time
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeValidity {
+    Current,     // The leaf certificate is valid now
+    Timestamped, // The leaf certificate expired, but the signature carries a trusted timestamp from while it was valid
+    Expired,     // The leaf certificate expired, and there is no timestamp to fall back to
+}
+
+///
+/// Describes an ad-hoc signature, which seals the code without any certificate chain.
+/// Only produced on macOS.
+///
+/// # Fields
+///
+/// `cd_hash`: The hex encoded code directory hash
+///
+/// `flags`: The `SecCodeSignatureFlags` recorded in the code directory
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdHocSignature {
+    pub cd_hash: String,
+    pub flags: u32,
+}
+
+///
+/// The kind of signer behind a macOS signature, see `SignatureContext::signer_kind`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SignerKind {
+    DeveloperId, // A Developer ID certificate, for distribution outside the Mac App Store
+    MacAppStore, // Apple's signature on an app distributed through the Mac App Store
+    AppleDevelopment, // An Apple Development certificate, for running on the developer's devices
+    ApplePlatform, // Code that ships with the OS, which has a platform identifier
+    AdHoc,       // No certificate at all
+}
+
+///
+/// A marker extension Apple's CAs put in the certificates they issue, naming the kind of
+/// certificate. See `SignatureContext::apple_markers`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AppleMarker {
+    DeveloperIdApplication, // Developer ID Application, 1.2.840.113635.100.6.1.13
+    DeveloperIdInstaller, // Developer ID Installer, for signing packages, 1.2.840.113635.100.6.1.14
+    DeveloperIdKext,      // Allowed to sign kernel extensions, 1.2.840.113635.100.6.1.18
+    MacAppStore,          // Apple's signature on a Mac App Store app, 1.2.840.113635.100.6.1.9
+    MacDevelopment,       // Mac or Apple Development, 1.2.840.113635.100.6.1.12
+    IPhoneDevelopment,    // iPhone or Apple Development, 1.2.840.113635.100.6.1.2
+    DeveloperIdCa,        // The Developer ID intermediate CA, 1.2.840.113635.100.6.2.6
+}
+
+impl AppleMarker {
+    /// The dotted object identifier of the extension
+    pub fn oid(&self) -> &'static str {
+        match self {
+            AppleMarker::DeveloperIdApplication => "1.2.840.113635.100.6.1.13",
+            AppleMarker::DeveloperIdInstaller => "1.2.840.113635.100.6.1.14",
+            AppleMarker::DeveloperIdKext => "1.2.840.113635.100.6.1.18",
+            AppleMarker::MacAppStore => "1.2.840.113635.100.6.1.9",
+            AppleMarker::MacDevelopment => "1.2.840.113635.100.6.1.12",
+            AppleMarker::IPhoneDevelopment => "1.2.840.113635.100.6.1.2",
+            AppleMarker::DeveloperIdCa => "1.2.840.113635.100.6.2.6",
+        }
     }
+}
 
-    /// Compute the sha256 thumbprint of the leaf certificate
-    pub fn sha256_thumbprint(&self) -> String {
-        self.0.sha256_thumbprint()
+///
+/// Where a file embeds its signature, see `SignatureContext::signature_locations`
+///
+/// # Fields
+///
+/// `format`: The header recording the signature, the PE certificate table or a Mach-O load command
+///
+/// `offset`, `size`: The byte range of the signature in the file. For PE files this is the whole
+/// certificate table, `WIN_CERTIFICATE` headers included, for Mach-O the code signature superblob.
+///
+/// `trailing_bytes`: How many bytes follow the signature up to the end of the file, or of its
+/// slice of a universal binary. Signing tools put the signature last, so anything here was
+/// appended afterwards.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignatureLocation {
+    pub format: SignatureRemnant,
+    pub offset: u64,
+    pub size: u64,
+    pub trailing_bytes: u64,
+}
+
+///
+/// The header a file records its signature in, which outlives a signature that was stripped,
+/// see `Error::SignatureStripped` and `SignatureLocation`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SignatureRemnant {
+    CertificateTable, // The PE security directory still points at a certificate table
+    LoadCommand,      // A Mach-O `LC_CODE_SIGNATURE` load command still points at a signature
+    BundleSeal,       // The bundle still has its `_CodeSignature` seal
+}
+
+///
+/// An owned value from a property list embedded in a signature
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Data(Vec<u8>),
+    Date(std::time::SystemTime),
+    Array(Vec<PropertyValue>),
+    Dictionary(std::collections::BTreeMap<String, PropertyValue>),
+}
+
+///
+/// The value of a single entitlement, see `SignatureContext::entitlement`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntitlementValue {
+    Bool(bool),     // Most entitlements, such as `com.apple.security.app-sandbox`
+    String(String), // Such as `com.apple.developer.team-identifier`
+    Array(Vec<EntitlementValue>), // Such as `keychain-access-groups`
+    Other(PropertyValue), // Any other property list value, such as a dictionary
+}
+
+impl EntitlementValue {
+    /// Whether the entitlement is the boolean `true`, which is how most entitlements are granted
+    pub fn is_true(&self) -> bool {
+        matches!(self, EntitlementValue::Bool(true))
     }
 
-    /// Retrieve the leaf certificate serial number
-    pub fn serial(&self) -> Option<String> {
-        self.0.serial()
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            EntitlementValue::String(s) => Some(s),
+            _ => None,
+        }
     }
 
-    /// Additional properties.
-    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
-        self.0.additional_properties()
+    /// The strings in an array value, skipping any other items
+    pub fn strings(&self) -> Vec<&str> {
+        match self {
+            EntitlementValue::Array(items) => items.iter().filter_map(Self::as_str).collect(),
+            _ => Vec::new(),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::Error;
-    use std::collections::HashMap;
+impl From<PropertyValue> for EntitlementValue {
+    fn from(value: PropertyValue) -> Self {
+        match value {
+            PropertyValue::Bool(b) => EntitlementValue::Bool(b),
+            PropertyValue::String(s) => EntitlementValue::String(s),
+            PropertyValue::Array(items) => {
+                EntitlementValue::Array(items.into_iter().map(EntitlementValue::from).collect())
+            }
+            other => EntitlementValue::Other(other),
+        }
+    }
+}
 
-    #[test]
-    #[cfg(target_os = "macos")]
-    fn test_signed() {
-        let verifier = super::CodeSignVerifier::for_file("/sbin/ping").unwrap(); // Should always be present on macOS
-        let ctx = verifier.verify("anchor apple generic").unwrap(); // Should always be signed
+#[cfg(feature = "plist")]
+impl From<PropertyValue> for plist::Value {
+    fn from(value: PropertyValue) -> Self {
+        match value {
+            PropertyValue::Bool(b) => plist::Value::Boolean(b),
+            PropertyValue::Integer(i) => plist::Value::Integer(i.into()),
+            PropertyValue::Real(r) => plist::Value::Real(r),
+            PropertyValue::String(s) => plist::Value::String(s),
+            PropertyValue::Data(d) => plist::Value::Data(d),
+            PropertyValue::Date(date) => plist::Value::Date(date.into()),
+            PropertyValue::Array(items) => {
+                plist::Value::Array(items.into_iter().map(plist::Value::from).collect())
+            }
+            PropertyValue::Dictionary(entries) => {
+                let mut dict = plist::Dictionary::new();
+                for (key, value) in entries {
+                    dict.insert(key, value.into());
+                }
+                plist::Value::Dictionary(dict)
+            }
+        }
+    }
+}
 
-        // If those values begin to fail, Apple probably changed their certficate
-        assert_eq!(
-            ctx.subject_name().organization.as_deref(),
-            Some("Apple Inc.")
-        );
+///
+/// The launch constraints embedded in a signature, available for code signed for macOS 13 and later.
+/// Each constraint is the raw constraint dictionary, as described in Apple's
+/// [Defining launch environment and library constraints](https://developer.apple.com/documentation/security/defining_launch_environment_and_library_constraints).
+///
+/// # Fields
+///
+/// `self_constraint`: Constraints the process itself must satisfy to launch
+///
+/// `parent`: Constraints on the parent process
+///
+/// `responsible`: Constraints on the process responsible for the launch
+///
+/// `library`: Constraints every library the process loads must satisfy
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchConstraints {
+    pub self_constraint: Option<PropertyValue>,
+    pub parent: Option<PropertyValue>,
+    pub responsible: Option<PropertyValue>,
+    pub library: Option<PropertyValue>,
+}
 
-        assert_eq!(
-            ctx.issuer_name().organization_unit.as_deref(),
-            Some("Apple Certification Authority")
-        );
+///
+/// The hardened runtime version recorded in the code directory, which is the SDK version
+/// the code was built against. Versions compare in release order.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuntimeVersion {
+    pub major: u16,
+    pub minor: u8,
+    pub patch: u8,
+}
 
-        assert_eq!(
-            ctx.additional_properties(),
-            Some(HashMap::from([
-                (
-                    "cd_hash".to_string(),
-                    "AEE97B850A12F9CAC3EC399094071CAD63325818".to_string()
-                ),
-                ("platform_id".to_string(), "15".to_string())
-            ]))
-        );
+impl RuntimeVersion {
+    /// Decode the packed `0xMMMMmmpp` format used by the code directory
+    #[cfg(target_os = "macos")]
+    pub(crate) fn from_encoded(version: u32) -> Self {
+        RuntimeVersion {
+            major: (version >> 16) as u16,
+            minor: (version >> 8) as u8,
+            patch: version as u8,
+        }
     }
+}
 
-    #[test]
-    #[cfg(windows)]
-    fn test_signed() {
-        let path = format!("{}/explorer.exe", std::env::var("windir").unwrap()); // Should always be present on Windows
-        let verifier = super::CodeSignVerifier::for_file(path).unwrap();
-        let ctx = verifier.verify("").unwrap(); // Should always be signed
+impl std::fmt::Display for RuntimeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
 
-        // If those values begin to fail, Microsoft probably changed their certficate
-        assert_eq!(
-            ctx.subject_name().organization.as_deref(),
-            Some("Microsoft Corporation")
-        );
+///
+/// A provisioning profile embedded in a development or enterprise signed bundle.
+/// The profile is extracted as is, its own signature isn't evaluated.
+///
+/// # Fields
+///
+/// `name`, `uuid`: Identify the profile
+///
+/// `team_ids`, `team_name`: The development team the profile was issued to
+///
+/// `entitlements`: The entitlements the profile permits
+///
+/// `creation_date`, `expiration_date`: The profile's validity period
+///
+/// `provisioned_devices`: The device UDIDs the profile is restricted to, if any
+///
+/// `provisions_all_devices`: Set for enterprise profiles, which aren't restricted to a device list
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProvisioningProfile {
+    pub name: Option<String>,
+    pub uuid: Option<String>,
+    pub team_ids: Vec<String>,
+    pub team_name: Option<String>,
+    pub entitlements: std::collections::BTreeMap<String, PropertyValue>,
+    pub creation_date: Option<std::time::SystemTime>,
+    pub expiration_date: Option<std::time::SystemTime>,
+    pub provisioned_devices: Vec<String>,
+    pub provisions_all_devices: bool,
+}
 
-        assert_eq!(
-            ctx.issuer_name().common_name.as_deref(),
-            Some("Microsoft Windows Production PCA 2011")
-        );
+///
+/// The kind of Microsoft signature a driver carries
+///
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DriverSigning {
+    Whql,             // Passed Windows Hardware Lab Kit testing, EKU 1.3.6.1.4.1.311.10.3.5
+    Attestation,      // Attestation signed by the Hardware Dev Center, EKU 1.3.6.1.4.1.311.10.3.5.1
+    WindowsComponent, // Shipped with Windows itself, such as ntfs.sys, EKU 1.3.6.1.4.1.311.10.3.6
+}
 
-        assert_eq!(
-            ctx.serial().as_deref(),
-            Some("3300000266bd1580efa75cd6d3000000000266")
-        );
+///
+/// Describes how a driver measures up to the Windows kernel-mode code signing policy,
+/// which since Windows 10 1607 requires drivers to be signed by Microsoft itself
+/// rather than by a vendor certificate cross-signed to the Microsoft Code Verification Root.
+///
+/// # Fields
+///
+/// `microsoft_root`: The leaf certificate chains to a Microsoft product root
+///
+/// `signing`: The Microsoft driver signing EKU on the leaf certificate, if any. In-box drivers
+/// carry the Windows component EKU instead.
+///
+#[cfg(windows)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DriverPolicy {
+    pub microsoft_root: bool,
+    pub signing: Option<DriverSigning>,
+}
+
+#[cfg(windows)]
+impl DriverPolicy {
+    /// Whether the driver would load under the current kernel policy with Secure Boot enabled.
+    /// Note that Windows Server only loads WHQL signed drivers, not attestation signed ones.
+    pub fn would_load(&self) -> bool {
+        self.microsoft_root && self.signing.is_some()
     }
+}
 
-    #[test]
-    fn test_unsigned() {
-        let path = std::env::args().next().unwrap(); // own path, always unsigned and present
+///
+/// Identifies the security catalog that vouched for a file without an embedded signature.
+/// Only produced on Windows.
+///
+/// # Fields
+///
+/// `path`: The location of the `.cat` file the file's hash was found in
+///
+/// `member_tag`: The tag of the catalog member, the hex encoded file hash
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CatalogInfo {
+    pub path: std::path::PathBuf,
+    pub member_tag: String,
+}
 
-        let res = super::CodeSignVerifier::for_file(path)
-            .unwrap()
-            .verify("anchor apple");
-        assert!(
+///
+/// The architecture of the code in a PE image
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PeMachine {
+    X86,
+    X64,
+    Arm,
+    Arm64,
+    Arm64EC,    // ARM64 code that interoperates with x64 code in an x64 process
+    Arm64X,     // ARM64 and ARM64EC code in one image, loads into either kind of process
+    Other(u16), // Any other IMAGE_FILE_MACHINE_* value
+}
+
+///
+/// The privileges an application manifest asks Windows to launch the file with
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExecutionLevel {
+    AsInvoker,            // The privileges of the parent process
+    HighestAvailable,     // The highest the user can get, elevating when they're an administrator
+    RequireAdministrator, // Always elevated, with a UAC prompt
+}
+
+///
+/// The `assemblyIdentity` an application manifest declares for the file itself
+///
+/// # Fields
+///
+/// `name`, `version`, `processor_architecture`: The attributes of the same names
+///
+/// `kind`: The `type` attribute, "win32" for applications
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssemblyIdentity {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub processor_architecture: Option<String>,
+    pub kind: Option<String>,
+}
+
+///
+/// The launch settings from the application manifest embedded in a PE file.
+/// Only produced on Windows.
+///
+/// # Fields
+///
+/// `execution_level`: The `requestedExecutionLevel`, None when the manifest doesn't request one
+/// and installer detection may decide to elevate instead
+///
+/// `ui_access`: The file asks to bypass UI isolation, which Windows only grants to signed files
+/// in secure locations such as Program Files
+///
+/// `assembly_identity`: The file's own identity, if the manifest declares one
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApplicationManifest {
+    pub execution_level: Option<ExecutionLevel>,
+    pub ui_access: bool,
+    pub assembly_identity: Option<AssemblyIdentity>,
+}
+
+impl ApplicationManifest {
+    /// Whether launching the file always prompts for elevation
+    pub fn requires_elevation(&self) -> bool {
+        self.execution_level == Some(ExecutionLevel::RequireAdministrator)
+    }
+}
+
+///
+/// Signature details only macOS records
+///
+/// # Fields
+///
+/// `cd_hash`: The hex encoded code directory hash
+///
+/// `team_id`: The team identifier of the signing certificate, absent for Apple's own code
+///
+/// `identifier`: The signing identifier, chosen at signing time. For bundles it's usually the
+/// bundle identifier, for bare binaries often the file name or a reverse-DNS name.
+///
+/// `bundle_id`, `short_version`, `bundle_version`: Read from the sealed Info.plist of a bundle
+///
+/// `platform_id`: The platform identifier, set for code that ships with the OS
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacProperties {
+    pub cd_hash: Option<String>,
+    pub team_id: Option<String>,
+    pub identifier: Option<String>,
+    pub bundle_id: Option<String>,
+    pub short_version: Option<String>,
+    pub bundle_version: Option<String>,
+    pub platform_id: Option<i32>,
+}
+
+///
+/// Signature details only Windows records
+///
+/// # Fields
+///
+/// `enhanced_key_usages`: The EKU OIDs of the leaf certificate
+///
+/// `microsoft_root`: The leaf certificate chains to a Microsoft product root
+///
+/// `store_signed`: The leaf certificate is the Microsoft Store's
+///
+/// `catalog`: The security catalog that vouched for the file, if it has no embedded signature
+///
+/// `product_name`, `file_version`, `company_name`, `original_filename`: Read from the file's
+/// version resource. Unlike the sealed Info.plist of a bundle, the signature doesn't vouch for
+/// them against anything but tampering, anyone can sign a file claiming any company.
+///
+/// `manifest`: The embedded application manifest, if the file has one
+///
+/// `machine`: The architecture of the file's code. Hybrid ARM64EC and ARM64X images are signed
+/// and verified like any other, their header only names one of the architectures they hold.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowsProperties {
+    pub enhanced_key_usages: Vec<String>,
+    pub microsoft_root: bool,
+    pub store_signed: bool,
+    pub catalog: Option<CatalogInfo>,
+    pub product_name: Option<String>,
+    pub file_version: Option<String>,
+    pub company_name: Option<String>,
+    pub original_filename: Option<String>,
+    pub manifest: Option<ApplicationManifest>,
+    pub machine: Option<PeMachine>,
+}
+
+///
+/// The platform specific details of a signature
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlatformProperties {
+    Mac(MacProperties),
+    Windows(WindowsProperties),
+}
+
+///
+/// Everything known about a verified signature in one owned value, detached from the OS
+/// objects it was read from so it can be stored, logged or sent to another process.
+///
+/// # Fields
+///
+/// `subject`, `issuer`: The names on the leaf certificate
+///
+/// `sha256_thumbprint`, `serial`: Identify the leaf certificate
+///
+/// `signing_time`: The unverified time claimed by the signer, only reported on macOS
+///
+/// `timestamp`: The time of the secure timestamp, if any
+///
+/// `validity_period`: The not before and not after times of the leaf certificate
+///
+/// `time_validity`: Why the leaf certificate is considered valid in time
+///
+/// `chain_status`: The trust status of the leaf certificate chain
+///
+/// `revocation`: The revocation status of each certificate in the chain, leaf first
+///
+/// `runtime_version`: The hardened runtime version, only reported on macOS
+///
+/// `platform`: The platform specific details of the signature
+///
+/// `driver_policy`: How the signature measures up to the kernel-mode signing policy, Windows only
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerificationReport {
+    pub subject: Name,
+    pub issuer: Name,
+    pub sha256_thumbprint: String,
+    pub serial: Option<String>,
+    pub signing_time: Option<std::time::SystemTime>,
+    pub timestamp: Option<std::time::SystemTime>,
+    pub validity_period: Option<(std::time::SystemTime, std::time::SystemTime)>,
+    pub time_validity: TimeValidity,
+    pub chain_status: ChainStatus,
+    pub revocation: Vec<RevocationStatus>,
+    pub runtime_version: Option<RuntimeVersion>,
+    pub platform: PlatformProperties,
+    #[cfg(windows)]
+    pub driver_policy: DriverPolicy,
+}
+
+///
+/// The verification result of a single piece of code nested inside a bundle,
+/// such as a helper tool, framework, plugin or XPC service.
+///
+/// # Fields
+///
+/// `path`: The location of the nested code
+///
+/// `result`: The outcome of verifying it against the requirement recorded in the bundle's seal
+///
+/// `nested`: The code nested inside this code in turn, up to the requested depth
+///
+#[derive(Debug)]
+pub struct NestedCode {
+    pub path: std::path::PathBuf,
+    pub result: Result<(), Error>,
+    pub nested: Vec<NestedCode>,
+}
+
+///
+/// A module loaded in a process, and the outcome of verifying it
+///
+/// `path`: The file the module was loaded from
+///
+/// `result`: The outcome of verifying the file with the given options
+///
+pub struct LoadedModule {
+    pub path: std::path::PathBuf,
+    pub result: Result<SignatureContext, Error>,
+}
+
+///
+/// An ancestor of a process, and the outcome of verifying it
+///
+/// `pid`: The process ID of the ancestor
+///
+/// `result`: The outcome of verifying its code with the given options
+///
+pub struct Ancestor {
+    pub pid: i32,
+    pub result: Result<SignatureContext, Error>,
+}
+
+///
+/// A file from a batch, and the outcome of verifying it
+///
+/// `path`: The file as it was passed in
+///
+/// `result`: The outcome of verifying it with the given options
+///
+/// `claimed`: Who the signature claims to be from when it failed verification, see
+/// `CodeSignVerifier::claimed_signer`
+///
+pub struct VerifiedFile {
+    pub path: std::path::PathBuf,
+    pub result: Result<SignatureContext, Error>,
+    pub claimed: Option<ClaimedSigner>,
+}
+
+///
+/// The signer a signature claims, read from it without validating anything.
+/// Only for reporting who an invalid signature claims to be from, never for trust decisions.
+///
+/// # Fields
+///
+/// `subject`, `issuer`: The names on the leaf certificate
+///
+/// `sha256_thumbprint`: The thumbprint of the leaf certificate
+///
+/// `serial`: The serial number of the leaf certificate, formatted as `SignatureContext::serial`
+///
+/// `timestamp`: The time of the timestamp attached to the signature, if any
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClaimedSigner {
+    pub subject: Name,
+    pub issuer: Name,
+    pub sha256_thumbprint: String,
+    pub serial: Option<String>,
+    pub timestamp: Option<std::time::SystemTime>,
+}
+
+///
+/// The category of a verification failure, see `Error::kind`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    Unsigned,          // The code carries no signature at all
+    SignatureStripped, // The code was signed, but its signature was removed or cut short
+    Tampered,          // The code or a sealed resource was modified after signing
+    UntrustedRoot,     // The certificate chain doesn't end at a trusted root
+    Expired,           // A certificate in the chain is expired, or not yet valid
+    Revoked,           // A certificate in the chain was revoked
+    RequirementFailed, // The signature is valid, but doesn't satisfy the requirement
+    Other,             // Any other failure
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Unsigned,                            // The binary file didn't have any singature
+    SignatureStripped(SignatureRemnant), // The file was signed, but the signature was removed or cut short
+    AdHocSigned(AdHocSignature), // The binary is validly signed, but ad-hoc without a certificate
+    OsError {
+        code: i32,               // Wraps an inner provider error code
+        message: Option<String>, // The description the OS gives the code
+    },
+    InvalidPath,                         // The provided path was malformed
+    LeafCertNotFound,                    // Unable to fetch certificate information
+    NestedCode(Vec<NestedCode>), // Some of the code nested inside the bundle failed verification
+    SignerNotFound,              // The requested signature index isn't present
+    RequirementFailed,           // The signature is valid, but doesn't satisfy the requirement
+    Unsupported,                 // The requested check has no equivalent on this platform
+    Timeout,                     // Verification didn't finish within the network timeout
+    Cancelled,                   // Verification was cancelled, or didn't finish by its deadline
+    Invalidated, // The running process no longer matches its signature, e.g. its pages were modified
+    ImageMismatch, // The image loaded in the process doesn't match the signed file on disk
+    ThumbprintMismatch, // The signature is valid, but the leaf certificate isn't the pinned one
+    PublisherMismatch, // The signature is valid, but the leaf certificate isn't from the pinned publisher
+    FileChanged,       // The held file was replaced or modified while it was verified
+    SymlinkRejected(std::path::PathBuf), // The path passes through this link, see `SymlinkPolicy::Reject`
+    InvalidCertificate,                  // A provided certificate couldn't be decoded
+    TimestampRequired, // The timestamp policy requires an RFC 3161 timestamp, and the signature has none
+    Tampered {
+        code: i32,               // The code or its signature was modified after signing
+        message: Option<String>, // The description the OS gave the failure
+    },
+    ResourceModified {
+        code: i32,               // A sealed resource was modified, added or removed
+        message: Option<String>, // The description the OS gave the failure
+    },
+    Expired {
+        code: i32,               // A certificate in the chain is expired, or not yet valid
+        status: ChainStatus,     // Every check the chain failed
+        message: Option<String>, // The description the OS gave the failure
+    },
+    Revoked {
+        code: i32,               // A certificate in the chain was revoked
+        status: ChainStatus,     // Every check the chain failed
+        message: Option<String>, // The description the OS gave the failure
+    },
+    UntrustedRoot {
+        code: i32,               // The chain ends at a root that isn't trusted
+        status: ChainStatus,     // Every check the chain failed
+        message: Option<String>, // The description the OS gave the failure
+    },
+    ChainFailed {
+        code: i32,               // The inner provider error code
+        status: ChainStatus,     // Why the certificate chain was rejected
+        message: Option<String>, // The description the OS gave the failure
+    },
+    #[cfg(windows)]
+    DriverPolicy(DriverPolicy), // The signature is valid, but doesn't satisfy kernel-mode signing policy
+    #[cfg(target_os = "macos")]
+    InvalidRequirement(RequirementSyntax), // The requirement text couldn't be compiled
+    #[cfg(target_os = "macos")]
+    CFError(core_foundation::error::CFError),
+    IoError(std::io::Error), // Reading or writing a file the check needs failed
+    #[cfg(feature = "notarization")]
+    HttpError(Box<ureq::Error>), // The online ticket lookup request failed
+    #[cfg(feature = "notarization")]
+    InvalidResponse, // The online service returned an unexpected response
+    #[cfg(feature = "sigstore")]
+    Sigstore(sigstore::SigstoreFailure), // The Sigstore bundle doesn't verify for the artifact
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Unsigned => write!(f, "the code isn't signed"),
+            Error::SignatureStripped(remnant) => {
+                let remnant = match remnant {
+                    SignatureRemnant::CertificateTable => {
+                        "the PE header records a certificate table"
+                    }
+                    SignatureRemnant::LoadCommand => "a Mach-O load command records a signature",
+                    SignatureRemnant::BundleSeal => "the bundle is still sealed",
+                };
+                write!(f, "the signature was removed or cut short, {}", remnant)
+            }
+            Error::AdHocSigned(signature) => write!(
+                f,
+                "the code is ad-hoc signed without a certificate, cdhash {}",
+                signature.cd_hash
+            ),
+            Error::OsError { code, message } => {
+                write!(f, "{}", describe_os_error(*code, message.as_deref()))
+            }
+            Error::InvalidPath => write!(f, "the path is malformed"),
+            Error::LeafCertNotFound => write!(f, "the signing certificate couldn't be read"),
+            Error::NestedCode(nested) => write!(
+                f,
+                "{} of the nested code items failed verification",
+                nested.iter().filter(|n| n.result.is_err()).count()
+            ),
+            Error::SignerNotFound => write!(f, "the requested signature isn't present"),
+            Error::RequirementFailed => {
+                write!(
+                    f,
+                    "the signature is valid, but doesn't satisfy the requirement"
+                )
+            }
+            Error::Unsupported => write!(f, "the requested check isn't supported on this platform"),
+            Error::Timeout => write!(f, "verification didn't finish within the network timeout"),
+            Error::Cancelled => write!(f, "verification was cancelled"),
+            Error::Invalidated => write!(f, "the running process no longer matches its signature"),
+            Error::ImageMismatch => {
+                write!(
+                    f,
+                    "the image loaded in the process doesn't match the file on disk"
+                )
+            }
+            Error::ThumbprintMismatch => {
+                write!(f, "the signing certificate isn't the pinned certificate")
+            }
+            Error::PublisherMismatch => {
+                write!(f, "the signing certificate isn't the pinned publisher")
+            }
+            Error::FileChanged => {
+                write!(f, "the file was replaced or modified while it was verified")
+            }
+            Error::SymlinkRejected(link) => {
+                write!(f, "the path passes through the link {}", link.display())
+            }
+            Error::InvalidCertificate => write!(f, "a provided certificate couldn't be decoded"),
+            Error::TimestampRequired => {
+                write!(
+                    f,
+                    "the signature has no RFC 3161 timestamp, which the policy requires"
+                )
+            }
+            Error::Tampered { code, message } => write!(
+                f,
+                "the code was modified after it was signed: {}",
+                describe_os_error(*code, message.as_deref())
+            ),
+            Error::ResourceModified { code, message } => write!(
+                f,
+                "a resource sealed by the signature was modified: {}",
+                describe_os_error(*code, message.as_deref())
+            ),
+            Error::Expired {
+                code,
+                status,
+                message,
+            }
+            | Error::Revoked {
+                code,
+                status,
+                message,
+            }
+            | Error::UntrustedRoot {
+                code,
+                status,
+                message,
+            }
+            | Error::ChainFailed {
+                code,
+                status,
+                message,
+            } => {
+                let reasons = [
+                    (status.is_expired(), "expired"),
+                    (status.is_revoked(), "revoked"),
+                    (status.is_untrusted_root(), "untrusted root"),
+                    (status.is_partial_chain(), "incomplete chain"),
+                    (status.is_revocation_unknown(), "revocation unknown"),
+                ]
+                .iter()
+                .filter(|(applies, _)| *applies)
+                .map(|(_, reason)| *reason)
+                .collect::<Vec<_>>();
+
+                write!(f, "the certificate chain was rejected")?;
+                if !reasons.is_empty() {
+                    write!(f, " ({})", reasons.join(", "))?;
+                }
+                write!(f, ": {}", describe_os_error(*code, message.as_deref()))
+            }
+            #[cfg(windows)]
+            Error::DriverPolicy(_) => {
+                write!(
+                    f,
+                    "the signature doesn't satisfy kernel-mode signing policy"
+                )
+            }
+            #[cfg(target_os = "macos")]
+            Error::InvalidRequirement(syntax) => match (syntax.line, syntax.column) {
+                (Some(line), Some(column)) => write!(
+                    f,
+                    "invalid requirement at line {} column {}: {}",
+                    line, column, syntax.message
+                ),
+                _ => write!(f, "invalid requirement: {}", syntax.message),
+            },
+            #[cfg(target_os = "macos")]
+            Error::CFError(err) => write!(f, "{}", err.description()),
+            Error::IoError(err) => write!(f, "{}", err),
+            #[cfg(feature = "notarization")]
+            Error::HttpError(err) => write!(f, "the notarization lookup failed: {}", err),
+            #[cfg(feature = "notarization")]
+            Error::InvalidResponse => write!(
+                f,
+                "the notarization service returned an unexpected response"
+            ),
+            #[cfg(feature = "sigstore")]
+            Error::Sigstore(failure) => {
+                write!(f, "the Sigstore bundle failed verification: {}", failure)
+            }
+        }
+    }
+}
+
+impl Error {
+    /// Wrap an inner provider error code, along with the description the OS gives it
+    pub(crate) fn os_error(code: i32) -> Self {
+        Error::OsError {
+            code,
+            message: os_error_message(code),
+        }
+    }
+
+    /// Report a rejected certificate chain by its most significant failure, revocation first.
+    /// The message is the OS description of the failure, when it gave a more specific one than
+    /// the code's own.
+    pub(crate) fn chain_failed(code: i32, status: ChainStatus, message: Option<String>) -> Self {
+        let message = message.or_else(|| os_error_message(code));
+
+        if status.is_revoked() {
+            Error::Revoked {
+                code,
+                status,
+                message,
+            }
+        } else if status.is_expired() {
+            Error::Expired {
+                code,
+                status,
+                message,
+            }
+        } else if status.is_untrusted_root() {
+            Error::UntrustedRoot {
+                code,
+                status,
+                message,
+            }
+        } else {
+            Error::ChainFailed {
+                code,
+                status,
+                message,
+            }
+        }
+    }
+
+    /// Break the error down into every check it failed, in the order they're run, with what
+    /// about each one failed. See the `diagnostics` module.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// if let Err(err) = CodeSignVerifier::for_file("/Applications/Tool.app").unwrap().verify("") {
+    ///     print!("{}", err.explain());
+    /// }
+    /// ```
+    pub fn explain(&self) -> diagnostics::Diagnostics {
+        diagnostics::explain(self)
+    }
+
+    /// The description the OS gave the failure, when it came with an OS error code
+    pub fn native_message(&self) -> Option<&str> {
+        match self {
+            Error::OsError { message, .. }
+            | Error::Tampered { message, .. }
+            | Error::ResourceModified { message, .. }
+            | Error::Expired { message, .. }
+            | Error::Revoked { message, .. }
+            | Error::UntrustedRoot { message, .. }
+            | Error::ChainFailed { message, .. } => message.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The OS status code behind the error, see the `status` module for the common ones
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Error::OsError { code, .. }
+            | Error::Tampered { code, .. }
+            | Error::ResourceModified { code, .. }
+            | Error::Expired { code, .. }
+            | Error::Revoked { code, .. }
+            | Error::UntrustedRoot { code, .. }
+            | Error::ChainFailed { code, .. } => Some(*code),
+            #[cfg(target_os = "macos")]
+            Error::CFError(err) => Some(err.code() as i32),
+            _ => None,
+        }
+    }
+
+    /// The status of the certificate chain, when the chain was rejected
+    pub fn chain_status(&self) -> Option<&ChainStatus> {
+        match self {
+            Error::Expired { status, .. }
+            | Error::Revoked { status, .. }
+            | Error::UntrustedRoot { status, .. }
+            | Error::ChainFailed { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Whether the code carries no signature at all, including files of a type that can't
+    /// be signed. False for code whose signature was stripped, see `is_signature_stripped`.
+    pub fn is_no_signature(&self) -> bool {
+        #[cfg(target_os = "macos")]
+        const NO_SIGNATURE: &[i32] = &[status::errSecCSUnsigned];
+        #[cfg(windows)]
+        const NO_SIGNATURE: &[i32] = &[
+            status::TRUST_E_NOSIGNATURE,
+            status::TRUST_E_SUBJECT_FORM_UNKNOWN,
+            status::TRUST_E_PROVIDER_UNKNOWN,
+        ];
+
+        matches!(self, Error::Unsigned)
+            || self
+                .code()
+                .map_or(false, |code| NO_SIGNATURE.contains(&code))
+    }
+
+    /// Whether the code was signed, but its signature has since been removed or truncated
+    pub fn is_signature_stripped(&self) -> bool {
+        matches!(self, Error::SignatureStripped(_))
+    }
+
+    /// Whether the code, its signature or a resource it seals was modified after signing
+    pub fn is_tampered(&self) -> bool {
+        matches!(
+            self,
+            Error::Tampered { .. } | Error::ResourceModified { .. }
+        )
+    }
+
+    /// Whether the chain was rejected, and doesn't end at a trusted root. Also true when the
+    /// chain was reported as expired or revoked, but doesn't end at a trusted root either.
+    pub fn is_untrusted_root(&self) -> bool {
+        self.chain_status()
+            .map_or(false, |status| status.is_untrusted_root())
+    }
+
+    /// Whether the chain was rejected, and has a certificate that is expired or not valid yet
+    pub fn is_expired(&self) -> bool {
+        self.chain_status()
+            .map_or(false, |status| status.is_expired())
+    }
+
+    /// Whether the chain was rejected, and has a revoked certificate
+    pub fn is_revoked(&self) -> bool {
+        self.chain_status()
+            .map_or(false, |status| status.is_revoked())
+    }
+
+    /// The single category the error falls in, for reporting it where only a code or a name
+    /// can be given, such as the C and Python interfaces. A chain that fails several checks
+    /// is reported by the most significant one, revocation first.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::RequirementFailed => ErrorKind::RequirementFailed,
+            _ if self.is_no_signature() => ErrorKind::Unsigned,
+            _ if self.is_signature_stripped() => ErrorKind::SignatureStripped,
+            _ if self.is_tampered() => ErrorKind::Tampered,
+            _ if self.is_revoked() => ErrorKind::Revoked,
+            _ if self.is_expired() => ErrorKind::Expired,
+            _ if self.is_untrusted_root() => ErrorKind::UntrustedRoot,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(err) => Some(err),
+            #[cfg(feature = "notarization")]
+            Error::HttpError(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl CodeSignVerifier {
+    fn new(verifier: Verifier) -> Self {
+        CodeSignVerifier(verifier)
+    }
+
+    /// Start describing the code to verify and how, as an alternative to the `for_*`
+    /// constructors and `verify_with`
+    pub fn builder() -> CodeSignVerifierBuilder {
+        CodeSignVerifierBuilder::default()
+    }
+
+    /// Create a verifier for a binary at a given path.
+    /// On macOS it can be either a binary or an application package.
+    pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        Verifier::for_file(path).map(CodeSignVerifier::new)
+    }
+
+    /// Create a verifier for a binary at a given path, treating links in the path as the
+    /// options' `symlinks` policy says. Only that option is used here, the options still
+    /// have to be passed to `verify_with`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, SymlinkPolicy, VerificationOptions};
+    ///
+    /// let options = VerificationOptions::new().symlinks(SymlinkPolicy::Resolve);
+    /// let ctx = CodeSignVerifier::for_file_with("/usr/local/bin/tool", &options)
+    ///     .unwrap()
+    ///     .verify_with(&options)
+    ///     .unwrap();
+    /// println!("verified {}", ctx.verified_path().unwrap().display());
+    /// ```
+    pub fn for_file_with<P: AsRef<std::path::Path>>(
+        path: P,
+        options: &VerificationOptions,
+    ) -> Result<Self, Error> {
+        CodeSignVerifier::for_file(options.resolve_path(path.as_ref())?)
+    }
+
+    /// Create a verifier for a binary that's held open until the verifier is dropped, so the
+    /// bytes that were verified are the bytes the caller goes on to hash or execute through
+    /// `held_file`, rather than whatever the path names by then. On Windows the file is opened
+    /// sharing only read access, so it can't be written, replaced or deleted while it's held.
+    /// macOS has no such lock, so each verification instead checks that the path still names
+    /// the held file, unmodified since it was opened, and fails with `Error::FileChanged` when
+    /// it doesn't. Writes made after that are still seen through the held file, so on macOS it
+    /// should be in a directory only trusted users can write to.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    /// use std::io::Read;
+    ///
+    /// let verifier = CodeSignVerifier::for_held_file("C:/Program Files/Vendor/tool.exe").unwrap();
+    /// verifier.verify_publisher("Vendor Inc.").unwrap();
+    ///
+    /// let mut data = Vec::new();
+    /// verifier.held_file().unwrap().read_to_end(&mut data).unwrap();
+    /// ```
+    pub fn for_held_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        Verifier::for_held_file(path.as_ref()).map(CodeSignVerifier::new)
+    }
+
+    /// The file held open by a verifier created with `for_held_file`, None for other verifiers
+    pub fn held_file(&self) -> Option<&std::fs::File> {
+        self.0.held_file()
+    }
+
+    /// Create a verifier for a binary held in memory, such as a download that hasn't been
+    /// written to disk yet. Neither platform verifies signatures from memory, so the data is
+    /// written to a private temporary file that's removed when the verifier is dropped.
+    /// On Windows the file is opened delete-on-close and verified through that handle, so
+    /// it's cleaned up even if the process exits abruptly. Only single binaries can be
+    /// verified this way, a bundle is a directory tree.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let data = std::fs::read("/sbin/ping").unwrap();
+    /// let ctx = CodeSignVerifier::for_bytes(&data).unwrap().verify("anchor apple").unwrap();
+    /// ```
+    pub fn for_bytes(data: &[u8]) -> Result<Self, Error> {
+        CodeSignVerifier::for_reader(std::io::Cursor::new(data))
+    }
+
+    /// Create a verifier for a binary read from a stream, such as a file inside an archive.
+    /// The whole stream is verified from its start, regardless of its current position.
+    /// Like `for_bytes`, the data is copied to a private temporary file which is removed when
+    /// the verifier is dropped, without holding all of it in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let file = std::fs::File::open("/sbin/ping").unwrap();
+    /// let ctx = CodeSignVerifier::for_reader(file).unwrap().verify("anchor apple").unwrap();
+    /// ```
+    pub fn for_reader<R: std::io::Read + std::io::Seek>(reader: R) -> Result<Self, Error> {
+        CodeSignVerifier::for_reader_with_progress(reader, |_, _| {})
+    }
+
+    /// Like `for_reader`, reporting progress while the stream is copied, which for
+    /// multi-gigabyte installers takes a while before verification can start. The callback
+    /// gets the number of bytes copied so far and the size of the stream, about once per
+    /// megabyte and once more at the end. The stream is copied in chunks and never held in
+    /// memory, and the platforms hash the temporary file incrementally as well.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let file = std::fs::File::open("setup.exe").unwrap();
+    /// let verifier = CodeSignVerifier::for_reader_with_progress(file, |copied, total| {
+    ///     println!("{}%", copied * 100 / total.max(1));
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn for_reader_with_progress<R, F>(mut reader: R, progress: F) -> Result<Self, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+        F: FnMut(u64, u64),
+    {
+        let total = reader
+            .seek(std::io::SeekFrom::End(0))
+            .map_err(Error::IoError)?;
+        reader
+            .seek(std::io::SeekFrom::Start(0))
+            .map_err(Error::IoError)?;
+        let mut reader = progress::ProgressReader::new(reader, total, progress);
+        Verifier::for_reader(&mut reader).map(CodeSignVerifier::new)
+    }
+
+    /// Create a verifier for a running application by PID.
+    /// On Windows it will get the full path to the running application first.
+    /// This can be used for e.g. verifying the app on the other end of a pipe.
+    pub fn for_pid(pid: i32) -> Result<Self, Error> {
+        Verifier::for_pid(pid).map(CodeSignVerifier::new)
+    }
+
+    /// Verify the client process on the other end of a named pipe, the server's handle to it,
+    /// and return its signature. The client is resolved with `GetNamedPipeClientProcessId`, and
+    /// its executable verified through a handle held open while verifying it, so it can't be
+    /// swapped in between.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    /// # let pipe = std::fs::File::open(r"\\.\pipe\foo").unwrap();
+    /// use std::os::windows::io::AsRawHandle;
+    ///
+    /// let options = VerificationOptions::new().publisher("Foo Inc.");
+    /// let ctx = CodeSignVerifier::verify_pipe_client(pipe.as_raw_handle(), &options).unwrap();
+    /// ```
+    #[cfg(windows)]
+    pub fn verify_pipe_client(
+        pipe: std::os::windows::io::RawHandle,
+        options: &VerificationOptions,
+    ) -> Result<SignatureContext, Error> {
+        CodeSignVerifier::new(Verifier::for_pipe_client(pipe as _)?).verify_with(options)
+    }
+
+    /// Verify the process on the other end of a connected Unix domain socket, and return its
+    /// signature. The peer is identified by its audit token, from `LOCAL_PEERTOKEN`, so unlike
+    /// resolving its PID the process can't exit and be replaced in between.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, Requirement, VerificationOptions};
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// let stream = std::os::unix::net::UnixStream::connect("/var/run/foo.sock").unwrap();
+    /// let options = VerificationOptions::new().requirement(Requirement::anchor_apple_generic().team_id("ABC123"));
+    /// let ctx = CodeSignVerifier::verify_socket_peer(stream.as_raw_fd(), &options).unwrap();
+    /// ```
+    #[cfg(target_os = "macos")]
+    pub fn verify_socket_peer(
+        fd: std::os::unix::io::RawFd,
+        options: &VerificationOptions,
+    ) -> Result<SignatureContext, Error> {
+        CodeSignVerifier::new(Verifier::for_socket_peer(fd)?).verify_with(options)
+    }
+
+    /// Verify the process on the other end of an XPC connection, and return its signature.
+    /// The peer is identified by the audit token of the connection.
+    ///
+    /// # Safety
+    ///
+    /// `connection` must be a valid `xpc_connection_t`.
+    #[cfg(target_os = "macos")]
+    pub unsafe fn verify_xpc_peer(
+        connection: *mut std::ffi::c_void,
+        options: &VerificationOptions,
+    ) -> Result<SignatureContext, Error> {
+        CodeSignVerifier::new(Verifier::for_xpc_peer(connection)?).verify_with(options)
+    }
+
+    /// Verify each ancestor of a running process, starting with its parent and walking up to the
+    /// first process the system started, so a trusted app launched by an untrusted launcher
+    /// stands out. An ancestor that fails verification doesn't stop the walk, its error is
+    /// reported in its own result.
+    /// On Windows a parent that exited, whose PID may since have been reused, ends the chain,
+    /// as do the kernel processes. On macOS orphaned processes are adopted by launchd.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    ///
+    /// let ancestors = CodeSignVerifier::verify_ancestors(1234, &VerificationOptions::new()).unwrap();
+    /// let trusted = ancestors.iter().all(|a| a.result.is_ok());
+    /// ```
+    pub fn verify_ancestors(
+        pid: i32,
+        options: &VerificationOptions,
+    ) -> Result<Vec<Ancestor>, Error> {
+        let mut ancestors: Vec<Ancestor> = Vec::new();
+        let mut current = pid;
+
+        loop {
+            let parent = match Verifier::parent_pid(current) {
+                Ok(Some(parent)) => parent,
+                Ok(None) => break,
+                Err(err) if current == pid => return Err(err),
+                Err(_) => break, // The ancestor exited while walking the chain
+            };
+            if parent == pid || ancestors.iter().any(|a| a.pid == parent) {
+                break;
+            }
+
+            let result = CodeSignVerifier::for_pid(parent).and_then(|v| v.verify_with(options));
+            ancestors.push(Ancestor {
+                pid: parent,
+                result,
+            });
+            current = parent;
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Verify every module loaded in a running process, the main executable along with each
+    /// DLL or dylib, so unsigned or tampered libraries injected into it stand out. A module that
+    /// fails verification doesn't stop the others from being verified, its error is reported in
+    /// its own result.
+    /// On Windows the modules are listed with `EnumProcessModulesEx`. On macOS they're the files
+    /// mapped executable into the process, libraries in the dyld shared cache have no file of
+    /// their own and are left out. Reading another process needs the same privileges as `for_pid`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    ///
+    /// let modules = CodeSignVerifier::for_pid_modules(1234, &VerificationOptions::new()).unwrap();
+    /// for module in modules.iter().filter(|m| m.result.is_err()) {
+    ///     println!("{} isn't validly signed", module.path.display());
+    /// }
+    /// ```
+    pub fn for_pid_modules(
+        pid: i32,
+        options: &VerificationOptions,
+    ) -> Result<Vec<LoadedModule>, Error> {
+        let paths = Verifier::module_paths(pid)?;
+
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let result = CodeSignVerifier::for_file(&path).and_then(|v| v.verify_with(options));
+                LoadedModule { path, result }
+            })
+            .collect())
+    }
+
+    /// Verify a batch of files with the same options, spreading them over a thread per core.
+    /// Results are returned in the order the paths were given, a file that fails doesn't
+    /// affect the others. On Windows each thread reuses its catalog admin context for all the
+    /// files it verifies, which is most of the cost of checking catalog signed files.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    ///
+    /// let paths = ["C:/Windows/explorer.exe", "C:/Windows/System32/svchost.exe"];
+    /// for file in CodeSignVerifier::verify_many(&paths, &VerificationOptions::new()) {
+    ///     println!("{}: {}", file.path.display(), file.result.is_ok());
+    /// }
+    /// ```
+    pub fn verify_many<I, P>(paths: I, options: &VerificationOptions) -> Vec<VerifiedFile>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<std::path::Path>,
+    {
+        let paths = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect::<Vec<_>>();
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+
+        // Each worker takes the next unclaimed path, so slow files don't hold up a whole share
+        let mut results = std::thread::scope(|scope| {
+            let workers = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            let path = match paths.get(index) {
+                                Some(path) => path,
+                                None => return results,
+                            };
+                            let verified = CodeSignVerifier::for_file_with(path, options)
+                                .map_err(|err| (err, None))
+                                .and_then(|v| {
+                                    v.verify_with(options)
+                                        .map_err(|err| (err, v.claimed_signer()))
+                                });
+                            results.push((index, verified));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(index, _)| *index);
+
+        paths
+            .into_iter()
+            .zip(results)
+            .map(|(path, (_, verified))| match verified {
+                Ok(ctx) => VerifiedFile {
+                    path,
+                    result: Ok(ctx),
+                    claimed: None,
+                },
+                Err((err, claimed)) => VerifiedFile {
+                    path,
+                    result: Err(err),
+                    claimed,
+                },
+            })
+            .collect()
+    }
+
+    /// Compare the main executable image of a running process against the signed file it was
+    /// loaded from, to detect process hollowing and images patched in memory, failing with
+    /// `Error::ImageMismatch` when they differ. This doesn't verify the signature itself, use
+    /// `for_pid` for that.
+    /// On macOS the code directory hash the kernel recorded when loading the process is compared
+    /// with the one of the file on disk. On Windows the executable sections mapped in the process
+    /// are compared with the file after applying base relocations, the import address table,
+    /// which the loader writes, is skipped.
+    pub fn compare_image(pid: i32) -> Result<(), Error> {
+        Verifier::compare_image(pid)
+    }
+
+    /// Create a verifier for an already open file, e.g. `File::as_raw_handle`.
+    /// The file is hashed through the handle rather than opened again by path, so it can't be
+    /// swapped between resolving and verifying it. The handle is duplicated, the caller keeps
+    /// ownership of it, and it needs read access.
+    #[cfg(windows)]
+    pub fn for_handle(handle: std::os::windows::io::RawHandle) -> Result<Self, Error> {
+        Verifier::for_handle(handle as _).map(CodeSignVerifier::new)
+    }
+
+    /// Create a verifier for the running executable itself, for a self-integrity check.
+    /// On macOS this is the code object of the process, from `SecCodeCopySelf`, so the
+    /// dynamic validity of the process is checked along with its file. On Windows the
+    /// executable is verified through a handle held open while verifying it.
+    pub fn for_current_process() -> Result<Self, Error> {
+        Verifier::for_current_process().map(CodeSignVerifier::new)
+    }
+
+    /// Create a verifier for a running process by its audit token, the `val` words of an
+    /// `audit_token_t`, e.g. from `xpc_connection_get_audit_token` or an Endpoint Security message.
+    /// Unlike a PID the token can't be reused by another process, so this is the race-free way
+    /// to verify the peer of an XPC connection.
+    #[cfg(target_os = "macos")]
+    pub fn for_audit_token(token: [u32; 8]) -> Result<Self, Error> {
+        Verifier::for_audit_token(token).map(CodeSignVerifier::new)
+    }
+
+    /// Perform the verification itself.
+    /// On macOS the verification uses the Security framework with "anchor trusted" as the requirement.
+    /// On Windows the verification uses WinTrust and the `WINTRUST_ACTION_GENERIC_VERIFY_V2` action.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
+    /// ```
+    pub fn verify(&self, requirement: &str) -> Result<SignatureContext, Error> {
+        self.verify_with(&VerificationOptions::new().requirement(requirement))
+    }
+
+    /// Perform the verification with explicit options, see `VerificationOptions`.
+    /// The verifier isn't consumed, so the same code can be verified against several
+    /// requirements or policies without creating the code object again.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{Architecture, CodeSignVerifier, VerificationOptions};
+    ///
+    /// let options = VerificationOptions::new()
+    ///     .requirement("anchor apple")
+    ///     .architecture(Architecture::Named("arm64".to_string()))
+    ///     .nested_code_depth(1);
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Applications/Safari.app").unwrap().verify_with(&options).unwrap();
+    /// assert!(ctx.nested_code().iter().all(|nested| nested.result.is_ok()));
+    /// ```
+    pub fn verify_with(&self, options: &VerificationOptions) -> Result<SignatureContext, Error> {
+        match &options.audit {
+            Some(sink) => {
+                let (time, started) = (std::time::SystemTime::now(), std::time::Instant::now());
+                let result = self.verify_unaudited(options);
+                sink.0.record(&audit::AuditRecord {
+                    time,
+                    target: self.0.path().ok(),
+                    executable: result
+                        .as_ref()
+                        .ok()
+                        .and_then(SignatureContext::executable_path),
+                    duration: started.elapsed(),
+                    error: result.as_ref().err().map(Error::to_string),
+                    subject: result.as_ref().ok().map(SignatureContext::subject_name),
+                    sha256_thumbprint: result
+                        .as_ref()
+                        .ok()
+                        .map(SignatureContext::sha256_thumbprint),
+                });
+                result
+            }
+            None => self.verify_unaudited(options),
+        }
+    }
+
+    fn verify_unaudited(&self, options: &VerificationOptions) -> Result<SignatureContext, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("verify").entered();
+
+        options.check_cancelled()?;
+        let result = revocation::verify(&self.0, options)
+            .map(|context| SignatureContext(std::sync::Arc::new(context)))
+            .and_then(|ctx| ctx.check_pins(options))
+            .map_err(|err| self.stripped_signature(err))
+            // A failure once cancelled may just be a phase that was cut short
+            .map_err(|err| options.check_cancelled().err().unwrap_or(err));
+
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &result {
+            tracing::debug!(error = %err, "verification failed");
+        }
+        result
+    }
+
+    /// Tell a signature that was removed or cut short from code that was never signed, or
+    /// that was modified. Neither platform does: a stripped signature reads as unsigned, and
+    /// a truncated one as modified code, but the headers that pointed at it remain.
+    fn stripped_signature(&self, err: Error) -> Error {
+        let unsigned = err.is_no_signature();
+        if !unsigned && !matches!(err, Error::Tampered { .. } | Error::OsError { .. }) {
+            return err;
+        }
+
+        let path = match self.0.path() {
+            Ok(path) => path,
+            Err(_) => return err,
+        };
+        match remnant::signature_record(&path) {
+            // Only a record pointing past the end of the file explains a failure other than
+            // a missing signature
+            Some(record) if unsigned || record.truncated => {
+                Error::SignatureStripped(record.remnant)
+            }
+            _ => err,
+        }
+    }
+
+    /// Verify the signature, and that the leaf certificate has the expected sha256 thumbprint.
+    /// Fails with `Error::ThumbprintMismatch` when the signature is valid, but made with another certificate.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// const PINNED: [u8; 32] = [0; 32]; // The thumbprint of the vendor's signing certificate
+    ///
+    /// CodeSignVerifier::for_file("C:/Program Files/Vendor/app.exe").unwrap().verify_thumbprint(&PINNED).unwrap();
+    /// ```
+    pub fn verify_thumbprint(&self, expected: &[u8]) -> Result<SignatureContext, Error> {
+        self.verify_with(&VerificationOptions::new().leaf_thumbprint(expected))
+    }
+
+    /// Verify the signature, and that the leaf certificate was issued to the expected publisher,
+    /// see `VerificationOptions::publisher` for how names are compared.
+    /// Fails with `Error::PublisherMismatch` when the signature is valid, but from another publisher.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify_publisher("Microsoft Corporation").unwrap();
+    /// ```
+    pub fn verify_publisher(&self, expected: &str) -> Result<SignatureContext, Error> {
+        self.verify_with(&VerificationOptions::new().publisher(expected))
+    }
+
+    /// Verify a downloaded update, and that it's signed by the same publisher as the binary it
+    /// replaces, see `SignatureContext::same_publisher`. Both files are verified with the default
+    /// options. Fails with `Error::PublisherMismatch` when the update's signature is valid, but
+    /// from another publisher.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let current = std::env::current_exe().unwrap();
+    /// CodeSignVerifier::verify_update(current, "/tmp/Updater/Tool").unwrap();
+    /// ```
+    pub fn verify_update<P, Q>(current_exe: P, new_file: Q) -> Result<SignatureContext, Error>
+    where
+        P: AsRef<std::path::Path>,
+        Q: AsRef<std::path::Path>,
+    {
+        CodeSignVerifier::for_file(current_exe)?
+            .verify_with(&VerificationOptions::new())?
+            .same_publisher_as_file(new_file)
+    }
+
+    /// Verify a driver against the kernel-mode code signing policy, rather than just any trusted root.
+    /// Fails with `Error::DriverPolicy` when the signature is valid, but the driver would not load.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let path = format!("{}/System32/drivers/ntfs.sys", std::env::var("windir").unwrap());
+    /// let ctx = CodeSignVerifier::for_file(path).unwrap().verify_driver().unwrap();
+    /// assert!(ctx.driver_policy().would_load());
+    /// ```
+    #[cfg(windows)]
+    pub fn verify_driver(&self) -> Result<SignatureContext, Error> {
+        let ctx = self.verify_with(&VerificationOptions::new())?;
+        let policy = ctx.driver_policy();
+
+        if policy.would_load() {
+            Ok(ctx)
+        } else {
+            Err(Error::DriverPolicy(policy))
+        }
+    }
+
+    /// Check that a running process is still dynamically valid, i.e. that the kernel hasn't
+    /// invalidated its signature at runtime, for example because code pages that don't match
+    /// it were loaded. Unlike `verify` the file on disk isn't validated again, so this is cheap
+    /// enough to repeat, and catches processes patched in memory after they were verified.
+    /// Only applies to verifiers created with `for_pid` or `for_audit_token`, fails with
+    /// `Error::Invalidated` when the signature is no longer valid.
+    #[cfg(target_os = "macos")]
+    pub fn check_dynamic_validity(&self) -> Result<(), Error> {
+        self.0.check_dynamic_validity()
+    }
+
+    /// Read the signer the signature claims without validating it, typically once verification
+    /// failed, so a report can say who an expired, revoked or modified signature claims to be
+    /// from. None when there is no signature, or it can't be read at all. On Windows only
+    /// embedded signatures are read, a catalog only vouches for the files it validly signs.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    ///
+    /// let verifier = CodeSignVerifier::for_file("C:/Downloads/setup.exe").unwrap();
+    /// if let Err(err) = verifier.verify_with(&VerificationOptions::new()) {
+    ///     match verifier.claimed_signer() {
+    ///         Some(signer) => println!("{}, claimed by {:?}", err, signer.subject.common_name),
+    ///         None => println!("{}", err),
+    ///     }
+    /// }
+    /// ```
+    pub fn claimed_signer(&self) -> Option<ClaimedSigner> {
+        let ctx = SignatureContext(std::sync::Arc::new(self.0.claimed_context()?));
+        Some(ClaimedSigner {
+            subject: ctx.subject_name(),
+            issuer: ctx.issuer_name(),
+            sha256_thumbprint: ctx.sha256_thumbprint(),
+            serial: ctx.serial(),
+            timestamp: ctx.timestamp(),
+        })
+    }
+
+    /// Retrieve the number of signatures on the file, including the primary one.
+    /// Windows files can carry nested secondary signatures, for example when a vendor signed
+    /// binary is re-signed by an enterprise pipeline. On macOS there is always a single signature.
+    pub fn signer_count(&self) -> Result<u32, Error> {
+        self.0.signer_count()
+    }
+
+    /// Verify the primary signature, and keep the trust provider state for follow-up queries
+    /// about the file's signatures. See `session::VerificationSession`.
+    pub fn session(
+        &self,
+        options: &VerificationOptions,
+    ) -> Result<session::VerificationSession<'_>, Error> {
+        session::VerificationSession::new(self, options)
+    }
+
+    /// Verify a specific signature by index, where 0 is the primary signature.
+    /// Returns `Error::SignerNotFound` if the file has no signature at that index.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+    ///
+    /// let verifier = CodeSignVerifier::for_file("C:/Program Files/Vendor/app.exe").unwrap();
+    /// for index in 0..verifier.signer_count().unwrap() {
+    ///     let ctx = verifier.verify_signer(index, &VerificationOptions::new()).unwrap();
+    ///     println!("{:?}", ctx.subject_name().organization);
+    /// }
+    /// ```
+    pub fn verify_signer(
+        &self,
+        index: u32,
+        options: &VerificationOptions,
+    ) -> Result<SignatureContext, Error> {
+        self.0
+            .verify_signer(index, options)
+            .map(|context| SignatureContext(std::sync::Arc::new(context)))
+            .and_then(|ctx| ctx.check_pins(options))
+    }
+}
+
+/// Normalize a publisher name for comparison: case, punctuation and spacing differences are ignored
+fn normalize_publisher(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compare two byte strings in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl SignatureContext {
+    /// Check the leaf certificate against the thumbprint pinned in the options, if any
+    fn check_pins(self, options: &VerificationOptions) -> Result<Self, Error> {
+        if let Some(expected) = &options.leaf_thumbprint {
+            if !constant_time_eq(expected, &self.thumbprint_bytes()) {
+                return Err(Error::ThumbprintMismatch);
+            }
+        }
+
+        if let Some(expected) = &options.publisher {
+            let expected = normalize_publisher(expected);
+            let subject = self.subject_name();
+
+            let matches = [subject.organization, subject.common_name]
+                .iter()
+                .flatten()
+                .any(|name| normalize_publisher(name) == expected);
+            if !matches {
+                return Err(Error::PublisherMismatch);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Whether the other signature is from the same publisher as this one. Signatures with a
+    /// team ID, on macOS, match on it alone. Others, such as Apple's own code and everything on
+    /// Windows, match on the organization the leaf certificate was issued to, or its common name
+    /// without one, compared like `VerificationOptions::publisher`.
+    pub fn same_publisher(&self, other: &SignatureContext) -> bool {
+        let team_id = |ctx: &SignatureContext| match ctx.platform_properties() {
+            PlatformProperties::Mac(mac) => mac.team_id,
+            PlatformProperties::Windows(_) => None,
+        };
+        let publisher = |ctx: &SignatureContext| {
+            let subject = ctx.subject_name();
+            subject
+                .organization
+                .or(subject.common_name)
+                .map(|name| normalize_publisher(&name))
+        };
+
+        match (team_id(self), team_id(other)) {
+            (Some(team_id), Some(other_team_id)) => team_id == other_team_id,
+            (None, None) => publisher(self).map_or(false, |name| Some(name) == publisher(other)),
+            _ => false,
+        }
+    }
+
+    /// Verify the file with the default options, and that it's signed by the same publisher as
+    /// this signature, see `same_publisher`. Fails with `Error::PublisherMismatch` when its
+    /// signature is valid, but from another publisher.
+    pub fn same_publisher_as_file<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> Result<SignatureContext, Error> {
+        let ctx = CodeSignVerifier::for_file(path)?.verify_with(&VerificationOptions::new())?;
+        if !self.same_publisher(&ctx) {
+            return Err(Error::PublisherMismatch);
+        }
+        Ok(ctx)
+    }
+
+    /// Retrieve the subject name on the leaf certificate
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let ctx = CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
+    /// assert_eq!(
+    ///    ctx.subject_name().organization.as_deref(),
+    ///    Some("Microsoft Corporation")
+    /// );
+    ///
+    /// ```
+    pub fn subject_name(&self) -> Name {
+        self.0.subject_name()
+    }
+
+    /// Retrieve the issuer name on the leaf certificate
+    pub fn issuer_name(&self) -> Name {
+        self.0.issuer_name()
+    }
+
+    /// Compute the sha256 thumbprint of the leaf certificate
+    pub fn sha256_thumbprint(&self) -> String {
+        self.0.sha256_thumbprint()
+    }
+
+    /// Compute the sha256 thumbprint of the leaf certificate in the given format
+    pub fn sha256_thumbprint_with(&self, format: &HexFormat) -> String {
+        format.format(&self.thumbprint_bytes())
+    }
+
+    /// Compute the sha256 thumbprint of the leaf certificate as raw bytes, for comparing
+    /// against binary allowlists
+    pub fn thumbprint_bytes(&self) -> [u8; 32] {
+        self.0.sha256_thumbprint_bytes()
+    }
+
+    /// Retrieve the leaf certificate serial number, as lowercase hex without leading zeros.
+    /// Both platforms format it the same way, see `serial_with` for other formats.
+    pub fn serial(&self) -> Option<String> {
+        self.serial_with(&HexFormat::new())
+    }
+
+    /// Retrieve the leaf certificate serial number in the given format
+    pub fn serial_with(&self, format: &HexFormat) -> Option<String> {
+        self.0
+            .serial_bytes()
+            .map(|serial| format.format_serial(&serial))
+    }
+
+    /// Retrieve the leaf certificate serial number as raw bytes, big endian and exactly as the
+    /// certificate encodes it, including the leading zero byte DER pads some serials with
+    pub fn serial_bytes(&self) -> Option<Vec<u8>> {
+        self.0.serial_bytes()
+    }
+
+    /// Retrieve the signing identifier the code was signed with, the `identifier` designated
+    /// requirements match on. It needn't be the bundle identifier, and bare binaries have one
+    /// too. Only available on macOS.
+    pub fn signing_identifier(&self) -> Option<String> {
+        self.0.identifier()
+    }
+
+    /// Classify the signer from the marker extensions Apple's CAs put in the leaf certificate,
+    /// and the platform identifier. Markers are only trusted in a chain that ends at an Apple
+    /// root, so None for other certificates, and always None on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, SignerKind};
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Applications/Tool.app").unwrap().verify("").unwrap();
+    /// if ctx.signer_kind() == Some(SignerKind::AppleDevelopment) {
+    ///     println!("development build");
+    /// }
+    /// ```
+    pub fn signer_kind(&self) -> Option<SignerKind> {
+        match self.platform_properties() {
+            PlatformProperties::Mac(_) => {
+                apple::signer_kind(&self.0.certificate_chain(), self.is_platform_binary())
+            }
+            PlatformProperties::Windows(_) => None,
+        }
+    }
+
+    /// Retrieve the Apple marker extensions in the leaf certificate, and the Developer ID CA
+    /// marker in its issuer. Empty unless the chain ends at one of Apple's roots, as anyone can
+    /// put the extensions in a certificate of their own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{AppleMarker, CodeSignVerifier};
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Library/Extensions/Driver.kext").unwrap().verify("").unwrap();
+    /// let kext_allowed = ctx.apple_markers().contains(&AppleMarker::DeveloperIdKext);
+    /// ```
+    pub fn apple_markers(&self) -> Vec<AppleMarker> {
+        apple::markers(&self.0.certificate_chain())
+    }
+
+    /// Retrieve the signing time claimed by the signer.
+    /// This is the unverified time from the signer's own clock, and is only available on macOS.
+    pub fn signing_time(&self) -> Option<std::time::SystemTime> {
+        self.0.signing_time()
+    }
+
+    /// Retrieve the time of the secure timestamp attached to the signature, if any.
+    /// On Windows this is the time recorded by the timestamp counter signature.
+    pub fn timestamp(&self) -> Option<std::time::SystemTime> {
+        self.0.timestamp()
+    }
+
+    /// Retrieve the validity period of the leaf certificate, as not before and not after times
+    pub fn validity_period(&self) -> Option<(std::time::SystemTime, std::time::SystemTime)> {
+        self.0.validity_period()
+    }
+
+    /// Report whether the signature is valid because the leaf certificate is valid now,
+    /// or because it was timestamped while the certificate was valid.
+    /// `Expired` is only reported on macOS, which ignores expiry unless asked not to.
+    /// Signatures accepted under `TimestampPolicy::ShortLived` report `Timestamped` once
+    /// their certificate expired, as that's the rule they rely on.
+    pub fn time_validity(&self) -> TimeValidity {
+        let now = std::time::SystemTime::now();
+
+        match self.validity_period() {
+            Some((not_before, not_after)) if now < not_before || now > not_after => {
+                match self.timestamp() {
+                    Some(_) => TimeValidity::Timestamped,
+                    None => TimeValidity::Expired,
+                }
+            }
+            _ => TimeValidity::Current,
+        }
+    }
+
+    /// The canonical path of the file that was verified, with links resolved, the bundle
+    /// directory for bundles. None when it couldn't be resolved, such as for a file that
+    /// was removed once verified.
+    pub fn verified_path(&self) -> Option<&std::path::Path> {
+        self.0.path()
+    }
+
+    /// The canonical path of the executable that was verified: the main executable for a
+    /// bundle, and the image a process was started from for verifiers created with `for_pid`
+    /// and the like. None when it couldn't be resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let ctx = CodeSignVerifier::for_pid(1234).unwrap().verify("").unwrap();
+    /// println!("verified {:?} in {:?}", ctx.executable_path(), ctx.bundle_path());
+    /// ```
+    pub fn executable_path(&self) -> Option<std::path::PathBuf> {
+        self.0.executable_path()
+    }
+
+    /// The canonical path of the bundle that was verified, or that contains the executable of
+    /// a verified process. None for bare executables, and always None on Windows.
+    pub fn bundle_path(&self) -> Option<std::path::PathBuf> {
+        self.0.bundle_path()
+    }
+
+    /// Read the quarantine and provenance attributes of the verified file or bundle, which
+    /// decide how Gatekeeper treats it on launch, see the `quarantine` module. None when the
+    /// path couldn't be resolved or the attributes couldn't be read.
+    #[cfg(target_os = "macos")]
+    pub fn quarantine_attributes(&self) -> Option<quarantine::Attributes> {
+        quarantine::attributes(self.verified_path()?).ok()
+    }
+
+    /// Read the CMS SignedData of the signature embedded in the executable, one for each slice
+    /// of a universal binary, and none for files signed through a catalog. The blob isn't
+    /// verified again, see the `cms` module.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let ctx = CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
+    /// for signed_data in ctx.signed_data() {
+    ///     std::fs::write("signature.p7s", &signed_data.der).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "cms")]
+    pub fn signed_data(&self) -> Vec<cms::SignedData> {
+        self.executable_path()
+            .map(cms::signed_data)
+            .unwrap_or_default()
+    }
+
+    /// Locate the signature embedded in the executable, for carving it out of the file. One
+    /// location for each slice of a universal binary, in the order of the slices, and none
+    /// for files signed through a catalog.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// let ctx = CodeSignVerifier::for_file("C:/Windows/explorer.exe").unwrap().verify("").unwrap();
+    /// let location = ctx.signature_locations()[0];
+    /// let mut file = std::fs::File::open(ctx.executable_path().unwrap()).unwrap();
+    /// let mut signature = vec![0; location.size as usize];
+    /// file.seek(SeekFrom::Start(location.offset)).unwrap();
+    /// file.read_exact(&mut signature).unwrap();
+    /// ```
+    pub fn signature_locations(&self) -> Vec<SignatureLocation> {
+        let path = match self.executable_path() {
+            Some(path) => path,
+            None => return Vec::new(),
+        };
+        remnant::signature_records(&path)
+            .into_iter()
+            .filter(|record| !record.truncated)
+            .map(|record| SignatureLocation {
+                format: record.remnant,
+                offset: record.offset,
+                size: record.size,
+                trailing_bytes: record.trailing,
+            })
+            .collect()
+    }
+
+    /// Retrieve the per-item results of nested code verification.
+    /// Empty unless `VerificationOptions::nested_code_depth` was set.
+    pub fn nested_code(&self) -> &[NestedCode] {
+        self.0.nested_code()
+    }
+
+    /// Retrieve the launch, parent process and library constraints embedded in the signature.
+    /// Always empty on Windows and on macOS versions before 13.3.
+    pub fn launch_constraints(&self) -> LaunchConstraints {
+        self.0.launch_constraints()
+    }
+
+    /// Whether the code is part of the OS. On macOS, that it has a platform identifier, rather
+    /// than looking for `platform_id` in `additional_properties`. On Windows, that it's signed
+    /// with the Windows System Component Verification EKU under a Microsoft root, or that
+    /// Windows Resource Protection guards the file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let pid = std::process::id() as i32;
+    /// let ctx = CodeSignVerifier::for_pid(pid).unwrap().verify("").unwrap();
+    /// if !ctx.is_platform_binary() {
+    ///     println!("running third party code");
+    /// }
+    /// ```
+    pub fn is_platform_binary(&self) -> bool {
+        self.0.is_platform_binary()
+    }
+
+    /// Evaluate the signature against the Windows kernel-mode code signing policy
+    #[cfg(windows)]
+    pub fn driver_policy(&self) -> DriverPolicy {
+        self.0.driver_policy()
+    }
+
+    /// Retrieve the hardened runtime version the code was signed with, if the hardened runtime is enabled.
+    /// Always None on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, RuntimeVersion};
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Applications/Safari.app").unwrap().verify("anchor apple").unwrap();
+    /// let minimum = RuntimeVersion { major: 10, minor: 14, patch: 0 };
+    /// assert!(ctx.runtime_version().unwrap() >= minimum);
+    /// ```
+    pub fn runtime_version(&self) -> Option<RuntimeVersion> {
+        self.0.runtime_version()
+    }
+
+    /// Retrieve the trust status of the leaf certificate chain.
+    /// Chains that fail verification are reported in the error instead, e.g. `Error::Expired`
+    /// or `Error::ChainFailed`.
+    pub fn chain_status(&self) -> ChainStatus {
+        self.0.chain_status()
+    }
+
+    /// Retrieve the revocation status of each certificate in the signing chain, from the leaf
+    /// certificate up to the root. Chains that fail verification because a certificate was
+    /// revoked are reported in `Error::Revoked` instead. When the `revocation` cache settled
+    /// the check, these are the statuses of the online check it remembered.
+    pub fn revocation_status(&self) -> Vec<RevocationStatus> {
+        self.0.revocation_status()
+    }
+
+    /// Retrieve the catalog the file was verified through, when it has no embedded signature.
+    /// The leaf certificate is then the catalog's signer. Always None on macOS.
+    pub fn catalog(&self) -> Option<&CatalogInfo> {
+        self.0.catalog()
+    }
+
+    /// Retrieve the provisioning profile embedded in the verified bundle, if any.
+    /// Always None on Windows.
+    pub fn provisioning_profile(&self) -> Option<ProvisioningProfile> {
+        self.0.provisioning_profile()
+    }
+
+    /// Retrieve the entitlements the code was signed with, if any. These are read from the
+    /// legacy plist slot, or from the DER slot for code signed without one.
+    /// Always None on Windows.
+    pub fn entitlements(&self) -> Option<std::collections::BTreeMap<String, PropertyValue>> {
+        self.0.entitlements()
+    }
+
+    /// Retrieve the DER encoded entitlements, the ones the kernel enforces since macOS 12.
+    /// None for code signed before DER entitlements, and always None on Windows.
+    pub fn der_entitlements(&self) -> Option<std::collections::BTreeMap<String, PropertyValue>> {
+        self.0.der_entitlements()
+    }
+
+    /// Retrieve a single entitlement the code was signed with, such as
+    /// `com.apple.security.cs.disable-library-validation`.
+    /// Always None on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Applications/Tool.app").unwrap().verify("").unwrap();
+    /// let unvalidated = ctx
+    ///     .entitlement("com.apple.security.cs.disable-library-validation")
+    ///     .map_or(false, |value| value.is_true());
+    /// ```
+    pub fn entitlement(&self, key: &str) -> Option<EntitlementValue> {
+        self.0
+            .entitlements()?
+            .remove(key)
+            .map(EntitlementValue::from)
+    }
+
+    /// Retrieve the complete Info.plist sealed by the signature, with every key rather than the
+    /// few `platform_properties` picks out. Bare binaries only have one when it's embedded in
+    /// their `__info_plist` section. Always None on Windows.
+    #[cfg(feature = "plist")]
+    pub fn info_plist(&self) -> Option<plist::Value> {
+        self.0.info_plist_value().map(plist::Value::from)
+    }
+
+    /// Retrieve the Certificate Transparency SCTs embedded in the leaf certificate.
+    /// Empty when the certificate wasn't logged, or its logs weren't embedded.
+    pub fn signed_certificate_timestamps(&self) -> Vec<ct::SignedCertificateTimestamp> {
+        self.0
+            .certificate_chain()
+            .first()
+            .map(|leaf| ct::embedded_scts(leaf))
+            .unwrap_or_default()
+    }
+
+    /// Check the signatures of the SCTs embedded in the leaf certificate against the known logs
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::ct::{CtLog, SctStatus};
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let log = CtLog::new("Example log", &std::fs::read("log_key.der").unwrap()).unwrap();
+    /// let ctx = CodeSignVerifier::for_file("/sbin/ping").unwrap().verify("").unwrap();
+    /// let logged = ctx
+    ///     .verify_certificate_transparency(&[log])
+    ///     .iter()
+    ///     .any(|(_, status)| *status == SctStatus::Valid);
+    /// ```
+    #[cfg(feature = "ct")]
+    pub fn verify_certificate_transparency(
+        &self,
+        logs: &[ct::CtLog],
+    ) -> Vec<(ct::SignedCertificateTimestamp, ct::SctStatus)> {
+        ct::verify(&self.0.certificate_chain(), logs)
+    }
+
+    /// Additional properties.
+    /// The same details are available typed from `platform_properties`.
+    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
+        self.0.additional_properties()
+    }
+
+    /// Retrieve the signature details specific to the platform
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, PlatformProperties};
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/sbin/ping").unwrap().verify("").unwrap();
+    /// if let PlatformProperties::Mac(properties) = ctx.platform_properties() {
+    ///     println!("{:?}", properties.team_id);
+    /// }
+    /// ```
+    pub fn platform_properties(&self) -> PlatformProperties {
+        self.0.platform_properties()
+    }
+
+    /// Collect everything known about the signature into an owned report
+    pub fn report(&self) -> VerificationReport {
+        VerificationReport {
+            subject: self.subject_name(),
+            issuer: self.issuer_name(),
+            sha256_thumbprint: self.sha256_thumbprint(),
+            serial: self.serial(),
+            signing_time: self.signing_time(),
+            timestamp: self.timestamp(),
+            validity_period: self.validity_period(),
+            time_validity: self.time_validity(),
+            chain_status: self.chain_status(),
+            revocation: self.revocation_status(),
+            runtime_version: self.runtime_version(),
+            platform: self.platform_properties(),
+            #[cfg(windows)]
+            driver_policy: self.driver_policy(),
+        }
+    }
+
+    /// Look up the notarization status of the signed code with Apple's online ticket service.
+    /// Useful when no ticket is stapled to the bundle. This performs a network request.
+    /// Fails with `Error::Unsupported` when the signature doesn't report its digest algorithm,
+    /// which the ticket is keyed on along with the cdhash.
+    #[cfg(all(target_os = "macos", feature = "notarization"))]
+    pub fn notarization_status(&self) -> Result<notarization::NotarizationStatus, Error> {
+        let cd_hash = self.0.cd_hash().ok_or(Error::Unsigned)?;
+        let hash_type = self.0.digest_algorithm().ok_or(Error::Unsupported)?;
+        notarization::lookup(&cd_hash, hash_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+    use std::collections::HashMap;
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_signed() {
+        let verifier = super::CodeSignVerifier::for_file("/sbin/ping").unwrap(); // Should always be present on macOS
+        let ctx = verifier.verify("anchor apple generic").unwrap(); // Should always be signed
+
+        // If those values begin to fail, Apple probably changed their certficate
+        assert_eq!(
+            ctx.subject_name().organization.as_deref(),
+            Some("Apple Inc.")
+        );
+
+        assert_eq!(
+            ctx.issuer_name().organization_unit.as_deref(),
+            Some("Apple Certification Authority")
+        );
+
+        assert_eq!(
+            ctx.additional_properties(),
+            Some(HashMap::from([
+                (
+                    "cd_hash".to_string(),
+                    "AEE97B850A12F9CAC3EC399094071CAD63325818".to_string()
+                ),
+                ("identifier".to_string(), "com.apple.ping".to_string()),
+                ("platform_id".to_string(), "15".to_string())
+            ]))
+        );
+        assert_eq!(ctx.signing_identifier().as_deref(), Some("com.apple.ping"));
+
+        match ctx.platform_properties() {
+            super::PlatformProperties::Mac(properties) => {
+                assert_eq!(properties.platform_id, Some(15));
+                assert_eq!(properties.team_id, None);
+                assert_eq!(properties.identifier.as_deref(), Some("com.apple.ping"));
+            }
+            _ => unreachable!(),
+        }
+
+        assert_eq!(ctx.signer_kind(), Some(super::SignerKind::ApplePlatform));
+        assert!(ctx.is_platform_binary());
+
+        let locations = ctx.signature_locations();
+        assert!(!locations.is_empty());
+        assert!(locations
+            .iter()
+            .all(|location| location.size > 0 && location.trailing_bytes == 0));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_verify_repeatedly() {
+        let verifier = super::CodeSignVerifier::for_file("/sbin/ping").unwrap();
+        assert!(verifier.verify("anchor apple").is_ok());
+        assert!(matches!(
+            verifier.verify("anchor apple generic and certificate leaf[subject.OU] = \"ABC123\""),
+            Err(Error::RequirementFailed) | Err(Error::CFError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_executable_only() {
+        use super::{ValidationMode, VerificationOptions};
+
+        // Nested code is skipped, whatever the depth asks for
+        let options = VerificationOptions::new()
+            .requirement("anchor apple")
+            .nested_code_depth(2)
+            .mode(ValidationMode::ExecutableOnly);
+        let verifier = super::CodeSignVerifier::for_pid(1).unwrap();
+        let ctx = verifier.verify_with(&options).unwrap();
+        assert!(ctx.nested_code().is_empty());
+        assert!(verifier
+            .verify_with(
+                &options.requirement(
+                    "anchor apple generic and certificate leaf[subject.OU] = \"ABC123\""
+                )
+            )
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_signed() {
+        let path = format!("{}/explorer.exe", std::env::var("windir").unwrap()); // Should always be present on Windows
+        let verifier = super::CodeSignVerifier::for_file(path).unwrap();
+        let ctx = verifier.verify("").unwrap(); // Should always be signed
+
+        // If those values begin to fail, Microsoft probably changed their certficate
+        assert_eq!(
+            ctx.subject_name().organization.as_deref(),
+            Some("Microsoft Corporation")
+        );
+
+        assert_eq!(
+            ctx.issuer_name().common_name.as_deref(),
+            Some("Microsoft Windows Production PCA 2011")
+        );
+
+        assert_eq!(
+            ctx.serial().as_deref(),
+            Some("3300000266bd1580efa75cd6d3000000000266")
+        );
+
+        // Windows only accepts an expired certificate with a timestamp
+        assert_ne!(ctx.time_validity(), super::TimeValidity::Expired);
+
+        match ctx.platform_properties() {
+            super::PlatformProperties::Windows(properties) => {
+                assert_eq!(
+                    properties.company_name.as_deref(),
+                    Some("Microsoft Corporation")
+                );
+                assert!(properties.file_version.is_some());
+                assert!(properties.machine.is_some());
+            }
+            _ => unreachable!(),
+        }
+        assert!(ctx.is_platform_binary());
+    }
+
+    #[test]
+    fn test_executable_path() {
+        let ctx = super::verified_system_binary();
+        assert!(ctx.executable_path().is_some());
+        assert_eq!(ctx.executable_path().as_deref(), ctx.verified_path());
+        assert_eq!(ctx.bundle_path(), None);
+
+        // Safari can be a link into a cryptex, the reported paths are canonical
+        #[cfg(target_os = "macos")]
+        {
+            let bundle = std::fs::canonicalize("/Applications/Safari.app").unwrap();
+            let ctx = super::CodeSignVerifier::for_file(&bundle)
+                .unwrap()
+                .verify("")
+                .unwrap();
+            assert_eq!(
+                ctx.executable_path(),
+                Some(bundle.join("Contents/MacOS/Safari"))
+            );
+            assert_eq!(ctx.bundle_path(), Some(bundle));
+        }
+    }
+
+    #[test]
+    fn test_platform_properties() {
+        use super::PlatformProperties;
+
+        #[cfg(target_os = "macos")]
+        let path = "/Applications/Safari.app";
+        #[cfg(windows)]
+        let path = "c:\\windows\\explorer.exe";
+
+        let ctx = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify("")
+            .unwrap();
+        let additional = ctx.additional_properties().unwrap();
+
+        // The map holds the same properties, as strings
+        match ctx.platform_properties() {
+            PlatformProperties::Mac(properties) => {
+                assert_eq!(properties.bundle_id.as_deref(), Some("com.apple.Safari"));
+                assert!(properties.short_version.is_some());
+                assert_eq!(additional.get("bundle_id"), properties.bundle_id.as_ref());
+                assert_eq!(additional.get("cd_hash"), properties.cd_hash.as_ref());
+            }
+            PlatformProperties::Windows(properties) => {
+                assert!(properties.microsoft_root);
+                assert!(!properties.enhanced_key_usages.is_empty());
+                assert_eq!(
+                    additional.get("company_name"),
+                    properties.company_name.as_ref()
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_dynamic_validity() {
+        let verifier = super::CodeSignVerifier::for_pid(1).unwrap(); // launchd
+        assert!(verifier.check_dynamic_validity().is_ok());
+
+        let ctx = verifier.verify("anchor apple").unwrap();
+        assert_eq!(
+            ctx.executable_path().as_deref(),
+            Some(std::path::Path::new("/sbin/launchd"))
+        );
+        assert_eq!(ctx.bundle_path(), None);
+
+        let verifier = super::CodeSignVerifier::for_file("/sbin/ping").unwrap();
+        let res = verifier.check_dynamic_validity();
+        assert!(matches!(res, Err(Error::Unsupported)));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_socket_peer() {
+        use std::os::unix::io::AsRawFd;
+
+        // The peer is the test executable itself, only resolving it is checked
+        let (stream, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let options = super::VerificationOptions::new();
+        let res = super::CodeSignVerifier::verify_socket_peer(stream.as_raw_fd(), &options);
+        assert!(!matches!(res, Err(Error::OsError { .. })));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_compare_image() {
+        assert!(super::CodeSignVerifier::compare_image(1).is_ok()); // launchd
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_validate_requirement() {
+        assert!(super::Requirement::anchor_apple_generic()
+            .team_id("ABC123")
+            .validate()
+            .is_ok());
+
+        let res = super::Requirement::from_text("anchor apple generic and").validate();
+        assert!(matches!(
+            res,
+            Err(Error::InvalidRequirement(super::RequirementSyntax {
+                line: Some(1),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_pid_modules() {
+        let options = super::VerificationOptions::new();
+        let modules =
+            super::CodeSignVerifier::for_pid_modules(std::process::id() as i32, &options).unwrap();
+        assert!(modules.len() > 1);
+
+        // The system libraries are always loaded, and signed
+        #[cfg(windows)]
+        let system = "ntdll.dll";
+        #[cfg(target_os = "macos")]
+        let system = "dyld";
+        let module = modules
+            .iter()
+            .find(|m| m.path.file_name().unwrap().eq_ignore_ascii_case(system))
+            .unwrap();
+        assert!(module.result.is_ok());
+    }
+
+    #[test]
+    fn test_error_display() {
+        fn assert_error<E: std::error::Error + Send + Sync + 'static>(_: &E) {}
+
+        let err = Error::RequirementFailed;
+        assert_error(&err);
+        assert_eq!(
+            err.to_string(),
+            "the signature is valid, but doesn't satisfy the requirement"
+        );
+
+        let err = Error::ChainFailed {
+            code: 0,
+            status: super::ChainStatus {
+                error_status: super::ChainStatus::IS_NOT_TIME_VALID,
+                ..super::ChainStatus::default()
+            },
+            message: Some("The certificate has expired".to_string()),
+        };
+        assert!(err.to_string().starts_with(
+            "the certificate chain was rejected (expired): The certificate has expired"
+        ));
+        assert_eq!(err.native_message(), Some("The certificate has expired"));
+
+        let status = super::ChainStatus::from_error_status(
+            super::ChainStatus::IS_NOT_TIME_VALID | super::ChainStatus::IS_REVOKED,
+        );
+        assert!(matches!(
+            Error::chain_failed(0, status, None),
+            Error::Revoked { ref status, .. } if status.is_expired()
+        ));
+    }
+
+    #[test]
+    fn test_chain_status() {
+        use super::ChainStatus;
+
+        let status = ChainStatus::from_error_status(
+            ChainStatus::IS_PARTIAL_CHAIN | ChainStatus::IS_OFFLINE_REVOCATION,
+        );
+        assert!(status.has_errors());
+        assert!(status.is_partial_chain() && status.is_revocation_unknown());
+        assert!(!status.is_expired() && !status.is_revoked() && !status.is_untrusted_root());
+        assert!(!ChainStatus::from_error_status(0).has_errors());
+
+        let ctx = super::verified_system_binary();
+        let status = ctx.chain_status();
+        assert!(!status.is_untrusted_root() && !status.is_revoked() && !status.is_expired());
+    }
+
+    #[test]
+    fn test_error_predicates() {
+        let status = super::ChainStatus::from_error_status(
+            super::ChainStatus::IS_REVOKED | super::ChainStatus::IS_UNTRUSTED_ROOT,
+        );
+        let err = Error::chain_failed(0, status, None);
+        assert!(err.is_revoked() && err.is_untrusted_root());
+        assert_eq!(err.kind(), super::ErrorKind::Revoked);
+        assert!(!err.is_expired() && !err.is_no_signature());
+        assert_eq!(err.code(), Some(0));
+
+        #[cfg(windows)]
+        let code = super::status::TRUST_E_SUBJECT_FORM_UNKNOWN;
+        #[cfg(target_os = "macos")]
+        let code = super::status::errSecCSUnsigned;
+        assert!(Error::os_error(code).is_no_signature());
+        assert!(Error::Unsigned.is_no_signature());
+        assert_eq!(Error::Unsigned.kind(), super::ErrorKind::Unsigned);
+        assert_eq!(
+            Error::RequirementFailed.kind(),
+            super::ErrorKind::RequirementFailed
+        );
+        assert!(!Error::RequirementFailed.is_untrusted_root());
+    }
+
+    #[test]
+    fn test_error_variants() {
+        use super::ChainStatus;
+
+        let failed = |bits| Error::chain_failed(0, ChainStatus::from_error_status(bits), None);
+        assert!(matches!(
+            failed(ChainStatus::IS_NOT_TIME_VALID),
+            Error::Expired { .. }
+        ));
+        assert!(matches!(
+            failed(ChainStatus::IS_UNTRUSTED_ROOT),
+            Error::UntrustedRoot { .. }
+        ));
+        assert!(matches!(
+            failed(ChainStatus::IS_REVOKED | ChainStatus::IS_UNTRUSTED_ROOT),
+            Error::Revoked { .. }
+        ));
+        assert!(matches!(
+            failed(ChainStatus::IS_PARTIAL_CHAIN),
+            Error::ChainFailed { .. }
+        ));
+
+        let source = super::signed_system_binary();
+
+        // Modify the code, past the headers, so the signature no longer covers it
+        let path = super::temp_path();
+        let mut data = std::fs::read(source).unwrap();
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+
+        let result = super::CodeSignVerifier::for_file(&path).unwrap().verify("");
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            matches!(result, Err(Error::Tampered { .. })),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_native_message() {
+        // TRUST_E_TIME_STAMP and errSecCSUnsigned
+        #[cfg(windows)]
+        let code = 0x80096005u32 as i32;
+        #[cfg(target_os = "macos")]
+        let code = -67062;
+
+        let err = Error::os_error(code);
+        let message = err.native_message().unwrap();
+        assert!(!message.is_empty());
+        assert!(err.to_string().starts_with(message));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let name = super::Name {
+            common_name: Some("Developer ID Application: Example (ABC123)".to_string()),
+            organization: Some("Example".to_string()),
+            organization_unit: Some("ABC123".to_string()),
+            country: None,
+        };
+        let json = serde_json::to_string(&name).unwrap();
+        assert_eq!(serde_json::from_str::<super::Name>(&json).unwrap(), name);
+
+        let status = super::ChainStatus::from_error_status(super::ChainStatus::IS_REVOKED);
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["error_status"], super::ChainStatus::IS_REVOKED);
+    }
+
+    #[test]
+    fn test_verify_many() {
+        #[cfg(target_os = "macos")]
+        let paths = [super::signed_system_binary(), "/bin/ls", "/nonexistent"];
+        #[cfg(windows)]
+        let paths = [
+            super::signed_system_binary(),
+            "c:\\windows\\system32\\svchost.exe",
+            "c:\\nonexistent.exe",
+        ];
+
+        let options = super::VerificationOptions::new();
+        let results = super::CodeSignVerifier::verify_many(&paths, &options);
+        assert_eq!(results.len(), paths.len());
+        assert!(results
+            .iter()
+            .zip(&paths)
+            .all(|(file, path)| file.path == std::path::Path::new(path)));
+        assert!(results[0].result.is_ok());
+        assert!(results[1].result.is_ok());
+        assert!(results[2].result.is_err());
+    }
+
+    #[test]
+    fn test_for_bytes() {
+        let path = super::signed_system_binary();
+
+        let data = std::fs::read(path).unwrap();
+        let verifier = super::CodeSignVerifier::for_bytes(&data).unwrap();
+        let ctx = verifier.verify_with(&super::VerificationOptions::new());
+        let expected = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify_with(&super::VerificationOptions::new());
+        assert_eq!(
+            ctx.map(|ctx| ctx.sha256_thumbprint()).ok(),
+            expected.map(|ctx| ctx.sha256_thumbprint()).ok()
+        );
+    }
+
+    #[test]
+    fn test_for_reader() {
+        use std::io::Read;
+
+        let path = super::signed_system_binary();
+
+        // The stream is verified from the start, even when partially read already
+        let mut file = std::fs::File::open(path).unwrap();
+        file.read_exact(&mut [0u8; 16]).unwrap();
+        let verifier = super::CodeSignVerifier::for_reader(file).unwrap();
+        assert!(verifier
+            .verify_with(&super::VerificationOptions::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_for_reader_with_progress() {
+        let path = super::signed_system_binary();
+
+        let size = std::fs::metadata(path).unwrap().len();
+        let file = std::fs::File::open(path).unwrap();
+        let mut reports = Vec::new();
+        let verifier = super::CodeSignVerifier::for_reader_with_progress(file, |copied, total| {
+            reports.push((copied, total))
+        })
+        .unwrap();
+        assert_eq!(reports.last(), Some(&(size, size)));
+        assert!(verifier
+            .verify_with(&super::VerificationOptions::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_builder() {
+        let path = super::signed_system_binary();
+
+        assert!(matches!(
+            super::CodeSignVerifier::builder().build(),
+            Err(Error::InvalidPath)
+        ));
+        assert!(super::CodeSignVerifier::builder()
+            .pid(1)
+            .path(path)
+            .architecture(super::Architecture::Native)
+            .verify()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<super::CodeSignVerifier>();
+        assert_send_sync::<super::SignatureContext>();
+
+        let path = super::signed_system_binary();
+
+        let verifier = super::CodeSignVerifier::for_file(path).unwrap();
+        let options = super::VerificationOptions::new();
+        let ctx = std::thread::scope(|scope| {
+            let first = scope.spawn(|| verifier.verify_with(&options).unwrap());
+            let second = scope.spawn(|| verifier.verify_with(&options).unwrap());
+            assert_eq!(
+                first.join().unwrap().sha256_thumbprint(),
+                second.join().unwrap().sha256_thumbprint()
+            );
+            verifier.verify_with(&options).unwrap()
+        });
+
+        let clone = ctx.clone();
+        drop(ctx);
+        assert!(!clone.sha256_thumbprint().is_empty());
+    }
+
+    #[test]
+    fn test_cancellation() {
+        use std::time::{Duration, Instant};
+
+        let path = super::signed_system_binary();
+
+        let verifier = super::CodeSignVerifier::for_file(path).unwrap();
+        let token = super::CancellationToken::new();
+        let options = super::VerificationOptions::new()
+            .cancellation(token.clone())
+            .deadline(Instant::now() + Duration::from_secs(60));
+        assert!(verifier.verify_with(&options).is_ok());
+
+        token.cancel();
+        let res = verifier.verify_with(&options);
+        assert!(matches!(res, Err(Error::Cancelled)));
+
+        let options = super::VerificationOptions::new().deadline(Instant::now());
+        let res = verifier.verify_with(&options);
+        assert!(matches!(res, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn test_report() {
+        let path = super::signed_system_binary();
+
+        let ctx = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify_with(&super::VerificationOptions::new())
+            .unwrap();
+        let report = ctx.report();
+        drop(ctx);
+
+        assert!(!report.sha256_thumbprint.is_empty());
+        assert!(report.subject.organization.is_some());
+        assert!(!report.chain_status.has_errors());
+        assert_ne!(report.time_validity, super::TimeValidity::Expired);
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let options = super::VerificationOptions::new();
+        let ancestors =
+            super::CodeSignVerifier::verify_ancestors(std::process::id() as i32, &options).unwrap();
+        assert!(!ancestors.is_empty());
+        assert!(ancestors.iter().all(|a| a.pid != std::process::id() as i32));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_thumbprint() {
+        let ctx = super::verified_system_binary();
+        let thumbprint = ctx.thumbprint_bytes();
+        assert_eq!(thumbprint.len(), 32);
+
+        let verifier = super::CodeSignVerifier::for_file(&path).unwrap();
+        assert!(verifier.verify_thumbprint(&thumbprint).is_ok());
+
+        let verifier = super::CodeSignVerifier::for_file(&path).unwrap();
+        let res = verifier.verify_thumbprint(&[0; 32]);
+        assert!(matches!(res, Err(Error::ThumbprintMismatch)));
+    }
+
+    #[test]
+    fn test_nested_code() {
+        let path = super::signed_system_binary();
+
+        let options = super::VerificationOptions::new().nested_code_depth(1);
+        let ctx = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify_with(&options)
+            .unwrap();
+        assert!(ctx.nested_code().is_empty());
+
+        // Safari ships its extensions and XPC services as nested code
+        #[cfg(target_os = "macos")]
+        {
+            let ctx = super::CodeSignVerifier::for_file("/Applications/Safari.app")
+                .unwrap()
+                .verify_with(&options)
+                .unwrap();
+            assert!(!ctx.nested_code().is_empty());
+            assert!(ctx
+                .nested_code()
+                .iter()
+                .all(|item| item.result.is_ok() && item.nested.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_signing_time() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let ctx = super::verified_system_binary();
+        #[cfg(windows)]
+        assert_eq!(ctx.signing_time(), None);
+
+        // System binaries aren't always timestamped, but any time reported is already past
+        for time in ctx.signing_time().into_iter().chain(ctx.timestamp()) {
+            assert!(time > UNIX_EPOCH && time <= SystemTime::now());
+        }
+    }
+
+    #[test]
+    fn test_runtime_version() {
+        let version = |major, minor, patch| super::RuntimeVersion {
+            major,
+            minor,
+            patch,
+        };
+        assert!(version(10, 15, 7) < version(11, 0, 0));
+        assert!(version(14, 2, 0) < version(14, 2, 1));
+        assert_eq!(version(14, 2, 1).to_string(), "14.2.1");
+
+        #[cfg(target_os = "macos")]
+        assert_eq!(
+            super::RuntimeVersion::from_encoded(0x000e_0201),
+            version(14, 2, 1)
+        );
+
+        #[cfg(windows)]
+        {
+            let ctx = super::verified_system_binary();
+            assert_eq!(ctx.runtime_version(), None);
+        }
+    }
+
+    #[test]
+    fn test_time_validity() {
+        use super::{TimeValidity, TimestampPolicy, VerificationOptions};
+
+        let path = super::signed_system_binary();
+
+        let verifier = super::CodeSignVerifier::for_file(path).unwrap();
+        let ctx = verifier.verify_with(&VerificationOptions::new()).unwrap();
+        let (not_before, not_after) = ctx.validity_period().unwrap();
+        assert!(not_before < not_after);
+
+        let now = std::time::SystemTime::now();
+        let validity = ctx.time_validity();
+        assert_eq!(
+            validity == TimeValidity::Current,
+            now >= not_before && now <= not_after
+        );
+        if validity != TimeValidity::Current {
+            assert_eq!(
+                validity == TimeValidity::Timestamped,
+                ctx.timestamp().is_some()
+            );
+        }
+
+        // Expired certificates are only accepted with a timestamp
+        let options =
+            VerificationOptions::new().timestamp_policy(TimestampPolicy::AllowExpiredIfTimestamped);
+        let result = verifier.verify_with(&options);
+        assert_eq!(result.is_ok(), validity != TimeValidity::Expired);
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_tracing_spans() {
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+
+        /// Records the name of every span created
+        struct Spans(Arc<Mutex<Vec<&'static str>>>);
+
+        impl tracing::Subscriber for Spans {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        #[cfg(target_os = "macos")]
+        let phase = "static_code_creation";
+        #[cfg(windows)]
+        let phase = "win_verify_trust";
+
+        let names = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Spans(names.clone()), || {
+            super::verified_system_binary();
+        });
+
+        let names = names.lock().unwrap();
+        for name in &["verify", "requirement_evaluation", phase] {
+            assert!(names.contains(name), "{} not in {:?}", name, names);
+        }
+    }
+
+    #[test]
+    fn test_raw_bytes() {
+        let ctx = super::verified_system_binary();
+        let leaf = &ctx.0.certificate_chain()[0];
+        assert_eq!(ctx.thumbprint_bytes(), super::digest::sha256(leaf));
+
+        // As encoded, only padded with a zero byte to keep the integer positive
+        let serial = ctx.serial_bytes().unwrap();
+        assert!(serial.len() == 1 || serial[0] != 0 || serial[1] & 0x80 != 0);
+        assert!(leaf
+            .windows(serial.len())
+            .any(|window| window == serial.as_slice()));
+    }
+
+    #[test]
+    fn test_serial_format() {
+        let ctx = super::verified_system_binary();
+        let serial = ctx.serial().unwrap();
+        assert!(serial
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        assert!(!serial.starts_with("00") || serial == "00");
+
+        let format = super::HexFormat::new().uppercase().separator(':');
+        assert_eq!(
+            ctx.serial_with(&format).unwrap().replace(':', ""),
+            serial.to_uppercase()
+        );
+        assert_eq!(ctx.sha256_thumbprint_with(&format).len(), 32 * 3 - 1);
+
+        let bytes = ctx.serial_bytes().unwrap();
+        assert_eq!(super::HexFormat::new().format_serial(&bytes), serial);
+        assert_eq!(
+            super::digest::hex(&ctx.thumbprint_bytes()),
+            ctx.sha256_thumbprint()
+        );
+    }
+
+    #[test]
+    fn test_verify_update() {
+        let path = super::signed_system_binary();
+        #[cfg(target_os = "macos")]
+        let other = "/bin/ls";
+        #[cfg(windows)]
+        let other = "c:\\windows\\notepad.exe";
+
+        let ctx = super::CodeSignVerifier::verify_update(path, other).unwrap();
+        assert!(ctx.same_publisher(&ctx));
+        assert_eq!(
+            ctx.same_publisher_as_file(path)
+                .unwrap()
+                .sha256_thumbprint(),
+            super::CodeSignVerifier::for_file(path)
+                .unwrap()
+                .verify("")
+                .unwrap()
+                .sha256_thumbprint()
+        );
+    }
+
+    #[test]
+    fn test_held_file() {
+        use std::io::Read;
+
+        let source = super::signed_system_binary();
+
+        let path = super::temp_path();
+        std::fs::copy(source, &path).unwrap();
+        let verifier = super::CodeSignVerifier::for_held_file(&path).unwrap();
+        assert!(verifier.verify("").is_ok());
+
+        let mut header = [0u8; 2];
+        verifier
+            .held_file()
+            .unwrap()
+            .read_exact(&mut header)
+            .unwrap();
+        assert_ne!(header, [0, 0]);
+
+        let write = std::fs::OpenOptions::new().append(true).open(&path);
+        #[cfg(windows)]
+        assert!(write.is_err());
+        #[cfg(target_os = "macos")]
+        {
+            use std::io::Write;
+            write.unwrap().write_all(b"\0").unwrap();
+            assert!(matches!(verifier.verify(""), Err(Error::FileChanged)));
+        }
+
+        drop(verifier);
+        std::fs::remove_file(&path).unwrap();
+        assert!(super::CodeSignVerifier::for_file(source)
+            .unwrap()
+            .held_file()
+            .is_none());
+    }
+
+    #[test]
+    fn test_claimed_signer() {
+        let source = super::signed_system_binary();
+
+        let verifier = super::CodeSignVerifier::for_file(source).unwrap();
+        let ctx = verifier.verify("").unwrap();
+        let claimed = verifier.claimed_signer().unwrap();
+        assert_eq!(claimed.sha256_thumbprint, ctx.sha256_thumbprint());
+        assert_eq!(claimed.subject, ctx.subject_name());
+
+        // Modify the code, past the headers, so the signature no longer covers it
+        let path = super::temp_path();
+        let mut data = std::fs::read(source).unwrap();
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+
+        let verifier = super::CodeSignVerifier::for_file(&path).unwrap();
+        let result = verifier.verify("");
+        let claimed = verifier.claimed_signer();
+        drop(verifier);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert_eq!(
+            claimed.map(|claimed| claimed.sha256_thumbprint),
+            Some(ctx.sha256_thumbprint())
+        );
+    }
+
+    #[test]
+    fn test_symlink_policy() {
+        use super::{SymlinkPolicy, VerificationOptions};
+
+        let link = super::temp_path();
+        let source = super::signed_system_binary();
+        #[cfg(target_os = "macos")]
+        std::os::unix::fs::symlink(source, &link).unwrap();
+        #[cfg(windows)]
+        if std::os::windows::fs::symlink_file(source, &link).is_err() {
+            return; // Creating links takes developer mode or elevation
+        }
+
+        let verify = |policy| {
+            let options = VerificationOptions::new().symlinks(policy);
+            super::CodeSignVerifier::for_file_with(&link, &options)
+                .and_then(|verifier| verifier.verify_with(&options))
+        };
+        let followed = verify(SymlinkPolicy::Follow);
+        let resolved = verify(SymlinkPolicy::Resolve);
+        let rejected = verify(SymlinkPolicy::Reject);
+        std::fs::remove_file(&link).unwrap();
+
+        let source = std::path::Path::new(source);
+        for ctx in [followed.unwrap(), resolved.unwrap()].iter() {
+            let verified = ctx.verified_path().unwrap();
+            assert!(verified.is_absolute());
+            assert_eq!(verified.file_name(), source.file_name());
+        }
+        assert!(matches!(rejected, Err(Error::SymlinkRejected(_))));
+    }
+
+    #[test]
+    fn test_normalize_publisher() {
+        assert_eq!(
+            super::normalize_publisher("  Microsoft   CORPORATION "),
+            super::normalize_publisher("microsoft corporation")
+        );
+        assert_eq!(super::normalize_publisher("Apple, Inc."), "apple inc");
+        assert_ne!(
+            super::normalize_publisher("Microsoft"),
+            super::normalize_publisher("Microsoft Corporation")
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(super::constant_time_eq(b"abc", b"abc"));
+        assert!(!super::constant_time_eq(b"abc", b"abd"));
+        assert!(!super::constant_time_eq(b"abc", b"ab"));
+    }
+
+    #[test]
+    fn test_revocation_freshness() {
+        use super::{RevocationState, RevocationStatus};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let day = Duration::from_secs(86400);
+        let status = RevocationStatus {
+            thumbprint: "AB".repeat(32),
+            state: RevocationState::Good,
+            revocation_time: None,
+            source: None,
+            this_update: Some(UNIX_EPOCH + day),
+            next_update: Some(UNIX_EPOCH + 8 * day),
+            produced_at: None,
+        };
+        assert!(status.is_current_at(UNIX_EPOCH + 2 * day));
+        assert!(!status.is_current_at(UNIX_EPOCH));
+        assert!(!status.is_current_at(UNIX_EPOCH + 8 * day));
+        assert!(!RevocationStatus {
+            next_update: None,
+            ..status
+        }
+        .is_current_at(UNIX_EPOCH + 2 * day));
+    }
+
+    #[test]
+    fn test_entitlement_value() {
+        use super::{EntitlementValue, PropertyValue};
+
+        let value = EntitlementValue::from(PropertyValue::Bool(true));
+        assert!(value.is_true());
+        assert!(!EntitlementValue::from(PropertyValue::Bool(false)).is_true());
+
+        let groups = EntitlementValue::from(PropertyValue::Array(vec![
+            PropertyValue::String("ABCDE12345.com.example.shared".to_string()),
+            PropertyValue::Integer(1),
+        ]));
+        assert_eq!(groups.strings(), ["ABCDE12345.com.example.shared"]);
+        assert!(!groups.is_true());
+
+        assert!(matches!(
+            EntitlementValue::from(PropertyValue::Integer(1)),
+            EntitlementValue::Other(PropertyValue::Integer(1))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "plist")]
+    fn test_info_plist_value() {
+        use super::PropertyValue;
+
+        let info = PropertyValue::Dictionary(
+            vec![
+                (
+                    "CFBundleIdentifier".to_string(),
+                    PropertyValue::String("com.example.tool".to_string()),
+                ),
+                (
+                    "LSArchitecturePriority".to_string(),
+                    PropertyValue::Array(vec![PropertyValue::String("arm64".to_string())]),
+                ),
+                ("LSUIElement".to_string(), PropertyValue::Bool(true)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let info = plist::Value::from(info);
+        let dict = info.as_dictionary().unwrap();
+        assert_eq!(
+            dict.get("CFBundleIdentifier")
+                .and_then(plist::Value::as_string),
+            Some("com.example.tool")
+        );
+        assert_eq!(
+            dict.get("LSArchitecturePriority")
+                .and_then(plist::Value::as_array)
+                .map(Vec::len),
+            Some(1)
+        );
+        assert_eq!(
+            dict.get("LSUIElement").and_then(plist::Value::as_boolean),
+            Some(true)
+        );
+    }
+
+    #[test]
+    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+    fn test_unsigned() {
+        let path = std::env::args().next().unwrap(); // own path, always unsigned and present
+
+        let res = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify("anchor apple");
+        assert!(
             matches!(res, Err(Error::Unsigned)),
             "error = {:?}",
             res.err()
         );
     }
+
+    #[test]
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    fn test_ad_hoc() {
+        let path = std::env::args().next().unwrap(); // own path, always ad-hoc signed by the linker on arm64
+
+        let res = super::CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify("anchor apple");
+        assert!(
+            matches!(res, Err(Error::AdHocSigned(ref sig)) if !sig.cd_hash.is_empty()),
+            "error = {:?}",
+            res.err()
+        );
+    }
 }
@@ -1,13 +1,30 @@
 use super::sec_sys::*;
-use crate::Name;
-use std::collections::HashMap;
+use super::{entitlements, plist, profile};
+use crate::digest;
+use crate::{
+    AdHocSignature, CatalogInfo, ChainStatus, LaunchConstraints, MacProperties, Name, NestedCode,
+    PlatformProperties, PropertyValue, ProvisioningProfile, RevocationState, RevocationStatus,
+    RuntimeVersion,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::SystemTime;
 
 pub struct Context {
     cert: SecCertificate,
     dict: CFDictionary<CFString, CFDictionary<CFString, CFType>>,
     all: CFDictionary,
+    nested: Vec<NestedCode>,
+    path: Option<PathBuf>,
+    thumbprint: std::sync::OnceLock<[u8; 32]>, // Hashed on first use
+    cached_revocation: Option<Vec<RevocationStatus>>, // Settled by the revocation cache
 }
 
+// The signing information is immutable once copied, and CoreFoundation reference counting is
+// thread safe, so a context can be handed to and shared between threads
+unsafe impl Send for Context {}
+unsafe impl Sync for Context {}
+
 enum SecProperty {
     Value,
     Label,
@@ -17,7 +34,8 @@ enum SecProperty {
 enum SecOID {
     SubjectName,
     IssuerName,
-    Serial,
+    NotBefore,
+    NotAfter,
 
     Country,
     CommonName,
@@ -49,7 +67,8 @@ impl Into<CFString> for SecOID {
             CFString::wrap_under_get_rule(match self {
                 SecOID::SubjectName => kSecOIDX509V1SubjectName,
                 SecOID::IssuerName => kSecOIDX509V1IssuerName,
-                SecOID::Serial => kSecOIDX509V1SerialNumber,
+                SecOID::NotBefore => kSecOIDX509V1ValidityNotBefore,
+                SecOID::NotAfter => kSecOIDX509V1ValidityNotAfter,
 
                 SecOID::CommonName => kSecOIDCommonName,
                 SecOID::Country => kSecOIDCountryName,
@@ -72,9 +91,39 @@ impl Context {
                 ))
             },
             all,
+            nested: Vec::new(),
+            path: None,
+            thumbprint: std::sync::OnceLock::new(),
+            cached_revocation: None,
         }
     }
 
+    pub fn set_path(&mut self, path: Option<PathBuf>) {
+        self.path = path;
+    }
+
+    pub fn path(&self) -> Option<&std::path::Path> {
+        self.path.as_deref()
+    }
+
+    pub fn executable_path(&self) -> Option<PathBuf> {
+        self.main_executable()
+            .map(|path| std::fs::canonicalize(&path).unwrap_or(path))
+    }
+
+    /// The bundle directory, when the code is a bundle rather than a bare executable
+    pub fn bundle_path(&self) -> Option<PathBuf> {
+        self.path.clone().filter(|path| path.is_dir())
+    }
+
+    pub fn set_nested_code(&mut self, nested: Vec<NestedCode>) {
+        self.nested = nested;
+    }
+
+    pub fn nested_code(&self) -> &[NestedCode] {
+        &self.nested
+    }
+
     fn get<T: Into<CFString>>(&self, key: T, wanted_kind: CFString) -> Option<CFType> {
         unsafe {
             let dict = self.dict.find(key.into())?;
@@ -147,8 +196,29 @@ impl Context {
         }
     }
 
-    pub fn serial(&self) -> Option<String> {
-        self.get_as_string(SecOID::Serial).map(|s| s.to_string())
+    /// The serial number as DER encodes it, the Security framework's string for it is
+    /// formatted differently between macOS versions
+    pub fn serial_bytes(&self) -> Option<Vec<u8>> {
+        let certificate = certificate_data(self.cert.as_concrete_TypeRef());
+        crate::der::serial_number(&certificate).map(<[u8]>::to_vec)
+    }
+
+    /// Validity dates are reported as an absolute time number, or a date on some systems
+    fn get_as_time<T: Into<CFString>>(&self, key: T) -> Option<SystemTime> {
+        let value = self.get(key, CFString::new("date"))?;
+
+        if let Some(date) = value.downcast::<CFDate>() {
+            return Some(plist::to_system_time(&date));
+        }
+        let secs = value.downcast::<CFNumber>()?.to_f64()?;
+        Some(plist::to_system_time(&CFDate::new(secs)))
+    }
+
+    pub fn validity_period(&self) -> Option<(SystemTime, SystemTime)> {
+        Some((
+            self.get_as_time(SecOID::NotBefore)?,
+            self.get_as_time(SecOID::NotAfter)?,
+        ))
     }
 
     pub fn subject_name(&self) -> Name {
@@ -160,16 +230,30 @@ impl Context {
     }
 
     pub fn sha256_thumbprint(&self) -> String {
-        let cert_data = unsafe {
-            CFData::wrap_under_create_rule(SecCertificateCopyData(self.cert.as_concrete_TypeRef()))
-        };
+        digest::hex(&self.sha256_thumbprint_bytes())
+    }
+
+    pub fn sha256_thumbprint_bytes(&self) -> [u8; 32] {
+        *self
+            .thumbprint
+            .get_or_init(|| thumbprint(self.cert.as_concrete_TypeRef()))
+    }
 
-        use sha2::Digest;
-        let hash = sha2::Sha256::digest(cert_data.bytes());
+    /// The DER certificates of the signing chain, from the leaf up
+    pub fn certificate_chain(&self) -> Vec<Vec<u8>> {
+        let trust = match code_trust(&self.all) {
+            Some(trust) => trust,
+            None => return vec![certificate_data(self.cert.as_concrete_TypeRef())],
+        };
 
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{:02x}", byte))
+        let count = unsafe { SecTrustGetCertificateCount(trust.as_concrete_TypeRef()) };
+        (0..count)
+            .map(|index| {
+                certificate_data(unsafe {
+                    SecTrustGetCertificateAtIndex(trust.as_concrete_TypeRef(), index)
+                })
+            })
+            .collect()
     }
 
     fn team_id(&self) -> Option<String> {
@@ -179,15 +263,125 @@ impl Context {
         return Some(team_id.to_string());
     }
 
-    fn cd_hash(&self) -> Option<String> {
-        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoUnique) };
+    pub fn identifier(&self) -> Option<String> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoIdentifier) };
+        let value_ref = self.all.find(key.as_CFTypeRef())?;
+        let identifier = unsafe { CFString::wrap_under_get_rule(*value_ref as _) };
+        Some(identifier.to_string())
+    }
+
+    pub fn cd_hash(&self) -> Option<String> {
+        cd_hash(&self.all)
+    }
+
+    #[cfg(feature = "notarization")]
+    pub fn digest_algorithm(&self) -> Option<u32> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoDigestAlgorithm) };
+        let value_ref = self.all.find(key.as_CFTypeRef())?;
+        let value = unsafe { CFNumber::wrap_under_get_rule(*value_ref as _) };
+        value.to_i32().map(|v| v as u32)
+    }
+
+    fn date(&self, key: CFStringRef) -> Option<SystemTime> {
+        let key = unsafe { CFString::wrap_under_get_rule(key) };
+        let value_ref = self.all.find(key.as_CFTypeRef())?;
+        let value = unsafe { CFDate::wrap_under_get_rule(*value_ref as _) };
+        Some(plist::to_system_time(&value))
+    }
+
+    pub fn signing_time(&self) -> Option<SystemTime> {
+        self.date(unsafe { kSecCodeInfoTime })
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.date(unsafe { kSecCodeInfoTimestamp })
+    }
+
+    /// Look up a signing information entry keyed by a constant only newer systems export
+    fn weak_value(&self, key: &str) -> Option<PropertyValue> {
+        let key = weak_string_constant(key)?;
         let value_ref = self.all.find(key.as_CFTypeRef())?;
-        let value = unsafe { CFData::wrap_under_get_rule(*value_ref as _) };
-        let hex = value
-            .iter()
-            .map(|b| format!("{:02X}", b))
-            .collect::<String>();
-        Some(hex)
+        plist::to_value(&unsafe { CFType::wrap_under_get_rule(*value_ref) })
+    }
+
+    pub fn launch_constraints(&self) -> LaunchConstraints {
+        LaunchConstraints {
+            self_constraint: self.weak_value("kSecCodeInfoLaunchConstraintsSelf"),
+            parent: self.weak_value("kSecCodeInfoLaunchConstraintsParent"),
+            responsible: self.weak_value("kSecCodeInfoLaunchConstraintsResponsible"),
+            library: self.weak_value("kSecCodeInfoLibraryConstraints"),
+        }
+    }
+
+    pub fn runtime_version(&self) -> Option<RuntimeVersion> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoRuntimeVersion) };
+        let value_ref = self.all.find(key.as_CFTypeRef())?;
+        let value = unsafe { CFNumber::wrap_under_get_rule(*value_ref as _) };
+        value
+            .to_i64()
+            .map(|v| RuntimeVersion::from_encoded(v as u32))
+    }
+
+    pub fn chain_status(&self) -> ChainStatus {
+        let status = chain_status(&self.all).unwrap_or_default();
+        match self.cached_revocation {
+            Some(_) => crate::revocation::settled_chain_status(status),
+            None => status,
+        }
+    }
+
+    pub fn set_cached_revocation(&mut self, statuses: Vec<RevocationStatus>) {
+        self.cached_revocation = Some(statuses);
+    }
+
+    pub fn revocation_status(&self) -> Vec<RevocationStatus> {
+        if let Some(statuses) = &self.cached_revocation {
+            return statuses.clone();
+        }
+        code_trust(&self.all)
+            .map(|trust| revocation_status(&trust))
+            .unwrap_or_default()
+    }
+
+    pub fn catalog(&self) -> Option<&CatalogInfo> {
+        None
+    }
+
+    pub fn provisioning_profile(&self) -> Option<ProvisioningProfile> {
+        profile::embedded_profile(self.path.as_ref()?)
+    }
+
+    /// The entitlements from the legacy plist slot, falling back to the DER slot without one
+    pub fn entitlements(&self) -> Option<BTreeMap<String, PropertyValue>> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoEntitlementsDict) };
+        let legacy =
+            self.all.find(key.as_CFTypeRef()).and_then(|value_ref| {
+                match plist::to_value(&unsafe { CFType::wrap_under_get_rule(*value_ref) })? {
+                    PropertyValue::Dictionary(entitlements) => Some(entitlements),
+                    _ => None,
+                }
+            });
+        legacy.or_else(|| self.der_entitlements())
+    }
+
+    pub fn der_entitlements(&self) -> Option<BTreeMap<String, PropertyValue>> {
+        entitlements::der_entitlements(&self.main_executable()?)
+    }
+
+    /// The executable of a bundle, or the code itself
+    fn main_executable(&self) -> Option<PathBuf> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoMainExecutable) };
+        match self.all.find(key.as_CFTypeRef()) {
+            Some(value_ref) => unsafe { CFURL::wrap_under_get_rule(*value_ref as _) }.to_path(),
+            None => self.path.clone(),
+        }
+    }
+
+    #[cfg(feature = "plist")]
+    pub fn info_plist_value(&self) -> Option<PropertyValue> {
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoPList) };
+        let value_ref = self.all.find(key.as_CFTypeRef())?;
+        plist::to_value(&unsafe { CFType::wrap_under_get_rule(*value_ref) })
     }
 
     fn info_plist(&self) -> Option<CFDictionary> {
@@ -211,32 +405,214 @@ impl Context {
         value.to_i32()
     }
 
-    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
-        let cd_hash = self.cd_hash()?;
+    /// Code that ships with the OS has a platform identifier
+    pub fn is_platform_binary(&self) -> bool {
+        self.platform_id().is_some()
+    }
+
+    fn mac_properties(&self) -> MacProperties {
         let info_plist = self.info_plist();
-        let team_id = self.team_id();
+        let plist_key = |key: &str| {
+            info_plist
+                .as_ref()
+                .and_then(|info_plist| self.key(info_plist, key))
+        };
+
+        MacProperties {
+            cd_hash: self.cd_hash(),
+            team_id: self.team_id(),
+            identifier: self.identifier(),
+            bundle_id: plist_key("CFBundleIdentifier"),
+            short_version: plist_key("CFBundleShortVersionString"),
+            bundle_version: plist_key("CFBundleVersion"),
+            platform_id: self.platform_id(),
+        }
+    }
+
+    pub fn platform_properties(&self) -> PlatformProperties {
+        PlatformProperties::Mac(self.mac_properties())
+    }
+
+    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
+        let properties = self.mac_properties();
+        let cd_hash = properties.cd_hash?;
         let mut ret = HashMap::new();
-        if let Some(info_plist) = info_plist {
-            let bundle_id = self.key(&info_plist, "CFBundleIdentifier");
-            let short_version = self.key(&info_plist, "CFBundleShortVersionString");
-            let bundle_version = self.key(&info_plist, "CFBundleVersion");
-            if let Some(bundle_id) = bundle_id {
-                ret.insert("bundle_id".to_string(), bundle_id);
+        let mut insert = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                ret.insert(key.to_string(), value);
             }
-            if let Some(short_version) = short_version {
-                ret.insert("short_version".to_string(), short_version);
+        };
+
+        insert("bundle_id", properties.bundle_id);
+        insert("short_version", properties.short_version);
+        insert("bundle_version", properties.bundle_version);
+        insert("team_id", properties.team_id);
+        insert("identifier", properties.identifier);
+        insert(
+            "platform_id",
+            properties.platform_id.map(|id| id.to_string()),
+        );
+        insert("cd_hash", Some(cd_hash));
+        Some(ret)
+    }
+}
+
+pub fn cd_hash(all: &CFDictionary) -> Option<String> {
+    let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoUnique) };
+    let value_ref = all.find(key.as_CFTypeRef())?;
+    let value = unsafe { CFData::wrap_under_get_rule(*value_ref as _) };
+    Some(digest::hex_upper(value.bytes()))
+}
+
+/// Describe the signature if it's ad-hoc, i.e. it seals the code without a certificate chain
+pub fn ad_hoc_signature(all: &CFDictionary) -> Option<AdHocSignature> {
+    let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoFlags) };
+    let value_ref = all.find(key.as_CFTypeRef())?;
+    let flags = unsafe { CFNumber::wrap_under_get_rule(*value_ref as _) }.to_i64()? as u32;
+
+    if flags & kSecCodeSignatureAdhoc == 0 {
+        return None;
+    }
+
+    Some(AdHocSignature {
+        cd_hash: cd_hash(all)?,
+        flags,
+    })
+}
+
+/// Retrieve the trust object the signature's certificate chain was evaluated with
+fn code_trust(all: &CFDictionary) -> Option<SecTrust> {
+    let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoTrust) };
+    let trust = all.find(key.as_CFTypeRef())?;
+    Some(unsafe { SecTrust::wrap_under_get_rule(*trust as _) })
+}
+
+/// Translate the SecTrust result details of the signature's trust evaluation into chain status bits
+pub fn chain_status(all: &CFDictionary) -> Option<ChainStatus> {
+    trust_status(&code_trust(all)?)
+}
+
+fn trust_result(trust: &SecTrust) -> Option<CFDictionary> {
+    let result = unsafe { SecTrustCopyResult(trust.as_concrete_TypeRef()) };
+    if result.is_null() {
+        return None;
+    }
+    Some(unsafe { CFDictionary::wrap_under_create_rule(result) })
+}
+
+/// The names of the checks each certificate in the chain failed, from the leaf up
+fn failed_checks(result: &CFDictionary) -> Option<Vec<Vec<String>>> {
+    let key = unsafe { CFString::wrap_under_get_rule(kSecTrustResultDetails) };
+    let details = result.find(key.as_CFTypeRef())?;
+    let details = unsafe { CFType::wrap_under_get_rule(*details) }.downcast_into::<CFArray>()?;
+
+    // Each certificate has a dictionary keyed by the checks it failed
+    let checks = details
+        .iter()
+        .map(|cert_details| {
+            let cert_details = unsafe { CFType::wrap_under_get_rule(*cert_details) };
+            match cert_details.downcast_into::<CFDictionary>() {
+                Some(cert_details) => plist::to_map(&cert_details).into_keys().collect(),
+                None => Vec::new(),
             }
-            if let Some(bundle_version) = bundle_version {
-                ret.insert("bundle_version".to_string(), bundle_version);
+        })
+        .collect();
+
+    Some(checks)
+}
+
+/// Translate the SecTrust result details of a trust evaluation into chain status bits
+pub fn trust_status(trust: &SecTrust) -> Option<ChainStatus> {
+    let checks = failed_checks(&trust_result(trust)?)?;
+    let mut status = ChainStatus::default();
+
+    for check in checks.into_iter().flatten() {
+        status.error_status |= match check.as_str() {
+            "TemporalValidity" | "ValidLeaf" | "ValidIntermediates" | "ValidRoot" => {
+                ChainStatus::IS_NOT_TIME_VALID
             }
+            "Revocation" => ChainStatus::IS_REVOKED,
+            "RevocationResponseRequired" => ChainStatus::REVOCATION_STATUS_UNKNOWN,
+            "AnchorTrusted" | "AnchorSHA1" | "AnchorSHA256" | "AnchorApple" => {
+                ChainStatus::IS_UNTRUSTED_ROOT
+            }
+            "MissingIntermediate" => ChainStatus::IS_PARTIAL_CHAIN,
+            "ExtendedKeyUsage" | "KeyUsage" | "CertificatePolicy" => {
+                ChainStatus::IS_NOT_VALID_FOR_USAGE
+            }
+            "SignatureHashAlgorithms" | "WeakKeySize" | "WeakSignature" => {
+                ChainStatus::IS_NOT_SIGNATURE_VALID
+            }
+            _ => 0,
+        };
+
+        if !status.details.contains(&check) {
+            status.details.push(check);
         }
-        if let Some(team_id) = team_id {
-            ret.insert("team_id".to_string(), team_id);
-        }
-        if let Some(platform_id) = self.platform_id() {
-            ret.insert("platform_id".to_string(), platform_id.to_string());
-        }
-        ret.insert("cd_hash".to_string(), cd_hash);
-        Some(ret)
     }
+
+    Some(status)
+}
+
+/// Read the revocation outcome of each certificate in the chain from the SecTrust result
+fn revocation_status(trust: &SecTrust) -> Vec<RevocationStatus> {
+    let result = match trust_result(trust) {
+        Some(result) => result,
+        None => return Vec::new(),
+    };
+    let checks = failed_checks(&result).unwrap_or_default();
+
+    let key = unsafe { CFString::wrap_under_get_rule(kSecTrustRevocationChecked) };
+    let checked = result
+        .find(key.as_CFTypeRef())
+        .map_or(false, |value| unsafe {
+            CFBoolean::wrap_under_get_rule(*value as _).into()
+        });
+    // The responses and CRLs SecTrust used aren't exposed, only when the soonest expires
+    let key = unsafe { CFString::wrap_under_get_rule(kSecTrustRevocationValidUntilDate) };
+    let valid_until = result
+        .find(key.as_CFTypeRef())
+        .map(|value| plist::to_system_time(&unsafe { CFDate::wrap_under_get_rule(*value as _) }))
+        .filter(|_| checked);
+
+    let count = unsafe { SecTrustGetCertificateCount(trust.as_concrete_TypeRef()) };
+    (0..count)
+        .map(|index| {
+            let cert = unsafe { SecTrustGetCertificateAtIndex(trust.as_concrete_TypeRef(), index) };
+            let failed = checks.get(index as usize).map_or(&[][..], |c| &c[..]);
+
+            let state = if failed.iter().any(|check| check == "Revocation") {
+                RevocationState::Revoked
+            } else if failed
+                .iter()
+                .any(|check| check == "RevocationResponseRequired")
+            {
+                RevocationState::Unknown
+            } else if checked {
+                RevocationState::Good
+            } else {
+                RevocationState::NotChecked
+            };
+
+            RevocationStatus {
+                thumbprint: digest::hex(&thumbprint(cert)),
+                state,
+                revocation_time: None,
+                source: None,
+                this_update: None,
+                next_update: valid_until,
+                produced_at: None,
+            }
+        })
+        .collect()
+}
+
+pub fn certificate_data(cert: SecCertificateRef) -> Vec<u8> {
+    let cert_data = unsafe { CFData::wrap_under_create_rule(SecCertificateCopyData(cert)) };
+    cert_data.bytes().to_vec()
+}
+
+fn thumbprint(cert: SecCertificateRef) -> [u8; 32] {
+    let cert_data = unsafe { CFData::wrap_under_create_rule(SecCertificateCopyData(cert)) };
+    digest::sha256(cert_data.bytes())
 }
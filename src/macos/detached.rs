@@ -0,0 +1,96 @@
+use super::context;
+use super::os_error_message;
+use super::sec_sys::*;
+use crate::{ChainStatus, Error};
+
+fn check(status: OSStatus) -> Result<(), Error> {
+    match status {
+        errSecSuccess => Ok(()),
+        err => Err(Error::os_error(err)),
+    }
+}
+
+/// Verify a detached CMS signature over the content, evaluating the first signer's certificate
+/// against the system trust settings. Returns the DER certificates of its chain, from the leaf up.
+pub fn verify(content: &[u8], signature: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut decoder: CMSDecoderRef = std::ptr::null();
+    let mut signers = 0;
+    let mut signer_status = 0;
+    let mut trust: SecTrustRef = std::ptr::null();
+    let mut cert_status: OSStatus = errSecSuccess;
+
+    let trust = unsafe {
+        check(CMSDecoderCreate(Some(&mut decoder)))?;
+        if decoder.is_null() {
+            return Err(Error::os_error(errSecCSSignatureInvalid));
+        }
+        let decoder = CMSDecoder::wrap_under_create_rule(decoder);
+        let content = CFData::from_buffer(content);
+
+        check(CMSDecoderUpdateMessage(
+            decoder.as_concrete_TypeRef(),
+            signature.as_ptr(),
+            signature.len(),
+        ))?;
+        check(CMSDecoderSetDetachedContent(
+            decoder.as_concrete_TypeRef(),
+            content.as_concrete_TypeRef(),
+        ))?;
+        check(CMSDecoderFinalizeMessage(decoder.as_concrete_TypeRef()))?;
+        check(CMSDecoderGetNumSigners(
+            decoder.as_concrete_TypeRef(),
+            Some(&mut signers),
+        ))?;
+        if signers == 0 {
+            return Err(Error::Unsigned);
+        }
+
+        let policy = SecPolicy::wrap_under_create_rule(SecPolicyCreateBasicX509());
+        check(CMSDecoderCopySignerStatus(
+            decoder.as_concrete_TypeRef(),
+            0,
+            policy.as_concrete_TypeRef(),
+            true as _,
+            Some(&mut signer_status),
+            Some(&mut trust),
+            Some(&mut cert_status),
+        ))?;
+        if trust.is_null() {
+            None
+        } else {
+            Some(SecTrust::wrap_under_create_rule(trust))
+        }
+    };
+
+    match signer_status {
+        kCMSSignerValid => {}
+        kCMSSignerInvalidSignature => {
+            return Err(Error::Tampered {
+                code: errSecCSSignatureFailed,
+                message: os_error_message(errSecCSSignatureFailed),
+            })
+        }
+        kCMSSignerInvalidCert => {
+            let status = trust
+                .as_ref()
+                .and_then(context::trust_status)
+                .unwrap_or_else(|| ChainStatus::from_error_status(ChainStatus::IS_UNTRUSTED_ROOT));
+            let code = match cert_status {
+                errSecSuccess => errSecNotTrusted,
+                code => code,
+            };
+            return Err(Error::chain_failed(code, status, None));
+        }
+        _ => return Err(Error::Unsigned),
+    }
+
+    let trust = trust.ok_or(Error::LeafCertNotFound)?;
+    let count = unsafe { SecTrustGetCertificateCount(trust.as_concrete_TypeRef()) };
+    Ok((0..count)
+        .map(|index| {
+            context::certificate_data(unsafe {
+                SecTrustGetCertificateAtIndex(trust.as_concrete_TypeRef(), index)
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,165 @@
+//!
+//! DER encoded entitlements, read from the embedded signature.
+//!
+//! Since macOS 12 the signature carries the entitlements twice: the legacy XML plist slot, and
+//! a DER encoding the kernel enforces. Code signed with newer tools may only have the DER slot,
+//! and the Security framework doesn't always report those, so they're decoded here instead.
+//!
+
+use crate::der::{self, Element};
+use crate::remnant::superblob_slot;
+use crate::PropertyValue;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The magic of the DER entitlements blob
+const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade7172;
+/// The superblob slot of the DER entitlements
+const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+
+/// The `[APPLICATION 16]` wrapper around the version and the entitlements
+const ENTITLEMENTS: u8 = 0x70;
+/// The `[CONTEXT 16]` tag of a dictionary
+const DICTIONARY: u8 = 0xb0;
+const UTF8_STRING: u8 = 0x0c;
+
+fn value(element: &Element<'_>) -> Option<PropertyValue> {
+    match element.tag {
+        der::BOOLEAN => Some(PropertyValue::Bool(element.contents.first()? != &0)),
+        der::INTEGER => {
+            let bytes = element.contents;
+            if bytes.is_empty() || bytes.len() > 8 {
+                return None;
+            }
+            let sign = if bytes[0] & 0x80 != 0 { -1i64 } else { 0 };
+            let value = bytes
+                .iter()
+                .fold(sign, |value, &byte| value << 8 | byte as i64);
+            Some(PropertyValue::Integer(value))
+        }
+        UTF8_STRING => Some(PropertyValue::String(
+            std::str::from_utf8(element.contents).ok()?.to_string(),
+        )),
+        der::SEQUENCE => {
+            let items = der::children(element.contents)?;
+            Some(PropertyValue::Array(
+                items.iter().filter_map(value).collect(),
+            ))
+        }
+        DICTIONARY => dictionary(element.contents).map(PropertyValue::Dictionary),
+        _ => None,
+    }
+}
+
+/// A dictionary's entries, each a sequence of its key and value
+fn dictionary(contents: &[u8]) -> Option<BTreeMap<String, PropertyValue>> {
+    let entries = der::children(contents)?;
+    Some(
+        entries
+            .iter()
+            .filter(|entry| entry.tag == der::SEQUENCE)
+            .filter_map(|entry| match der::children(entry.contents)?.as_slice() {
+                [key, item] if key.tag == UTF8_STRING => Some((
+                    std::str::from_utf8(key.contents).ok()?.to_string(),
+                    value(item)?,
+                )),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Decode the DER entitlements, a version followed by the entitlements dictionary
+pub fn from_der(data: &[u8]) -> Option<BTreeMap<String, PropertyValue>> {
+    let wrapper = der::parse(data).filter(|wrapper| wrapper.tag == ENTITLEMENTS)?;
+    match der::children(wrapper.contents)?.as_slice() {
+        [version, entitlements] if version.tag == der::INTEGER => match value(entitlements)? {
+            PropertyValue::Dictionary(entitlements) => Some(entitlements),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The DER entitlements embedded in the signature of an executable
+pub fn der_entitlements(executable: &Path) -> Option<BTreeMap<String, PropertyValue>> {
+    let superblob = crate::remnant::embedded_signature(executable)?;
+    from_der(superblob_slot(
+        &superblob,
+        CSSLOT_DER_ENTITLEMENTS,
+        CSMAGIC_EMBEDDED_DER_ENTITLEMENTS,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remnant::CSMAGIC_EMBEDDED_SIGNATURE;
+
+    #[test]
+    fn test_der_entitlements() {
+        let entry = |key: &str, value: Vec<u8>| {
+            der::encode(
+                der::SEQUENCE,
+                &[der::encode(UTF8_STRING, key.as_bytes()), value].concat(),
+            )
+        };
+        let groups = der::encode(
+            der::SEQUENCE,
+            &der::encode(UTF8_STRING, b"ABCDE12345.com.example.shared"),
+        );
+        let entitlements = der::encode(
+            DICTIONARY,
+            &[
+                entry(
+                    "com.apple.security.cs.disable-library-validation",
+                    der::encode(der::BOOLEAN, &[0xff]),
+                ),
+                entry("keychain-access-groups", groups),
+                entry("com.example.level", der::encode(der::INTEGER, &[0xff])),
+            ]
+            .concat(),
+        );
+        let encoded = der::encode(
+            ENTITLEMENTS,
+            &[der::encode(der::INTEGER, &[1]), entitlements].concat(),
+        );
+
+        let mut superblob = Vec::new();
+        for value in [
+            CSMAGIC_EMBEDDED_SIGNATURE,
+            0,
+            1,
+            CSSLOT_DER_ENTITLEMENTS,
+            20,
+        ] {
+            superblob.extend_from_slice(&value.to_be_bytes());
+        }
+        superblob.extend_from_slice(&CSMAGIC_EMBEDDED_DER_ENTITLEMENTS.to_be_bytes());
+        superblob.extend_from_slice(&(8 + encoded.len() as u32).to_be_bytes());
+        superblob.extend_from_slice(&encoded);
+
+        let data = superblob_slot(
+            &superblob,
+            CSSLOT_DER_ENTITLEMENTS,
+            CSMAGIC_EMBEDDED_DER_ENTITLEMENTS,
+        )
+        .unwrap();
+        let entitlements = from_der(data).unwrap();
+        assert_eq!(
+            entitlements.get("com.apple.security.cs.disable-library-validation"),
+            Some(&PropertyValue::Bool(true))
+        );
+        assert_eq!(
+            entitlements.get("keychain-access-groups"),
+            Some(&PropertyValue::Array(vec![PropertyValue::String(
+                "ABCDE12345.com.example.shared".to_string()
+            )]))
+        );
+        assert_eq!(
+            entitlements.get("com.example.level"),
+            Some(&PropertyValue::Integer(-1))
+        );
+        assert!(superblob_slot(&superblob, 5, CSMAGIC_EMBEDDED_DER_ENTITLEMENTS).is_none());
+    }
+}
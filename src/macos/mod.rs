@@ -1,32 +1,263 @@
 mod context;
+#[cfg(feature = "cms")]
+pub(crate) mod detached;
+mod entitlements;
+mod plist;
+mod profile;
 #[allow(non_upper_case_globals)]
 mod sec_sys;
+#[allow(non_upper_case_globals)]
+pub(crate) mod watch;
+pub(crate) mod xattr;
 
-use super::Error;
+use super::{
+    Architecture, ChainStatus, Error, NestedCode, Policy, Requirement, RequirementSyntax,
+    RevocationMode, TimestampPolicy, ValidationMode, VerificationOptions,
+};
 use sec_sys::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub struct Verifier {
+    code: SecCodeKind,
+    temp: Option<Arc<TempFile>>, // Keeps data written only to be verified until the code is done with
+    held: Option<HeldFile>,      // The file held open since the verifier was created, if any
+}
 
-pub struct Verifier(SecCodeKind);
+// Code objects are reference counted CF objects, and the Code Signing Services lock each code
+// object's own state, so a verifier can be shared between threads and checked from several
+unsafe impl Send for Verifier {}
+unsafe impl Sync for Verifier {}
+
+/// A file written only to be verified, removed once the last code object reading it is dropped
+struct TempFile(PathBuf);
+
+/// A file held open from creating the verifier, and what it was when it was opened
+struct HeldFile {
+    file: std::fs::File,
+    path: PathBuf,
+    identity: FileIdentity,
+}
+
+/// What identifies a file and its contents: replacing the file changes the device or inode,
+/// writing to it the size or modification time, and renaming it the change time
+#[derive(PartialEq)]
+struct FileIdentity {
+    device: u64,
+    inode: u64,
+    size: u64,
+    modified: (i64, i64),
+    changed: (i64, i64),
+}
+
+impl FileIdentity {
+    fn of(metadata: &std::fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        FileIdentity {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.size(),
+            modified: (metadata.mtime(), metadata.mtime_nsec()),
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+        }
+    }
+}
+
+impl HeldFile {
+    /// Whether the path still names the held file, unmodified since it was opened
+    fn unchanged(&self) -> bool {
+        let held = self
+            .file
+            .metadata()
+            .map(|metadata| FileIdentity::of(&metadata));
+        let named = std::fs::metadata(&self.path).map(|metadata| FileIdentity::of(&metadata));
+
+        matches!((held, named), (Ok(held), Ok(named)) if held == self.identity && named == self.identity)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
 pub(crate) use context::Context;
-pub use sec_sys::errSecCSBadResource;
 
-#[derive(Debug)]
+/// How often a validity check running on a worker thread is checked for cancellation
+const CANCELLATION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Mac App Store leaf certificates carry the 1.2.840.113635.100.6.1.9 extension
+const MAC_APP_STORE_REQUIREMENT: &str =
+    "anchor apple generic and certificate leaf[field.1.2.840.113635.100.6.1.9] exists";
+
+#[derive(Debug, Clone)]
 enum SecCodeKind {
     Static(SecStaticCode), // Static code is created for files on disk
     Dynamic(SecCode),      // Regular code is created for a guest pid
 }
 
+/// Translate the options into the flags passed to the validity check
+fn validity_flags(options: &VerificationOptions) -> SecCSFlags {
+    let mut flags = SecCSFlags::from_bits(options.macos_flags);
+    let thorough = options.mode == ValidationMode::Thorough;
+
+    if thorough && options.architecture == Architecture::All {
+        flags |= SecCSFlags::kSecCSCheckAllArchitectures;
+    }
+    if options.effective_nested_code_depth() > 0 {
+        flags |= SecCSFlags::kSecCSCheckNestedCode;
+    }
+    if thorough && options.strict {
+        flags |= SecCSFlags::kSecCSStrictValidate;
+    }
+    if !thorough || !options.validate_resources {
+        flags |= SecCSFlags::kSecCSDoNotValidateResources;
+    }
+    if options.timestamp_policy == TimestampPolicy::LifetimeSigning {
+        flags |= SecCSFlags::kSecCSConsiderExpiration;
+    }
+    // The system only checks revocation on a best effort basis, unless enforced
+    if !options.offline
+        && matches!(
+            options.revocation,
+            Some(RevocationMode::EndCertificate) | Some(RevocationMode::WholeChain)
+        )
+    {
+        flags |= SecCSFlags::kSecCSEnforceRevocationChecks;
+    }
+    if !options.allow_network || options.offline {
+        flags |= SecCSFlags::kSecCSNoNetworkAccess;
+    }
+
+    flags
+}
+
 impl Verifier {
+    fn new(code: SecCodeKind) -> Self {
+        Verifier {
+            code,
+            temp: None,
+            held: None,
+        }
+    }
+
+    /// Open the file and hold it until the verifier is dropped, verifying that it's still the
+    /// file at the path, unmodified, after each verification
+    pub fn for_held_file(path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(Error::IoError)?;
+        let identity = FileIdentity::of(&file.metadata().map_err(Error::IoError)?);
+
+        let mut verifier = Verifier::for_file(path)?;
+        verifier.held = Some(HeldFile {
+            file,
+            path: path.to_path_buf(),
+            identity,
+        });
+        Ok(verifier)
+    }
+
+    pub fn held_file(&self) -> Option<&std::fs::File> {
+        self.held.as_ref().map(|held| &held.file)
+    }
+
+    /// Copy the data to a temporary file only the current user can read, and verify that
+    pub fn for_reader(reader: &mut dyn std::io::Read) -> Result<Self, Error> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = crate::temp_path();
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(Error::IoError)?;
+        let temp = TempFile(path);
+        std::io::copy(reader, &mut file).map_err(Error::IoError)?;
+
+        let mut verifier = Verifier::for_file(&temp.0)?;
+        verifier.temp = Some(Arc::new(temp));
+        Ok(verifier)
+    }
+
     /// Retrieve the code object for the process with the given pid
     pub fn for_pid(pid: i32) -> Result<Self, Error> {
-        let mut sec: SecCodeRef = std::ptr::null_mut();
-
         let attributes = unsafe {
             CFDictionary::from_CFType_pairs(&[(
                 CFString::wrap_under_get_rule(kSecGuestAttributePid),
-                CFNumber::from(pid),
+                CFNumber::from(pid).as_CFType(),
+            )])
+        };
+
+        Verifier::for_guest(attributes)
+    }
+
+    /// Retrieve the code object for the process identified by the audit token, which unlike
+    /// a pid can't be reused by another process
+    pub fn for_audit_token(token: [u32; 8]) -> Result<Self, Error> {
+        let bytes = token
+            .iter()
+            .flat_map(|val| val.to_ne_bytes())
+            .collect::<Vec<u8>>();
+
+        let attributes = unsafe {
+            CFDictionary::from_CFType_pairs(&[(
+                CFString::wrap_under_get_rule(kSecGuestAttributeAudit),
+                CFData::from_buffer(&bytes).as_CFType(),
             )])
         };
 
+        Verifier::for_guest(attributes)
+    }
+
+    /// Retrieve the code object for this process
+    pub fn for_current_process() -> Result<Self, Error> {
+        let mut sec: SecCodeRef = std::ptr::null_mut();
+
+        unsafe {
+            match SecCodeCopySelf(SecCSFlags::kSecCSDefaultFlags, Some(&mut sec)) {
+                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier::new(
+                    SecCodeKind::Dynamic(SecCode::wrap_under_create_rule(sec)),
+                )),
+                err => Err(Error::os_error(err)),
+            }
+        }
+    }
+
+    /// Retrieve the code object for the peer of a connected Unix domain socket
+    pub fn for_socket_peer(fd: std::ffi::c_int) -> Result<Self, Error> {
+        let mut token = [0u32; 8];
+        let mut len = std::mem::size_of_val(&token) as u32;
+
+        if unsafe {
+            getsockopt(
+                fd,
+                SOL_LOCAL,
+                LOCAL_PEERTOKEN,
+                token.as_mut_ptr() as _,
+                &mut len,
+            )
+        } != 0
+        {
+            let err = std::io::Error::last_os_error();
+            return Err(Error::os_error(err.raw_os_error().unwrap_or(0)));
+        }
+
+        Verifier::for_audit_token(token)
+    }
+
+    /// Retrieve the code object for the peer of an XPC connection
+    pub unsafe fn for_xpc_peer(connection: *mut std::ffi::c_void) -> Result<Self, Error> {
+        let mut token = [0u32; 8];
+        xpc_connection_get_audit_token(connection, &mut token);
+        Verifier::for_audit_token(token)
+    }
+
+    /// Retrieve the code object for the running guest matching the attributes
+    fn for_guest(attributes: CFDictionary<CFString, CFType>) -> Result<Self, Error> {
+        let mut sec: SecCodeRef = std::ptr::null_mut();
+
         unsafe {
             match SecCodeCopyGuestWithAttributes(
                 std::ptr::null_mut(),
@@ -34,16 +265,20 @@ impl Verifier {
                 SecCSFlags::kSecCSDefaultFlags,
                 Some(&mut sec),
             ) {
-                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier(SecCodeKind::Dynamic(
-                    SecCode::wrap_under_create_rule(sec),
-                ))),
-                err => Err(Error::OsError(err)),
+                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier::new(
+                    SecCodeKind::Dynamic(SecCode::wrap_under_create_rule(sec)),
+                )),
+                err => Err(Error::os_error(err)),
             }
         }
     }
 
     /// Retrieve the code object for the file at the target location
     pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("static_code_creation", path = %path.as_ref().display()).entered();
+
         let mut sec: SecStaticCodeRef = std::ptr::null_mut();
         let url = CFURL::from_path(path.as_ref(), false).ok_or(Error::InvalidPath)?;
 
@@ -53,91 +288,530 @@ impl Verifier {
                 SecCSFlags::kSecCSDefaultFlags,
                 Some(&mut sec),
             ) {
-                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier(SecCodeKind::Static(
+                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier::new(SecCodeKind::Static(
                     SecStaticCode::wrap_under_create_rule(sec),
                 ))),
-                err => Err(Error::OsError(err)),
+                err => Err(Error::os_error(err)),
             }
         }
     }
 
-    pub fn verify(&self, requirement: &str) -> Result<Context, Error> {
-        self.check_validity(requirement)?;
-        let sec_info = self.get_code_singing_info()?;
-        let cert_key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoCertificates) };
+    /// Retrieve the code object for a single architecture slice of the file at the target location
+    fn for_file_architecture(path: &Path, architecture: &str) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("static_code_creation", path = %path.display(), architecture)
+                .entered();
 
-        let certs_ref = sec_info
-            .find(cert_key.as_CFTypeRef())
-            .ok_or(Error::LeafCertNotFound)?;
+        let mut sec: SecStaticCodeRef = std::ptr::null_mut();
+        let url = CFURL::from_path(path, false).ok_or(Error::InvalidPath)?;
 
-        let certs = unsafe { CFArray::<SecCertificate>::wrap_under_get_rule(*certs_ref as _) };
+        let attributes = unsafe {
+            CFDictionary::from_CFType_pairs(&[(
+                CFString::wrap_under_get_rule(kSecCodeAttributeArchitecture),
+                CFString::new(architecture),
+            )])
+        };
+
+        unsafe {
+            match SecStaticCodeCreateWithPathAndAttributes(
+                url.as_concrete_TypeRef(),
+                SecCSFlags::kSecCSDefaultFlags,
+                attributes.as_concrete_TypeRef(),
+                Some(&mut sec),
+            ) {
+                sec_sys::errSecSuccess if !sec.is_null() => Ok(Verifier::new(SecCodeKind::Static(
+                    SecStaticCode::wrap_under_create_rule(sec),
+                ))),
+                err => Err(Error::os_error(err)),
+            }
+        }
+    }
+
+    /// Retrieve the code object for the file, restricted to the requested architecture if any
+    fn for_architecture(path: &Path, architecture: &Architecture) -> Result<Self, Error> {
+        match architecture {
+            Architecture::Named(name) => Verifier::for_file_architecture(path, name),
+            _ => Verifier::for_file(path),
+        }
+    }
+
+    pub fn verify(&self, options: &VerificationOptions) -> Result<Context, Error> {
+        // Running code is always validated as the slice that was loaded
+        let result = match (&self.code, &options.architecture) {
+            (SecCodeKind::Static(_), Architecture::Named(name)) => {
+                Verifier::for_file_architecture(&self.path()?, name)?.verify_code(options)
+            }
+            _ => self.verify_code(options),
+        };
+
+        // The Security framework opens the path itself, so it must still be the held file
+        match &self.held {
+            Some(held) if !held.unchanged() => Err(Error::FileChanged),
+            _ => result,
+        }
+    }
+
+    fn verify_code(&self, options: &VerificationOptions) -> Result<Context, Error> {
+        let flags = validity_flags(options);
+        let requirement = requirement_text(options)?;
+        // Revocation is checked by the Security framework within the validity check
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            revocation = ?options.revocation,
+            enforced = flags.contains(SecCSFlags::kSecCSEnforceRevocationChecks),
+            "revocation checking"
+        );
+        let validity = self.check_validity_within(requirement.as_deref(), flags, options);
+
+        let nested = match options.effective_nested_code_depth() {
+            0 => Vec::new(),
+            depth => self.verify_nested_code(options, flags, depth)?,
+        };
+
+        match validity {
+            Ok(()) => {}
+            Err(_) if nested.iter().any(|n| n.result.is_err()) => {
+                return Err(Error::NestedCode(nested))
+            }
+            // An ad-hoc signature has no certificate chain, so it can't satisfy an anchor requirement
+            Err(err @ Error::RequirementFailed) => {
+                let sec_info = self.get_code_singing_info()?;
+                return Err(context::ad_hoc_signature(&sec_info).map_or(err, Error::AdHocSigned));
+            }
+            Err(err) => {
+                // Explain chain failures with the trust evaluation the signature was checked with
+                let status = self
+                    .get_code_singing_info()
+                    .ok()
+                    .and_then(|sec_info| context::chain_status(&sec_info));
+
+                return Err(match (status, err.os_status()) {
+                    (Some(status), Some(code)) if status.has_errors() => {
+                        Error::chain_failed(code, status, err.description())
+                    }
+                    _ => err,
+                });
+            }
+        }
+
+        let sec_info = self.get_code_singing_info()?;
+        if let Some(ad_hoc) = context::ad_hoc_signature(&sec_info) {
+            return Err(Error::AdHocSigned(ad_hoc));
+        }
+
+        let certs = signing_certificates(&sec_info).ok_or(Error::LeafCertNotFound)?;
         let leaf_cert = certs.get(0).ok_or(Error::LeafCertNotFound)?;
 
-        Ok(Context::new(leaf_cert.as_concrete_TypeRef(), sec_info))
+        let mut context = Context::new(leaf_cert.as_concrete_TypeRef(), sec_info);
+
+        // An expired leaf certificate is only acceptable if it was valid at the timestamp, and
+        // a short-lived one always has to be. Apple's secure timestamps are RFC 3161 ones.
+        let verify_time = match (options.timestamp_policy, context.validity_period()) {
+            (TimestampPolicy::ShortLived, _) => {
+                let timestamp = context.timestamp().ok_or(Error::TimestampRequired)?;
+                Some(options.verify_time.unwrap_or(timestamp))
+            }
+            (TimestampPolicy::AllowExpiredIfTimestamped, Some((_, not_after)))
+                if options.verify_time.is_none() && SystemTime::now() > not_after =>
+            {
+                match context.timestamp() {
+                    Some(timestamp) => Some(timestamp),
+                    None => {
+                        return Err(Error::Expired {
+                            code: errSecCertificateExpired,
+                            status: ChainStatus::from_error_status(ChainStatus::IS_NOT_TIME_VALID),
+                            message: os_error_message(errSecCertificateExpired),
+                        })
+                    }
+                }
+            }
+            _ => options.verify_time,
+        };
+
+        if !options.trust_anchors.is_empty() || verify_time.is_some() {
+            options.check_cancelled()?;
+            evaluate_chain(&certs, options, verify_time)?;
+        }
+
+        context.set_nested_code(nested);
+        context.set_path(
+            self.path()
+                .ok()
+                .map(|path| std::fs::canonicalize(&path).unwrap_or(path)),
+        );
+        Ok(context)
     }
 
-    /// Retreive a dictionary of various pieces of information from a code signature.
-    fn get_code_singing_info(&self) -> Result<CFDictionary, Error> {
-        let mut dict: CFDictionaryRef = std::ptr::null_mut();
+    /// The signer the signature claims, read from the signing information without validating
+    /// it, which the Security framework gives for signatures that fail verification as well
+    pub fn claimed_context(&self) -> Option<Context> {
+        let sec_info = self.get_code_singing_info().ok()?;
+        let certs = signing_certificates(&sec_info)?;
+        let leaf_cert = certs.get(0)?;
+        Some(Context::new(leaf_cert.as_concrete_TypeRef(), sec_info))
+    }
+
+    /// The sha256 thumbprint of the certificate the signature claims to be signed with, read
+    /// without validating anything
+    pub fn claimed_thumbprint(&self) -> Option<String> {
+        self.claimed_context()
+            .map(|context| context.sha256_thumbprint())
+    }
+
+    /// macOS code signatures always have a single signer
+    pub fn signer_count(&self) -> Result<u32, Error> {
+        Ok(1)
+    }
+
+    pub fn verify_signer(
+        &self,
+        index: u32,
+        options: &VerificationOptions,
+    ) -> Result<Context, Error> {
+        match index {
+            0 => self.verify(options),
+            _ => Err(Error::SignerNotFound),
+        }
+    }
+
+    /// Verify each piece of nested code recorded in the bundle's resource seal individually,
+    /// against the requirement the seal recorded for it, descending `depth` levels
+    fn verify_nested_code(
+        &self,
+        options: &VerificationOptions,
+        flags: SecCSFlags,
+        depth: u32,
+    ) -> Result<Vec<NestedCode>, Error> {
+        let path = self.path()?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("nested_code", path = %path.display(), depth).entered();
+        let (base, files) = match sealed_files(&path) {
+            Some(sealed) => sealed,
+            None => return Ok(Vec::new()), // Not a bundle, so nothing is nested
+        };
+
+        let requirement_key = CFString::new("requirement");
+        let (keys, values) = files.get_keys_and_values();
+        let mut nested = Vec::new();
+
+        for (key, value) in keys.into_iter().zip(values) {
+            options.check_cancelled()?;
+
+            let value = unsafe { CFType::wrap_under_get_rule(value) };
+            let entry = match value.downcast::<CFDictionary>() {
+                Some(entry) => entry,
+                None => continue, // Legacy entries are plain resource hashes
+            };
 
-        let sec = match &self.0 {
+            // Only nested code is sealed with a designated requirement
+            let requirement = match entry.find(requirement_key.as_CFTypeRef()) {
+                Some(req) => unsafe { CFString::wrap_under_get_rule(*req as _) }.to_string(),
+                None => continue,
+            };
+
+            let relative = unsafe { CFString::wrap_under_get_rule(key as _) }.to_string();
+            let item = base.join(relative);
+
+            let (result, children) = match Verifier::for_architecture(&item, &options.architecture)
+            {
+                Ok(verifier) => {
+                    let result = verifier.check_validity(Some(&requirement), flags);
+                    let children = match depth {
+                        1 => Vec::new(),
+                        _ => verifier.verify_nested_code(options, flags, depth - 1)?,
+                    };
+                    (result, children)
+                }
+                Err(err) => (Err(err), Vec::new()),
+            };
+
+            nested.push(NestedCode {
+                path: item,
+                result,
+                nested: children,
+            });
+        }
+
+        nested.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(nested)
+    }
+
+    /// Retrieve the on disk path of the code, for bundles this is the bundle directory
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        let mut url: CFURLRef = std::ptr::null_mut();
+
+        unsafe {
+            match SecCodeCopyPath(
+                self.static_ref(),
+                SecCSFlags::kSecCSDefaultFlags,
+                Some(&mut url),
+            ) {
+                sec_sys::errSecSuccess if !url.is_null() => CFURL::wrap_under_create_rule(url)
+                    .to_path()
+                    .ok_or(Error::InvalidPath),
+                err => Err(Error::os_error(err)),
+            }
+        }
+    }
+
+    fn static_ref(&self) -> SecStaticCodeRef {
+        match &self.code {
             SecCodeKind::Static(sec) => sec.as_concrete_TypeRef(),
             SecCodeKind::Dynamic(sec) => sec.as_CFTypeRef() as _, // Dynamic will be implicitly converted to static
+        }
+    }
+
+    /// The parent of the process, processes whose parent exits are adopted by launchd
+    pub fn parent_pid(pid: i32) -> Result<Option<i32>, Error> {
+        let mut info = proc_bsdshortinfo::default();
+        let size = std::mem::size_of::<proc_bsdshortinfo>() as i32;
+
+        if unsafe {
+            proc_pidinfo(
+                pid,
+                PROC_PIDT_SHORTBSDINFO,
+                0,
+                &mut info as *mut _ as _,
+                size,
+            )
+        } != size
+        {
+            let err = std::io::Error::last_os_error();
+            return Err(Error::os_error(err.raw_os_error().unwrap_or(0)));
+        }
+
+        // The kernel has no code object to verify
+        match info.pbsi_ppid {
+            0 => Ok(None),
+            parent => Ok(Some(parent as i32)),
+        }
+    }
+
+    /// The files mapped executable into the process, found by walking its memory regions.
+    /// Libraries in the dyld shared cache have no file of their own, so they're left out
+    pub fn module_paths(pid: i32) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = Vec::new();
+        let mut address = 0u64;
+
+        loop {
+            let mut region = proc_regioninfo::default();
+            let size = std::mem::size_of::<proc_regioninfo>() as i32;
+            if unsafe {
+                proc_pidinfo(
+                    pid,
+                    PROC_PIDREGIONINFO,
+                    address,
+                    &mut region as *mut _ as _,
+                    size,
+                )
+            } != size
+            {
+                // Past the last region, unless even the first one couldn't be read
+                if paths.is_empty() && address == 0 {
+                    let err = std::io::Error::last_os_error();
+                    return Err(Error::os_error(err.raw_os_error().unwrap_or(0)));
+                }
+                break;
+            }
+            address = region.pri_address + region.pri_size;
+
+            if region.pri_protection & VM_PROT_EXECUTE == 0 {
+                continue;
+            }
+
+            let mut buf = [0u8; MAXPATHLEN];
+            let len = unsafe {
+                proc_regionfilename(
+                    pid,
+                    region.pri_address,
+                    buf.as_mut_ptr() as _,
+                    buf.len() as u32,
+                )
+            };
+            if len <= 0 {
+                continue; // Anonymous memory, e.g. JIT code
+            }
+
+            let path = PathBuf::from(String::from_utf8_lossy(&buf[..len as usize]).into_owned());
+            let shared_cache = path.file_name().map_or(false, |name| {
+                name.to_string_lossy().starts_with("dyld_shared_cache")
+            });
+            if !shared_cache && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Compare the code directory hash the kernel recorded when the process was loaded with
+    /// the one of the file on disk, for the architecture slice that's running
+    pub fn compare_image(pid: i32) -> Result<(), Error> {
+        let code = match Verifier::for_pid(pid)?.code {
+            SecCodeKind::Dynamic(code) => code,
+            SecCodeKind::Static(_) => unreachable!(),
         };
 
-        unsafe {
+        let mut loaded = [0u8; 20];
+        if unsafe { csops(pid, CS_OPS_CDHASH, loaded.as_mut_ptr() as _, loaded.len()) } != 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(Error::os_error(err.raw_os_error().unwrap_or(0)));
+        }
+        let loaded = crate::digest::hex_upper(&loaded);
+
+        let mut sec: SecStaticCodeRef = std::ptr::null_mut();
+        let on_disk = unsafe {
+            match SecCodeCopyStaticCode(
+                code.as_concrete_TypeRef(),
+                SecCSFlags::kSecCSDefaultFlags,
+                Some(&mut sec),
+            ) {
+                sec_sys::errSecSuccess if !sec.is_null() => Verifier::new(SecCodeKind::Static(
+                    SecStaticCode::wrap_under_create_rule(sec),
+                )),
+                err => return Err(Error::os_error(err)),
+            }
+        };
+
+        let info = on_disk.get_code_singing_info()?;
+        match context::cd_hash(&info) {
+            Some(cd_hash) if cd_hash == loaded => Ok(()),
+            Some(_) => Err(Error::ImageMismatch),
+            None => Err(Error::Unsigned),
+        }
+    }
+
+    /// Check the kernel's view of a running process, whose signature becomes invalid once
+    /// pages that don't match it are loaded, without validating the file on disk again
+    pub fn check_dynamic_validity(&self) -> Result<(), Error> {
+        let code = match &self.code {
+            SecCodeKind::Dynamic(code) => code,
+            SecCodeKind::Static(_) => return Err(Error::Unsupported), // Files have no dynamic state
+        };
+
+        let mut dict: CFDictionaryRef = std::ptr::null_mut();
+        let info = unsafe {
             match SecCodeCopySigningInformation(
-                sec,
-                SecCSFlags::kSecCSSigningInformation,
+                code.as_CFTypeRef() as _,
+                SecCSFlags::kSecCSDynamicInformation,
                 Some(&mut dict),
             ) {
                 sec_sys::errSecSuccess if !dict.is_null() => {
-                    Ok(CFDictionary::wrap_under_create_rule(dict))
+                    CFDictionary::wrap_under_create_rule(dict)
                 }
-                err => Err(Error::OsError(err)),
+                err => return Err(Error::os_error(err)),
             }
+        };
+
+        let key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoStatus) };
+        let status = info
+            .find(key.as_CFTypeRef())
+            .and_then(|value| unsafe { CFNumber::wrap_under_get_rule(*value as _) }.to_i64())
+            .unwrap_or(0) as u32;
+
+        if status & kSecCodeStatusValid == 0 {
+            return Err(Error::Invalidated);
         }
+        Ok(())
     }
 
-    fn check_validity(&self, requirement: &str) -> Result<(), Error> {
-        let mut req: SecRequirementRef = std::ptr::null_mut();
-        let mut err: CFErrorRef = std::ptr::null_mut();
+    /// Retreive a dictionary of various pieces of information from a code signature.
+    fn get_code_singing_info(&self) -> Result<CFDictionary, Error> {
+        let mut dict: CFDictionaryRef = std::ptr::null_mut();
 
-        // Generate a new requirement object using the Apple [Code Signing Requirement Language](https://developer.apple.com/library/archive/documentation/Security/Conceptual/CodeSigningGuide/RequirementLang/RequirementLang.html#//apple_ref/doc/uid/TP40005929-CH5-SW1)
-        let req = unsafe {
-            match SecRequirementCreateWithStringAndErrors(
-                CFString::new(requirement).as_concrete_TypeRef(),
-                SecCSFlags::kSecCSDefaultFlags,
-                Some(&mut err),
-                Some(&mut req),
+        unsafe {
+            match SecCodeCopySigningInformation(
+                self.static_ref(),
+                // Entitlements are only included with the requirement information
+                SecCSFlags::kSecCSSigningInformation | SecCSFlags::kSecCSRequirementInformation,
+                Some(&mut dict),
             ) {
-                sec_sys::errSecSuccess if !req.is_null() => {
-                    SecRequirement::wrap_under_create_rule(req)
-                }
-                status => {
-                    if !err.is_null() {
-                        return Err(err.into());
-                    } else {
-                        return Err(Error::OsError(status));
-                    }
+                sec_sys::errSecSuccess if !dict.is_null() => {
+                    Ok(CFDictionary::wrap_under_create_rule(dict))
                 }
+                err => Err(Error::os_error(err)),
+            }
+        }
+    }
+
+    /// Check validity, giving up once the network timeout of the options passes, or the
+    /// verification is cancelled. The Security framework can't bound its own network lookups or
+    /// be interrupted, so the check runs on a worker thread of its own, which is left to finish
+    /// in the background when it's given up on.
+    fn check_validity_within(
+        &self,
+        requirement: Option<&str>,
+        flags: SecCSFlags,
+        options: &VerificationOptions,
+    ) -> Result<(), Error> {
+        let timeout = options
+            .network_budget()
+            .filter(|_| !flags.contains(SecCSFlags::kSecCSNoNetworkAccess));
+        if timeout.is_none() && !options.is_cancellable() {
+            return self.check_validity(requirement, flags);
+        }
+
+        let verifier = Verifier {
+            code: self.code.clone(),
+            temp: self.temp.clone(),
+            held: None,
+        };
+        let requirement = requirement.map(str::to_string);
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let result = verifier.check_validity(requirement.as_deref(), flags);
+            let _ = sender.send(result);
+        });
+
+        // Cancellation is polled for while waiting, the check can't be told about it
+        let started = std::time::Instant::now();
+        loop {
+            match receiver.recv_timeout(CANCELLATION_POLL_INTERVAL) {
+                Ok(result) => return result,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return Err(Error::Timeout),
+            }
+
+            options.check_cancelled()?;
+            if timeout.map_or(false, |timeout| started.elapsed() >= timeout) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?timeout, "validity check timed out");
+                return Err(Error::Timeout);
             }
+        }
+    }
+
+    fn check_validity(&self, requirement: Option<&str>, flags: SecCSFlags) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("requirement_evaluation", requirement).entered();
+
+        let mut err: CFErrorRef = std::ptr::null_mut();
+
+        // Without a requirement only the signature itself is validated
+        let req = match requirement {
+            Some(requirement) => Some(create_requirement(requirement)?),
+            None => None,
         };
+        let req_ref = req
+            .as_ref()
+            .map_or(std::ptr::null(), |req| req.as_concrete_TypeRef());
 
-        let status = match &self.0 {
+        let status = match &self.code {
             SecCodeKind::Static(sec) => unsafe {
                 SecStaticCodeCheckValidityWithErrors(
                     sec.as_concrete_TypeRef(),
-                    SecCSFlags::kSecCSCheckAllArchitectures,
-                    req.as_concrete_TypeRef(),
+                    flags,
+                    req_ref,
                     Some(&mut err),
                 )
             },
             SecCodeKind::Dynamic(sec) => unsafe {
                 SecCodeCheckValidityWithErrors(
                     sec.as_concrete_TypeRef(),
-                    SecCSFlags::kSecCSCheckAllArchitectures,
-                    req.as_concrete_TypeRef(),
+                    flags,
+                    req_ref,
                     Some(&mut err),
                 )
             },
@@ -147,16 +821,296 @@ impl Verifier {
             sec_sys::errSecSuccess => Ok(()),
             sec_sys::errSecCSUnsigned => Err(Error::Unsigned),
             status => {
-                if !err.is_null() {
-                    Err(err.into())
+                let err = if !err.is_null() {
+                    err.into()
                 } else {
-                    Err(Error::OsError(status))
+                    Error::os_error(status)
+                };
+                Err(validity_error(status, err))
+            }
+        }
+    }
+}
+
+/// The certificates of the signature, leaf first, None without any
+fn signing_certificates(sec_info: &CFDictionary) -> Option<CFArray<SecCertificate>> {
+    let cert_key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoCertificates) };
+    let certs_ref = sec_info.find(cert_key.as_CFTypeRef())?;
+    let certs = unsafe { CFArray::<SecCertificate>::wrap_under_get_rule(*certs_ref as _) };
+    Some(certs).filter(|certs| !certs.is_empty())
+}
+
+/// Replace the validity check status codes policy decisions depend on with their own variant
+fn validity_error(status: OSStatus, err: Error) -> Error {
+    let message = err.description();
+    let chain_failed = |error_status| {
+        Error::chain_failed(
+            status,
+            ChainStatus::from_error_status(error_status),
+            message.clone(),
+        )
+    };
+
+    match status {
+        errSecCSReqFailed => Error::RequirementFailed,
+        errSecCSSignatureFailed | errSecCSSignatureInvalid => Error::Tampered {
+            code: status,
+            message,
+        },
+        errSecCSResourcesNotSealed
+        | errSecCSResourcesNotFound
+        | errSecCSResourcesInvalid
+        | errSecCSBadResource
+        | errSecCSResourceDirectoryFailed => Error::ResourceModified {
+            code: status,
+            message,
+        },
+        errSecCertificateExpired | errSecCertificateNotValidYet => {
+            chain_failed(ChainStatus::IS_NOT_TIME_VALID)
+        }
+        errSecCertificateRevoked => chain_failed(ChainStatus::IS_REVOKED),
+        errSecNotTrusted => chain_failed(ChainStatus::IS_UNTRUSTED_ROOT),
+        _ => err,
+    }
+}
+
+/// The requirement language for the policy and requirement in the options, joined together
+fn requirement_text(options: &VerificationOptions) -> Result<Option<String>, Error> {
+    let policy = match options.policy {
+        None => None,
+        Some(Policy::AnySystemTrusted) => Some(Requirement::anchor_trusted()),
+        Some(Policy::AppleAnchored) => Some(Requirement::anchor_apple_generic()),
+        Some(Policy::StoreSigned) => Some(Requirement::from_text(MAC_APP_STORE_REQUIREMENT)),
+        Some(Policy::MicrosoftRootsOnly) => return Err(Error::Unsupported),
+    };
+
+    Ok(match (policy, &options.requirement) {
+        (Some(policy), Some(requirement)) => Some(format!("({}) and ({})", policy, requirement)),
+        (policy, requirement) => policy
+            .as_ref()
+            .or(requirement.as_ref())
+            .map(|r| r.to_string()),
+    })
+}
+
+/// Generate a new requirement object using the Apple [Code Signing Requirement Language](https://developer.apple.com/library/archive/documentation/Security/Conceptual/CodeSigningGuide/RequirementLang/RequirementLang.html#//apple_ref/doc/uid/TP40005929-CH5-SW1)
+pub(crate) fn create_requirement(requirement: &str) -> Result<SecRequirement, Error> {
+    let mut req: SecRequirementRef = std::ptr::null_mut();
+    let mut err: CFErrorRef = std::ptr::null_mut();
+
+    unsafe {
+        match SecRequirementCreateWithStringAndErrors(
+            CFString::new(requirement).as_concrete_TypeRef(),
+            SecCSFlags::kSecCSDefaultFlags,
+            Some(&mut err),
+            Some(&mut req),
+        ) {
+            sec_sys::errSecSuccess if !req.is_null() => {
+                Ok(SecRequirement::wrap_under_create_rule(req))
+            }
+            status => {
+                if err.is_null() {
+                    return Err(Error::os_error(status));
+                }
+                let err = CFError::wrap_under_create_rule(err);
+                match syntax_error(&err) {
+                    Some(syntax) => Err(Error::InvalidRequirement(syntax)),
+                    None => Err(Error::CFError(err)),
                 }
             }
         }
     }
 }
 
+/// The parser message attached to a requirement that failed to compile
+fn syntax_error(err: &CFError) -> Option<RequirementSyntax> {
+    let info = unsafe { CFErrorCopyUserInfo(err.as_concrete_TypeRef()) };
+    if info.is_null() {
+        return None;
+    }
+    let info: CFDictionary = unsafe { CFDictionary::wrap_under_create_rule(info) };
+
+    let value = info.find(unsafe { kSecCFErrorRequirementSyntax } as *const _)?;
+    let message = unsafe { CFType::wrap_under_get_rule(*value) }.downcast_into::<CFString>()?;
+    Some(RequirementSyntax::parse(&message.to_string()))
+}
+
+/// Evaluate the signing chain again, against the trust anchors of the options when there are
+/// any, and as of the given time rather than now
+fn evaluate_chain(
+    certs: &CFArray<SecCertificate>,
+    options: &VerificationOptions,
+    verify_time: Option<std::time::SystemTime>,
+) -> Result<(), Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "chain_building",
+        anchors = options.trust_anchors.len(),
+        ?verify_time
+    )
+    .entered();
+
+    let mut trust: SecTrustRef = std::ptr::null_mut();
+    let trust = unsafe {
+        let policy = SecPolicy::wrap_under_create_rule(SecPolicyCreateBasicX509());
+        match SecTrustCreateWithCertificates(
+            certs.as_concrete_TypeRef(),
+            policy.as_concrete_TypeRef(),
+            Some(&mut trust),
+        ) {
+            sec_sys::errSecSuccess if !trust.is_null() => SecTrust::wrap_under_create_rule(trust),
+            err => return Err(Error::os_error(err)),
+        }
+    };
+
+    if !options.trust_anchors.is_empty() {
+        set_anchors(&trust, &options.trust_anchors, options.trust_system_roots)?;
+    }
+    if let Some(time) = verify_time {
+        let date = plist::from_system_time(time);
+        match unsafe {
+            SecTrustSetVerifyDate(trust.as_concrete_TypeRef(), date.as_concrete_TypeRef())
+        } {
+            sec_sys::errSecSuccess => {}
+            err => return Err(Error::os_error(err)),
+        }
+    }
+
+    // The chain status explains the failure, the error itself only describes it
+    let message = unsafe {
+        let mut err: CFErrorRef = std::ptr::null_mut();
+        if SecTrustEvaluateWithError(trust.as_concrete_TypeRef(), Some(&mut err)) {
+            return Ok(());
+        }
+        if err.is_null() {
+            None
+        } else {
+            Some(
+                CFError::wrap_under_create_rule(err)
+                    .description()
+                    .to_string(),
+            )
+        }
+    };
+
+    let status = context::trust_status(&trust)
+        .unwrap_or_else(|| ChainStatus::from_error_status(ChainStatus::IS_UNTRUSTED_ROOT));
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        error_status = status.error_status,
+        "chain evaluation failed"
+    );
+    let code = if status.is_revoked() {
+        errSecCertificateRevoked
+    } else if status.is_expired() {
+        errSecCertificateExpired
+    } else {
+        errSecNotTrusted
+    };
+
+    Err(Error::chain_failed(code, status, message))
+}
+
+/// Trust chains ending at one of the given DER encoded roots, and the system roots only if asked to
+fn set_anchors(trust: &SecTrust, anchors: &[Vec<u8>], system_roots: bool) -> Result<(), Error> {
+    let anchors = anchors
+        .iter()
+        .map(|der| {
+            let cert = unsafe {
+                SecCertificateCreateWithData(
+                    std::ptr::null(),
+                    CFData::from_buffer(der).as_concrete_TypeRef(),
+                )
+            };
+            if cert.is_null() {
+                return Err(Error::InvalidCertificate);
+            }
+            Ok(unsafe { SecCertificate::wrap_under_create_rule(cert) })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let anchors = CFArray::from_CFTypes(&anchors);
+
+    unsafe {
+        match SecTrustSetAnchorCertificates(
+            trust.as_concrete_TypeRef(),
+            anchors.as_concrete_TypeRef(),
+        ) {
+            sec_sys::errSecSuccess => {}
+            err => return Err(Error::os_error(err)),
+        }
+        match SecTrustSetAnchorCertificatesOnly(trust.as_concrete_TypeRef(), (!system_roots) as _) {
+            sec_sys::errSecSuccess => Ok(()),
+            err => Err(Error::os_error(err)),
+        }
+    }
+}
+
+/// Load the `files2` section of a bundle's resource seal, along with the directory its paths are relative to
+fn sealed_files(bundle: &Path) -> Option<(PathBuf, CFDictionary)> {
+    let base = [bundle.join("Contents"), bundle.to_path_buf()]
+        .iter()
+        .find(|base| base.join("_CodeSignature/CodeResources").is_file())?
+        .clone();
+
+    let bytes = std::fs::read(base.join("_CodeSignature/CodeResources")).ok()?;
+    let (plist, _) =
+        create_with_data(CFData::from_buffer(&bytes), kCFPropertyListImmutable).ok()?;
+    let resources =
+        unsafe { CFType::wrap_under_create_rule(plist) }.downcast_into::<CFDictionary>()?;
+
+    let files = resources.find(CFString::new("files2").as_CFTypeRef())?;
+    let files = unsafe { CFType::wrap_under_get_rule(*files) }.downcast_into::<CFDictionary>()?;
+
+    Some((base, files))
+}
+
+/// The Security framework's message for an OSStatus, when there is one
+pub fn os_error_message(status: OSStatus) -> Option<String> {
+    let message = unsafe { SecCopyErrorMessageString(status, std::ptr::null_mut()) };
+    if message.is_null() {
+        return None;
+    }
+    Some(unsafe { CFString::wrap_under_create_rule(message) }.to_string())
+}
+
+/// Describe an OSStatus, with its message when there is one
+pub fn describe_os_error(status: OSStatus, message: Option<&str>) -> String {
+    match message {
+        Some(message) => format!("{} (OSStatus {})", message, status),
+        None => format!("OSStatus {}", status),
+    }
+}
+
+impl Error {
+    /// The OSStatus behind the error, if it came from the Security framework and a failed
+    /// certificate chain could explain it
+    fn os_status(&self) -> Option<OSStatus> {
+        match self {
+            Error::OsError { code, .. } => Some(*code),
+            Error::CFError(err) => Some(err.code() as OSStatus),
+            Error::Expired { code, .. }
+            | Error::Revoked { code, .. }
+            | Error::UntrustedRoot { code, .. }
+            | Error::ChainFailed { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The description of an error from the Security framework, a CFError describes the
+    /// failure more specifically than its status, e.g. naming the resource that was modified
+    fn description(&self) -> Option<String> {
+        match self {
+            Error::CFError(err) => Some(err.description().to_string()),
+            err => err.native_message().map(str::to_string),
+        }
+    }
+}
+
+// CFError is immutable, and CoreFoundation reference counting is thread safe, so errors can be
+// sent across threads, as error handling crates like anyhow require
+unsafe impl Send for Error {}
+unsafe impl Sync for Error {}
+
 impl From<CFErrorRef> for Error {
     fn from(err: CFErrorRef) -> Self {
         if err.is_null() {
@@ -168,3 +1122,120 @@ impl From<CFErrorRef> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A root the system trusts, other than the given one
+    fn other_root(root: &[u8]) -> Vec<u8> {
+        let mut anchors: CFArrayRef = std::ptr::null();
+        assert_eq!(
+            unsafe { SecTrustCopyAnchorCertificates(&mut anchors) },
+            errSecSuccess
+        );
+        let anchors = unsafe { CFArray::<SecCertificate>::wrap_under_create_rule(anchors) };
+        anchors
+            .iter()
+            .map(|cert| context::certificate_data(cert.as_concrete_TypeRef()))
+            .find(|der| der.as_slice() != root)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_audit_token() {
+        use std::os::unix::io::AsRawFd;
+
+        // The peer of a socket pair is this process, resolved through its audit token
+        let (stream, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let verifier = Verifier::for_socket_peer(stream.as_raw_fd()).unwrap();
+        let current = Verifier::for_current_process().unwrap();
+        assert_eq!(verifier.path().unwrap(), current.path().unwrap());
+
+        // No process has a pid of -1
+        let mut token = [0; 8];
+        token[5] = u32::MAX;
+        assert!(Verifier::for_audit_token(token).is_err());
+    }
+
+    #[test]
+    fn test_validity_flags() {
+        let flags = validity_flags(&VerificationOptions::new());
+        assert!(!flags.contains(SecCSFlags::kSecCSCheckNestedCode));
+        assert!(!flags.contains(SecCSFlags::kSecCSStrictValidate));
+        assert!(!flags.contains(SecCSFlags::kSecCSDoNotValidateResources));
+        assert!(!flags.contains(SecCSFlags::kSecCSConsiderExpiration));
+
+        let flags = validity_flags(&VerificationOptions::new().nested_code_depth(1));
+        assert!(flags.contains(SecCSFlags::kSecCSCheckNestedCode));
+
+        let flags = validity_flags(
+            &VerificationOptions::new()
+                .strict(true)
+                .validate_resources(false),
+        );
+        assert!(flags.contains(SecCSFlags::kSecCSStrictValidate));
+        assert!(flags.contains(SecCSFlags::kSecCSDoNotValidateResources));
+
+        let options = VerificationOptions::new().timestamp_policy(TimestampPolicy::LifetimeSigning);
+        assert!(validity_flags(&options).contains(SecCSFlags::kSecCSConsiderExpiration));
+    }
+
+    #[test]
+    fn test_strict_validation() {
+        let verifier = Verifier::for_file("/Applications/Safari.app").unwrap();
+        let options = VerificationOptions::new().strict(true);
+        assert!(verifier.verify(&options).is_ok());
+
+        let options = VerificationOptions::new().validate_resources(false);
+        assert!(verifier.verify(&options).is_ok());
+    }
+
+    #[test]
+    fn test_revocation_flags() {
+        let enforced = |options: VerificationOptions| {
+            validity_flags(&options).contains(SecCSFlags::kSecCSEnforceRevocationChecks)
+        };
+        assert!(!enforced(VerificationOptions::new()));
+        assert!(!enforced(
+            VerificationOptions::new().revocation(RevocationMode::None)
+        ));
+        assert!(enforced(
+            VerificationOptions::new().revocation(RevocationMode::EndCertificate)
+        ));
+        assert!(enforced(
+            VerificationOptions::new().revocation(RevocationMode::WholeChain)
+        ));
+        assert!(!enforced(
+            VerificationOptions::new()
+                .revocation(RevocationMode::WholeChain)
+                .offline(true)
+        ));
+
+        // Cache-only retrieval
+        let no_network = |options: VerificationOptions| {
+            validity_flags(&options).contains(SecCSFlags::kSecCSNoNetworkAccess)
+        };
+        assert!(!no_network(VerificationOptions::new()));
+        assert!(no_network(VerificationOptions::new().allow_network(false)));
+        assert!(no_network(VerificationOptions::new().offline(true)));
+    }
+
+    #[test]
+    fn test_trust_anchors() {
+        let verifier = Verifier::for_file("/sbin/ping").unwrap();
+        let chain = verifier
+            .verify(&VerificationOptions::new())
+            .unwrap()
+            .certificate_chain();
+        let root = chain.last().unwrap();
+
+        // Only the pinned root is trusted, the chain of the system binary ends at it
+        let options = VerificationOptions::new().trust_anchor(root);
+        assert!(verifier.verify(&options).is_ok());
+
+        let options = VerificationOptions::new().trust_anchor(&other_root(root));
+        let err = verifier.verify(&options).unwrap_err();
+        assert!(err.chain_status().is_some(), "{:?}", err);
+    }
+}
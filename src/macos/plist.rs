@@ -0,0 +1,79 @@
+use super::sec_sys::*;
+use crate::PropertyValue;
+use core_foundation::array::CFArray;
+use core_foundation::boolean::CFBoolean;
+use core_foundation::number::CFNumberIsFloatType;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+/// Seconds between the Unix epoch and the CoreFoundation reference date (2001-01-01)
+const CF_ABSOLUTE_TIME_SINCE_1970: f64 = 978307200.0;
+
+pub fn to_system_time(date: &CFDate) -> SystemTime {
+    let secs = date.abs_time() + CF_ABSOLUTE_TIME_SINCE_1970;
+    SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs.max(0.0))
+}
+
+pub fn from_system_time(time: SystemTime) -> CFDate {
+    let secs = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    };
+    CFDate::new(secs - CF_ABSOLUTE_TIME_SINCE_1970)
+}
+
+/// Convert a property list object into an owned value, returns None for non plist types
+pub fn to_value(value: &CFType) -> Option<PropertyValue> {
+    if let Some(b) = value.downcast::<CFBoolean>() {
+        return Some(PropertyValue::Bool(b.into()));
+    }
+
+    if let Some(n) = value.downcast::<CFNumber>() {
+        let is_float = unsafe { CFNumberIsFloatType(n.as_concrete_TypeRef()) } != 0;
+        return if is_float {
+            n.to_f64().map(PropertyValue::Real)
+        } else {
+            n.to_i64().map(PropertyValue::Integer)
+        };
+    }
+
+    if let Some(s) = value.downcast::<CFString>() {
+        return Some(PropertyValue::String(s.to_string()));
+    }
+
+    if let Some(d) = value.downcast::<CFData>() {
+        return Some(PropertyValue::Data(d.bytes().to_vec()));
+    }
+
+    if let Some(d) = value.downcast::<CFDate>() {
+        return Some(PropertyValue::Date(to_system_time(&d)));
+    }
+
+    if let Some(a) = value.downcast::<CFArray>() {
+        let items = a
+            .iter()
+            .filter_map(|item| to_value(&unsafe { CFType::wrap_under_get_rule(*item) }))
+            .collect();
+        return Some(PropertyValue::Array(items));
+    }
+
+    if let Some(d) = value.downcast::<CFDictionary>() {
+        return Some(PropertyValue::Dictionary(to_map(&d)));
+    }
+
+    None
+}
+
+/// Convert a dictionary with string keys, entries with other keys are skipped
+pub fn to_map(dict: &CFDictionary) -> BTreeMap<String, PropertyValue> {
+    let (keys, values) = dict.get_keys_and_values();
+
+    keys.into_iter()
+        .zip(values)
+        .filter_map(|(key, value)| {
+            let key = unsafe { CFType::wrap_under_get_rule(key) }.downcast::<CFString>()?;
+            let value = to_value(&unsafe { CFType::wrap_under_get_rule(value) })?;
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
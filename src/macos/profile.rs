@@ -0,0 +1,144 @@
+use super::plist;
+use super::sec_sys::*;
+use crate::{PropertyValue, ProvisioningProfile};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Locate and parse the provisioning profile embedded in a bundle
+pub fn embedded_profile(bundle: &Path) -> Option<ProvisioningProfile> {
+    let path = [
+        "Contents/embedded.provisionprofile", // macOS
+        "embedded.mobileprovision",           // iOS
+    ]
+    .iter()
+    .map(|name| bundle.join(name))
+    .find(|path| path.is_file())?;
+
+    let bytes = std::fs::read(path).ok()?;
+    let content = decode_cms(&bytes)?;
+
+    let (value, _) =
+        create_with_data(CFData::from_buffer(&content), kCFPropertyListImmutable).ok()?;
+    let dict = unsafe { CFType::wrap_under_create_rule(value) }.downcast_into::<CFDictionary>()?;
+
+    Some(from_map(plist::to_map(&dict)))
+}
+
+/// Extract the content of a CMS signed message, the signature itself isn't evaluated
+fn decode_cms(message: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder: CMSDecoderRef = std::ptr::null();
+    let mut content: CFDataRef = std::ptr::null();
+
+    unsafe {
+        if CMSDecoderCreate(Some(&mut decoder)) != errSecSuccess || decoder.is_null() {
+            return None;
+        }
+        let decoder = CMSDecoder::wrap_under_create_rule(decoder);
+
+        if CMSDecoderUpdateMessage(
+            decoder.as_concrete_TypeRef(),
+            message.as_ptr(),
+            message.len(),
+        ) != errSecSuccess
+            || CMSDecoderFinalizeMessage(decoder.as_concrete_TypeRef()) != errSecSuccess
+            || CMSDecoderCopyContent(decoder.as_concrete_TypeRef(), Some(&mut content))
+                != errSecSuccess
+            || content.is_null()
+        {
+            return None;
+        }
+
+        Some(CFData::wrap_under_create_rule(content).bytes().to_vec())
+    }
+}
+
+fn from_map(mut map: BTreeMap<String, PropertyValue>) -> ProvisioningProfile {
+    let string = |value: Option<PropertyValue>| match value {
+        Some(PropertyValue::String(s)) => Some(s),
+        _ => None,
+    };
+    let date = |value: Option<PropertyValue>| match value {
+        Some(PropertyValue::Date(d)) => Some(d),
+        _ => None,
+    };
+    let strings = |value: Option<PropertyValue>| match value {
+        Some(PropertyValue::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| string(Some(item)))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    ProvisioningProfile {
+        name: string(map.remove("Name")),
+        uuid: string(map.remove("UUID")),
+        team_ids: strings(map.remove("TeamIdentifier")),
+        team_name: string(map.remove("TeamName")),
+        entitlements: match map.remove("Entitlements") {
+            Some(PropertyValue::Dictionary(entitlements)) => entitlements,
+            _ => BTreeMap::new(),
+        },
+        creation_date: date(map.remove("CreationDate")),
+        expiration_date: date(map.remove("ExpirationDate")),
+        provisioned_devices: strings(map.remove("ProvisionedDevices")),
+        provisions_all_devices: map.remove("ProvisionsAllDevices")
+            == Some(PropertyValue::Bool(true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_from_map() {
+        let string = |s: &str| PropertyValue::String(s.to_string());
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut entitlements = BTreeMap::new();
+        entitlements.insert("get-task-allow".to_string(), PropertyValue::Bool(true));
+
+        let mut map = BTreeMap::new();
+        map.insert("Name".to_string(), string("Development"));
+        map.insert(
+            "TeamIdentifier".to_string(),
+            PropertyValue::Array(vec![string("ABC123")]),
+        );
+        map.insert(
+            "Entitlements".to_string(),
+            PropertyValue::Dictionary(entitlements.clone()),
+        );
+        map.insert("CreationDate".to_string(), PropertyValue::Date(created));
+        map.insert(
+            "ProvisionedDevices".to_string(),
+            PropertyValue::Array(vec![string("0000")]),
+        );
+
+        let profile = from_map(map);
+        assert_eq!(profile.name.as_deref(), Some("Development"));
+        assert_eq!(profile.uuid, None);
+        assert_eq!(profile.team_ids, ["ABC123"]);
+        assert_eq!(profile.entitlements, entitlements);
+        assert_eq!(profile.creation_date, Some(created));
+        assert_eq!(profile.expiration_date, None);
+        assert_eq!(profile.provisioned_devices, ["0000"]);
+        assert!(!profile.provisions_all_devices);
+    }
+
+    #[test]
+    fn test_embedded_profile() {
+        let bundle = crate::temp_path();
+        std::fs::create_dir_all(bundle.join("Contents")).unwrap();
+        assert_eq!(embedded_profile(&bundle), None);
+
+        // Not a CMS message
+        std::fs::write(
+            bundle.join("Contents/embedded.provisionprofile"),
+            b"profile",
+        )
+        .unwrap();
+        let profile = embedded_profile(&bundle);
+        std::fs::remove_dir_all(&bundle).unwrap();
+        assert_eq!(profile, None);
+    }
+}
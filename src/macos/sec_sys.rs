@@ -1,32 +1,155 @@
 pub use core_foundation::array::{CFArray, CFArrayRef};
 pub use core_foundation::base::{CFType, CFTypeID, OSStatus, TCFType};
+pub use core_foundation::boolean::CFBoolean;
 pub use core_foundation::data::{CFData, CFDataRef};
+pub use core_foundation::date::{CFDate, CFDateRef};
 pub use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
-pub use core_foundation::error::{CFError, CFErrorRef};
+pub use core_foundation::error::{CFError, CFErrorCopyUserInfo, CFErrorRef};
 pub use core_foundation::number::CFNumber;
+pub use core_foundation::propertylist::{create_with_data, kCFPropertyListImmutable};
 pub use core_foundation::string::{CFString, CFStringRef};
 pub use core_foundation::url::{CFURLRef, CFURL};
 pub use core_foundation::{declare_TCFType, impl_CFTypeDescription, impl_TCFType};
 
 pub const errSecSuccess: OSStatus = 0;
-pub const errSecCSUnsigned: OSStatus = -67062;
-pub const errSecCSBadResource: OSStatus = -67054;
+
+const RTLD_DEFAULT: *mut std::ffi::c_void = -2isize as _;
+
+/// The `csops` operation returning the code directory hash the kernel recorded for a process
+pub const CS_OPS_CDHASH: u32 = 5;
+
+/// The `proc_pidinfo` flavor describing the memory region at, or after, an address
+pub const PROC_PIDREGIONINFO: i32 = 7;
+/// The `proc_pidinfo` flavor with the basic BSD process information, including the parent
+pub const PROC_PIDT_SHORTBSDINFO: i32 = 13;
+pub const VM_PROT_EXECUTE: u32 = 0x04;
+pub const MAXPATHLEN: usize = 1024;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Default)]
+pub struct proc_regioninfo {
+    pub pri_protection: u32,
+    pub pri_max_protection: u32,
+    pub pri_inheritance: u32,
+    pub pri_flags: u32,
+    pub pri_offset: u64,
+    pub pri_behavior: u32,
+    pub pri_user_wired_count: u32,
+    pub pri_user_tag: u32,
+    pub pri_pages_resident: u32,
+    pub pri_pages_shared_now_private: u32,
+    pub pri_pages_swapped_out: u32,
+    pub pri_pages_dirtied: u32,
+    pub pri_ref_count: u32,
+    pub pri_shadow_depth: u32,
+    pub pri_share_mode: u32,
+    pub pri_private_pages_resident: u32,
+    pub pri_shared_pages_resident: u32,
+    pub pri_obj_id: u32,
+    pub pri_depth: u32,
+    pub pri_address: u64,
+    pub pri_size: u64,
+}
+
+/// The `getsockopt` option returning the audit token of a Unix domain socket's peer
+pub const SOL_LOCAL: i32 = 0;
+pub const LOCAL_PEERTOKEN: i32 = 0x006;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Default)]
+pub struct proc_bsdshortinfo {
+    pub pbsi_pid: u32,
+    pub pbsi_ppid: u32,
+    pub pbsi_pgid: u32,
+    pub pbsi_status: u32,
+    pub pbsi_comm: [u8; 16],
+    pub pbsi_flags: u32,
+    pub pbsi_uid: u32,
+    pub pbsi_gid: u32,
+    pub pbsi_ruid: u32,
+    pub pbsi_rgid: u32,
+    pub pbsi_svuid: u32,
+    pub pbsi_svgid: u32,
+    pub pbsi_rfu: u32,
+}
+
+extern "C" {
+    pub fn getsockopt(
+        socket: std::ffi::c_int,
+        level: std::ffi::c_int,
+        option_name: std::ffi::c_int,
+        option_value: *mut std::ffi::c_void,
+        option_len: *mut u32,
+    ) -> std::ffi::c_int;
+
+    pub fn xpc_connection_get_audit_token(connection: *mut std::ffi::c_void, token: *mut [u32; 8]);
+
+    pub fn proc_pidinfo(
+        pid: i32,
+        flavor: i32,
+        arg: u64,
+        buffer: *mut std::ffi::c_void,
+        buffersize: i32,
+    ) -> std::ffi::c_int;
+
+    pub fn proc_regionfilename(
+        pid: i32,
+        address: u64,
+        buffer: *mut std::ffi::c_void,
+        buffersize: u32,
+    ) -> std::ffi::c_int;
+
+    pub fn csops(
+        pid: i32,
+        ops: u32,
+        useraddr: *mut std::ffi::c_void,
+        usersize: usize,
+    ) -> std::ffi::c_int;
+
+    fn dlsym(
+        handle: *mut std::ffi::c_void,
+        symbol: *const std::ffi::c_char,
+    ) -> *mut std::ffi::c_void;
+}
+
+pub use crate::status::*;
+
+pub const kSecCodeSignatureAdhoc: u32 = 0x0002;
+pub const kSecCodeStatusValid: u32 = 0x0001;
+
+/// The `CMSSignerStatus` of a signer whose signature and certificate both verified
+pub const kCMSSignerValid: u32 = 1;
+/// The signature doesn't match the content
+pub const kCMSSignerInvalidSignature: u32 = 3;
+/// The signature matches, but the signer's certificate isn't trusted
+pub const kCMSSignerInvalidCert: u32 = 4;
 
 pub struct __SecCode {}
 pub struct __SecStaticCode {}
 pub struct __SecCertificate {}
 pub struct __SecRequirement {}
+pub struct __CMSDecoder {}
+pub struct __SecTrust {}
+pub struct __SecPolicy {}
 
 pub type SecCertificateRef = *const __SecCertificate;
 pub type SecCodeRef = *const __SecCode;
 pub type SecStaticCodeRef = *const __SecStaticCode;
 pub type SecRequirementRef = *const __SecRequirement;
+pub type CMSDecoderRef = *const __CMSDecoder;
+pub type SecTrustRef = *const __SecTrust;
+pub type SecPolicyRef = *const __SecPolicy;
 
 extern "C" {
     pub fn SecCertificateGetTypeID() -> CFTypeID;
     pub fn SecCodeGetTypeID() -> CFTypeID;
     pub fn SecStaticCodeGetTypeID() -> CFTypeID;
     pub fn SecRequirementGetTypeID() -> CFTypeID;
+    pub fn CMSDecoderGetTypeID() -> CFTypeID;
+    pub fn SecTrustGetTypeID() -> CFTypeID;
+    pub fn SecPolicyGetTypeID() -> CFTypeID;
 }
 
 declare_TCFType!(SecCertificate, SecCertificateRef);
@@ -45,18 +168,65 @@ declare_TCFType!(SecRequirement, SecRequirementRef);
 impl_TCFType!(SecRequirement, SecRequirementRef, SecRequirementGetTypeID);
 impl_CFTypeDescription!(SecRequirement);
 
-#[repr(u32)]
-#[allow(dead_code, non_camel_case_types)]
-pub enum SecCSFlags {
-    kSecCSDefaultFlags = 0,
-    kSecCSCheckAllArchitectures = 1 << 0,
-    kSecCSSigningInformation = 1 << 1,
-    kSecCSConsiderExpiration = 1 << 31,
-    kSecCSEnforceRevocationChecks = 1 << 30,
-    kSecCSCheckTrustedAnchors = 1 << 27,
-    kSecCSNoNetworkAccess = 1 << 29,
-    kSecCSReportProgress = 1 << 28,
-    kSecCSQuickCheck = 1 << 26,
+declare_TCFType!(SecTrust, SecTrustRef);
+impl_TCFType!(SecTrust, SecTrustRef, SecTrustGetTypeID);
+impl_CFTypeDescription!(SecTrust);
+
+declare_TCFType!(SecPolicy, SecPolicyRef);
+impl_TCFType!(SecPolicy, SecPolicyRef, SecPolicyGetTypeID);
+impl_CFTypeDescription!(SecPolicy);
+
+declare_TCFType!(CMSDecoder, CMSDecoderRef);
+impl_TCFType!(CMSDecoder, CMSDecoderRef, CMSDecoderGetTypeID);
+impl_CFTypeDescription!(CMSDecoder);
+
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SecCSFlags(u32);
+
+#[allow(dead_code)]
+impl SecCSFlags {
+    pub const kSecCSDefaultFlags: SecCSFlags = SecCSFlags(0);
+    pub const kSecCSCheckAllArchitectures: SecCSFlags = SecCSFlags(1 << 0);
+    pub const kSecCSSigningInformation: SecCSFlags = SecCSFlags(1 << 1);
+    pub const kSecCSRequirementInformation: SecCSFlags = SecCSFlags(1 << 2);
+    pub const kSecCSDynamicInformation: SecCSFlags = SecCSFlags(1 << 3);
+    pub const kSecCSDoNotValidateExecutable: SecCSFlags = SecCSFlags(1 << 1);
+    pub const kSecCSDoNotValidateResources: SecCSFlags = SecCSFlags(1 << 2);
+    pub const kSecCSCheckNestedCode: SecCSFlags = SecCSFlags(1 << 3);
+    pub const kSecCSStrictValidate: SecCSFlags = SecCSFlags(1 << 4);
+    pub const kSecCSConsiderExpiration: SecCSFlags = SecCSFlags(1 << 31);
+    pub const kSecCSEnforceRevocationChecks: SecCSFlags = SecCSFlags(1 << 30);
+    pub const kSecCSCheckTrustedAnchors: SecCSFlags = SecCSFlags(1 << 27);
+    pub const kSecCSNoNetworkAccess: SecCSFlags = SecCSFlags(1 << 29);
+    pub const kSecCSReportProgress: SecCSFlags = SecCSFlags(1 << 28);
+    pub const kSecCSQuickCheck: SecCSFlags = SecCSFlags(1 << 26);
+
+    pub const fn from_bits(bits: u32) -> SecCSFlags {
+        SecCSFlags(bits)
+    }
+
+    pub fn contains(self, other: SecCSFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn remove(&mut self, other: SecCSFlags) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for SecCSFlags {
+    type Output = SecCSFlags;
+
+    fn bitor(self, rhs: SecCSFlags) -> SecCSFlags {
+        SecCSFlags(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for SecCSFlags {
+    fn bitor_assign(&mut self, rhs: SecCSFlags) {
+        self.0 |= rhs.0;
+    }
 }
 
 #[allow(improper_ctypes)]
@@ -72,12 +242,32 @@ extern "C" {
         guest: Option<&mut SecCodeRef>,
     ) -> OSStatus;
 
+    pub fn SecCopyErrorMessageString(
+        status: OSStatus,
+        reserved: *mut std::ffi::c_void,
+    ) -> CFStringRef;
+
+    pub fn SecCodeCopySelf(flags: SecCSFlags, code: Option<&mut SecCodeRef>) -> OSStatus;
+
+    pub fn SecCodeCopyStaticCode(
+        code: SecCodeRef,
+        flags: SecCSFlags,
+        static_code: Option<&mut SecStaticCodeRef>,
+    ) -> OSStatus;
+
     pub fn SecStaticCodeCreateWithPath(
         path: CFURLRef,
         flags: SecCSFlags,
         static_code: Option<&mut SecStaticCodeRef>,
     ) -> OSStatus;
 
+    pub fn SecStaticCodeCreateWithPathAndAttributes(
+        path: CFURLRef,
+        flags: SecCSFlags,
+        attributes: CFDictionaryRef,
+        static_code: Option<&mut SecStaticCodeRef>,
+    ) -> OSStatus;
+
     pub fn SecCodeCheckValidityWithErrors(
         code: SecCodeRef,
         flags: SecCSFlags,
@@ -99,12 +289,82 @@ extern "C" {
         requirement: Option<&mut SecRequirementRef>,
     ) -> OSStatus;
 
+    pub fn SecCodeCopyPath(
+        code: SecStaticCodeRef,
+        flags: SecCSFlags,
+        path: Option<&mut CFURLRef>,
+    ) -> OSStatus;
+
     pub fn SecCodeCopySigningInformation(
         code: SecStaticCodeRef,
         flags: SecCSFlags,
         information: Option<&mut CFDictionaryRef>,
     ) -> OSStatus;
 
+    pub fn SecTrustCopyResult(trust: SecTrustRef) -> CFDictionaryRef;
+
+    pub fn SecTrustGetCertificateCount(trust: SecTrustRef) -> isize;
+
+    pub fn SecTrustGetCertificateAtIndex(trust: SecTrustRef, index: isize) -> SecCertificateRef;
+
+    pub fn SecTrustCreateWithCertificates(
+        certificates: CFArrayRef,
+        policies: SecPolicyRef,
+        trust: Option<&mut SecTrustRef>,
+    ) -> OSStatus;
+
+    pub fn SecTrustSetAnchorCertificates(trust: SecTrustRef, anchors: CFArrayRef) -> OSStatus;
+
+    pub fn SecTrustSetAnchorCertificatesOnly(trust: SecTrustRef, only: u8) -> OSStatus;
+
+    pub fn SecTrustSetVerifyDate(trust: SecTrustRef, verify_date: CFDateRef) -> OSStatus;
+
+    pub fn SecTrustEvaluateWithError(trust: SecTrustRef, error: Option<&mut CFErrorRef>) -> bool;
+
+    pub fn SecTrustCopyAnchorCertificates(anchors: &mut CFArrayRef) -> OSStatus;
+
+    pub fn SecPolicyCreateBasicX509() -> SecPolicyRef;
+
+    pub fn SecCertificateCreateWithData(
+        allocator: *const std::ffi::c_void,
+        data: CFDataRef,
+    ) -> SecCertificateRef;
+
+    pub fn CMSDecoderCreate(decoder: Option<&mut CMSDecoderRef>) -> OSStatus;
+
+    pub fn CMSDecoderUpdateMessage(
+        decoder: CMSDecoderRef,
+        msg_bytes: *const u8,
+        msg_bytes_len: usize,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderFinalizeMessage(decoder: CMSDecoderRef) -> OSStatus;
+
+    pub fn CMSDecoderCopyContent(
+        decoder: CMSDecoderRef,
+        content: Option<&mut CFDataRef>,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderSetDetachedContent(
+        decoder: CMSDecoderRef,
+        detached_content: CFDataRef,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderGetNumSigners(
+        decoder: CMSDecoderRef,
+        num_signers: Option<&mut usize>,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderCopySignerStatus(
+        decoder: CMSDecoderRef,
+        signer_index: usize,
+        policy_or_array: SecPolicyRef,
+        evaluate_sec_trust: u8,
+        signer_status: Option<&mut u32>,
+        sec_trust: Option<&mut SecTrustRef>,
+        cert_verify_result_code: Option<&mut OSStatus>,
+    ) -> OSStatus;
+
     // pub fn CFErrorCopyUserInfo(err: CFErrorRef) -> CFDictionaryRef;
 
     pub fn SecCertificateCopyData(certificate: SecCertificateRef) -> CFDataRef;
@@ -116,11 +376,28 @@ extern "C" {
     ) -> CFDictionaryRef;
 
     pub static kSecGuestAttributePid: CFStringRef;
+    pub static kSecGuestAttributeAudit: CFStringRef;
+    pub static kSecCodeAttributeArchitecture: CFStringRef;
     pub static kSecCodeInfoCertificates: CFStringRef;
     pub static kSecCodeInfoTeamIdentifier: CFStringRef;
+    pub static kSecCodeInfoIdentifier: CFStringRef;
     pub static kSecCodeInfoPList: CFStringRef;
+    pub static kSecCodeInfoMainExecutable: CFStringRef;
     pub static kSecCodeInfoUnique: CFStringRef;
+    pub static kSecCodeInfoFlags: CFStringRef;
+    pub static kSecCodeInfoStatus: CFStringRef;
+    pub static kSecCodeInfoTime: CFStringRef;
+    pub static kSecCodeInfoTimestamp: CFStringRef;
+    pub static kSecCodeInfoRuntimeVersion: CFStringRef;
+    pub static kSecCodeInfoTrust: CFStringRef;
+    pub static kSecTrustResultDetails: CFStringRef;
+    pub static kSecTrustRevocationChecked: CFStringRef;
+    pub static kSecTrustRevocationValidUntilDate: CFStringRef;
+    pub static kSecCFErrorRequirementSyntax: CFStringRef;
     pub static kSecCodeInfoPlatformIdentifier: CFStringRef;
+    pub static kSecCodeInfoEntitlementsDict: CFStringRef;
+    #[cfg(feature = "notarization")]
+    pub static kSecCodeInfoDigestAlgorithm: CFStringRef;
 
     pub static kSecPropertyKeyValue: CFStringRef;
     pub static kSecPropertyKeyLabel: CFStringRef;
@@ -129,9 +406,25 @@ extern "C" {
     pub static kSecOIDX509V1SubjectName: CFStringRef;
     pub static kSecOIDX509V1IssuerName: CFStringRef;
     pub static kSecOIDX509V1SerialNumber: CFStringRef;
+    pub static kSecOIDX509V1ValidityNotBefore: CFStringRef;
+    pub static kSecOIDX509V1ValidityNotAfter: CFStringRef;
 
     pub static kSecOIDCountryName: CFStringRef;
     pub static kSecOIDCommonName: CFStringRef;
     pub static kSecOIDOrganizationalUnitName: CFStringRef;
     pub static kSecOIDOrganizationName: CFStringRef;
 }
+
+/// Look up a string constant that only newer versions of the Security framework export,
+/// linking against those directly would fail to load on older systems
+pub fn weak_string_constant(name: &str) -> Option<CFString> {
+    let name = std::ffi::CString::new(name).ok()?;
+
+    unsafe {
+        let symbol = dlsym(RTLD_DEFAULT, name.as_ptr()) as *const CFStringRef;
+        if symbol.is_null() || (*symbol).is_null() {
+            return None;
+        }
+        Some(CFString::wrap_under_get_rule(*symbol))
+    }
+}
@@ -0,0 +1,166 @@
+//!
+//! FSEvents streams reporting file changes for the watch module.
+//!
+
+use super::sec_sys::*;
+use crate::Error;
+use std::ffi::{c_char, c_void, CStr, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+type FSEventStreamRef = *mut c_void;
+type DispatchQueueRef = *mut c_void;
+
+type FSEventStreamCallback = extern "C" fn(
+    stream: *const c_void,
+    info: *mut c_void,
+    count: usize,
+    paths: *mut c_void,
+    flags: *const u32,
+    ids: *const u64,
+);
+
+#[repr(C)]
+struct FSEventStreamContext {
+    version: isize,
+    info: *mut c_void,
+    retain: Option<extern "C" fn(*const c_void) -> *const c_void>,
+    release: Option<extern "C" fn(*const c_void)>,
+    copy_description: Option<extern "C" fn(*const c_void) -> CFStringRef>,
+}
+
+const kFSEventStreamEventIdSinceNow: u64 = u64::MAX;
+const kFSEventStreamCreateFlagNoDefer: u32 = 0x02;
+const kFSEventStreamCreateFlagFileEvents: u32 = 0x10;
+const kFSEventStreamEventFlagItemCreated: u32 = 0x100;
+const kFSEventStreamEventFlagItemRenamed: u32 = 0x800;
+const kFSEventStreamEventFlagItemModified: u32 = 0x1000;
+const kFSEventStreamEventFlagItemIsFile: u32 = 0x10000;
+
+/// How long FSEvents coalesces changes before delivering them, in seconds
+const LATENCY: f64 = 0.1;
+
+#[link(name = "CoreServices", kind = "framework")]
+extern "C" {
+    fn FSEventStreamCreate(
+        allocator: *const c_void,
+        callback: FSEventStreamCallback,
+        context: *const FSEventStreamContext,
+        paths: CFArrayRef,
+        since_when: u64,
+        latency: f64,
+        flags: u32,
+    ) -> FSEventStreamRef;
+    fn FSEventStreamSetDispatchQueue(stream: FSEventStreamRef, queue: DispatchQueueRef);
+    fn FSEventStreamStart(stream: FSEventStreamRef) -> u8;
+    fn FSEventStreamStop(stream: FSEventStreamRef);
+    fn FSEventStreamInvalidate(stream: FSEventStreamRef);
+    fn FSEventStreamRelease(stream: FSEventStreamRef);
+}
+
+extern "C" {
+    fn dispatch_queue_create(label: *const c_char, attributes: *const c_void) -> DispatchQueueRef;
+    fn dispatch_release(object: *mut c_void);
+}
+
+/// Forward the files created, modified or renamed into place to the sender in `info`
+extern "C" fn stream_callback(
+    _stream: *const c_void,
+    info: *mut c_void,
+    count: usize,
+    paths: *mut c_void,
+    flags: *const u32,
+    _ids: *const u64,
+) {
+    let events = unsafe { &*(info as *const Sender<PathBuf>) };
+    let paths = paths as *const *const c_char;
+    let changed = kFSEventStreamEventFlagItemCreated
+        | kFSEventStreamEventFlagItemRenamed
+        | kFSEventStreamEventFlagItemModified;
+
+    for i in 0..count {
+        let flags = unsafe { *flags.add(i) };
+        if flags & kFSEventStreamEventFlagItemIsFile == 0 || flags & changed == 0 {
+            continue;
+        }
+        let path = unsafe { CStr::from_ptr(*paths.add(i)) };
+        // A closed receiver means the watch is being stopped
+        let _ = events.send(PathBuf::from(OsStr::from_bytes(path.to_bytes())));
+    }
+}
+
+/// Drop the sender once the stream no longer needs it
+extern "C" fn release_sender(info: *const c_void) {
+    drop(unsafe { Box::from_raw(info as *mut Sender<PathBuf>) });
+}
+
+/// A running FSEvents stream, stopped when dropped
+pub struct DirectoryWatch {
+    stream: FSEventStreamRef,
+    queue: DispatchQueueRef,
+}
+
+// The stream is only touched again to stop it, which FSEvents allows from any thread
+unsafe impl Send for DirectoryWatch {}
+
+impl Drop for DirectoryWatch {
+    fn drop(&mut self) {
+        unsafe {
+            FSEventStreamStop(self.stream);
+            FSEventStreamInvalidate(self.stream);
+            FSEventStreamRelease(self.stream);
+            dispatch_release(self.queue);
+        }
+    }
+}
+
+/// Send the path of every file created or modified under the directories, recursively
+pub fn watch(directories: &[PathBuf], events: Sender<PathBuf>) -> Result<DirectoryWatch, Error> {
+    let paths = directories
+        .iter()
+        .map(|directory| CFString::new(&directory.to_string_lossy()))
+        .collect::<Vec<_>>();
+    let paths = CFArray::from_CFTypes(&paths);
+
+    let context = FSEventStreamContext {
+        version: 0,
+        info: Box::into_raw(Box::new(events)) as *mut c_void,
+        retain: None,
+        release: Some(release_sender),
+        copy_description: None,
+    };
+
+    unsafe {
+        let stream = FSEventStreamCreate(
+            std::ptr::null(),
+            stream_callback,
+            &context,
+            paths.as_concrete_TypeRef(),
+            kFSEventStreamEventIdSinceNow,
+            LATENCY,
+            kFSEventStreamCreateFlagFileEvents | kFSEventStreamCreateFlagNoDefer,
+        );
+        if stream.is_null() {
+            release_sender(context.info);
+            return Err(Error::IoError(std::io::Error::other(
+                "the FSEvents stream couldn't be created",
+            )));
+        }
+
+        // Callbacks run one at a time on a serial queue of their own
+        let queue = dispatch_queue_create(
+            b"codesign-verify.watch\0".as_ptr() as *const c_char,
+            std::ptr::null(),
+        );
+        FSEventStreamSetDispatchQueue(stream, queue);
+        let watch = DirectoryWatch { stream, queue };
+
+        if FSEventStreamStart(stream) == 0 {
+            return Err(Error::IoError(std::io::Error::other(
+                "the FSEvents stream couldn't be started",
+            )));
+        }
+        Ok(watch)
+    }
+}
@@ -0,0 +1,65 @@
+//!
+//! Reading extended attributes for the quarantine module.
+//!
+
+use crate::Error;
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// The attribute doesn't exist
+const ENOATTR: i32 = 93;
+/// The attribute grew between reading its size and its value
+const ERANGE: i32 = 34;
+
+extern "C" {
+    fn getxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *mut c_void,
+        size: usize,
+        position: u32,
+        options: c_int,
+    ) -> isize;
+}
+
+/// The value of the named attribute of the file, None when it has no such attribute
+pub fn read(path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::InvalidPath)?;
+    let name = CString::new(name).map_err(|_| Error::InvalidPath)?;
+
+    loop {
+        let len = unsafe { getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0, 0, 0) };
+        if len < 0 {
+            return missing(std::io::Error::last_os_error());
+        }
+
+        let mut value = vec![0u8; len as usize];
+        let read = unsafe {
+            getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as _,
+                value.len(),
+                0,
+                0,
+            )
+        };
+        if read < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERANGE) {
+                continue;
+            }
+            return missing(err);
+        }
+        value.truncate(read as usize);
+        return Ok(Some(value));
+    }
+}
+
+fn missing(err: std::io::Error) -> Result<Option<Vec<u8>>, Error> {
+    match err.raw_os_error() {
+        Some(ENOATTR) => Ok(None),
+        _ => Err(Error::IoError(err)),
+    }
+}
@@ -0,0 +1,98 @@
+//!
+//! Online notarization ticket lookup.
+//!
+//! Gatekeeper normally reads the notarization ticket stapled to a bundle, and
+//! falls back to asking Apple's ticket delivery service when none is present.
+//! This module performs the same online lookup, keyed on the code directory hash.
+//!
+
+use crate::Error;
+
+const LOOKUP_URL: &str =
+    "https://api.apple-cloudkit.com/database/1/com.apple.gk.ticket-delivery/production/public/records/lookup";
+
+/// The code directory hash type used in the ticket record name (`kSecCodeSignatureHashSHA256`)
+const HASH_TYPE_SHA256: u32 = 2;
+
+///
+/// The notarization state Apple reports for a given cdhash
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotarizationStatus {
+    Notarized,    // A valid ticket exists for the code directory
+    Revoked,      // A ticket existed, but Apple has since revoked it
+    NotNotarized, // Apple has no ticket for the code directory
+}
+
+/// Query Apple's ticket lookup service for the given cdhash of a SHA-256 code directory.
+/// The cdhash is hex encoded and, like every cdhash, truncated to 20 bytes (40 hex digits),
+/// as reported in the `cd_hash` additional property on macOS.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::notarization::{lookup_cd_hash, NotarizationStatus};
+///
+/// let status = lookup_cd_hash("AEE97B850A12F9CAC3EC399094071CAD63325818").unwrap();
+/// assert_eq!(status, NotarizationStatus::Notarized);
+/// ```
+pub fn lookup_cd_hash(cd_hash: &str) -> Result<NotarizationStatus, Error> {
+    lookup(cd_hash, HASH_TYPE_SHA256)
+}
+
+/// Query Apple's ticket lookup service for a cdhash of an explicit hash type
+/// (1 for SHA-1, 2 for SHA-256).
+pub fn lookup(cd_hash: &str, hash_type: u32) -> Result<NotarizationStatus, Error> {
+    let record_name = format!("2/{}/{}", hash_type, cd_hash.to_ascii_lowercase());
+
+    let response: serde_json::Value = ureq::post(LOOKUP_URL)
+        .send_json(serde_json::json!({ "records": [{ "recordName": record_name }] }))
+        .map_err(|e| Error::HttpError(Box::new(e)))?
+        .into_json()
+        .map_err(|e| Error::HttpError(Box::new(e.into())))?;
+
+    let record = response["records"].get(0).ok_or(Error::InvalidResponse)?;
+
+    Ok(status_from_record(record))
+}
+
+fn status_from_record(record: &serde_json::Value) -> NotarizationStatus {
+    if record["serverErrorCode"] == "NOT_FOUND" {
+        NotarizationStatus::NotNotarized
+    } else if record["deleted"] == true || record["fields"]["signedTicket"].is_null() {
+        // Revoked tickets are kept as tombstones with the ticket data removed
+        NotarizationStatus::Revoked
+    } else {
+        NotarizationStatus::Notarized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_status() {
+        let not_found = serde_json::json!({
+            "recordName": "2/2/aee97b850a12f9cac3ec399094071cad63325818",
+            "reason": "Record not found",
+            "serverErrorCode": "NOT_FOUND"
+        });
+        assert_eq!(
+            status_from_record(&not_found),
+            NotarizationStatus::NotNotarized
+        );
+
+        let found = serde_json::json!({
+            "recordName": "2/2/aee97b850a12f9cac3ec399094071cad63325818",
+            "fields": { "signedTicket": { "type": "BYTES", "value": "czhjaAEAAAA=" } }
+        });
+        assert_eq!(status_from_record(&found), NotarizationStatus::Notarized);
+
+        let revoked = serde_json::json!({
+            "recordName": "2/2/aee97b850a12f9cac3ec399094071cad63325818",
+            "deleted": true
+        });
+        assert_eq!(status_from_record(&revoked), NotarizationStatus::Revoked);
+    }
+}
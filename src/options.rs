@@ -0,0 +1,484 @@
+use crate::audit::{AuditSink, SharedSink};
+use crate::{Error, Requirement};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+///
+/// Which certificates to check for revocation
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationMode {
+    None,           // Don't check revocation at all
+    EndCertificate, // Only check the leaf certificate, the Windows default
+    WholeChain,     // Check every certificate in the chain
+}
+
+///
+/// Which architectures of a universal binary to verify
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Architecture {
+    All,           // Every architecture slice, the default
+    Native,        // Only the slice that would run on this machine
+    Named(String), // Only the named slice, e.g. "arm64" or "x86_64"
+}
+
+///
+/// How the signature's timestamp is taken into account when certificates expire
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampPolicy {
+    Default,         // The platform default, timestamped signatures outlive their certificate
+    LifetimeSigning, // Certificates must be valid now, the timestamp is ignored
+    AllowExpiredIfTimestamped, // Expired certificates are only accepted if they were valid at a trusted timestamp
+    ShortLived, // Certificates that expire within days, like Azure Trusted Signing's, are only valid with an RFC 3161 timestamp
+}
+
+///
+/// How symbolic links in a path are treated before the file is opened for verification
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    Follow,  // Pass the path on as given and let the platform follow any links, the default
+    Resolve, // Resolve the path to its canonical form first, so the file checked is fixed up front
+    Reject,  // Fail with `Error::SymlinkRejected` if any component of the path is a link
+}
+
+///
+/// How much of the code is validated, see `VerificationOptions::mode`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    Thorough,       // The executable, the bundle resources and any nested code, the default
+    ExecutableOnly, // Only the executable's own signature and code, for latency sensitive checks
+}
+
+///
+/// Common requirements, without platform specific requirement strings
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    AnySystemTrusted,   // Any chain to a root trusted by the system
+    AppleAnchored,      // A certificate issued by Apple, including Developer ID, macOS only
+    MicrosoftRootsOnly, // A chain to a Microsoft product root, Windows only
+    StoreSigned,        // Signed by the Mac App Store, or the Microsoft Store
+}
+
+///
+/// Lets another thread abort verifications in progress, see `VerificationOptions::cancellation`.
+/// Clones share the same state, so cancelling any of them cancels them all.
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort every verification using this token, they fail with `Error::Cancelled`
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+///
+/// Configures how a signature is verified.
+///
+/// Every option applies to both platforms where the platform has an equivalent, and is
+/// ignored otherwise. `macos_flags` and `windows_provider_flags` pass raw flags straight
+/// to the platform for anything not covered.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::{CodeSignVerifier, RevocationMode, VerificationOptions};
+///
+/// let options = VerificationOptions::new()
+///     .requirement("anchor apple generic")
+///     .revocation(RevocationMode::WholeChain)
+///     .allow_network(false);
+///
+/// CodeSignVerifier::for_file("/Applications/Safari.app").unwrap().verify_with(&options).unwrap();
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct VerificationOptions {
+    pub(crate) requirement: Option<Requirement>,
+    pub(crate) policy: Option<Policy>,
+    pub(crate) revocation: Option<RevocationMode>,
+    pub(crate) allow_network: bool,
+    pub(crate) offline: bool,
+    pub(crate) network_timeout: Option<std::time::Duration>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) nested_code_depth: u32,
+    pub(crate) architecture: Architecture,
+    pub(crate) symlinks: SymlinkPolicy,
+    pub(crate) timestamp_policy: TimestampPolicy,
+    pub(crate) strict: bool,
+    pub(crate) validate_resources: bool,
+    pub(crate) mode: ValidationMode,
+    pub(crate) trust_anchors: Vec<Vec<u8>>,
+    pub(crate) trust_system_roots: bool,
+    pub(crate) verify_time: Option<std::time::SystemTime>,
+    pub(crate) leaf_thumbprint: Option<Vec<u8>>,
+    pub(crate) publisher: Option<String>,
+    pub(crate) audit: Option<SharedSink>,
+    pub(crate) macos_flags: u32,
+    pub(crate) windows_provider_flags: u32,
+}
+
+impl Default for VerificationOptions {
+    fn default() -> Self {
+        VerificationOptions {
+            requirement: None,
+            policy: None,
+            revocation: None,
+            allow_network: true,
+            offline: false,
+            network_timeout: None,
+            cancellation: None,
+            deadline: None,
+            nested_code_depth: 0,
+            architecture: Architecture::All,
+            symlinks: SymlinkPolicy::Follow,
+            timestamp_policy: TimestampPolicy::Default,
+            strict: false,
+            validate_resources: true,
+            mode: ValidationMode::Thorough,
+            trust_anchors: Vec::new(),
+            trust_system_roots: false,
+            verify_time: None,
+            leaf_thumbprint: None,
+            publisher: None,
+            audit: None,
+            macos_flags: 0,
+            windows_provider_flags: 0,
+        }
+    }
+}
+
+impl VerificationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The requirement the code must satisfy, either a `Requirement` or a string in the Apple
+    /// [Code Signing Requirement Language](https://developer.apple.com/library/archive/documentation/Security/Conceptual/CodeSigningGuide/RequirementLang/RequirementLang.html).
+    /// Without one, only the signature itself is validated. On Windows only the certificate
+    /// conditions of a `Requirement` are checked.
+    pub fn requirement<R: Into<Requirement>>(mut self, requirement: R) -> Self {
+        self.requirement = Some(requirement.into()).filter(|r| !r.is_empty());
+        self
+    }
+
+    /// Require one of the built-in policies, in addition to any requirement.
+    /// Policies without an equivalent on the current platform fail with `Error::Unsupported`,
+    /// and code that doesn't satisfy the policy with `Error::RequirementFailed` on Windows.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Select which certificates are checked for revocation.
+    /// On Windows this maps to the `WTD_REVOCATION_CHECK_*` provider flags, with `WholeChain` also
+    /// setting `WTD_REVOKE_WHOLECHAIN`. On macOS revocation is checked on a best effort basis by
+    /// default, and any mode other than `None` enforces it with `kSecCSEnforceRevocationChecks`.
+    pub fn revocation(mut self, mode: RevocationMode) -> Self {
+        self.revocation = Some(mode);
+        self
+    }
+
+    /// Allow fetching CRLs, OCSP responses and other data over the network (the default).
+    /// When disabled only cached revocation data is used, so air-gapped hosts don't wait on
+    /// network timeouts. On Windows this sets `WTD_CACHE_ONLY_URL_RETRIEVAL`, on macOS
+    /// `kSecCSNoNetworkAccess`.
+    pub fn allow_network(mut self, allow: bool) -> Self {
+        self.allow_network = allow;
+        self
+    }
+
+    /// Guarantee verification performs no network I/O at all, for hosts without connectivity.
+    /// Beyond `allow_network(false)`, revocation that can't be determined from cached data
+    /// no longer fails verification, it's reported by `ChainStatus::is_revocation_unknown`
+    /// on `SignatureContext::chain_status` instead. On macOS this means revocation is never
+    /// enforced, regardless of the requested `RevocationMode`.
+    pub fn offline(mut self, enabled: bool) -> Self {
+        self.offline = enabled;
+        self
+    }
+
+    /// Bound the time spent on network retrievals, such as CRL and OCSP lookups, so an
+    /// unreachable responder can't stall verification. Revocation that couldn't be checked
+    /// in time fails verification, unless revocation checking is off.
+    /// On Windows the signature is verified against cached data first, and revocation is
+    /// retrieved with the timeout passed to `CertGetCertificateChain`, failing with
+    /// `Error::ChainFailed`. The Security framework has no such setting, so on macOS the
+    /// signature check is abandoned once the timeout passes, failing with `Error::Timeout`.
+    pub fn network_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.network_timeout = Some(timeout);
+        self
+    }
+
+    /// Abort the verification with `Error::Cancelled` once the token is cancelled.
+    /// Verification is checked for cancellation between its phases and between the nested
+    /// code items of a bundle. On macOS the validity check itself is abandoned as soon as the
+    /// token is cancelled, and left to finish in the background. On Windows a running
+    /// WinVerifyTrust call can't be interrupted, so it only stops once that call returns.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Abort the verification with `Error::Cancelled` once the deadline passes, in the same
+    /// places as a cancelled token. Network retrievals are also bounded by the time left, the
+    /// way `network_timeout` bounds them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, Error, VerificationOptions};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let options = VerificationOptions::new().deadline(Instant::now() + Duration::from_secs(2));
+    ///
+    /// match CodeSignVerifier::for_file("/Applications/Xcode.app").unwrap().verify_with(&options) {
+    ///     Err(Error::Cancelled) => println!("gave up, still verifying"),
+    ///     result => println!("verified: {}", result.is_ok()),
+    /// }
+    /// ```
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Also verify code nested inside a bundle: helper tools, frameworks, plugins and so on.
+    /// Any depth above 0 has macOS check all nested code with `kSecCSCheckNestedCode`, and the
+    /// depth sets how many levels of nesting are verified individually and reported, with the
+    /// results available from `SignatureContext::nested_code`, or in `Error::NestedCode` when
+    /// any of them is invalid. Ignored on Windows.
+    pub fn nested_code_depth(mut self, depth: u32) -> Self {
+        self.nested_code_depth = depth;
+        self
+    }
+
+    /// Select which architectures of a universal binary are verified, all of them by default.
+    /// Ignored on Windows.
+    pub fn architecture(mut self, architecture: Architecture) -> Self {
+        self.architecture = architecture;
+        self
+    }
+
+    /// Select how links in the path are treated, following them like any other path by
+    /// default. This applies where a path is opened with the options: `for_file_with`, the
+    /// builder and `verify_many`. Paths supplied by a less trusted process should be resolved,
+    /// so the file verified is the one reported by `SignatureContext::verified_path`, or
+    /// rejected outright. Rejecting checks every component of the absolute path, so on macOS
+    /// it also rejects paths under `/tmp`, `/var` and `/etc`, which link into `/private`. On
+    /// Windows junctions count as links.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Select how timestamps are taken into account once a certificate expires.
+    /// `LifetimeSigning` sets `WTD_LIFETIME_SIGNING_FLAG` on Windows, and `kSecCSConsiderExpiration`
+    /// on macOS. `AllowExpiredIfTimestamped` is what Windows does by default, on macOS, which
+    /// otherwise ignores expiry, the chain is evaluated again as of the timestamp once the leaf
+    /// certificate expired, and rejected if there is none. `SignatureContext::time_validity`
+    /// reports which case applied. Certificates carrying the lifetime signing EKU (1.3.6.1.4.1.311.10.3.13) are
+    /// always evaluated that way on Windows.
+    ///
+    /// `ShortLived` is for signing services that issue certificates valid for only a few days,
+    /// such as Azure Trusted Signing, where nearly every signature outlives its certificate.
+    /// Signatures must carry a trusted RFC 3161 timestamp, failing with `Error::TimestampRequired`
+    /// otherwise, even while the certificate is still valid, and the chain is evaluated as of
+    /// that timestamp. A legacy Authenticode counter signature doesn't count on Windows.
+    /// `SignatureContext::time_validity` reports `Timestamped` for signatures that are only
+    /// valid because of the timestamp.
+    pub fn timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = policy;
+        self
+    }
+
+    /// Enable strict validation, which additionally rejects bundles with unsealed or
+    /// unexpected content, the same way `codesign --verify --strict` does. Ignored on Windows.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Choose whether the bundle resource envelope is validated (the default), or only the
+    /// executable itself. Skipping resources is much faster for large bundles, but does not
+    /// detect modified resource files. Ignored on Windows.
+    pub fn validate_resources(mut self, enabled: bool) -> Self {
+        self.validate_resources = enabled;
+        self
+    }
+
+    /// Select how much of the code is validated. `Thorough`, the default, validates everything
+    /// the other options ask for. `ExecutableOnly` validates the executable's signature, code
+    /// and certificate chain, and the requirement, but skips the bundle resource envelope,
+    /// nested code, strict validation and the other architectures of a universal binary,
+    /// whatever the other options say.
+    ///
+    /// This is meant for checks on a hot path, such as authorizing each IPC peer, where the
+    /// code is a running process the kernel already validates page by page. It does not
+    /// detect a modified, added or removed resource file, a tampered helper or framework,
+    /// or a tampered slice for another architecture, so it's no substitute for `Thorough` when
+    /// deciding whether to trust a bundle on disk. Ignored on Windows, which has no resources
+    /// or nested code to skip.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{CodeSignVerifier, ValidationMode, VerificationOptions};
+    ///
+    /// let options = VerificationOptions::new()
+    ///     .requirement("anchor apple generic and certificate leaf[subject.OU] = \"ABCDE12345\"")
+    ///     .mode(ValidationMode::ExecutableOnly);
+    ///
+    /// let peer = CodeSignVerifier::for_pid(1234).unwrap().verify_with(&options);
+    /// ```
+    pub fn mode(mut self, mode: ValidationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The nesting depth to verify, none unless validating thoroughly
+    #[cfg(target_os = "macos")]
+    pub(crate) fn effective_nested_code_depth(&self) -> u32 {
+        match self.mode {
+            ValidationMode::Thorough => self.nested_code_depth,
+            ValidationMode::ExecutableOnly => 0,
+        }
+    }
+
+    /// Add a DER encoded root certificate the signing chain must terminate at.
+    /// Once any anchor is added the system trust store is no longer consulted, only a chain
+    /// ending at one of the given roots is accepted, otherwise verification fails with
+    /// `Error::UntrustedRoot`, or `Error::ChainFailed` when no root is reached at all.
+    /// On macOS this uses `SecTrustSetAnchorCertificates`, on Windows a chain engine with
+    /// the anchors as its exclusive roots.
+    ///
+    /// This is how binaries signed by an internal CA are verified: pass the CA certificate,
+    /// or the certificate itself for a self-signed leaf. Private PKIs rarely publish revocation
+    /// information, so a chain ending at an anchor is not checked for revocation unless
+    /// `revocation` is set explicitly.
+    pub fn trust_anchor(mut self, der: &[u8]) -> Self {
+        self.trust_anchors.push(der.to_vec());
+        self
+    }
+
+    /// Keep trusting the system roots next to the anchors given with `trust_anchor`, so code
+    /// signed through either a public or an internal CA is accepted. Off by default, adding an
+    /// anchor opts out of public-root anchoring. Has no effect without anchors.
+    pub fn trust_system_roots(mut self, enabled: bool) -> Self {
+        self.trust_system_roots = enabled;
+        self
+    }
+
+    /// Evaluate the certificate chain as of the given time rather than now, for example the
+    /// signature's timestamp, so archived samples whose certificates have since expired can
+    /// still be validated. On macOS the chain is evaluated again with `SecTrustSetVerifyDate`,
+    /// on Windows it is rebuilt with the time passed to `CertGetCertificateChain`.
+    pub fn verify_time(mut self, time: std::time::SystemTime) -> Self {
+        self.verify_time = Some(time);
+        self
+    }
+
+    /// Pin the sha256 thumbprint of the leaf certificate, as raw bytes.
+    /// A valid signature made with any other certificate fails with `Error::ThumbprintMismatch`.
+    /// The thumbprints are compared in constant time.
+    pub fn leaf_thumbprint(mut self, sha256: &[u8]) -> Self {
+        self.leaf_thumbprint = Some(sha256.to_vec());
+        self
+    }
+
+    /// Require the leaf certificate to be issued to the given publisher, matching either the
+    /// subject organization or common name. Names are compared ignoring case, punctuation and
+    /// spacing, so "Apple, Inc." matches "apple inc". A valid signature from any other publisher
+    /// fails with `Error::PublisherMismatch`.
+    pub fn publisher(mut self, publisher: &str) -> Self {
+        self.publisher = Some(publisher.to_string());
+        self
+    }
+
+    /// Record every verification made with these options, successful or not, to the sink.
+    /// See the `audit` module.
+    pub fn audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(SharedSink(sink));
+        self
+    }
+
+    /// Additional raw `SecCSFlags` passed to the macOS validity check
+    pub fn macos_flags(mut self, flags: u32) -> Self {
+        self.macos_flags = flags;
+        self
+    }
+
+    /// Additional raw `WTD_*` provider flags passed to WinVerifyTrust on Windows
+    pub fn windows_provider_flags(mut self, flags: u32) -> Self {
+        self.windows_provider_flags = flags;
+        self
+    }
+
+    /// Apply the symlink policy to a path about to be opened
+    pub(crate) fn resolve_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        match self.symlinks {
+            SymlinkPolicy::Follow => Ok(path.to_path_buf()),
+            SymlinkPolicy::Resolve => std::fs::canonicalize(path).map_err(Error::IoError),
+            SymlinkPolicy::Reject => {
+                let path = std::env::current_dir().map_err(Error::IoError)?.join(path);
+                // Components that don't exist can't be links, opening the path fails later
+                let link = path.ancestors().find(|ancestor| {
+                    std::fs::symlink_metadata(ancestor)
+                        .map_or(false, |metadata| metadata.file_type().is_symlink())
+                });
+                match link {
+                    Some(link) => Err(Error::SymlinkRejected(link.to_path_buf())),
+                    None => Ok(path),
+                }
+            }
+        }
+    }
+
+    /// Whether verification can be aborted midway, by a token or a deadline
+    pub(crate) fn is_cancellable(&self) -> bool {
+        self.cancellation.is_some() || self.deadline.is_some()
+    }
+
+    /// Fail with `Error::Cancelled` once the token is cancelled or the deadline passed
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        let cancelled = self
+            .cancellation
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled);
+        let expired = self
+            .deadline
+            .map_or(false, |deadline| Instant::now() >= deadline);
+        if cancelled || expired {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// The time network retrievals may take, the network timeout or the time left before the
+    /// deadline, whichever is shorter
+    pub(crate) fn network_budget(&self) -> Option<Duration> {
+        let left = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match (self.network_timeout, left) {
+            (Some(timeout), Some(left)) => Some(timeout.min(left)),
+            (timeout, left) => timeout.or(left),
+        }
+    }
+}
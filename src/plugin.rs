@@ -0,0 +1,145 @@
+//!
+//! Verifying plugins before they are loaded.
+//!
+//! A `PluginVerifier` checks a dylib or DLL against the signer a host expects its plugins from,
+//! by default the host's own, before it's passed to `dlopen` or `LoadLibrary`. This mimics
+//! library validation where the OS doesn't enforce it: on Windows, and for macOS hosts without
+//! the hardened runtime.
+//!
+//! The check reads the file by path, so a plugin directory writable by less privileged users
+//! can still have a file swapped between the check and the load.
+//!
+
+use crate::{CodeSignVerifier, Error, PlatformProperties, SignatureContext, VerificationOptions};
+use std::path::Path;
+
+///
+/// The signer plugins are expected from
+///
+#[derive(Clone)]
+pub enum ExpectedSigner {
+    Host(SignatureContext), // The host's publisher, see `SignatureContext::same_publisher`
+    TeamId(String),         // The team identifier, macOS only
+    Thumbprint([u8; 32]),   // The sha256 thumbprint of the leaf certificate
+}
+
+///
+/// Whether a plugin may be loaded
+///
+pub enum PluginDecision {
+    Load(SignatureContext), // The plugin is validly signed by the expected signer
+    WrongSigner(SignatureContext), // The signature is valid, but from another signer
+    Unsigned,               // The plugin carries no signature
+    Invalid(Error),         // The signature failed verification
+}
+
+impl PluginDecision {
+    pub fn should_load(&self) -> bool {
+        matches!(self, PluginDecision::Load(_))
+    }
+}
+
+///
+/// Checks plugins against the signer the host expects
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::plugin::PluginVerifier;
+///
+/// let plugins = PluginVerifier::for_host().unwrap();
+/// let path = "/Library/Application Support/Tool/PlugIns/libextra.dylib";
+/// if plugins.check(path).should_load() {
+///     // dlopen(path)
+/// }
+/// ```
+///
+#[derive(Clone)]
+pub struct PluginVerifier {
+    expected: ExpectedSigner,
+    options: VerificationOptions,
+}
+
+impl PluginVerifier {
+    /// Expect plugins from the publisher of the running executable, verifying it once now
+    pub fn for_host() -> Result<Self, Error> {
+        let path = std::env::current_exe().map_err(Error::IoError)?;
+        let host = CodeSignVerifier::for_file(path)?.verify_with(&VerificationOptions::new())?;
+        Ok(PluginVerifier::new(ExpectedSigner::Host(host)))
+    }
+
+    /// Expect plugins from the given signer
+    pub fn new(expected: ExpectedSigner) -> Self {
+        PluginVerifier {
+            expected,
+            options: VerificationOptions::new(),
+        }
+    }
+
+    /// Verify each plugin with the given options
+    pub fn options(mut self, options: VerificationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Verify the plugin, and decide whether it may be loaded
+    pub fn check<P: AsRef<Path>>(&self, path: P) -> PluginDecision {
+        let ctx = match CodeSignVerifier::for_file(path).and_then(|v| v.verify_with(&self.options))
+        {
+            Ok(ctx) => ctx,
+            Err(err) if err.is_no_signature() => return PluginDecision::Unsigned,
+            Err(err) => return PluginDecision::Invalid(err),
+        };
+
+        let matches = match &self.expected {
+            ExpectedSigner::Host(host) => host.same_publisher(&ctx),
+            ExpectedSigner::TeamId(team_id) => match ctx.platform_properties() {
+                PlatformProperties::Mac(mac) => mac.team_id.as_ref() == Some(team_id),
+                PlatformProperties::Windows(_) => false,
+            },
+            ExpectedSigner::Thumbprint(thumbprint) => {
+                crate::constant_time_eq(thumbprint, &ctx.thumbprint_bytes())
+            }
+        };
+
+        if matches {
+            PluginDecision::Load(ctx)
+        } else {
+            PluginDecision::WrongSigner(ctx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpectedSigner, PluginDecision, PluginVerifier};
+    use crate::CodeSignVerifier;
+
+    #[test]
+    fn test_plugin() {
+        let path = crate::signed_system_binary();
+
+        let ctx = CodeSignVerifier::for_file(path)
+            .unwrap()
+            .verify("")
+            .unwrap();
+        let thumbprint = ctx.thumbprint_bytes();
+
+        let plugins = PluginVerifier::new(ExpectedSigner::Thumbprint(thumbprint));
+        assert!(plugins.check(path).should_load());
+        let plugins = PluginVerifier::new(ExpectedSigner::Host(ctx));
+        assert!(plugins.check(path).should_load());
+
+        let plugins = PluginVerifier::new(ExpectedSigner::TeamId("ABCDE12345".to_string()));
+        assert!(matches!(
+            plugins.check(path),
+            PluginDecision::WrongSigner(_)
+        ));
+
+        let unsigned = crate::temp_path();
+        std::fs::write(&unsigned, "#!/bin/sh\n").unwrap();
+        let decision = plugins.check(&unsigned);
+        std::fs::remove_file(&unsigned).unwrap();
+        assert!(!decision.should_load());
+    }
+}
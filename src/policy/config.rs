@@ -0,0 +1,394 @@
+//!
+//! Loading rulesets from JSON and TOML files.
+//!
+//! Both formats describe the same document: a default action and an ordered list of rules.
+//! Each rule has a name, an action, and the conditions it matches on. A rule with several
+//! conditions only matches when they all do, and `all`, `any` and `not` nest further.
+//!
+//! ```toml
+//! default = "deny"
+//!
+//! [[rules]]
+//! name = "leaked certificate"
+//! action = "deny"
+//! thumbprint = "0c1e5d7b..."
+//!
+//! [[rules]]
+//! name = "vendor"
+//! action = "allow"
+//! team_id = "ABCDE12345"
+//! notarized = true
+//!
+//! [[rules]]
+//! name = "drivers"
+//! action = "allow"
+//! any = [{ eku = "1.3.6.1.4.1.311.10.3.5" }, { organization = "Microsoft Corporation" }]
+//! ```
+//!
+//! Documents are checked against this layout as they are read, and errors name the offending
+//! key, e.g. `rules[1].thumbprint: expected 64 hex digits`.
+//!
+
+use super::{Action, Condition, Rule, Ruleset};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// The keys a condition can be written with
+const CONDITION_KEYS: &[&str] = &[
+    "team_id",
+    "identifier",
+    "thumbprint",
+    "organization",
+    "common_name",
+    "issuer",
+    "eku",
+    "notarized",
+    "all",
+    "any",
+    "not",
+];
+
+///
+/// Why a ruleset couldn't be loaded
+///
+#[derive(Debug)]
+pub enum PolicyError {
+    Io(std::io::Error), // Reading the file failed
+    Syntax(String),     // The file isn't valid JSON or TOML
+    Schema {
+        path: String,    // Where in the document the problem is, e.g. `rules[2].action`
+        message: String, // What is wrong with it
+    },
+    UnknownFormat, // The file extension is neither .json nor .toml
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::Io(err) => write!(f, "{}", err),
+            PolicyError::Syntax(message) => write!(f, "{}", message),
+            PolicyError::Schema { path, message } => write!(f, "{}: {}", path, message),
+            PolicyError::UnknownFormat => {
+                write!(f, "policy files must have a .json or .toml extension")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PolicyError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn schema_error(path: &str, message: &str) -> PolicyError {
+    PolicyError::Schema {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str, PolicyError> {
+    value
+        .as_str()
+        .ok_or_else(|| schema_error(path, "expected a string"))
+}
+
+fn action(value: &Value, path: &str) -> Result<Action, PolicyError> {
+    match string(value, path)? {
+        "allow" => Ok(Action::Allow),
+        "deny" => Ok(Action::Deny),
+        other => Err(schema_error(
+            path,
+            &format!("expected \"allow\" or \"deny\", found \"{}\"", other),
+        )),
+    }
+}
+
+fn thumbprint(value: &Value, path: &str) -> Result<String, PolicyError> {
+    let thumbprint = string(value, path)?;
+    if thumbprint.len() != 64 || !thumbprint.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(schema_error(path, "expected 64 hex digits"));
+    }
+    Ok(thumbprint.to_string())
+}
+
+fn oid(value: &Value, path: &str) -> Result<String, PolicyError> {
+    let oid = string(value, path)?;
+    let valid = oid.split('.').count() >= 2
+        && oid
+            .split('.')
+            .all(|arc| !arc.is_empty() && arc.chars().all(|c| c.is_ascii_digit()));
+    if !valid {
+        return Err(schema_error(
+            path,
+            "expected a dotted OID, e.g. 1.3.6.1.5.5.7.3.3",
+        ));
+    }
+    Ok(oid.to_string())
+}
+
+fn conditions(value: &Value, path: &str) -> Result<Vec<Condition>, PolicyError> {
+    let conditions = value
+        .as_array()
+        .ok_or_else(|| schema_error(path, "expected a list of conditions"))?;
+    if conditions.is_empty() {
+        return Err(schema_error(path, "expected at least one condition"));
+    }
+    conditions
+        .iter()
+        .enumerate()
+        .map(|(i, condition)| {
+            let path = format!("{}[{}]", path, i);
+            let table = condition
+                .as_object()
+                .ok_or_else(|| schema_error(&path, "expected a table of conditions"))?;
+            condition_table(table, &path, &[])
+        })
+        .collect()
+}
+
+/// Read the conditions in a table into one condition, skipping the keys that aren't conditions
+fn condition_table(
+    table: &Map<String, Value>,
+    path: &str,
+    other_keys: &[&str],
+) -> Result<Condition, PolicyError> {
+    let mut all = Vec::new();
+
+    for (key, value) in table {
+        let path = format!("{}.{}", path, key);
+        let condition = match key.as_str() {
+            "team_id" => Condition::TeamId(string(value, &path)?.to_string()),
+            "identifier" => Condition::Identifier(string(value, &path)?.to_string()),
+            "thumbprint" => Condition::Thumbprint(thumbprint(value, &path)?),
+            "organization" => Condition::Organization(string(value, &path)?.to_string()),
+            "common_name" => Condition::CommonName(string(value, &path)?.to_string()),
+            "issuer" => Condition::Issuer(string(value, &path)?.to_string()),
+            "eku" => Condition::Eku(oid(value, &path)?),
+            "notarized" => Condition::Notarized(
+                value
+                    .as_bool()
+                    .ok_or_else(|| schema_error(&path, "expected true or false"))?,
+            ),
+            "all" => Condition::All(conditions(value, &path)?),
+            "any" => Condition::Any(conditions(value, &path)?),
+            "not" => {
+                let table = value
+                    .as_object()
+                    .ok_or_else(|| schema_error(&path, "expected a table of conditions"))?;
+                Condition::Not(Box::new(condition_table(table, &path, &[])?))
+            }
+            key if other_keys.contains(&key) => continue,
+            key => {
+                let expected = [other_keys, CONDITION_KEYS].concat().join(", ");
+                return Err(schema_error(
+                    &path,
+                    &format!("unknown key `{}`, expected one of {}", key, expected),
+                ));
+            }
+        };
+        all.push(condition);
+    }
+
+    match all.len() {
+        0 => Err(schema_error(
+            path,
+            &format!("expected a condition, one of {}", CONDITION_KEYS.join(", ")),
+        )),
+        1 => Ok(all.remove(0)),
+        _ => Ok(Condition::All(all)),
+    }
+}
+
+fn rule(value: &Value, path: &str) -> Result<Rule, PolicyError> {
+    let table = value
+        .as_object()
+        .ok_or_else(|| schema_error(path, "expected a table"))?;
+    let field = |key: &str| {
+        table
+            .get(key)
+            .ok_or_else(|| schema_error(path, &format!("missing `{}`", key)))
+    };
+
+    Ok(Rule {
+        name: string(field("name")?, &format!("{}.name", path))?.to_string(),
+        action: action(field("action")?, &format!("{}.action", path))?,
+        condition: condition_table(table, path, &["name", "action"])?,
+    })
+}
+
+/// Check a parsed document against the ruleset layout
+fn ruleset(document: &Value) -> Result<Ruleset, PolicyError> {
+    let table = document
+        .as_object()
+        .ok_or_else(|| schema_error("document", "expected a table"))?;
+    if let Some(key) = table
+        .keys()
+        .find(|key| *key != "default" && *key != "rules")
+    {
+        return Err(schema_error(
+            key,
+            "unknown key, expected `default` or `rules`",
+        ));
+    }
+
+    let default = match table.get("default") {
+        Some(default) => action(default, "default")?,
+        None => {
+            return Err(schema_error(
+                "default",
+                "missing, set it to \"allow\" or \"deny\"",
+            ))
+        }
+    };
+    let rules = match table.get("rules") {
+        Some(rules) => rules
+            .as_array()
+            .ok_or_else(|| schema_error("rules", "expected a list of rules"))?
+            .iter()
+            .enumerate()
+            .map(|(i, value)| rule(value, &format!("rules[{}]", i)))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    // Decisions report the rule by name, so two rules with the same name would be ambiguous
+    for (i, rule) in rules.iter().enumerate() {
+        if rules[..i].iter().any(|earlier| earlier.name == rule.name) {
+            return Err(schema_error(
+                &format!("rules[{}].name", i),
+                &format!("duplicate rule name \"{}\"", rule.name),
+            ));
+        }
+    }
+
+    Ok(Ruleset { rules, default })
+}
+
+impl Ruleset {
+    /// Read a ruleset from a JSON document
+    pub fn from_json(json: &str) -> Result<Self, PolicyError> {
+        let document: Value =
+            serde_json::from_str(json).map_err(|err| PolicyError::Syntax(err.to_string()))?;
+        ruleset(&document)
+    }
+
+    /// Read a ruleset from a TOML document
+    pub fn from_toml(document: &str) -> Result<Self, PolicyError> {
+        let document: toml::Table =
+            toml::from_str(document).map_err(|err| PolicyError::Syntax(err.to_string()))?;
+        // TOML tables and arrays map one to one onto JSON's, and the layout needs no dates
+        let document =
+            serde_json::to_value(document).map_err(|err| PolicyError::Syntax(err.to_string()))?;
+        ruleset(&document)
+    }
+
+    /// Read a ruleset from a `.json` or `.toml` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PolicyError> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ruleset::from_json,
+            Some("toml") => Ruleset::from_toml,
+            _ => return Err(PolicyError::UnknownFormat),
+        };
+        parse(&std::fs::read_to_string(path).map_err(PolicyError::Io)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_rules() {
+        let json = r#"{
+            "default": "deny",
+            "rules": [
+                {"name": "vendor", "action": "allow", "team_id": "ABCDE12345", "notarized": true},
+                {"name": "drivers", "action": "allow", "not": {"eku": "1.3.6.1.4.1.311.10.3.5"}},
+                {"name": "tool", "action": "allow", "identifier": "com.example.tool"}
+            ]
+        }"#;
+        let toml = r#"
+            default = "deny"
+
+            [[rules]]
+            name = "vendor"
+            action = "allow"
+            team_id = "ABCDE12345"
+            notarized = true
+
+            [[rules]]
+            name = "drivers"
+            action = "allow"
+            not = { eku = "1.3.6.1.4.1.311.10.3.5" }
+
+            [[rules]]
+            name = "tool"
+            action = "allow"
+            identifier = "com.example.tool"
+        "#;
+
+        let rules = Ruleset::from_json(json).unwrap();
+        assert_eq!(rules, Ruleset::from_toml(toml).unwrap());
+        assert_eq!(rules.default, Action::Deny);
+        assert_eq!(
+            rules.rules()[0].condition,
+            Condition::All(vec![
+                Condition::Notarized(true),
+                Condition::TeamId("ABCDE12345".to_string())
+            ])
+        );
+        assert_eq!(
+            rules.rules()[1].condition,
+            Condition::Not(Box::new(Condition::Eku(
+                "1.3.6.1.4.1.311.10.3.5".to_string()
+            )))
+        );
+        assert_eq!(
+            rules.rules()[2].condition,
+            Condition::Identifier("com.example.tool".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        let error = |json| Ruleset::from_json(json).unwrap_err().to_string();
+
+        assert_eq!(
+            error(
+                r#"{"default": "deny", "rules": [{"name": "a", "action": "allow", "team": "x"}]}"#
+            ),
+            "rules[0].team: unknown key `team`, expected one of name, action, team_id, \
+             identifier, thumbprint, organization, common_name, issuer, eku, notarized, all, \
+             any, not"
+        );
+        assert_eq!(
+            error(
+                r#"{"default": "deny", "rules": [{"name": "a", "action": "allow", "thumbprint": "00"}]}"#
+            ),
+            "rules[0].thumbprint: expected 64 hex digits"
+        );
+        assert_eq!(
+            error(r#"{"default": "maybe"}"#),
+            "default: expected \"allow\" or \"deny\", found \"maybe\""
+        );
+        assert_eq!(
+            error(r#"{"default": "deny", "rules": [{"name": "a", "action": "deny"}]}"#),
+            "rules[0]: expected a condition, one of team_id, identifier, thumbprint, \
+             organization, common_name, issuer, eku, notarized, all, any, not"
+        );
+        assert!(matches!(
+            Ruleset::from_json("{"),
+            Err(PolicyError::Syntax(_))
+        ));
+        assert!(matches!(
+            Ruleset::load("rules.yaml"),
+            Err(PolicyError::UnknownFormat)
+        ));
+    }
+}
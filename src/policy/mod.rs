@@ -0,0 +1,264 @@
+//!
+//! Allow and deny rules for verified signatures.
+//!
+//! A `Ruleset` is an ordered list of rules, each matching a condition on the verification
+//! report and deciding to allow or deny. The first rule that matches decides, and the
+//! ruleset's default decides when none does, so specific exceptions go before broad rules.
+//!
+//! With the `policy-files` feature, rulesets can also be loaded from JSON or TOML files, see
+//! `Ruleset::load`.
+//!
+
+#[cfg(feature = "policy-files")]
+mod config;
+
+#[cfg(feature = "policy-files")]
+pub use config::PolicyError;
+
+use crate::{PlatformProperties, VerificationReport};
+
+///
+/// What a rule decides for the signatures it matches
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+///
+/// A condition on a verified signature
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    TeamId(String),       // The team identifier of the signature, macOS only
+    Identifier(String),   // The signing identifier of the signature, macOS only
+    Thumbprint(String),   // The hex sha256 thumbprint of the leaf certificate, in either case
+    Organization(String), // The organization the leaf certificate was issued to
+    CommonName(String),   // The common name the leaf certificate was issued to
+    Issuer(String),       // The common name of the leaf certificate's issuer
+    Eku(String),          // The leaf certificate carries the EKU OID, Windows only
+    Notarized(bool),      // Whether Apple notarized the code, if it was looked up
+    All(Vec<Condition>),  // Every one of the conditions
+    Any(Vec<Condition>),  // At least one of the conditions
+    Not(Box<Condition>),  // The condition doesn't match
+}
+
+impl Condition {
+    /// Whether the condition matches the report. `notarized` is the notarization status when
+    /// it was looked up, see `SignatureContext::notarization_status`.
+    pub fn matches(&self, report: &VerificationReport, notarized: Option<bool>) -> bool {
+        match self {
+            Condition::TeamId(team_id) => match &report.platform {
+                PlatformProperties::Mac(mac) => mac.team_id.as_ref() == Some(team_id),
+                PlatformProperties::Windows(_) => false,
+            },
+            Condition::Identifier(identifier) => match &report.platform {
+                PlatformProperties::Mac(mac) => mac.identifier.as_ref() == Some(identifier),
+                PlatformProperties::Windows(_) => false,
+            },
+            Condition::Thumbprint(thumbprint) => {
+                report.sha256_thumbprint.eq_ignore_ascii_case(thumbprint)
+            }
+            Condition::Organization(organization) => {
+                report.subject.organization.as_ref() == Some(organization)
+            }
+            Condition::CommonName(common_name) => {
+                report.subject.common_name.as_ref() == Some(common_name)
+            }
+            Condition::Issuer(common_name) => {
+                report.issuer.common_name.as_ref() == Some(common_name)
+            }
+            Condition::Eku(oid) => match &report.platform {
+                PlatformProperties::Windows(windows) => windows.enhanced_key_usages.contains(oid),
+                PlatformProperties::Mac(_) => false,
+            },
+            Condition::Notarized(expected) => notarized == Some(*expected),
+            Condition::All(conditions) => conditions
+                .iter()
+                .all(|condition| condition.matches(report, notarized)),
+            Condition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.matches(report, notarized)),
+            Condition::Not(condition) => !condition.matches(report, notarized),
+        }
+    }
+}
+
+///
+/// A named condition and what to decide when it matches
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub name: String,
+    pub action: Action,
+    pub condition: Condition,
+}
+
+impl Rule {
+    /// Allow the signatures matching the condition
+    pub fn allow(name: &str, condition: Condition) -> Self {
+        Rule {
+            name: name.to_string(),
+            action: Action::Allow,
+            condition,
+        }
+    }
+
+    /// Deny the signatures matching the condition
+    pub fn deny(name: &str, condition: Condition) -> Self {
+        Rule {
+            name: name.to_string(),
+            action: Action::Deny,
+            condition,
+        }
+    }
+}
+
+///
+/// The outcome of evaluating a ruleset
+///
+/// `action`: Whether the signature is allowed
+///
+/// `rule`: The name of the rule that decided, None when no rule matched and the default applied
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub action: Action,
+    pub rule: Option<String>,
+}
+
+impl Decision {
+    pub fn is_allowed(&self) -> bool {
+        self.action == Action::Allow
+    }
+}
+
+///
+/// An ordered list of rules, where the first match decides
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::policy::{Action, Condition, Rule, Ruleset};
+/// use codesign_verify::CodeSignVerifier;
+///
+/// let rules = Ruleset::new(Action::Deny)
+///     .rule(Rule::deny(
+///         "leaked certificate",
+///         Condition::Thumbprint("0c1e...".to_string()),
+///     ))
+///     .rule(Rule::allow("vendor", Condition::TeamId("ABCDE12345".to_string())));
+///
+/// let ctx = CodeSignVerifier::for_file("/Applications/Tool.app").unwrap().verify("").unwrap();
+/// let decision = rules.evaluate(&ctx.report(), None);
+/// if !decision.is_allowed() {
+///     println!("denied by {:?}", decision.rule);
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    pub(crate) rules: Vec<Rule>,
+    pub(crate) default: Action,
+}
+
+impl Ruleset {
+    /// An empty ruleset, deciding `default` for every signature until rules are added
+    pub fn new(default: Action) -> Self {
+        Ruleset {
+            rules: Vec::new(),
+            default,
+        }
+    }
+
+    /// Add a rule, after the ones already added
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Decide for the report. `notarized` is the notarization status when it was looked up,
+    /// `Condition::Notarized` never matches without it.
+    pub fn evaluate(&self, report: &VerificationReport, notarized: Option<bool>) -> Decision {
+        match self
+            .rules
+            .iter()
+            .find(|rule| rule.condition.matches(report, notarized))
+        {
+            Some(rule) => Decision {
+                action: rule.action,
+                rule: Some(rule.name.clone()),
+            },
+            None => Decision {
+                action: self.default,
+                rule: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodeSignVerifier;
+
+    #[test]
+    fn test_ruleset() {
+        let ctx = crate::verified_system_binary();
+        let report = ctx.report();
+        let thumbprint = report.sha256_thumbprint.to_uppercase();
+        let organization = report.subject.organization.clone().unwrap();
+
+        let rules = Ruleset::new(Action::Deny)
+            .rule(Rule::deny("notarized", Condition::Notarized(true)))
+            .rule(Rule::allow(
+                "vendor",
+                Condition::All(vec![
+                    Condition::Organization(organization),
+                    Condition::Not(Box::new(Condition::Thumbprint("00".repeat(32)))),
+                ]),
+            ));
+        let decision = rules.evaluate(&report, None);
+        assert!(decision.is_allowed());
+        assert_eq!(decision.rule.as_deref(), Some("vendor"));
+        assert_eq!(
+            rules.evaluate(&report, Some(true)).rule.as_deref(),
+            Some("notarized")
+        );
+
+        let rules = Ruleset::new(Action::Allow).rule(Rule::deny(
+            "revoked certificate",
+            Condition::Any(vec![Condition::Thumbprint(thumbprint)]),
+        ));
+        assert_eq!(rules.evaluate(&report, None).action, Action::Deny);
+
+        let decision = Ruleset::new(Action::Deny).evaluate(&report, None);
+        assert_eq!(decision.action, Action::Deny);
+        assert_eq!(decision.rule, None);
+    }
+
+    #[test]
+    fn test_identifier() {
+        let ctx = crate::verified_system_binary();
+        let report = ctx.report();
+
+        // Only macOS signatures have a signing identifier
+        let rules = Ruleset::new(Action::Deny).rule(Rule::allow(
+            "ping",
+            Condition::Identifier("com.apple.ping".to_string()),
+        ));
+        assert_eq!(
+            rules.evaluate(&report, None).is_allowed(),
+            cfg!(target_os = "macos")
+        );
+        assert_eq!(
+            ctx.signing_identifier().is_some(),
+            cfg!(target_os = "macos")
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//!
+//! Progress reporting while streaming large inputs.
+//!
+
+use std::io::Read;
+
+/// Report progress at most once for every this many bytes read
+const REPORT_INTERVAL: u64 = 1024 * 1024;
+
+/// Passes reads through, reporting how much was read so far to a callback
+pub(crate) struct ProgressReader<R, F> {
+    inner: R,
+    progress: F,
+    total: u64,
+    read: u64,
+    reported: Option<u64>, // How much had been read at the last report
+}
+
+impl<R: Read, F: FnMut(u64, u64)> ProgressReader<R, F> {
+    pub(crate) fn new(inner: R, total: u64, progress: F) -> Self {
+        ProgressReader {
+            inner,
+            progress,
+            total,
+            read: 0,
+            reported: None,
+        }
+    }
+
+    fn report(&mut self) {
+        self.reported = Some(self.read);
+        (self.progress)(self.read, self.total.max(self.read));
+    }
+}
+
+impl<R: Read, F: FnMut(u64, u64)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.read += len as u64;
+
+        let since = self.read - self.reported.unwrap_or(0);
+        // The end of the stream is always reported once, even for an empty stream
+        if (len == 0 && self.reported != Some(self.read)) || since >= REPORT_INTERVAL {
+            self.report();
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgressReader, REPORT_INTERVAL};
+    use std::io::Read;
+
+    #[test]
+    fn test_progress() {
+        let total = REPORT_INTERVAL * 5 / 2;
+        let data = std::io::Cursor::new(vec![0u8; total as usize]);
+        let mut reports = Vec::new();
+        let mut reader =
+            ProgressReader::new(data, total, |read, total| reports.push((read, total)));
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(reports.last(), Some(&(total, total)));
+
+        let mut reports = Vec::new();
+        let mut reader = ProgressReader::new(std::io::empty(), 0, |read, total| {
+            reports.push((read, total))
+        });
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(reader.read(&mut [0u8; 16]).unwrap(), 0);
+        assert_eq!(reports, vec![(0, 0)]);
+    }
+}
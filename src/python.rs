@@ -0,0 +1,132 @@
+//!
+//! A Python extension module, enabled with the `python` feature.
+//!
+//! Build it with maturin, or `cargo rustc --release --features python,pyo3/extension-module
+//! --crate-type cdylib` and rename the library to `codesign_verify.so` (`.pyd` on Windows).
+//!
+//! ```text
+//! import codesign_verify
+//!
+//! try:
+//!     report = codesign_verify.verify_path("/Applications/Safari.app", "anchor apple")
+//!     print(report["subject"]["organization"], report["sha256_thumbprint"])
+//! except codesign_verify.VerificationError as err:
+//!     message, kind = err.args
+//! ```
+//!
+//! Reports are dicts with the layout of `VerificationReport::to_json`. A failed verification
+//! raises `VerificationError` with the message and one of `"unsigned"`, `"signature_stripped"`,
+//! `"tampered"`, `"untrusted_root"`, `"expired"`, `"revoked"`, `"requirement_failed"` or
+//! `"other"`.
+//!
+
+use crate::{CodeSignVerifier, Error, ErrorKind, SignatureContext, VerificationOptions};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(codesign_verify, VerificationError, PyException);
+
+fn error_kind(err: &Error) -> &'static str {
+    match err.kind() {
+        ErrorKind::Unsigned => "unsigned",
+        ErrorKind::SignatureStripped => "signature_stripped",
+        ErrorKind::Tampered => "tampered",
+        ErrorKind::UntrustedRoot => "untrusted_root",
+        ErrorKind::Expired => "expired",
+        ErrorKind::Revoked => "revoked",
+        ErrorKind::RequirementFailed => "requirement_failed",
+        ErrorKind::Other => "other",
+    }
+}
+
+/// Verify without holding the GIL, and convert the report into a dict
+fn verify<F>(py: Python<'_>, requirement: Option<&str>, verifier: F) -> PyResult<PyObject>
+where
+    F: FnOnce() -> Result<CodeSignVerifier, Error> + Send,
+{
+    let result = py.allow_threads(|| {
+        let mut options = VerificationOptions::new();
+        if let Some(requirement) = requirement {
+            options = options.requirement(requirement);
+        }
+        verifier().and_then(|verifier| verifier.verify_with(&options))
+    });
+
+    match result {
+        Ok(ctx) => report(py, &ctx),
+        Err(err) => Err(VerificationError::new_err((
+            err.to_string(),
+            error_kind(&err),
+        ))),
+    }
+}
+
+fn report(py: Python<'_>, ctx: &SignatureContext) -> PyResult<PyObject> {
+    // Going through the JSON layout keeps the dict the same as the documented schema
+    let json = py.import_bound("json")?;
+    Ok(json
+        .call_method1("loads", (ctx.report().to_json(),))?
+        .unbind())
+}
+
+/// Verify the file or bundle at the path, against the requirement if one is given
+#[pyfunction]
+#[pyo3(signature = (path, requirement = None))]
+fn verify_path(py: Python<'_>, path: &str, requirement: Option<&str>) -> PyResult<PyObject> {
+    verify(py, requirement, || CodeSignVerifier::for_file(path))
+}
+
+/// Verify the running process with the given PID, against the requirement if one is given
+#[pyfunction]
+#[pyo3(signature = (pid, requirement = None))]
+fn verify_pid(py: Python<'_>, pid: i32, requirement: Option<&str>) -> PyResult<PyObject> {
+    verify(py, requirement, || CodeSignVerifier::for_pid(pid))
+}
+
+#[pymodule]
+fn codesign_verify(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(verify_path, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_pid, m)?)?;
+    m.add(
+        "VerificationError",
+        m.py().get_type_bound::<VerificationError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChainStatus;
+
+    #[test]
+    fn test_error_kind() {
+        let chain = |bits| Error::chain_failed(0, ChainStatus::from_error_status(bits), None);
+
+        assert_eq!(error_kind(&Error::RequirementFailed), "requirement_failed");
+        assert_eq!(error_kind(&Error::Unsigned), "unsigned");
+        assert_eq!(
+            error_kind(&Error::Tampered {
+                code: 0,
+                message: None
+            }),
+            "tampered"
+        );
+        assert_eq!(
+            error_kind(&chain(
+                ChainStatus::IS_REVOKED | ChainStatus::IS_NOT_TIME_VALID
+            )),
+            "revoked"
+        );
+        assert_eq!(
+            error_kind(&chain(ChainStatus::IS_NOT_TIME_VALID)),
+            "expired"
+        );
+        assert_eq!(
+            error_kind(&chain(ChainStatus::IS_UNTRUSTED_ROOT)),
+            "untrusted_root"
+        );
+        assert_eq!(error_kind(&Error::InvalidPath), "other");
+    }
+}
@@ -0,0 +1,172 @@
+//!
+//! The quarantine and provenance attributes Gatekeeper acts on, macOS only.
+//!
+//! Files downloaded by a quarantine aware app carry `com.apple.quarantine`, which has Gatekeeper
+//! assess them on first launch, and records whether the user has since approved them. macOS 13
+//! and later also tag apps Gatekeeper assessed, and files they create, with
+//! `com.apple.provenance`. Neither is part of the signature, so verification doesn't look at
+//! them, but whether Gatekeeper would let the code run depends on both.
+//!
+
+use crate::Error;
+use std::convert::TryInto;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const QUARANTINE: &str = "com.apple.quarantine";
+const PROVENANCE: &str = "com.apple.provenance";
+
+/// The file was downloaded, rather than created by a sandboxed app
+pub const QTN_FLAG_DOWNLOAD: u16 = 0x0001;
+/// The file was created by a sandboxed app
+pub const QTN_FLAG_SANDBOX: u16 = 0x0002;
+/// The quarantine can't be lifted by the user
+pub const QTN_FLAG_HARD: u16 = 0x0004;
+/// The user approved the file in the Gatekeeper prompt
+pub const QTN_FLAG_USER_APPROVED: u16 = 0x0040;
+
+///
+/// The `com.apple.quarantine` attribute
+///
+/// # Fields
+///
+/// `flags`: The `QTN_FLAG_*` bits
+///
+/// `time`: When the file was quarantined
+///
+/// `agent`: The app that quarantined the file, such as "Safari"
+///
+/// `event_id`: The UUID of the download in the quarantine events database
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quarantine {
+    pub flags: u16,
+    pub time: Option<SystemTime>,
+    pub agent: Option<String>,
+    pub event_id: Option<String>,
+}
+
+impl Quarantine {
+    /// Parse the attribute's value, `flags;hex time;agent;event id`
+    pub fn parse(value: &str) -> Option<Quarantine> {
+        let mut fields = value.trim_end_matches('\0').split(';');
+        let flags = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let time = fields
+            .next()
+            .and_then(|time| u64::from_str_radix(time, 16).ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let mut text = || {
+            fields
+                .next()
+                .filter(|field| !field.is_empty())
+                .map(str::to_string)
+        };
+        let agent = text();
+        let event_id = text();
+
+        Some(Quarantine {
+            flags,
+            time,
+            agent,
+            event_id,
+        })
+    }
+
+    pub fn is_user_approved(&self) -> bool {
+        self.flags & QTN_FLAG_USER_APPROVED != 0
+    }
+}
+
+///
+/// The `com.apple.provenance` attribute, which Apple doesn't document
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    pub data: Vec<u8>,
+}
+
+impl Provenance {
+    /// The identifier of the provenance sandbox the file was tagged with, from the 11 byte
+    /// layout macOS 13 and 14 write: a version, two flag bytes, then the little endian identifier
+    pub fn tracking_id(&self) -> Option<u64> {
+        match self.data.as_slice() {
+            [1, _, _, id @ ..] if id.len() == 8 => Some(u64::from_le_bytes(id.try_into().ok()?)),
+            _ => None,
+        }
+    }
+}
+
+///
+/// The attributes Gatekeeper acts on, see `attributes`
+///
+/// # Fields
+///
+/// `quarantine`: The quarantine attribute, None for files that were never quarantined or
+/// whose quarantine was removed
+///
+/// `provenance`: The provenance attribute, if any
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attributes {
+    pub quarantine: Option<Quarantine>,
+    pub provenance: Option<Provenance>,
+}
+
+/// Read the quarantine and provenance attributes of a file or bundle
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::quarantine;
+///
+/// let attributes = quarantine::attributes("/Applications/Tool.app").unwrap();
+/// if let Some(quarantine) = attributes.quarantine {
+///     println!("downloaded by {:?}, approved: {}", quarantine.agent, quarantine.is_user_approved());
+/// }
+/// ```
+pub fn attributes<P: AsRef<Path>>(path: P) -> Result<Attributes, Error> {
+    let path = path.as_ref();
+    let quarantine = crate::macos::xattr::read(path, QUARANTINE)?
+        .and_then(|value| Quarantine::parse(&String::from_utf8_lossy(&value)));
+    let provenance = crate::macos::xattr::read(path, PROVENANCE)?.map(|data| Provenance { data });
+
+    Ok(Attributes {
+        quarantine,
+        provenance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine() {
+        let quarantine =
+            Quarantine::parse("0083;65a1b2c3;Safari;6E0B0F7A-3C7D-4D0E-9F44-0A1B2C3D4E5F").unwrap();
+        assert_eq!(quarantine.flags, 0x83);
+        assert!(!quarantine.is_user_approved());
+        assert_eq!(
+            quarantine.time,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(0x65a1b2c3))
+        );
+        assert_eq!(quarantine.agent.as_deref(), Some("Safari"));
+        assert!(Quarantine::parse("00c3;;;").unwrap().is_user_approved());
+        assert!(Quarantine::parse("").is_none());
+
+        let provenance = Provenance {
+            data: vec![1, 2, 0, 0x2a, 0, 0, 0, 0, 0, 0, 0],
+        };
+        assert_eq!(provenance.tracking_id(), Some(42));
+
+        let path = crate::temp_path();
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        let attributes = attributes(&path);
+        std::fs::remove_file(&path).unwrap();
+        // Files the test creates may still be tagged with provenance, but aren't quarantined
+        assert_eq!(attributes.unwrap().quarantine, None);
+    }
+}
@@ -0,0 +1,385 @@
+//!
+//! Traces a removed or truncated signature leaves behind.
+//!
+//! Neither platform tells a file that was never signed from one whose signature was stripped,
+//! both report it as unsigned, and a signature cut short reads as modified code. The headers
+//! that point at a signature usually outlive it though: the PE security directory, the Mach-O
+//! `LC_CODE_SIGNATURE` load command, and a bundle's `_CodeSignature` seal. The same records
+//! locate a signature that's still intact, for the parts the platform APIs don't expose.
+//!
+
+use crate::SignatureRemnant;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The Mach-O load command pointing at the code signature
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+/// The index of the certificate table in the PE data directories
+const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+/// More slices than any universal binary has, Java class files share the fat magic
+const MAX_SLICES: u32 = 32;
+/// The magic of the superblob holding an embedded code signature's blobs
+#[cfg(any(target_os = "macos", feature = "cms"))]
+pub(crate) const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade0cc0;
+
+///
+/// A signature the file's headers still record
+///
+/// # Fields
+///
+/// `remnant`: Where the record was found
+///
+/// `truncated`: Whether the record points past the end of the file
+///
+/// `offset`, `size`: Where in the file the record says the signature is, zero for a bundle seal
+///
+/// `trailing`: How many bytes follow the signature up to the end of the file, or of its slice
+/// of a universal binary
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SignatureRecord {
+    pub remnant: SignatureRemnant,
+    pub truncated: bool,
+    pub offset: u64,
+    pub size: u64,
+    pub trailing: u64,
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0; len];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut buf).ok()?;
+    Some(buf)
+}
+
+fn u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// The certificate table a PE file's security directory records
+fn pe_record(file: &mut File, len: u64) -> Option<SignatureRecord> {
+    let dos = read_at(file, 0, 0x40)?;
+    let pe_offset = u32_le(&dos, 0x3c)? as u64;
+    let headers = read_at(file, pe_offset, 24 + 240)?;
+    if headers.get(..4)? != b"PE\0\0" {
+        return None;
+    }
+
+    // The data directories follow the optional header's fixed fields, 8 bytes each
+    let (count, directories) = match u16_le(&headers, 24)? {
+        0x10b => (u32_le(&headers, 24 + 92)?, 24 + 96), // PE32
+        0x20b => (u32_le(&headers, 24 + 108)?, 24 + 112), // PE32+
+        _ => return None,
+    };
+    if count as usize <= IMAGE_DIRECTORY_ENTRY_SECURITY {
+        return None;
+    }
+    let entry = directories + IMAGE_DIRECTORY_ENTRY_SECURITY * 8;
+    // Unlike the other directories, the certificate table is located by file offset
+    let (offset, size) = (u32_le(&headers, entry)?, u32_le(&headers, entry + 4)?);
+    if offset == 0 && size == 0 {
+        return None;
+    }
+
+    Some(SignatureRecord {
+        remnant: SignatureRemnant::CertificateTable,
+        truncated: offset as u64 + size as u64 > len,
+        offset: offset as u64,
+        size: size as u64,
+        trailing: len.saturating_sub(offset as u64 + size as u64),
+    })
+}
+
+/// The code signature the load commands of a Mach-O slice from `base` to `end` record
+fn macho_record(file: &mut File, base: u64, end: u64, len: u64) -> Option<SignatureRecord> {
+    let header = read_at(file, base, 32)?;
+    let header_size = match u32_le(&header, 0)? {
+        0xfeedface => 28,
+        0xfeedfacf => 32,
+        _ => return None,
+    };
+    let (count, size) = (u32_le(&header, 16)?, u32_le(&header, 20)?);
+    let commands = read_at(file, base + header_size, size as usize)?;
+
+    let mut offset = 0;
+    for _ in 0..count {
+        let (cmd, cmd_size) = (u32_le(&commands, offset)?, u32_le(&commands, offset + 4)?);
+        if cmd == LC_CODE_SIGNATURE {
+            let (data_offset, data_size) = (
+                u32_le(&commands, offset + 8)?,
+                u32_le(&commands, offset + 12)?,
+            );
+            let offset = base + data_offset as u64;
+            return Some(SignatureRecord {
+                remnant: SignatureRemnant::LoadCommand,
+                truncated: offset + data_size as u64 > len,
+                offset,
+                size: data_size as u64,
+                trailing: end.saturating_sub(offset + data_size as u64),
+            });
+        }
+        if cmd_size < 8 {
+            return None;
+        }
+        offset += cmd_size as usize;
+    }
+    None
+}
+
+/// The code signatures the slices of a Mach-O file record, in the order of the slices
+fn macho_records(file: &mut File, len: u64) -> Option<Vec<SignatureRecord>> {
+    let header = read_at(file, 0, 8)?;
+    let (count, entry_size) = match u32_be(&header, 0)? {
+        0xcafebabe => (u32_be(&header, 4)?, 20),
+        0xcafebabf => (u32_be(&header, 4)?, 32), // Slices with 64-bit offsets
+        _ => return Some(macho_record(file, 0, len, len).into_iter().collect()),
+    };
+    if count > MAX_SLICES {
+        return None;
+    }
+
+    let entries = read_at(file, 8, count as usize * entry_size)?;
+    let slices = (0..count as usize)
+        .filter_map(|i| {
+            let entry = i * entry_size;
+            match entry_size {
+                20 => Some((
+                    u32_be(&entries, entry + 8)? as u64,
+                    u32_be(&entries, entry + 12)? as u64,
+                )),
+                _ => Some((u64_be(&entries, entry + 8)?, u64_be(&entries, entry + 16)?)),
+            }
+        })
+        .collect::<Vec<_>>();
+    Some(
+        slices
+            .into_iter()
+            .filter_map(|(base, size)| macho_record(file, base, base + size, len))
+            .collect(),
+    )
+}
+
+/// The signatures the headers of a file record, one for each slice of a universal binary
+pub(crate) fn signature_records(path: &Path) -> Vec<SignatureRecord> {
+    let records = || {
+        let mut file = File::open(path).ok()?;
+        let len = file.metadata().ok()?.len();
+        match read_at(&mut file, 0, 2)?.as_slice() {
+            b"MZ" => Some(pe_record(&mut file, len).into_iter().collect()),
+            _ => macho_records(&mut file, len),
+        }
+    };
+    records().unwrap_or_default()
+}
+
+/// The signature the file or bundle still records, if any, truncated ones first
+pub(crate) fn signature_record(path: &Path) -> Option<SignatureRecord> {
+    if path.is_dir() {
+        let sealed = ["Contents", "Versions/Current", ""]
+            .iter()
+            .any(|contents| path.join(contents).join("_CodeSignature").is_dir());
+        return sealed.then_some(SignatureRecord {
+            remnant: SignatureRemnant::BundleSeal,
+            truncated: false,
+            offset: 0,
+            size: 0,
+            trailing: 0,
+        });
+    }
+
+    let mut records = signature_records(path);
+    records.sort_by_key(|record| !record.truncated);
+    records.into_iter().next()
+}
+
+/// The signature a file embeds, as recorded by its headers, unless it's cut short
+#[cfg(target_os = "macos")]
+pub(crate) fn embedded_signature(path: &Path) -> Option<Vec<u8>> {
+    let record = signature_record(path).filter(|record| !record.truncated && record.size > 0)?;
+    let mut file = File::open(path).ok()?;
+    read_at(&mut file, record.offset, record.size as usize)
+}
+
+/// The signatures a file embeds, one for each slice of a universal binary, skipping any that
+/// are cut short
+#[cfg(feature = "cms")]
+pub(crate) fn embedded_signatures(path: &Path) -> Vec<(SignatureRemnant, Vec<u8>)> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    signature_records(path)
+        .into_iter()
+        .filter(|record| !record.truncated && record.size > 0)
+        .filter_map(|record| {
+            let data = read_at(&mut file, record.offset, record.size as usize)?;
+            Some((record.remnant, data))
+        })
+        .collect()
+}
+
+/// The contents of the blob in the given slot of an embedded signature superblob
+#[cfg(any(target_os = "macos", feature = "cms"))]
+pub(crate) fn superblob_slot(superblob: &[u8], slot: u32, magic: u32) -> Option<&[u8]> {
+    if u32_be(superblob, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        return None;
+    }
+
+    let count = u32_be(superblob, 8)? as usize;
+    let offset = (0..count).find_map(|i| {
+        let entry = 12 + i * 8;
+        match u32_be(superblob, entry)? {
+            kind if kind == slot => u32_be(superblob, entry + 4),
+            _ => None,
+        }
+    })? as usize;
+
+    let blob = superblob.get(offset..)?;
+    if u32_be(blob, 0)? != magic {
+        return None;
+    }
+    let len = u32_be(blob, 4)? as usize;
+    blob.get(8..len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{signature_record, signature_records, SignatureRecord};
+    use crate::SignatureRemnant;
+
+    /// A 64-bit Mach-O header with a single `LC_CODE_SIGNATURE` command
+    fn macho(data_offset: u32, data_size: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [0xfeedfacf, 0x0100000c, 0, 2, 1, 16, 0, 0] {
+            data.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        for value in [0x1d, 16, data_offset, data_size] {
+            data.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        data.resize(0x100, 0);
+        data
+    }
+
+    /// A PE32+ header with the given security directory
+    fn pe(offset: u32, size: u32) -> Vec<u8> {
+        let mut data = vec![0; 0x200];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3c..0x40].copy_from_slice(&u32::to_le_bytes(0x80));
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+        let optional = 0x80 + 24;
+        data[optional..optional + 2].copy_from_slice(&u16::to_le_bytes(0x20b));
+        data[optional + 108..optional + 112].copy_from_slice(&u32::to_le_bytes(16));
+        let security = optional + 112 + 4 * 8;
+        data[security..security + 4].copy_from_slice(&u32::to_le_bytes(offset));
+        data[security + 4..security + 8].copy_from_slice(&u32::to_le_bytes(size));
+        data
+    }
+
+    #[test]
+    fn test_signature_record() {
+        let record = |data: &[u8]| {
+            let path = crate::temp_path();
+            std::fs::write(&path, data).unwrap();
+            let record = signature_record(&path);
+            std::fs::remove_file(&path).unwrap();
+            record
+        };
+
+        assert_eq!(record(&pe(0, 0)), None);
+        assert_eq!(
+            record(&pe(0x180, 0x80)),
+            Some(SignatureRecord {
+                remnant: SignatureRemnant::CertificateTable,
+                truncated: false,
+                offset: 0x180,
+                size: 0x80,
+                trailing: 0
+            })
+        );
+        assert_eq!(
+            record(&pe(0x180, 0x1000)).map(|record| record.truncated),
+            Some(true)
+        );
+        assert_eq!(
+            record(&pe(0x100, 0x80)).map(|record| record.trailing),
+            Some(0x80)
+        );
+        assert_eq!(
+            record(&macho(0x80, 0x80)),
+            Some(SignatureRecord {
+                remnant: SignatureRemnant::LoadCommand,
+                truncated: false,
+                offset: 0x80,
+                size: 0x80,
+                trailing: 0
+            })
+        );
+        assert_eq!(
+            record(&macho(0x80, 0x1000)).map(|record| record.truncated),
+            Some(true)
+        );
+        assert_eq!(record(b"#!/bin/sh\n"), None);
+
+        let bundle = crate::temp_path();
+        assert_eq!(signature_record(&bundle), None);
+        std::fs::create_dir_all(bundle.join("Contents/MacOS")).unwrap();
+        assert_eq!(signature_record(&bundle), None);
+        std::fs::create_dir_all(bundle.join("Contents/_CodeSignature")).unwrap();
+        let sealed = signature_record(&bundle);
+        std::fs::remove_dir_all(&bundle).unwrap();
+        assert_eq!(
+            sealed.map(|record| record.remnant),
+            Some(SignatureRemnant::BundleSeal)
+        );
+    }
+
+    #[test]
+    fn test_signature_records() {
+        // A universal binary with a slice at 0x1000 and one at 0x2000, and data appended to it
+        let mut data = Vec::new();
+        for value in [
+            0xcafebabe, 2, 0x0100000c, 0, 0x1000, 0x100, 12, 0x01000007, 3, 0x2000, 0x100, 12,
+        ] {
+            data.extend_from_slice(&u32::to_be_bytes(value));
+        }
+        data.resize(0x1000, 0);
+        data.extend(macho(0x80, 0x40));
+        data.resize(0x2000, 0);
+        data.extend(macho(0x80, 0x80));
+        data.extend_from_slice(&[0; 16]);
+
+        let path = crate::temp_path();
+        std::fs::write(&path, &data).unwrap();
+        let records = signature_records(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        // Trailing bytes are counted to the end of the slice, not of the file
+        let ranges: Vec<_> = records
+            .iter()
+            .map(|record| (record.offset, record.size, record.trailing))
+            .collect();
+        assert_eq!(ranges, [(0x1080, 0x40, 0x40), (0x2080, 0x80, 0)]);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::Name;
+
+///
+/// The root a signing chain has to end at
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Apple,        // Apple's own code, "anchor apple"
+    AppleGeneric, // Any certificate issued by Apple, including Developer ID, "anchor apple generic"
+    Trusted,      // Any root trusted by the system, "anchor trusted"
+}
+
+///
+/// A code requirement built from typed conditions, rather than written by hand in the Apple
+/// [Code Signing Requirement Language](https://developer.apple.com/library/archive/documentation/Security/Conceptual/CodeSigningGuide/RequirementLang/RequirementLang.html).
+///
+/// On macOS the requirement compiles to requirement language, which is what `Display` produces.
+/// On Windows the certificate conditions are checked against the subject of the leaf certificate,
+/// while the anchor, identifier and requirement text have no equivalent and are ignored,
+/// WinVerifyTrust always requires a chain to a trusted root.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::{CodeSignVerifier, Requirement, VerificationOptions};
+///
+/// let requirement = Requirement::anchor_apple_generic()
+///     .team_id("ABC123")
+///     .bundle_id("com.foo");
+///
+/// let options = VerificationOptions::new().requirement(requirement);
+/// CodeSignVerifier::for_file("/Applications/Foo.app").unwrap().verify_with(&options).unwrap();
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Requirement {
+    text: Option<String>,
+    anchor: Option<Anchor>,
+    identifier: Option<String>,
+    team_id: Option<String>,
+    organization: Option<String>,
+    common_name: Option<String>,
+}
+
+impl Requirement {
+    /// A requirement without any conditions, only the signature itself is validated
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A requirement written in requirement language, further conditions are joined to it
+    pub fn from_text(text: &str) -> Self {
+        Requirement {
+            text: Some(text.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Require the code to be signed by Apple itself
+    pub fn anchor_apple() -> Self {
+        Self::new().anchor(Anchor::Apple)
+    }
+
+    /// Require a certificate issued by Apple, e.g. a Developer ID
+    pub fn anchor_apple_generic() -> Self {
+        Self::new().anchor(Anchor::AppleGeneric)
+    }
+
+    /// Require a chain to any root trusted by the system
+    pub fn anchor_trusted() -> Self {
+        Self::new().anchor(Anchor::Trusted)
+    }
+
+    /// Require a chain to the given anchor
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// Require the signing identifier, which for bundles is the bundle identifier
+    pub fn bundle_id(mut self, identifier: &str) -> Self {
+        self.identifier = Some(identifier.to_string());
+        self
+    }
+
+    /// Require the Apple developer team, recorded as the organization unit of the leaf certificate
+    pub fn team_id(mut self, team_id: &str) -> Self {
+        self.team_id = Some(team_id.to_string());
+        self
+    }
+
+    /// Require the organization of the leaf certificate subject
+    pub fn organization(mut self, organization: &str) -> Self {
+        self.organization = Some(organization.to_string());
+        self
+    }
+
+    /// Require the common name of the leaf certificate subject
+    pub fn common_name(mut self, common_name: &str) -> Self {
+        self.common_name = Some(common_name.to_string());
+        self
+    }
+
+    /// Compile the requirement without verifying anything, so requirements read from
+    /// configuration can be rejected at startup rather than on first use. A requirement
+    /// that doesn't parse fails with `Error::InvalidRequirement`, holding the position
+    /// of the problem. Uses `SecRequirementCreateWithStringAndErrors`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::{Error, Requirement};
+    ///
+    /// match Requirement::from_text("anchor apple generic and").validate() {
+    ///     Err(Error::InvalidRequirement(syntax)) => println!("{:?}:{:?} {}", syntax.line, syntax.column, syntax.message),
+    ///     _ => {}
+    /// }
+    /// ```
+    #[cfg(target_os = "macos")]
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        crate::macos::create_requirement(&self.to_string()).map(|_| ())
+    }
+
+    /// Whether the requirement has no conditions at all
+    pub(crate) fn is_empty(&self) -> bool {
+        self.text.as_deref().map_or(true, str::is_empty) && self.is_text_only()
+    }
+
+    fn is_text_only(&self) -> bool {
+        self.anchor.is_none()
+            && self.identifier.is_none()
+            && self.team_id.is_none()
+            && self.organization.is_none()
+            && self.common_name.is_none()
+    }
+
+    /// Check the certificate conditions against the subject of the leaf certificate
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn matches_subject(&self, subject: &Name) -> bool {
+        let matches = |expected: &Option<String>, actual: &Option<String>| match expected {
+            Some(expected) => actual.as_ref() == Some(expected),
+            None => true,
+        };
+
+        matches(&self.team_id, &subject.organization_unit)
+            && matches(&self.organization, &subject.organization)
+            && matches(&self.common_name, &subject.common_name)
+    }
+}
+
+///
+/// Why a requirement failed to compile, as reported by the requirement language parser
+///
+/// `line`, `column`: The position of the problem in the requirement text, when the parser reported one
+///
+/// `message`: The parser's description of the problem
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementSyntax {
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl RequirementSyntax {
+    /// Parse the first parser message, which reads like `line 1:7: unexpected token: foo`
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub(crate) fn parse(errors: &str) -> Self {
+        let first = errors.lines().next().unwrap_or_default().trim();
+
+        let position = first.find("line ").and_then(|start| {
+            let rest = &first[start + "line ".len()..];
+            let mut parts = rest.splitn(3, ':');
+            let line = parts.next()?.trim().parse().ok()?;
+            let column = parts.next()?.trim().parse().ok()?;
+            Some((line, column, parts.next().unwrap_or_default().trim()))
+        });
+
+        match position {
+            Some((line, column, message)) => RequirementSyntax {
+                line: Some(line),
+                column: Some(column),
+                message: message.to_string(),
+            },
+            None => RequirementSyntax {
+                line: None,
+                column: None,
+                message: first.to_string(),
+            },
+        }
+    }
+}
+
+impl From<&str> for Requirement {
+    fn from(text: &str) -> Self {
+        Requirement::from_text(text)
+    }
+}
+
+/// Quote a string constant, escaping the characters requirement language treats specially
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut clauses = Vec::new();
+
+        if let Some(text) = &self.text {
+            clauses.push(if self.is_text_only() {
+                text.clone()
+            } else {
+                format!("({})", text)
+            });
+        }
+        if let Some(anchor) = self.anchor {
+            clauses.push(
+                match anchor {
+                    Anchor::Apple => "anchor apple",
+                    Anchor::AppleGeneric => "anchor apple generic",
+                    Anchor::Trusted => "anchor trusted",
+                }
+                .to_string(),
+            );
+        }
+        if let Some(identifier) = &self.identifier {
+            clauses.push(format!("identifier {}", quote(identifier)));
+        }
+        if let Some(team_id) = &self.team_id {
+            clauses.push(format!("certificate leaf[subject.OU] = {}", quote(team_id)));
+        }
+        if let Some(organization) = &self.organization {
+            clauses.push(format!(
+                "certificate leaf[subject.O] = {}",
+                quote(organization)
+            ));
+        }
+        if let Some(common_name) = &self.common_name {
+            clauses.push(format!(
+                "certificate leaf[subject.CN] = {}",
+                quote(common_name)
+            ));
+        }
+
+        write!(f, "{}", clauses.join(" and "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Requirement, RequirementSyntax};
+
+    #[test]
+    fn test_display() {
+        let requirement = Requirement::anchor_apple_generic()
+            .team_id("ABC123")
+            .bundle_id("com.foo");
+        assert_eq!(
+            requirement.to_string(),
+            "anchor apple generic and identifier \"com.foo\" and certificate leaf[subject.OU] = \"ABC123\""
+        );
+
+        let requirement =
+            Requirement::from_text("anchor apple or anchor trusted").common_name("A \"B\"");
+        assert_eq!(
+            requirement.to_string(),
+            "(anchor apple or anchor trusted) and certificate leaf[subject.CN] = \"A \\\"B\\\"\""
+        );
+
+        assert!(Requirement::from("").is_empty());
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let syntax = RequirementSyntax::parse("line 1:25: unexpected end of file\nline 2:1: x");
+        assert_eq!(syntax.line, Some(1));
+        assert_eq!(syntax.column, Some(25));
+        assert_eq!(syntax.message, "unexpected end of file");
+
+        let syntax = RequirementSyntax::parse("unexpected token");
+        assert_eq!(syntax.line, None);
+        assert_eq!(syntax.message, "unexpected token");
+    }
+}
@@ -0,0 +1,328 @@
+//!
+//! The process wide cache of revocation checks.
+//!
+//! Both platforms cache CRLs and OCSP responses themselves, but only for as long as the issuer
+//! says they're current, which for OCSP can be minutes. Bulk scans verify thousands of files
+//! signed with the same few certificates, so they end up asking the same responders over and
+//! over. Every chain that checks out online is remembered here, keyed by its leaf certificate,
+//! and later verifications of code claiming the same leaf first try with cached revocation
+//! data only, taking the remembered result for anything that couldn't be checked that way.
+//! The claimed leaf is read from the signature without verifying it, so code signed with a
+//! chain that isn't remembered is only verified once. Catalog signed files on Windows don't
+//! embed a leaf to read, they always go to the network.
+//!
+//! Only chains that passed are kept, never for longer than the cache's TTL or past the next
+//! update of the revocation data they were checked with. A certificate revoked meanwhile is
+//! still accepted until its entry expires, so keep the TTL within what the policy tolerates,
+//! or set it to zero to always go to the network.
+//!
+
+use crate::{ChainStatus, Context, Error, RevocationMode, RevocationState, RevocationStatus};
+use crate::{VerificationOptions, Verifier};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long checked chains are trusted by default
+pub const DEFAULT_TTL: Duration = Duration::from_secs(600);
+/// How many chains are remembered by default
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// The chain status bits cleared when the cache settles what cached data alone couldn't
+const REVOCATION_UNKNOWN: u32 =
+    ChainStatus::REVOCATION_STATUS_UNKNOWN | ChainStatus::IS_OFFLINE_REVOCATION;
+
+struct Entry {
+    statuses: Vec<RevocationStatus>, // The whole chain, leaf first, as checked online
+    mode: Option<RevocationMode>,    // Which certificates were checked
+    checked_at: Instant,
+}
+
+impl Entry {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        let now = SystemTime::now();
+        self.checked_at.elapsed() < ttl
+            && self
+                .statuses
+                .iter()
+                .all(|status| status.next_update.map_or(true, |next| now < next))
+    }
+
+    /// The statuses of the same chain checked with cached data only, with the remembered
+    /// results standing in for those that weren't settled that way. None for another chain,
+    /// or one with a certificate that turned out revoked.
+    fn settle(&self, statuses: &[RevocationStatus]) -> Option<Vec<RevocationStatus>> {
+        if statuses.len() != self.statuses.len() {
+            return None;
+        }
+
+        statuses
+            .iter()
+            .zip(&self.statuses)
+            .map(|(status, cached)| match status.state {
+                _ if status.thumbprint != cached.thumbprint => None,
+                RevocationState::Revoked => None,
+                RevocationState::Good => Some(status.clone()),
+                _ => Some(cached.clone()),
+            })
+            .collect()
+    }
+}
+
+struct Cache {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<String, Entry>, // Keyed by the thumbprint of the leaf certificate
+}
+
+impl Cache {
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    /// The fresh entry for the leaf, checked with the revocation mode
+    fn entry(&self, thumbprint: &str, mode: Option<RevocationMode>) -> Option<&Entry> {
+        self.entries
+            .get(thumbprint)
+            .filter(|entry| entry.mode == mode && entry.is_fresh(self.ttl))
+    }
+
+    fn insert(&mut self, thumbprint: String, entry: Entry) {
+        if !self.entries.contains_key(&thumbprint) && self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries.retain(|_, entry| entry.is_fresh(ttl));
+        }
+        // Still full of fresh entries, the one checked the longest ago makes room
+        while self.entries.len() >= self.capacity {
+            let oldest = match self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.checked_at)
+            {
+                Some((oldest, _)) => oldest.clone(),
+                None => break,
+            };
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(thumbprint, entry);
+    }
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        Mutex::new(Cache {
+            ttl: DEFAULT_TTL,
+            capacity: DEFAULT_CAPACITY,
+            entries: HashMap::new(),
+        })
+    })
+}
+
+/// Trust checked chains for this long, `DEFAULT_TTL` unless set. Zero disables the cache.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::revocation;
+/// use std::time::Duration;
+///
+/// // A nightly scan can live with revocations it only notices an hour later
+/// revocation::set_cache_ttl(Duration::from_secs(3600));
+/// revocation::set_cache_capacity(10_000);
+/// ```
+pub fn set_cache_ttl(ttl: Duration) {
+    cache().lock().unwrap().ttl = ttl;
+}
+
+/// Remember up to this many chains, `DEFAULT_CAPACITY` unless set. Zero disables the cache.
+pub fn set_cache_capacity(capacity: usize) {
+    let mut cache = cache().lock().unwrap();
+    cache.capacity = capacity;
+    // Evicted in no particular order, shrinking is rare
+    let excess = cache.entries.len().saturating_sub(capacity);
+    let evicted: Vec<String> = cache.entries.keys().take(excess).cloned().collect();
+    for thumbprint in evicted {
+        cache.entries.remove(&thumbprint);
+    }
+}
+
+/// Forget every checked chain, e.g. after a revocation was announced
+pub fn clear_cache() {
+    cache().lock().unwrap().entries.clear();
+}
+
+/// The number of chains remembered, including ones that may since have expired
+pub fn cache_len() -> usize {
+    cache().lock().unwrap().entries.len()
+}
+
+/// Whether the options check revocation online, where the cache can save the network trip
+fn uses_cache(options: &VerificationOptions) -> bool {
+    options.allow_network
+        && !options.offline
+        && options.revocation != Some(RevocationMode::None)
+        && cache().lock().unwrap().is_enabled()
+}
+
+/// Verify with cached revocation data first when the claimed leaf was checked before,
+/// settling what that leaves unknown from the remembered chain, and going to the network
+/// only for chains that weren't
+pub(crate) fn verify(verifier: &Verifier, options: &VerificationOptions) -> Result<Context, Error> {
+    if !uses_cache(options) {
+        return verify_online(verifier, options);
+    }
+    // Only worth a pass with cached data when it may settle the chain
+    let remembered = verifier.claimed_thumbprint().map_or(false, |thumbprint| {
+        let cache = cache().lock().unwrap();
+        cache.entry(&thumbprint, options.revocation).is_some()
+    });
+    if !remembered {
+        return verify_online(verifier, options);
+    }
+
+    match verifier.verify(&options.clone().offline(true)) {
+        Ok(mut context) => {
+            let statuses = context.revocation_status();
+            let settled = {
+                let cache = cache().lock().unwrap();
+                statuses
+                    .first()
+                    .and_then(|leaf| cache.entry(&leaf.thumbprint, options.revocation))
+                    .and_then(|entry| entry.settle(&statuses))
+            };
+            if let Some(settled) = settled {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(leaf = %statuses[0].thumbprint, "revocation settled from cache");
+                context.set_cached_revocation(settled);
+                return Ok(context);
+            }
+        }
+        // Only the chain can turn out differently with the network
+        Err(err) if err.chain_status().is_none() => return Err(err),
+        Err(_) => {}
+    }
+
+    verify_online(verifier, options)
+}
+
+fn verify_online(verifier: &Verifier, options: &VerificationOptions) -> Result<Context, Error> {
+    let context = verifier.verify(options)?;
+    if uses_cache(options) {
+        remember(context.revocation_status(), options.revocation);
+    }
+    Ok(context)
+}
+
+/// Remember a chain checked online, unless some of it couldn't be checked
+fn remember(statuses: Vec<RevocationStatus>, mode: Option<RevocationMode>) {
+    let checked = statuses.iter().all(|status| {
+        matches!(
+            status.state,
+            RevocationState::Good | RevocationState::NotChecked
+        )
+    }) && statuses
+        .iter()
+        .any(|status| status.state == RevocationState::Good);
+    let thumbprint = match statuses.first() {
+        Some(leaf) if checked => leaf.thumbprint.clone(),
+        _ => return,
+    };
+
+    let entry = Entry {
+        statuses,
+        mode,
+        checked_at: Instant::now(),
+    };
+    cache().lock().unwrap().insert(thumbprint, entry);
+}
+
+/// The chain status of a context whose revocation the cache settled
+pub(crate) fn settled_chain_status(mut status: ChainStatus) -> ChainStatus {
+    status.error_status &= !REVOCATION_UNKNOWN;
+    status
+        .details
+        .retain(|detail| detail != "RevocationResponseRequired");
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(thumbprint: &str, state: RevocationState) -> RevocationStatus {
+        RevocationStatus {
+            thumbprint: thumbprint.to_string(),
+            state,
+            revocation_time: None,
+            source: None,
+            this_update: None,
+            next_update: None,
+            produced_at: None,
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = Cache {
+            ttl: DEFAULT_TTL,
+            capacity: 2,
+            entries: HashMap::new(),
+        };
+        let checked = vec![
+            status("leaf", RevocationState::Good),
+            status("root", RevocationState::NotChecked),
+        ];
+        cache.insert(
+            "leaf".to_string(),
+            Entry {
+                statuses: checked.clone(),
+                mode: None,
+                checked_at: Instant::now() - Duration::from_secs(60),
+            },
+        );
+
+        let offline = vec![
+            status("leaf", RevocationState::Unknown),
+            status("root", RevocationState::NotChecked),
+        ];
+        assert!(cache
+            .entry("leaf", Some(RevocationMode::WholeChain))
+            .is_none());
+        let entry = cache.entry("leaf", None).unwrap();
+        assert_eq!(entry.settle(&offline), Some(checked.clone()));
+        // Another chain for the same leaf, or a revocation found offline, isn't settled
+        assert_eq!(entry.settle(&offline[..1]), None);
+        let revoked = vec![status("leaf", RevocationState::Revoked), offline[1].clone()];
+        assert_eq!(entry.settle(&revoked), None);
+
+        // Entries don't outlive the revocation data they were checked with
+        let expired = Entry {
+            statuses: vec![RevocationStatus {
+                next_update: Some(SystemTime::now() - Duration::from_secs(1)),
+                ..checked[0].clone()
+            }],
+            mode: None,
+            checked_at: Instant::now(),
+        };
+        assert!(!expired.is_fresh(DEFAULT_TTL));
+
+        for leaf in &["second", "third"] {
+            cache.insert(
+                leaf.to_string(),
+                Entry {
+                    statuses: vec![status(leaf, RevocationState::Good)],
+                    mode: None,
+                    checked_at: Instant::now(),
+                },
+            );
+        }
+        assert_eq!(cache.entries.len(), 2);
+        assert!(!cache.entries.contains_key("leaf"));
+
+        let settled = settled_chain_status(ChainStatus::from_error_status(
+            ChainStatus::REVOCATION_STATUS_UNKNOWN | ChainStatus::IS_REVOKED,
+        ));
+        assert_eq!(settled.error_status, ChainStatus::IS_REVOKED);
+    }
+}
@@ -0,0 +1,341 @@
+//!
+//! Recursive directory scanning.
+//!
+//! Walks a directory tree, picks out the files that can carry a code signature by
+//! their format, and verifies each one as it is reached. Symbolic links aren't
+//! followed, and directories that can't be read are skipped.
+//!
+
+use crate::{CodeSignVerifier, Error, SignatureContext, VerificationOptions};
+use std::fs::ReadDir;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Script extensions Windows can carry an embedded Authenticode signature in
+const SCRIPT_EXTENSIONS: &[&str] = &["ps1", "psm1", "psd1", "vbs", "wsf"];
+
+///
+/// The executable format a scanned file was recognized as
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Pe,     // A Windows executable, DLL or driver
+    MachO,  // A Mach-O binary, thin or universal
+    Script, // A script with a shebang line, or a signable Windows script
+}
+
+impl FileFormat {
+    /// Recognize the format from the first bytes of the file and its extension
+    pub fn detect(header: &[u8], path: &Path) -> Option<Self> {
+        match header {
+            [b'M', b'Z', ..] => return Some(FileFormat::Pe),
+            [0xfe, 0xed, 0xfa, 0xce, ..]
+            | [0xfe, 0xed, 0xfa, 0xcf, ..]
+            | [0xce, 0xfa, 0xed, 0xfe, ..]
+            | [0xcf, 0xfa, 0xed, 0xfe, ..]
+            | [0xca, 0xfe, 0xba, 0xbe, ..] => return Some(FileFormat::MachO),
+            [b'#', b'!', ..] => return Some(FileFormat::Script),
+            _ => {}
+        }
+
+        let extension = path.extension()?.to_str()?;
+        SCRIPT_EXTENSIONS
+            .iter()
+            .any(|script| script.eq_ignore_ascii_case(extension))
+            .then_some(FileFormat::Script)
+    }
+}
+
+///
+/// A scanned file, and the outcome of verifying it
+///
+/// `path`: The location of the file
+///
+/// `format`: The executable format it was recognized as
+///
+/// `result`: The outcome of verifying it with the scan options
+///
+pub struct ScannedFile {
+    pub path: PathBuf,
+    pub format: FileFormat,
+    pub result: Result<SignatureContext, Error>,
+}
+
+///
+/// Scans a directory tree for signed code
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::scan::Scanner;
+///
+/// for file in Scanner::new("/Applications").max_depth(4).scan() {
+///     if file.result.is_err() {
+///         println!("{} failed verification", file.path.display());
+///     }
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    root: PathBuf,
+    options: VerificationOptions,
+    max_depth: Option<usize>,
+    extensions: Option<Vec<String>>,
+    max_size: Option<u64>,
+    #[cfg(feature = "rayon")]
+    threads: Option<usize>,
+}
+
+impl Scanner {
+    /// Scan everything under `root`, verifying with the default options
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Scanner {
+            root: root.as_ref().to_path_buf(),
+            options: VerificationOptions::new(),
+            max_depth: None,
+            extensions: None,
+            max_size: None,
+            #[cfg(feature = "rayon")]
+            threads: None,
+        }
+    }
+
+    /// Verify each file with the given options
+    pub fn options(mut self, options: VerificationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Only descend this many directories below the root, 0 scans just the root's own files
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Only consider files with one of the given extensions, compared case insensitively.
+    /// Pass an empty string to include files without an extension.
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = Some(extensions.iter().map(|e| e.to_lowercase()).collect());
+        self
+    }
+
+    /// Skip files larger than the given size in bytes
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Verify at most this many files at once in `par_scan`, rather than one per core
+    #[cfg(feature = "rayon")]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Walk the tree, verifying each executable file as the iterator reaches it
+    pub fn scan(&self) -> Scan<'_> {
+        Scan {
+            scanner: self,
+            pending: vec![(self.root.clone(), 0)],
+            current: None,
+        }
+    }
+
+    /// Walk the whole tree first, then verify the executable files found in parallel, returning
+    /// them in the order `scan` would. Without a thread limit the files are spread over rayon's
+    /// global pool, otherwise over a pool of its own. Pool threads outlive a single file, so on
+    /// Windows each keeps reusing its catalog admin context like `CodeSignVerifier::verify_many`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::scan::Scanner;
+    ///
+    /// let files = Scanner::new("C:/Program Files").threads(4).par_scan().unwrap();
+    /// println!("{} unsigned", files.iter().filter(|f| f.result.is_err()).count());
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_scan(&self) -> Result<Vec<ScannedFile>, Error> {
+        use rayon::prelude::*;
+
+        let mut scan = self.scan();
+        let candidates = std::iter::from_fn(|| scan.next_candidate()).collect::<Vec<_>>();
+        let verify = || {
+            candidates
+                .into_par_iter()
+                .map(|(path, format)| self.verify(path, format))
+                .collect::<Vec<_>>()
+        };
+
+        match self.threads {
+            None => Ok(verify()),
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map(|pool| pool.install(verify))
+                .map_err(|err| Error::IoError(std::io::Error::other(err))),
+        }
+    }
+
+    fn verify(&self, path: PathBuf, format: FileFormat) -> ScannedFile {
+        let result = CodeSignVerifier::for_file(&path).and_then(|v| v.verify_with(&self.options));
+
+        ScannedFile {
+            path,
+            format,
+            result,
+        }
+    }
+
+    /// Whether the file passes the extension and size filters
+    fn wanted(&self, path: &Path, size: u64) -> bool {
+        let extension = path
+            .extension()
+            .map_or(String::new(), |e| e.to_string_lossy().to_lowercase());
+
+        self.max_size.map_or(true, |max| size <= max)
+            && self
+                .extensions
+                .as_ref()
+                .map_or(true, |extensions| extensions.contains(&extension))
+    }
+}
+
+///
+/// An iterator over the executable files of a scanned tree, see `Scanner::scan`
+///
+pub struct Scan<'a> {
+    scanner: &'a Scanner,
+    pending: Vec<(PathBuf, usize)>, // Directories left to list, and their depth below the root
+    current: Option<(ReadDir, usize)>, // The directory being listed, and its depth
+}
+
+impl Scan<'_> {
+    /// Open the next directory waiting to be listed
+    fn next_dir(&mut self) -> Option<()> {
+        while let Some((dir, depth)) = self.pending.pop() {
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                self.current = Some((entries, depth));
+                return Some(());
+            }
+        }
+        None
+    }
+
+    pub(crate) fn format(path: &Path) -> Option<FileFormat> {
+        let mut header = [0u8; 4];
+        let mut file = std::fs::File::open(path).ok()?;
+        let len = file.read(&mut header).ok()?;
+        FileFormat::detect(&header[..len], path)
+    }
+
+    /// Walk on to the next executable file that passes the filters, without verifying it
+    fn next_candidate(&mut self) -> Option<(PathBuf, FileFormat)> {
+        loop {
+            let (entries, depth) = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    self.next_dir()?;
+                    continue;
+                }
+            };
+
+            let entry = match entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(_)) => continue,
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            let depth = *depth;
+            let (file_type, metadata) = match (entry.file_type(), entry.metadata()) {
+                (Ok(file_type), Ok(metadata)) => (file_type, metadata),
+                _ => continue,
+            };
+
+            let path = entry.path();
+            if file_type.is_dir() {
+                if self.scanner.max_depth.map_or(true, |max| depth < max) {
+                    self.pending.push((path, depth + 1));
+                }
+                continue;
+            }
+            if !file_type.is_file() || !self.scanner.wanted(&path, metadata.len()) {
+                continue;
+            }
+
+            if let Some(format) = Scan::format(&path) {
+                return Some((path, format));
+            }
+        }
+    }
+}
+
+impl Iterator for Scan<'_> {
+    type Item = ScannedFile;
+
+    fn next(&mut self) -> Option<ScannedFile> {
+        let (path, format) = self.next_candidate()?;
+        Some(self.scanner.verify(path, format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileFormat, Scanner};
+    use std::path::Path;
+
+    #[test]
+    fn test_detect() {
+        let path = Path::new("file");
+        assert_eq!(FileFormat::detect(b"MZ\x90\0", path), Some(FileFormat::Pe));
+        assert_eq!(
+            FileFormat::detect(&[0xcf, 0xfa, 0xed, 0xfe], path),
+            Some(FileFormat::MachO)
+        );
+        assert_eq!(
+            FileFormat::detect(&[0xca, 0xfe, 0xba, 0xbe], path),
+            Some(FileFormat::MachO)
+        );
+        assert_eq!(FileFormat::detect(b"#!/b", path), Some(FileFormat::Script));
+        assert_eq!(
+            FileFormat::detect(b"para", Path::new("Install.PS1")),
+            Some(FileFormat::Script)
+        );
+        assert_eq!(FileFormat::detect(b"text", Path::new("notes.txt")), None);
+        assert_eq!(FileFormat::detect(b"", path), None);
+    }
+
+    #[test]
+    fn test_filters() {
+        let scanner = Scanner::new("/").extensions(&["EXE", ""]).max_file_size(10);
+        assert!(scanner.wanted(Path::new("a.exe"), 10));
+        assert!(scanner.wanted(Path::new("a"), 1));
+        assert!(!scanner.wanted(Path::new("a.dll"), 1));
+        assert!(!scanner.wanted(Path::new("a.exe"), 11));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_par_scan() {
+        let root = crate::temp_path();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        for name in ["a.sh", "b.sh", "sub/c.sh"].iter() {
+            std::fs::write(root.join(name), "#!/bin/sh\n").unwrap();
+        }
+        std::fs::write(root.join("notes.txt"), "text").unwrap();
+
+        let scanner = Scanner::new(&root).threads(2);
+        let sequential = scanner.scan().map(|f| f.path).collect::<Vec<_>>();
+        let parallel = scanner.par_scan().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(sequential.len(), 3);
+        assert_eq!(
+            parallel.into_iter().map(|f| f.path).collect::<Vec<_>>(),
+            sequential
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//!
+//! Sessions for repeated queries about the signatures of one file.
+//!
+//! Every `CodeSignVerifier::verify_with` or `verify_signer` call sets up the trust provider
+//! state from scratch and releases it again, and on Windows creates a chain engine for the
+//! pinned trust anchors each time. A session verifies the primary signature once, keeps its
+//! provider data open for follow-up queries on the context, and reuses the chain engine and
+//! the results for the secondary signatures verified after it.
+//!
+
+use crate::{CodeSignVerifier, Error, SignatureContext, VerificationOptions};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+///
+/// The verified signatures of a file, all verified with the same options.
+/// Created with `CodeSignVerifier::session`. macOS files only carry a single signature, so
+/// there the session only saves verifying the primary signature again.
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::{CodeSignVerifier, VerificationOptions};
+///
+/// let verifier = CodeSignVerifier::for_file("C:/Program Files/Vendor/app.exe").unwrap();
+/// let session = verifier.session(&VerificationOptions::new()).unwrap();
+/// println!("{:?}", session.primary().subject_name());
+///
+/// for index in 1..session.signer_count().unwrap() {
+///     println!("{:?}", session.signer(index).unwrap().subject_name());
+/// }
+/// ```
+///
+pub struct VerificationSession<'a> {
+    verifier: &'a CodeSignVerifier,
+    options: VerificationOptions,
+    #[cfg(windows)]
+    state: crate::windows::Session,
+    primary: SignatureContext,
+    signer_count: OnceLock<u32>,
+    signers: Mutex<HashMap<u32, SignatureContext>>, // Secondary signatures verified so far
+}
+
+impl<'a> VerificationSession<'a> {
+    pub(crate) fn new(
+        verifier: &'a CodeSignVerifier,
+        options: &VerificationOptions,
+    ) -> Result<Self, Error> {
+        #[cfg(windows)]
+        let state = crate::windows::Session::default();
+        #[cfg(windows)]
+        let primary = verify_signer(verifier, &state, 0, options)?;
+        #[cfg(not(windows))]
+        let primary = verifier.verify_with(options)?;
+
+        Ok(VerificationSession {
+            verifier,
+            options: options.clone(),
+            #[cfg(windows)]
+            state,
+            primary,
+            signer_count: OnceLock::new(),
+            signers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The options every signature in the session is verified with
+    pub fn options(&self) -> &VerificationOptions {
+        &self.options
+    }
+
+    /// The primary signature, verified when the session was created
+    pub fn primary(&self) -> &SignatureContext {
+        &self.primary
+    }
+
+    /// The number of signatures on the file, including the primary one, counted on first use
+    pub fn signer_count(&self) -> Result<u32, Error> {
+        if let Some(count) = self.signer_count.get() {
+            return Ok(*count);
+        }
+        let count = self.verifier.signer_count()?;
+        Ok(*self.signer_count.get_or_init(|| count))
+    }
+
+    /// The signature at the given index, where 0 is the primary signature. Each one is only
+    /// verified the first time it's asked for, failures aren't kept and are tried again.
+    /// Returns `Error::SignerNotFound` if the file has no signature at that index.
+    pub fn signer(&self, index: u32) -> Result<SignatureContext, Error> {
+        if index == 0 {
+            return Ok(self.primary.clone());
+        }
+        if let Some(ctx) = self.signers.lock().unwrap().get(&index) {
+            return Ok(ctx.clone());
+        }
+
+        // The lock isn't held while verifying, so other signatures can be looked up meanwhile
+        #[cfg(windows)]
+        let ctx = verify_signer(self.verifier, &self.state, index, &self.options)?;
+        #[cfg(not(windows))]
+        let ctx = self.verifier.verify_signer(index, &self.options)?;
+        self.signers.lock().unwrap().insert(index, ctx.clone());
+        Ok(ctx)
+    }
+
+    /// Every signature on the file, starting with the primary one
+    pub fn signers(&self) -> Result<Vec<SignatureContext>, Error> {
+        (0..self.signer_count()?)
+            .map(|index| self.signer(index))
+            .collect()
+    }
+}
+
+/// Like `CodeSignVerifier::verify_signer`, with the chain engine kept in the session state
+#[cfg(windows)]
+fn verify_signer(
+    verifier: &CodeSignVerifier,
+    state: &crate::windows::Session,
+    index: u32,
+    options: &VerificationOptions,
+) -> Result<SignatureContext, Error> {
+    options.check_cancelled()?;
+    verifier
+        .0
+        .verify_signer_in(state, index, options)
+        .map(|context| SignatureContext(std::sync::Arc::new(context)))
+        .and_then(|ctx| ctx.check_pins(options))
+        .map_err(|err| options.check_cancelled().err().unwrap_or(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CodeSignVerifier, VerificationOptions};
+
+    #[test]
+    fn test_session() {
+        let path = crate::signed_system_binary();
+
+        let verifier = CodeSignVerifier::for_file(path).unwrap();
+        let session = verifier.session(&VerificationOptions::new()).unwrap();
+        let count = session.signer_count().unwrap();
+        assert!(count >= 1);
+
+        let signers = session.signers().unwrap();
+        assert_eq!(signers.len(), count as usize);
+        assert_eq!(
+            signers[0].sha256_thumbprint(),
+            session.primary().sha256_thumbprint()
+        );
+        assert!(matches!(
+            session.signer(count),
+            Err(crate::Error::SignerNotFound)
+        ));
+    }
+}
@@ -0,0 +1,1052 @@
+//!
+//! Sigstore bundle verification.
+//!
+//! Artifacts signed with cosign carry a Sigstore bundle next to, or instead of, a platform
+//! code signature: a signature made with a short-lived Fulcio certificate, and the entry
+//! recording it in the Rekor transparency log. Verifying the bundle along with the code
+//! signature lets a policy require both, e.g. that a binary is Authenticode signed by the
+//! vendor and that its release was published to the log by the vendor's CI identity.
+//!
+//! Both the protobuf bundle format (`application/vnd.dev.sigstore.bundle*+json`, as written by
+//! `cosign sign-blob --new-bundle-format`) and the older cosign bundle are accepted, for
+//! artifacts signed directly. DSSE attestations aren't supported.
+//!
+//! Nothing is trusted by default: the Fulcio certificates and Rekor keys to trust have to be
+//! provided, from the Sigstore trusted root of the instance the bundles come from.
+//!
+
+use crate::{digest, CodeSignVerifier, Error, SignatureContext, VerificationOptions};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::Value;
+use sha2::Digest;
+use std::convert::TryInto;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use x509_cert::der::asn1::{ObjectIdentifier, Utf8StringRef};
+use x509_cert::der::{Decode, Encode};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAltName};
+use x509_cert::Certificate;
+
+const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.15");
+const SUBJECT_ALT_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.17");
+const BASIC_CONSTRAINTS: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.19");
+const EXTENDED_KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.37");
+const CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+/// The OIDC issuer of the identity, as a DER UTF8String
+const FULCIO_ISSUER_V2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+/// The OIDC issuer of the identity, as raw bytes, set by older Fulcio releases
+const FULCIO_ISSUER_V1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+
+/// The longest certificate chain followed up to a trusted Fulcio certificate
+const MAX_CHAIN_LENGTH: usize = 8;
+
+///
+/// Why a Sigstore bundle failed verification
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigstoreFailure {
+    MalformedBundle(String), // The bundle couldn't be parsed, or uses a format that isn't supported
+    DigestMismatch,          // The artifact doesn't match the digest the bundle signs
+    InvalidSignature,        // The signature doesn't verify with the signing certificate's key
+    UntrustedCertificate,    // Not a code signing certificate, or not issued by trusted Fulcio CAs
+    CertificateExpired,      // A certificate in the chain wasn't valid when the entry was logged
+    IdentityMismatch,        // The certificate identity or its issuer isn't the expected one
+    MissingLogEntry,         // The bundle has no transparency log entry
+    LogEntryMismatch,        // The log entry records another artifact or signature
+    InclusionProofFailed,    // The inclusion proof doesn't lead up to the root hash
+    UntrustedLog,            // The log entry isn't signed by a trusted Rekor key
+    UntrustedTime,           // The entry is in the log, but no trusted log key signs when
+}
+
+impl std::fmt::Display for SigstoreFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigstoreFailure::MalformedBundle(what) => {
+                write!(f, "the bundle is malformed: {}", what)
+            }
+            SigstoreFailure::DigestMismatch => {
+                write!(f, "the artifact doesn't match the signed digest")
+            }
+            SigstoreFailure::InvalidSignature => write!(f, "the artifact signature is invalid"),
+            SigstoreFailure::UntrustedCertificate => write!(
+                f,
+                "the signing certificate doesn't chain to a trusted Fulcio certificate"
+            ),
+            SigstoreFailure::CertificateExpired => write!(
+                f,
+                "the signing certificate wasn't valid when the signature was logged"
+            ),
+            SigstoreFailure::IdentityMismatch => {
+                write!(f, "the signing identity isn't the expected one")
+            }
+            SigstoreFailure::MissingLogEntry => {
+                write!(f, "the bundle has no transparency log entry")
+            }
+            SigstoreFailure::LogEntryMismatch => write!(
+                f,
+                "the transparency log entry records another artifact or signature"
+            ),
+            SigstoreFailure::InclusionProofFailed => {
+                write!(f, "the transparency log inclusion proof is invalid")
+            }
+            SigstoreFailure::UntrustedLog => write!(
+                f,
+                "the transparency log entry isn't signed by a trusted log key"
+            ),
+            SigstoreFailure::UntrustedTime => write!(
+                f,
+                "the time the transparency log entry was logged isn't signed by a trusted log key"
+            ),
+        }
+    }
+}
+
+impl From<SigstoreFailure> for Error {
+    fn from(failure: SigstoreFailure) -> Self {
+        Error::Sigstore(failure)
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    SigstoreFailure::MalformedBundle(what.to_string()).into()
+}
+
+fn base64_field(value: &Value, what: &str) -> Result<Vec<u8>, Error> {
+    value
+        .as_str()
+        .and_then(|value| BASE64.decode(value).ok())
+        .ok_or_else(|| malformed(what))
+}
+
+/// Protobuf JSON encodes 64-bit integers as strings, cosign bundles as numbers
+fn integer_field(value: &Value, what: &str) -> Result<u64, Error> {
+    value
+        .as_u64()
+        .or_else(|| value.as_str().and_then(|value| value.parse().ok()))
+        .ok_or_else(|| malformed(what))
+}
+
+fn hash_field(value: &Value, what: &str) -> Result<[u8; 32], Error> {
+    base64_field(value, what)?
+        .try_into()
+        .map_err(|_| malformed(what))
+}
+
+/// The DER certificate in a PEM block
+fn pem_certificate(pem: &str) -> Option<Vec<u8>> {
+    let start = pem.find("-----BEGIN CERTIFICATE-----")? + "-----BEGIN CERTIFICATE-----".len();
+    let end = start + pem[start..].find("-----END CERTIFICATE-----")?;
+    let body: String = pem[start..end]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    BASE64.decode(body).ok()
+}
+
+/// A Rekor entry proving the signature was logged
+struct LogEntry {
+    log_index: u64,
+    log_id: String,       // Hex encoded, as the entry timestamp signs it
+    integrated_time: u64, // When the entry was added to the log
+    body: String,         // Base64 encoded, as the entry timestamp signs it
+    signed_entry_timestamp: Option<Vec<u8>>,
+    inclusion_proof: Option<InclusionProof>,
+}
+
+struct InclusionProof {
+    log_index: u64, // The position within the tree, which differs from the entry's for sharded logs
+    tree_size: u64,
+    root_hash: [u8; 32],
+    hashes: Vec<[u8; 32]>,
+    checkpoint: Option<String>, // The signed note committing the log to the root hash
+}
+
+impl LogEntry {
+    fn from_protobuf(entry: &Value) -> Result<Self, Error> {
+        let log_id = base64_field(&entry["logId"]["keyId"], "tlog entry log id")?;
+        let inclusion_proof = match &entry["inclusionProof"] {
+            Value::Null => None,
+            proof => Some(InclusionProof {
+                log_index: integer_field(&proof["logIndex"], "inclusion proof log index")?,
+                tree_size: integer_field(&proof["treeSize"], "inclusion proof tree size")?,
+                root_hash: hash_field(&proof["rootHash"], "inclusion proof root hash")?,
+                hashes: proof["hashes"]
+                    .as_array()
+                    .ok_or_else(|| malformed("inclusion proof hashes"))?
+                    .iter()
+                    .map(|hash| hash_field(hash, "inclusion proof hash"))
+                    .collect::<Result<_, _>>()?,
+                checkpoint: proof["checkpoint"]["envelope"].as_str().map(str::to_string),
+            }),
+        };
+
+        Ok(LogEntry {
+            log_index: integer_field(&entry["logIndex"], "tlog entry log index")?,
+            log_id: digest::hex(&log_id),
+            integrated_time: integer_field(&entry["integratedTime"], "tlog entry time")?,
+            body: entry["canonicalizedBody"]
+                .as_str()
+                .ok_or_else(|| malformed("tlog entry body"))?
+                .to_string(),
+            signed_entry_timestamp: match &entry["inclusionPromise"]["signedEntryTimestamp"] {
+                Value::Null => None,
+                set => Some(base64_field(set, "signed entry timestamp")?),
+            },
+            inclusion_proof,
+        })
+    }
+
+    fn from_cosign(rekor_bundle: &Value) -> Result<Self, Error> {
+        let payload = &rekor_bundle["Payload"];
+        Ok(LogEntry {
+            log_index: integer_field(&payload["logIndex"], "rekor bundle log index")?,
+            log_id: payload["logID"]
+                .as_str()
+                .ok_or_else(|| malformed("rekor bundle log id"))?
+                .to_string(),
+            integrated_time: integer_field(&payload["integratedTime"], "rekor bundle time")?,
+            body: payload["body"]
+                .as_str()
+                .ok_or_else(|| malformed("rekor bundle body"))?
+                .to_string(),
+            signed_entry_timestamp: Some(base64_field(
+                &rekor_bundle["SignedEntryTimestamp"],
+                "signed entry timestamp",
+            )?),
+            inclusion_proof: None,
+        })
+    }
+
+    fn integrated_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.integrated_time)
+    }
+}
+
+///
+/// A parsed Sigstore bundle, or cosign bundle
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::sigstore::{Bundle, SigstoreVerifier};
+///
+/// let bundle = Bundle::from_json(&std::fs::read_to_string("tool.sigstore.json").unwrap()).unwrap();
+/// let verifier = SigstoreVerifier::new()
+///     .fulcio_certificate(&std::fs::read("fulcio_root.der").unwrap())
+///     .rekor_key(&std::fs::read("rekor.pub.der").unwrap())
+///     .identity("https://github.com/vendor/tool/.github/workflows/release.yml@refs/heads/main")
+///     .oidc_issuer("https://token.actions.githubusercontent.com");
+///
+/// let logged = verifier.verify_file("tool", &bundle).unwrap();
+/// println!("logged at index {}", logged.log_index);
+/// ```
+///
+pub struct Bundle {
+    certificates: Vec<Vec<u8>>, // The signing certificate first, then any intermediates
+    digest: Option<[u8; 32]>,   // The sha256 of the artifact, cosign bundles don't record it
+    signature: Vec<u8>,
+    entry: Option<LogEntry>,
+}
+
+impl Bundle {
+    /// Parse a bundle in either the protobuf JSON format or the older cosign format
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let bundle: Value =
+            serde_json::from_str(json).map_err(|err| malformed(&err.to_string()))?;
+
+        if bundle.get("mediaType").is_some() {
+            Bundle::from_protobuf(&bundle)
+        } else if bundle.get("base64Signature").is_some() {
+            Bundle::from_cosign(&bundle)
+        } else {
+            Err(malformed("unrecognized bundle format"))
+        }
+    }
+
+    fn from_protobuf(bundle: &Value) -> Result<Self, Error> {
+        if bundle.get("dsseEnvelope").is_some() {
+            return Err(malformed("DSSE envelopes aren't supported"));
+        }
+
+        let material = &bundle["verificationMaterial"];
+        let certificates = match (&material["certificate"], &material["x509CertificateChain"]) {
+            (Value::Null, Value::Null) => {
+                return Err(malformed("only certificate signed bundles are supported"))
+            }
+            (Value::Null, chain) => chain["certificates"]
+                .as_array()
+                .ok_or_else(|| malformed("certificate chain"))?
+                .iter()
+                .map(|certificate| base64_field(&certificate["rawBytes"], "certificate"))
+                .collect::<Result<Vec<_>, _>>()?,
+            (certificate, _) => vec![base64_field(&certificate["rawBytes"], "certificate")?],
+        };
+        if certificates.is_empty() {
+            return Err(malformed("certificate chain"));
+        }
+
+        let signature = &bundle["messageSignature"];
+        let algorithm = &signature["messageDigest"]["algorithm"];
+        let digest = match algorithm.as_str() {
+            Some("SHA2_256") => hash_field(&signature["messageDigest"]["digest"], "digest")?,
+            _ => return Err(malformed("only sha256 message digests are supported")),
+        };
+
+        let entry = match material["tlogEntries"].as_array().and_then(|e| e.first()) {
+            Some(entry) => Some(LogEntry::from_protobuf(entry)?),
+            None => None,
+        };
+
+        Ok(Bundle {
+            certificates,
+            digest: Some(digest),
+            signature: base64_field(&signature["signature"], "signature")?,
+            entry,
+        })
+    }
+
+    fn from_cosign(bundle: &Value) -> Result<Self, Error> {
+        let pem = base64_field(&bundle["cert"], "certificate")?;
+        let certificate = std::str::from_utf8(&pem)
+            .ok()
+            .and_then(pem_certificate)
+            .ok_or_else(|| malformed("certificate"))?;
+
+        let entry = match &bundle["rekorBundle"] {
+            Value::Null => None,
+            rekor_bundle => Some(LogEntry::from_cosign(rekor_bundle)?),
+        };
+
+        Ok(Bundle {
+            certificates: vec![certificate],
+            digest: None,
+            signature: base64_field(&bundle["base64Signature"], "signature")?,
+            entry,
+        })
+    }
+
+    /// The DER signing certificate
+    pub fn certificate(&self) -> &[u8] {
+        &self.certificates[0]
+    }
+}
+
+///
+/// A verified Sigstore bundle
+///
+/// `identity`: The email address or URI the signing certificate was issued to
+///
+/// `oidc_issuer`: The OpenID Connect issuer that vouched for the identity
+///
+/// `log_index`: The index of the entry in the transparency log
+///
+/// `integrated_time`: When the entry was added to the log, i.e. when the artifact was signed
+///
+/// `inclusion_proven`: Whether an inclusion proof was verified, rather than only the log's
+/// promise to include the entry
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigstoreVerification {
+    pub identity: Option<String>,
+    pub oidc_issuer: Option<String>,
+    pub log_index: u64,
+    pub integrated_time: SystemTime,
+    pub inclusion_proven: bool,
+}
+
+///
+/// Verifies Sigstore bundles against a set of trusted Fulcio certificates and Rekor keys,
+/// and optionally a pinned signing identity
+///
+#[derive(Debug, Clone, Default)]
+pub struct SigstoreVerifier {
+    fulcio_certificates: Vec<Vec<u8>>,
+    rekor_keys: Vec<Vec<u8>>,
+    identity: Option<String>,
+    oidc_issuer: Option<String>,
+}
+
+impl SigstoreVerifier {
+    /// A verifier that trusts nothing yet
+    pub fn new() -> Self {
+        SigstoreVerifier::default()
+    }
+
+    /// Trust signing certificates issued under this DER encoded Fulcio root or intermediate
+    pub fn fulcio_certificate(mut self, der: &[u8]) -> Self {
+        self.fulcio_certificates.push(der.to_vec());
+        self
+    }
+
+    /// Trust log entries signed with this Rekor key, a DER encoded SubjectPublicKeyInfo
+    pub fn rekor_key(mut self, der: &[u8]) -> Self {
+        self.rekor_keys.push(der.to_vec());
+        self
+    }
+
+    /// Require the signing certificate to be issued to this email address or URI
+    pub fn identity(mut self, identity: &str) -> Self {
+        self.identity = Some(identity.to_string());
+        self
+    }
+
+    /// Require the identity to be vouched for by this OpenID Connect issuer
+    pub fn oidc_issuer(mut self, issuer: &str) -> Self {
+        self.oidc_issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Verify the bundle for the file at the path
+    pub fn verify_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        bundle: &Bundle,
+    ) -> Result<SigstoreVerification, Error> {
+        let digest = digest::sha256_file(path.as_ref()).map_err(Error::IoError)?;
+        self.verify_digest(&digest, bundle)
+    }
+
+    /// Verify the bundle for an artifact held in memory
+    pub fn verify_bytes(
+        &self,
+        data: &[u8],
+        bundle: &Bundle,
+    ) -> Result<SigstoreVerification, Error> {
+        self.verify_digest(&digest::sha256(data), bundle)
+    }
+
+    /// Verify both the platform code signature and the Sigstore bundle of the file at the
+    /// path, failing unless both are valid
+    pub fn verify_with_code_signature<P: AsRef<Path>>(
+        &self,
+        path: P,
+        bundle: &Bundle,
+        options: &VerificationOptions,
+    ) -> Result<(SignatureContext, SigstoreVerification), Error> {
+        let ctx = CodeSignVerifier::for_file(path.as_ref())?.verify_with(options)?;
+        let logged = self.verify_file(path, bundle)?;
+        Ok((ctx, logged))
+    }
+
+    /// Verify the bundle for an artifact with the given sha256 digest
+    pub fn verify_digest(
+        &self,
+        digest: &[u8; 32],
+        bundle: &Bundle,
+    ) -> Result<SigstoreVerification, Error> {
+        if bundle.digest.map_or(false, |signed| signed != *digest) {
+            return Err(SigstoreFailure::DigestMismatch.into());
+        }
+
+        let certificates = bundle
+            .certificates
+            .iter()
+            .map(|der| Certificate::from_der(der).map_err(|_| malformed("certificate")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let leaf = &certificates[0];
+        if !PublicKey::of(leaf).map_or(false, |key| key.verify(digest, &bundle.signature)) {
+            return Err(SigstoreFailure::InvalidSignature.into());
+        }
+
+        let entry = bundle
+            .entry
+            .as_ref()
+            .ok_or(SigstoreFailure::MissingLogEntry)?;
+        check_entry_body(entry, digest, &bundle.signature)?;
+        let inclusion_proven = self.check_logged(entry)?;
+
+        // The certificates are short-lived, they only have to be valid when the entry was logged
+        self.check_chain(&certificates, entry.integrated_time())?;
+
+        let (identity, oidc_issuer) = identity(leaf);
+        if self.identity.is_some() && self.identity != identity
+            || self.oidc_issuer.is_some() && self.oidc_issuer != oidc_issuer
+        {
+            return Err(SigstoreFailure::IdentityMismatch.into());
+        }
+
+        Ok(SigstoreVerification {
+            identity,
+            oidc_issuer,
+            log_index: entry.log_index,
+            integrated_time: entry.integrated_time(),
+            inclusion_proven,
+        })
+    }
+
+    /// Check the entry was logged by a trusted log, through a verified inclusion proof with a
+    /// signed checkpoint, or the log's signed promise to include it. Returns whether inclusion
+    /// was proven. Only the promise, the signed entry timestamp, covers the integrated time
+    /// the certificates are checked at, so it has to verify either way.
+    fn check_logged(&self, entry: &LogEntry) -> Result<bool, Error> {
+        let body = BASE64
+            .decode(&entry.body)
+            .map_err(|_| malformed("tlog entry body"))?;
+
+        let inclusion_proven = match &entry.inclusion_proof {
+            Some(proof) => {
+                let leaf_hash = leaf_hash(&body);
+                if !verify_inclusion(
+                    proof.log_index,
+                    proof.tree_size,
+                    &leaf_hash,
+                    &proof.hashes,
+                    &proof.root_hash,
+                ) {
+                    return Err(SigstoreFailure::InclusionProofFailed.into());
+                }
+
+                let checkpoint = proof.checkpoint.as_deref().and_then(|note| {
+                    self.rekor_keys
+                        .iter()
+                        .find_map(|key| verify_checkpoint(note, key))
+                });
+                match checkpoint {
+                    Some((size, root)) if size == proof.tree_size && root == proof.root_hash => {
+                        true
+                    }
+                    Some(_) => return Err(SigstoreFailure::InclusionProofFailed.into()),
+                    None => false, // The entry timestamp may still vouch for the entry
+                }
+            }
+            None => false,
+        };
+        // A proven entry may be in the log, but its time is still whatever the bundle says
+        let untrusted = if inclusion_proven {
+            SigstoreFailure::UntrustedTime
+        } else {
+            SigstoreFailure::UntrustedLog
+        };
+
+        let set = match &entry.signed_entry_timestamp {
+            Some(set) => set,
+            None => return Err(untrusted.into()),
+        };
+        let payload = serde_json::json!({
+            "body": entry.body,
+            "integratedTime": entry.integrated_time,
+            "logID": entry.log_id,
+            "logIndex": entry.log_index,
+        });
+        // Without preserve_order, serde_json sorts the keys, as the canonical form requires
+        let digest = digest::sha256(payload.to_string().as_bytes());
+        let signed = self
+            .rekor_keys
+            .iter()
+            .any(|key| PublicKey::from_spki(key).map_or(false, |key| key.verify(&digest, set)));
+
+        if signed {
+            Ok(inclusion_proven)
+        } else {
+            Err(untrusted.into())
+        }
+    }
+
+    /// Follow the chain from the signing certificate up to a trusted Fulcio certificate,
+    /// checking each certificate was valid at the given time. The signing certificate has to
+    /// be for code signing, and every certificate above it a CA allowed to issue it.
+    fn check_chain(&self, certificates: &[Certificate], time: SystemTime) -> Result<(), Error> {
+        let trusted = self
+            .fulcio_certificates
+            .iter()
+            .filter_map(|der| Certificate::from_der(der).ok())
+            .collect::<Vec<_>>();
+
+        let mut current = &certificates[0];
+        if !is_code_signing(current) {
+            return Err(SigstoreFailure::UntrustedCertificate.into());
+        }
+
+        // The depth is the number of CAs between the current certificate and the leaf
+        for depth in 0..MAX_CHAIN_LENGTH {
+            let validity = &current.tbs_certificate.validity;
+            if time < validity.not_before.to_system_time()
+                || time > validity.not_after.to_system_time()
+            {
+                return Err(SigstoreFailure::CertificateExpired.into());
+            }
+
+            if trusted
+                .iter()
+                .any(|issuer| issued_by(current, issuer, depth))
+            {
+                return Ok(());
+            }
+            current = certificates[1..]
+                .iter()
+                .find(|issuer| issued_by(current, issuer, depth))
+                .ok_or(SigstoreFailure::UntrustedCertificate)?;
+        }
+        Err(SigstoreFailure::UntrustedCertificate.into())
+    }
+}
+
+/// Check the log entry records the same artifact and signature as the bundle
+fn check_entry_body(entry: &LogEntry, digest: &[u8; 32], signature: &[u8]) -> Result<(), Error> {
+    let body: Value = BASE64
+        .decode(&entry.body)
+        .ok()
+        .and_then(|body| serde_json::from_slice(&body).ok())
+        .ok_or_else(|| malformed("tlog entry body"))?;
+
+    let hash = &body["spec"]["data"]["hash"];
+    let logged_signature = body["spec"]["signature"]["content"]
+        .as_str()
+        .and_then(|content| BASE64.decode(content).ok());
+
+    if body["kind"] != "hashedrekord"
+        || hash["algorithm"] != "sha256"
+        || hash["value"] != digest::hex(digest).as_str()
+        || logged_signature.as_deref() != Some(signature)
+    {
+        return Err(SigstoreFailure::LogEntryMismatch.into());
+    }
+    Ok(())
+}
+
+/// The DER value of the certificate's extension with the OID
+fn extension(certificate: &Certificate, oid: ObjectIdentifier) -> Option<&[u8]> {
+    certificate
+        .tbs_certificate
+        .extensions
+        .as_deref()?
+        .iter()
+        .find(|extension| extension.extn_id == oid)
+        .map(|extension| extension.extn_value.as_bytes())
+}
+
+/// The email address or URI the certificate was issued to, and the OIDC issuer that vouched
+fn identity(certificate: &Certificate) -> (Option<String>, Option<String>) {
+    let extension = |oid| extension(certificate, oid);
+
+    let identity = extension(SUBJECT_ALT_NAME)
+        .and_then(|der| SubjectAltName::from_der(der).ok())
+        .and_then(|names| {
+            names.0.into_iter().find_map(|name| match name {
+                GeneralName::Rfc822Name(email) => Some(email.to_string()),
+                GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+                _ => None,
+            })
+        });
+    let issuer = extension(FULCIO_ISSUER_V2)
+        .and_then(|der| Utf8StringRef::from_der(der).ok())
+        .map(|issuer| issuer.as_str().to_string())
+        .or_else(|| {
+            extension(FULCIO_ISSUER_V1).and_then(|raw| String::from_utf8(raw.to_vec()).ok())
+        });
+
+    (identity, issuer)
+}
+
+/// Whether the certificate's extended key usages allow code signing, as Fulcio's always do
+fn is_code_signing(certificate: &Certificate) -> bool {
+    extension(certificate, EXTENDED_KEY_USAGE)
+        .and_then(|der| ExtendedKeyUsage::from_der(der).ok())
+        .map_or(false, |usage| usage.0.contains(&CODE_SIGNING))
+}
+
+/// Whether the issuer is a CA that may sign certificates with this many CAs below it
+fn is_ca(issuer: &Certificate, depth: usize) -> bool {
+    let constraints =
+        extension(issuer, BASIC_CONSTRAINTS).and_then(|der| BasicConstraints::from_der(der).ok());
+    let path_len_allows = match constraints {
+        Some(constraints) if constraints.ca => constraints
+            .path_len_constraint
+            .map_or(true, |path_len| depth <= path_len as usize),
+        _ => return false,
+    };
+    // Without the extension, the key usage is unrestricted
+    let may_sign = extension(issuer, KEY_USAGE).map_or(true, |der| {
+        KeyUsage::from_der(der).map_or(false, |usage| usage.key_cert_sign())
+    });
+
+    path_len_allows && may_sign
+}
+
+/// Whether the certificate was issued by the issuer, a CA with this many CAs below it
+fn issued_by(certificate: &Certificate, issuer: &Certificate, depth: usize) -> bool {
+    is_ca(issuer, depth) && signed_by(certificate, issuer)
+}
+
+/// Whether the certificate was issued and signed by the issuer
+fn signed_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    let names_match = certificate.tbs_certificate.issuer.to_der().ok()
+        == issuer.tbs_certificate.subject.to_der().ok();
+    let tbs = match certificate.tbs_certificate.to_der() {
+        Ok(tbs) if names_match => tbs,
+        _ => return false,
+    };
+
+    let algorithm = certificate.signature_algorithm.oid;
+    let digest = if algorithm == ECDSA_WITH_SHA256 {
+        sha2::Sha256::digest(&tbs).to_vec()
+    } else if algorithm == ECDSA_WITH_SHA384 {
+        sha2::Sha384::digest(&tbs).to_vec()
+    } else {
+        return false;
+    };
+    match (certificate.signature.as_bytes(), PublicKey::of(issuer)) {
+        (Some(signature), Some(key)) => key.verify(&digest, signature),
+        _ => false,
+    }
+}
+
+/// An ECDSA key on one of the curves Sigstore uses
+enum PublicKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+}
+
+impl PublicKey {
+    fn from_spki(der: &[u8]) -> Option<Self> {
+        use p256::pkcs8::DecodePublicKey;
+
+        p256::ecdsa::VerifyingKey::from_public_key_der(der)
+            .map(PublicKey::P256)
+            .or_else(|_| p384::ecdsa::VerifyingKey::from_public_key_der(der).map(PublicKey::P384))
+            .ok()
+    }
+
+    fn of(certificate: &Certificate) -> Option<Self> {
+        let spki = certificate
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .ok()?;
+        PublicKey::from_spki(&spki)
+    }
+
+    /// Verify a DER encoded signature over a message digest
+    fn verify(&self, digest: &[u8], signature: &[u8]) -> bool {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+        match self {
+            PublicKey::P256(key) => p256::ecdsa::Signature::from_der(signature)
+                .map_or(false, |signature| {
+                    key.verify_prehash(digest, &signature).is_ok()
+                }),
+            PublicKey::P384(key) => p384::ecdsa::Signature::from_der(signature)
+                .map_or(false, |signature| {
+                    key.verify_prehash(digest, &signature).is_ok()
+                }),
+        }
+    }
+}
+
+/// The RFC 6962 hash of a log leaf
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// The RFC 6962 hash of an interior node
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Verify a Merkle inclusion proof, as described in RFC 9162 section 2.1.3.2
+fn verify_inclusion(
+    index: u64,
+    tree_size: u64,
+    leaf_hash: &[u8; 32],
+    proof: &[[u8; 32]],
+    root_hash: &[u8; 32],
+) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+
+    let (mut fnode, mut snode) = (index, tree_size - 1);
+    let mut hash = *leaf_hash;
+    for sibling in proof {
+        if snode == 0 {
+            return false;
+        }
+        if fnode & 1 == 1 || fnode == snode {
+            hash = node_hash(sibling, &hash);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+
+    snode == 0 && hash == *root_hash
+}
+
+/// Verify a checkpoint, a signed note with the log origin, tree size and root hash, against
+/// the log key. Returns the tree size and root hash when one of its signatures verifies.
+fn verify_checkpoint(note: &str, key: &[u8]) -> Option<(u64, [u8; 32])> {
+    let split = note.find("\n\n")?;
+    let (text, signatures) = (&note[..split + 1], &note[split + 2..]);
+
+    let mut lines = text.lines();
+    let _origin = lines.next()?;
+    let size = lines.next()?.parse().ok()?;
+    let root: [u8; 32] = BASE64.decode(lines.next()?).ok()?.try_into().ok()?;
+
+    // Each signature line is "— <name> <base64 of a 4 byte key hint and the signature>"
+    let key_hint = &digest::sha256(key)[..4];
+    let public_key = PublicKey::from_spki(key)?;
+    let digest = digest::sha256(text.as_bytes());
+    let signed = signatures.lines().any(|line| {
+        let signature = line
+            .strip_prefix("\u{2014} ")
+            .and_then(|line| line.rsplit(' ').next())
+            .and_then(|signature| BASE64.decode(signature).ok());
+        match signature {
+            Some(signature) if signature.len() > 4 && &signature[..4] == key_hint => {
+                public_key.verify(&digest, &signature[4..])
+            }
+            _ => false,
+        }
+    });
+
+    signed.then_some((size, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+    use p256::pkcs8::EncodePublicKey;
+    use std::str::FromStr;
+    use x509_cert::der::asn1::{BitString, OctetString};
+    use x509_cert::ext::Extension;
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+    use x509_cert::time::Validity;
+    use x509_cert::{TbsCertificate, Version};
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_slice(&[seed; 32]).unwrap()
+    }
+
+    fn public_key(key: &SigningKey) -> Vec<u8> {
+        key.verifying_key().to_public_key_der().unwrap().into_vec()
+    }
+
+    fn extension<T: Encode>(oid: ObjectIdentifier, value: T) -> Extension {
+        Extension {
+            extn_id: oid,
+            critical: false,
+            extn_value: OctetString::new(value.to_der().unwrap()).unwrap(),
+        }
+    }
+
+    /// A certificate for the key, signed by the issuer's key
+    fn certificate(
+        subject: &str,
+        key: &SigningKey,
+        issuer: &str,
+        issuer_key: &SigningKey,
+        extensions: Vec<Extension>,
+    ) -> Certificate {
+        let algorithm = AlgorithmIdentifierOwned {
+            oid: ECDSA_WITH_SHA256,
+            parameters: None,
+        };
+        let tbs_certificate = TbsCertificate {
+            version: Version::V3,
+            serial_number: SerialNumber::new(&[1]).unwrap(),
+            signature: algorithm.clone(),
+            issuer: Name::from_str(issuer).unwrap(),
+            validity: Validity::from_now(Duration::from_secs(600)).unwrap(),
+            subject: Name::from_str(subject).unwrap(),
+            subject_public_key_info: SubjectPublicKeyInfoOwned::from_der(&public_key(key)).unwrap(),
+            issuer_unique_id: None,
+            subject_unique_id: None,
+            extensions: Some(extensions),
+        };
+
+        let signature: p256::ecdsa::DerSignature =
+            issuer_key.sign(&tbs_certificate.to_der().unwrap());
+        Certificate {
+            tbs_certificate,
+            signature_algorithm: algorithm,
+            signature: BitString::from_bytes(signature.as_bytes()).unwrap(),
+        }
+    }
+
+    /// A checkpoint note for the tree, signed with the key
+    fn checkpoint(key: &SigningKey, size: u64, root: &[u8; 32]) -> String {
+        let text = format!("rekor.example - 1\n{}\n{}\n", size, BASE64.encode(root));
+        let signature: p256::ecdsa::DerSignature = key.sign(text.as_bytes());
+        let mut signed = digest::sha256(&public_key(key))[..4].to_vec();
+        signed.extend_from_slice(signature.as_bytes());
+        format!(
+            "{}\n\u{2014} rekor.example {}\n",
+            text,
+            BASE64.encode(&signed)
+        )
+    }
+
+    /// The RFC 6962 tree hash of the leaves, and the inclusion proof of the leaf at the index
+    fn tree(leaves: &[[u8; 32]], index: usize) -> ([u8; 32], Vec<[u8; 32]>) {
+        if leaves.len() == 1 {
+            return (leaves[0], Vec::new());
+        }
+        let split = leaves.len().next_power_of_two() / 2;
+        let (left, right) = leaves.split_at(split);
+        let (left_hash, mut left_proof) = tree(left, index.min(split - 1));
+        let (right_hash, mut right_proof) = tree(right, index.saturating_sub(split));
+
+        let proof = if index < split {
+            left_proof.push(right_hash);
+            left_proof
+        } else {
+            right_proof.push(left_hash);
+            right_proof
+        };
+        (node_hash(&left_hash, &right_hash), proof)
+    }
+
+    #[test]
+    fn test_inclusion_proof() {
+        let leaves: Vec<[u8; 32]> = (0..7u8).map(|i| leaf_hash(&[i])).collect();
+        for index in 0..leaves.len() {
+            let (root, proof) = tree(&leaves, index);
+            let size = leaves.len() as u64;
+            let verifies = |index, leaf| verify_inclusion(index, size, leaf, &proof, &root);
+            let other = (index + 1) % leaves.len();
+            assert!(verifies(index as u64, &leaves[index]));
+            assert!(!verifies(index as u64, &leaves[other]));
+            assert!(!verifies(size, &leaves[index]));
+        }
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let signing_key = key(7);
+        let key = public_key(&signing_key);
+        let root = [0xab; 32];
+
+        let note = checkpoint(&signing_key, 42, &root);
+        assert_eq!(verify_checkpoint(&note, &key), Some((42, root)));
+        let tampered = note.replace("\n42\n", "\n43\n");
+        assert_eq!(verify_checkpoint(&tampered, &key), None);
+    }
+
+    #[test]
+    fn test_chain() {
+        let (root_key, leaf_key, forged_key) = (key(1), key(2), key(3));
+        let ca = BasicConstraints {
+            ca: true,
+            path_len_constraint: None,
+        };
+        let code_signing = ExtendedKeyUsage(vec![CODE_SIGNING]);
+
+        let root = certificate(
+            "CN=root",
+            &root_key,
+            "CN=root",
+            &root_key,
+            vec![extension(BASIC_CONSTRAINTS, ca)],
+        );
+        let leaf = certificate(
+            "CN=leaf",
+            &leaf_key,
+            "CN=root",
+            &root_key,
+            vec![extension(EXTENDED_KEY_USAGE, code_signing.clone())],
+        );
+        let verifier = SigstoreVerifier::new().fulcio_certificate(&root.to_der().unwrap());
+        let now = SystemTime::now();
+        assert!(verifier.check_chain(&[leaf.clone()], now).is_ok());
+
+        // A leaf can't issue certificates, whatever identity they claim
+        let forged = certificate(
+            "CN=forged",
+            &forged_key,
+            "CN=leaf",
+            &leaf_key,
+            vec![extension(EXTENDED_KEY_USAGE, code_signing)],
+        );
+        assert!(matches!(
+            verifier.check_chain(&[forged, leaf], now),
+            Err(Error::Sigstore(SigstoreFailure::UntrustedCertificate))
+        ));
+
+        // Nor is a certificate that isn't for code signing accepted
+        let server = certificate("CN=server", &leaf_key, "CN=root", &root_key, Vec::new());
+        assert!(matches!(
+            verifier.check_chain(&[server], now),
+            Err(Error::Sigstore(SigstoreFailure::UntrustedCertificate))
+        ));
+    }
+
+    #[test]
+    fn test_logged_time() {
+        let log_key = key(7);
+        let verifier = SigstoreVerifier::new().rekor_key(&public_key(&log_key));
+        let body = BASE64.encode(br#"{"kind": "hashedrekord"}"#);
+        let root = leaf_hash(&BASE64.decode(&body).unwrap());
+
+        let mut entry = LogEntry {
+            log_index: 0,
+            log_id: "00".to_string(),
+            integrated_time: 1_700_000_000,
+            body,
+            signed_entry_timestamp: None,
+            inclusion_proof: Some(InclusionProof {
+                log_index: 0,
+                tree_size: 1,
+                root_hash: root,
+                hashes: Vec::new(),
+                checkpoint: Some(checkpoint(&log_key, 1, &root)),
+            }),
+        };
+        // Proven to be in the log, but nothing signs when
+        assert!(matches!(
+            verifier.check_logged(&entry),
+            Err(Error::Sigstore(SigstoreFailure::UntrustedTime))
+        ));
+
+        let payload = serde_json::json!({
+            "body": entry.body,
+            "integratedTime": entry.integrated_time,
+            "logID": entry.log_id,
+            "logIndex": entry.log_index,
+        });
+        let set: p256::ecdsa::DerSignature = log_key.sign(payload.to_string().as_bytes());
+        entry.signed_entry_timestamp = Some(set.as_bytes().to_vec());
+        assert!(verifier.check_logged(&entry).unwrap());
+
+        // A time moved back, e.g. into a certificate's validity, no longer matches the signature
+        entry.integrated_time -= 3600;
+        assert!(matches!(
+            verifier.check_logged(&entry),
+            Err(Error::Sigstore(SigstoreFailure::UntrustedTime))
+        ));
+    }
+
+    #[test]
+    fn test_bundle_format() {
+        assert!(matches!(
+            Bundle::from_json("{}"),
+            Err(Error::Sigstore(SigstoreFailure::MalformedBundle(_)))
+        ));
+        let dsse = r#"{"mediaType": "application/vnd.dev.sigstore.bundle.v0.3+json",
+                       "dsseEnvelope": {}}"#;
+        assert!(matches!(
+            Bundle::from_json(dsse),
+            Err(Error::Sigstore(SigstoreFailure::MalformedBundle(_)))
+        ));
+    }
+}
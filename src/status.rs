@@ -0,0 +1,96 @@
+//!
+//! The status codes the platform verification APIs report most often.
+//!
+//! Errors that wrap an OS code keep it as an `i32`, see `Error::code`. These constants
+//! let callers match on it without looking the values up, though the predicates on
+//! `Error`, such as `Error::is_untrusted_root`, cover the common decisions on both
+//! platforms and are usually what's wanted.
+//!
+
+#![allow(non_upper_case_globals)]
+
+/// The code isn't signed at all
+#[cfg(target_os = "macos")]
+pub const errSecCSUnsigned: i32 = -67062;
+/// The signature doesn't match the code, it was modified after signing
+#[cfg(target_os = "macos")]
+pub const errSecCSSignatureFailed: i32 = -67061;
+/// The bundle has resources, but no resource seal
+#[cfg(target_os = "macos")]
+pub const errSecCSResourcesNotSealed: i32 = -67057;
+/// The resource seal is missing from the bundle
+#[cfg(target_os = "macos")]
+pub const errSecCSResourcesNotFound: i32 = -67056;
+/// The resource seal is malformed
+#[cfg(target_os = "macos")]
+pub const errSecCSResourcesInvalid: i32 = -67055;
+/// A sealed resource was modified, added or removed
+#[cfg(target_os = "macos")]
+pub const errSecCSBadResource: i32 = -67054;
+/// The signature is valid, but doesn't satisfy the requirement
+#[cfg(target_os = "macos")]
+pub const errSecCSReqFailed: i32 = -67050;
+/// The signature itself is malformed
+#[cfg(target_os = "macos")]
+pub const errSecCSSignatureInvalid: i32 = -67045;
+/// The resource directory of the signature doesn't match the bundle
+#[cfg(target_os = "macos")]
+pub const errSecCSResourceDirectoryFailed: i32 = -67023;
+/// The certificate chain doesn't end at a trusted root
+#[cfg(target_os = "macos")]
+pub const errSecNotTrusted: i32 = -67843;
+/// A certificate in the chain is expired
+#[cfg(target_os = "macos")]
+pub const errSecCertificateExpired: i32 = -67818;
+/// A certificate in the chain isn't valid yet
+#[cfg(target_os = "macos")]
+pub const errSecCertificateNotValidYet: i32 = -67819;
+/// A certificate in the chain was revoked
+#[cfg(target_os = "macos")]
+pub const errSecCertificateRevoked: i32 = -67820;
+
+/// The file isn't signed, or its signature is malformed
+#[cfg(windows)]
+pub const TRUST_E_NOSIGNATURE: i32 = 0x800B0100u32 as i32;
+/// The file type can't carry a signature
+#[cfg(windows)]
+pub const TRUST_E_SUBJECT_FORM_UNKNOWN: i32 = 0x800B0003u32 as i32;
+/// No trust provider handles the file type
+#[cfg(windows)]
+pub const TRUST_E_PROVIDER_UNKNOWN: i32 = 0x800B0001u32 as i32;
+/// The file doesn't match the hash its signature covers, it was modified after signing
+#[cfg(windows)]
+pub const TRUST_E_BAD_DIGEST: i32 = 0x80096010u32 as i32;
+/// A certificate signature in the chain couldn't be verified
+#[cfg(windows)]
+pub const TRUST_E_CERT_SIGNATURE: i32 = 0x80096004u32 as i32;
+/// The timestamp signature or its certificate couldn't be verified
+#[cfg(windows)]
+pub const TRUST_E_TIME_STAMP: i32 = 0x80096005u32 as i32;
+/// The signature has no signing certificate
+#[cfg(windows)]
+pub const TRUST_E_NO_SIGNER_CERT: i32 = 0x80096002u32 as i32;
+/// A certificate in the chain was explicitly marked as untrusted
+#[cfg(windows)]
+pub const TRUST_E_EXPLICIT_DISTRUST: i32 = 0x800B0111u32 as i32;
+/// The certificate chain doesn't end at a trusted root
+#[cfg(windows)]
+pub const CERT_E_UNTRUSTEDROOT: i32 = 0x800B0109u32 as i32;
+/// The certificate chain couldn't be built up to a root
+#[cfg(windows)]
+pub const CERT_E_CHAINING: i32 = 0x800B010Au32 as i32;
+/// A certificate in the chain is expired, or not valid yet
+#[cfg(windows)]
+pub const CERT_E_EXPIRED: i32 = 0x800B0101u32 as i32;
+/// A certificate in the chain was revoked by its issuer
+#[cfg(windows)]
+pub const CERT_E_REVOKED: i32 = 0x800B010Cu32 as i32;
+/// A certificate in the chain was revoked
+#[cfg(windows)]
+pub const CRYPT_E_REVOKED: i32 = 0x80092010u32 as i32;
+/// Revocation couldn't be checked
+#[cfg(windows)]
+pub const CRYPT_E_NO_REVOCATION_CHECK: i32 = 0x80092012u32 as i32;
+/// Revocation couldn't be checked because the revocation server was offline
+#[cfg(windows)]
+pub const CRYPT_E_REVOCATION_OFFLINE: i32 = 0x80092013u32 as i32;
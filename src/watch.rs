@@ -0,0 +1,218 @@
+//!
+//! Watching directories for new executables.
+//!
+//! A `Watcher` follows changes under its directories, with FSEvents on macOS and
+//! ReadDirectoryChangesW on Windows, and verifies each executable file created, modified or
+//! renamed into place. Files are only verified once they've stopped changing for the settle
+//! time, so an installer still writing one isn't reported as tampered halfway through.
+//!
+//! Results are delivered as `ScannedFile`s, like a directory scan's, to a callback or a
+//! channel. Verification happens on a thread of the watch's own, one file at a time.
+//!
+
+use crate::scan::{Scan, ScannedFile};
+use crate::{CodeSignVerifier, Error, VerificationOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "macos")]
+use crate::macos::watch as platform;
+#[cfg(windows)]
+use crate::windows::watch as platform;
+
+///
+/// Watches directory trees, verifying executables as they appear
+///
+/// # Examples
+///
+/// ```no_run
+/// use codesign_verify::watch::Watcher;
+///
+/// let (watch, files) = Watcher::new().directory("/Applications").start_channel().unwrap();
+/// for file in files {
+///     if file.result.is_err() {
+///         println!("{} failed verification", file.path.display());
+///     }
+/// }
+/// # drop(watch);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    directories: Vec<PathBuf>,
+    options: VerificationOptions,
+    settle: Duration,
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Watcher::new()
+    }
+}
+
+impl Watcher {
+    /// Watch nothing yet, verifying with the default options once a file settles for a second
+    pub fn new() -> Self {
+        Watcher {
+            directories: Vec::new(),
+            options: VerificationOptions::new(),
+            settle: Duration::from_secs(1),
+        }
+    }
+
+    /// Watch this directory and everything below it
+    pub fn directory<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.directories.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Verify each file with the given options
+    pub fn options(mut self, options: VerificationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Wait until a file has gone this long without changing before verifying it
+    pub fn settle_time(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// Start watching, calling `callback` with each verified file until the handle is dropped
+    pub fn start<F>(self, callback: F) -> Result<WatchHandle, Error>
+    where
+        F: FnMut(ScannedFile) + Send + 'static,
+    {
+        if self.directories.is_empty() {
+            return Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "a watch needs at least one directory",
+            )));
+        }
+
+        let (events, changes) = mpsc::channel();
+        let platform = platform::watch(&self.directories, events)?;
+        let worker = std::thread::spawn(move || self.run(changes, callback));
+
+        Ok(WatchHandle {
+            platform: Some(platform),
+            worker: Some(worker),
+        })
+    }
+
+    /// Start watching, sending each verified file to the returned channel. The channel
+    /// closes once the handle is dropped.
+    pub fn start_channel(self) -> Result<(WatchHandle, Receiver<ScannedFile>), Error> {
+        let (sender, receiver) = mpsc::channel();
+        let handle = self.start(move |file| {
+            let _ = sender.send(file);
+        })?;
+        Ok((handle, receiver))
+    }
+
+    /// Verify changed files once they settle, until the platform watch is dropped
+    fn run<F: FnMut(ScannedFile)>(&self, changes: Receiver<PathBuf>, mut callback: F) {
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
+        loop {
+            let wait = pending
+                .values()
+                .min()
+                .map(|changed| (*changed + self.settle).saturating_duration_since(Instant::now()));
+            let change = match wait {
+                Some(wait) => changes.recv_timeout(wait),
+                None => changes.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match change {
+                Ok(path) => {
+                    pending.insert(path, Instant::now());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            let settled = pending
+                .iter()
+                .filter(|(_, changed)| now >= **changed + self.settle)
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>();
+            for path in settled {
+                pending.remove(&path);
+                if let Some(file) = self.verify(path) {
+                    callback(file);
+                }
+            }
+        }
+    }
+
+    /// Verify the file if it's still there and is an executable
+    fn verify(&self, path: PathBuf) -> Option<ScannedFile> {
+        if !std::fs::metadata(&path).ok()?.is_file() {
+            return None;
+        }
+        let format = Scan::format(&path)?;
+        let result = CodeSignVerifier::for_file(&path).and_then(|v| v.verify_with(&self.options));
+
+        Some(ScannedFile {
+            path,
+            format,
+            result,
+        })
+    }
+}
+
+///
+/// A running watch, stopped when dropped
+///
+pub struct WatchHandle {
+    platform: Option<platform::DirectoryWatch>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Stop watching, waiting for the file being verified, if any, to finish. Changes that
+    /// haven't settled yet are dropped.
+    pub fn stop(self) {}
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        // Stopping the platform watch drops the senders, which ends the worker
+        drop(self.platform.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watcher;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watch() {
+        let root = crate::temp_path();
+        std::fs::create_dir_all(&root).unwrap();
+
+        let (watch, files) = Watcher::new()
+            .directory(&root)
+            .settle_time(Duration::from_millis(100))
+            .start_channel()
+            .unwrap();
+        std::fs::write(root.join("notes.txt"), "text").unwrap();
+        std::fs::write(root.join("a.sh"), "#!/bin/sh\n").unwrap();
+
+        let file = files.recv_timeout(Duration::from_secs(10)).unwrap();
+        watch.stop();
+        assert!(files.recv().is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(file.path.file_name().unwrap(), "a.sh");
+        assert!(file.result.is_err());
+    }
+}
@@ -0,0 +1,609 @@
+//!
+//! Evaluating files against App Control for Business (WDAC) policies, Windows only.
+//!
+//! `WdacPolicy` reads the policy XML that `New-CIPolicy` and the App Control wizard write, and
+//! decides whether a file would be allowed to run under it, so policy authoring tools can
+//! check a policy against the files it's meant to cover before deploying it.
+//!
+//! Publisher and signer rules (`CertRoot` TBS hashes, with `CertPublisher`, `CertIssuer`,
+//! `CertEKU` and `FileAttribRef` refinements), sha256 Authenticode hash rules, file path rules
+//! and file name and version rules are evaluated. Like Windows, a matching deny rule wins over
+//! any allow rule, and a file nothing allows is blocked. What the evaluation doesn't cover:
+//!
+//! * SHA1 hash and TBS values, policies list the sha256 equivalents alongside them
+//! * Well-known roots, page hashes, packaged app and COM object rules, which never match
+//! * The Intelligent Security Graph and managed installer options
+//! * Supplemental policies, each one is evaluated on its own
+//!
+
+use crate::xml::{self, Element};
+use crate::{der, digest, PlatformProperties, SignatureContext};
+use sha2::Digest;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The value of the `SigningScenario` for drivers
+const SCENARIO_KERNEL_MODE: u32 = 131;
+/// The value of the `SigningScenario` for everything else
+const SCENARIO_USER_MODE: u32 = 12;
+const AUDIT_MODE: &str = "Enabled:Audit Mode";
+
+///
+/// Why a policy couldn't be loaded
+///
+#[derive(Debug)]
+pub enum WdacError {
+    Io(std::io::Error), // Reading the file failed
+    Syntax,             // The file isn't well formed XML
+    NotAPolicy,         // The document isn't an `SiPolicy`
+}
+
+impl std::fmt::Display for WdacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WdacError::Io(err) => write!(f, "{}", err),
+            WdacError::Syntax => write!(f, "the policy isn't well formed XML"),
+            WdacError::NotAPolicy => write!(f, "the document isn't an App Control policy"),
+        }
+    }
+}
+
+impl std::error::Error for WdacError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WdacError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+///
+/// The code a policy's rules are evaluated for
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningScenario {
+    UserMode,   // Applications, scripts and DLLs
+    KernelMode, // Drivers
+}
+
+///
+/// What a policy decides for a file
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Allowed(String), // The ID of the signer or file rule that allows the file
+    Denied(String),  // The ID of the signer or file rule that denies the file
+    NotAllowed,      // No rule matched, which blocks the file as well
+}
+
+impl Decision {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allowed(_))
+    }
+}
+
+/// A file version, in the order it compares
+type Version = [u16; 4];
+
+/// An `Allow`, `Deny` or `FileAttrib` element of the `FileRules`
+#[derive(Debug)]
+struct FileRule {
+    id: String,
+    deny: bool,
+    hash: Option<String>,
+    file_name: Option<String>,
+    file_path: Option<String>,
+    product_name: Option<String>,
+    minimum_version: Option<Version>,
+    maximum_version: Option<Version>,
+    unsupported: bool, // Has conditions that aren't evaluated, so it never matches
+}
+
+#[derive(Debug)]
+struct Signer {
+    id: String,
+    roots: Vec<String>, // The hex TBS hashes of the certificates it trusts
+    publisher: Option<String>,
+    issuer: Option<String>,
+    ekus: Vec<String>, // The IDs of the EKUs, one of which the leaf must carry
+    file_attribs: Vec<String>, // The IDs of the file attributes, one of which must match
+}
+
+#[derive(Debug)]
+struct Scenario {
+    value: u32,
+    allowed_signers: Vec<String>,
+    denied_signers: Vec<String>,
+    file_rules: Vec<String>,
+}
+
+/// What the rules are matched against
+#[derive(Default)]
+struct Subject {
+    path: Option<String>,
+    authenticode: Option<String>,
+    chain: Vec<Vec<u8>>,
+    publisher: Option<String>,
+    issuer: Option<String>,
+    ekus: Vec<String>,
+    original_filename: Option<String>,
+    product_name: Option<String>,
+    version: Option<Version>,
+}
+
+/// Parse the leading numbers of a file version string, such as "10.0.19041.1 (WinBuild...)"
+fn version(text: &str) -> Option<Version> {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',' || c == ' '))
+        .unwrap_or(text.len());
+    let mut version = [0u16; 4];
+    let parts = text[..end]
+        .split(['.', ','])
+        .map(str::trim)
+        .filter(|part| !part.is_empty());
+    let mut count = 0;
+    for (slot, part) in version.iter_mut().zip(parts) {
+        *slot = part.parse().ok()?;
+        count += 1;
+    }
+    (count > 0).then_some(version)
+}
+
+/// Expand the macros path rules are written with
+fn expand(path: &str) -> String {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    let windir = var("windir");
+    path.replace("%OSDRIVE%", &var("SystemDrive"))
+        .replace("%WINDIR%", &windir)
+        .replace("%SYSTEM32%", &format!("{}\\System32", windir))
+        .replace('/', "\\")
+}
+
+/// Match a path against a rule's pattern, where `*` matches any run of characters
+fn wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last star swallow one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The hex hash of a certificate's TBSCertificate, with the algorithm the value's length implies
+fn tbs_hash(certificate: &[u8], len: usize) -> Option<String> {
+    let tbs = der::tbs_certificate(certificate)?;
+    match len {
+        64 => Some(digest::hex(&sha2::Sha256::digest(tbs))),
+        96 => Some(digest::hex(&sha2::Sha384::digest(tbs))),
+        128 => Some(digest::hex(&sha2::Sha512::digest(tbs))),
+        _ => None,
+    }
+}
+
+impl FileRule {
+    fn parse(element: &Element) -> FileRule {
+        let attribute = |name: &str| element.attribute(name).map(str::to_string);
+        let hash = attribute("Hash");
+        let unsupported = ["InternalName", "FileDescription", "PackageFamilyName", "AppIDs"]
+            .iter()
+            .any(|name| element.attribute(name).is_some())
+            // Only sha256 hashes are computed
+            || hash.as_ref().map_or(false, |hash| hash.len() != 64);
+
+        FileRule {
+            id: attribute("ID").unwrap_or_default(),
+            deny: element.name == "Deny",
+            hash,
+            file_name: attribute("FileName"),
+            file_path: attribute("FilePath"),
+            product_name: attribute("ProductName"),
+            minimum_version: element.attribute("MinimumFileVersion").and_then(version),
+            maximum_version: element.attribute("MaximumFileVersion").and_then(version),
+            unsupported,
+        }
+    }
+
+    fn matches(&self, subject: &Subject) -> bool {
+        if self.unsupported {
+            return false;
+        }
+
+        let checks = [
+            self.hash.as_ref().map(|hash| {
+                subject.authenticode.as_ref().map_or(false, |authenticode| {
+                    authenticode.eq_ignore_ascii_case(hash)
+                })
+            }),
+            self.file_name.as_ref().map(|file_name| {
+                file_name == "*"
+                    || subject
+                        .original_filename
+                        .as_ref()
+                        .map_or(false, |name| name.eq_ignore_ascii_case(file_name))
+            }),
+            self.file_path.as_ref().map(|pattern| {
+                subject
+                    .path
+                    .as_ref()
+                    .map_or(false, |path| wildcard(&expand(pattern), path))
+            }),
+            self.product_name.as_ref().map(|product_name| {
+                subject
+                    .product_name
+                    .as_ref()
+                    .map_or(false, |name| name.eq_ignore_ascii_case(product_name))
+            }),
+            self.minimum_version
+                .map(|minimum| subject.version.map_or(false, |version| version >= minimum)),
+            self.maximum_version
+                .map(|maximum| subject.version.map_or(false, |version| version <= maximum)),
+        ];
+
+        // A rule needs at least one condition, and all of them to match
+        checks.iter().any(Option::is_some) && checks.iter().all(|check| check.unwrap_or(true))
+    }
+}
+
+impl Signer {
+    fn parse(element: &Element) -> Signer {
+        let values = |name: &str| {
+            element
+                .children(name)
+                .filter_map(|child| child.attribute("Value").map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        let ids = |name: &str, attribute: &str| {
+            element
+                .children(name)
+                .filter_map(|child| child.attribute(attribute).map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        Signer {
+            id: element.attribute("ID").unwrap_or_default().to_string(),
+            roots: element
+                .children("CertRoot")
+                .filter(|root| root.attribute("Type") == Some("TBS"))
+                .filter_map(|root| root.attribute("Value").map(str::to_string))
+                .collect(),
+            publisher: values("CertPublisher").into_iter().next(),
+            issuer: values("CertIssuer").into_iter().next(),
+            ekus: ids("CertEKU", "ID"),
+            file_attribs: ids("FileAttribRef", "RuleID"),
+        }
+    }
+}
+
+///
+/// An App Control for Business policy, see the module documentation
+///
+#[derive(Debug)]
+pub struct WdacPolicy {
+    policy_id: Option<String>,
+    audit_mode: bool,
+    ekus: HashMap<String, String>, // The hex values of the EKUs, by ID
+    file_rules: Vec<FileRule>,
+    signers: Vec<Signer>,
+    scenarios: Vec<Scenario>,
+}
+
+impl WdacPolicy {
+    /// Parse a policy from its XML
+    pub fn parse(xml: &str) -> Result<WdacPolicy, WdacError> {
+        let policy = xml::parse(xml).ok_or(WdacError::Syntax)?;
+        if policy.name != "SiPolicy" {
+            return Err(WdacError::NotAPolicy);
+        }
+        let section = |name: &str| {
+            policy
+                .child(name)
+                .into_iter()
+                .flat_map(|section| &section.children)
+        };
+
+        let audit_mode = section("Rules")
+            .filter_map(|rule| rule.child("Option"))
+            .any(|option| option.text == AUDIT_MODE);
+        let ekus = section("EKUs")
+            .filter_map(|eku| {
+                Some((
+                    eku.attribute("ID")?.to_string(),
+                    eku.attribute("Value")?.to_string(),
+                ))
+            })
+            .collect();
+        let file_rules = section("FileRules").map(FileRule::parse).collect();
+        let signers = section("Signers").map(Signer::parse).collect();
+
+        let references = |element: &Element, list: &str, item: &str, attribute: &str| {
+            element
+                .find(list)
+                .into_iter()
+                .flat_map(|list| list.children(item))
+                .filter_map(|item| item.attribute(attribute).map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        let scenarios = section("SigningScenarios")
+            .filter_map(|scenario| {
+                Some(Scenario {
+                    value: scenario.attribute("Value")?.parse().ok()?,
+                    allowed_signers: references(
+                        scenario,
+                        "AllowedSigners",
+                        "AllowedSigner",
+                        "SignerId",
+                    ),
+                    denied_signers: references(
+                        scenario,
+                        "DeniedSigners",
+                        "DeniedSigner",
+                        "SignerId",
+                    ),
+                    file_rules: references(scenario, "FileRulesRef", "FileRuleRef", "RuleID"),
+                })
+            })
+            .collect();
+
+        Ok(WdacPolicy {
+            policy_id: policy
+                .child("PolicyID")
+                .map(|id| id.text.clone())
+                .filter(|id| !id.is_empty()),
+            audit_mode,
+            ekus,
+            file_rules,
+            signers,
+            scenarios,
+        })
+    }
+
+    /// Read and parse a policy XML file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<WdacPolicy, WdacError> {
+        let xml = std::fs::read_to_string(path).map_err(WdacError::Io)?;
+        WdacPolicy::parse(&xml)
+    }
+
+    /// The policy's ID, if it has one
+    pub fn policy_id(&self) -> Option<&str> {
+        self.policy_id.as_deref()
+    }
+
+    /// Whether the policy only logs what it would block, rather than blocking it
+    pub fn is_audit_mode(&self) -> bool {
+        self.audit_mode
+    }
+
+    /// Decide whether a verified file would be allowed to run under the policy
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::wdac::{SigningScenario, WdacPolicy};
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let policy = WdacPolicy::load("C:/Policies/Contoso.xml").unwrap();
+    /// let ctx = CodeSignVerifier::for_file("C:/Program Files/Contoso/app.exe").unwrap().verify("").unwrap();
+    /// println!("{:?}", policy.evaluate(&ctx, SigningScenario::UserMode));
+    /// ```
+    pub fn evaluate(&self, ctx: &SignatureContext, scenario: SigningScenario) -> Decision {
+        let path = ctx.executable_path();
+        let mut subject = Subject::for_file(path.as_deref());
+        subject.chain = ctx.0.certificate_chain();
+        subject.publisher = ctx.subject_name().common_name;
+        subject.issuer = ctx.issuer_name().common_name;
+        if let PlatformProperties::Windows(properties) = ctx.platform_properties() {
+            subject.ekus = properties.enhanced_key_usages;
+        }
+        self.decide(&subject, scenario)
+    }
+
+    /// Decide whether an unsigned file would be allowed to run under the policy, which only
+    /// hash, path and file name rules can allow
+    pub fn evaluate_unsigned<P: AsRef<Path>>(
+        &self,
+        path: P,
+        scenario: SigningScenario,
+    ) -> Decision {
+        self.decide(&Subject::for_file(Some(path.as_ref())), scenario)
+    }
+
+    fn signer_matches(&self, signer: &Signer, subject: &Subject) -> bool {
+        let root = signer.roots.iter().any(|root| {
+            subject.chain.iter().any(|certificate| {
+                tbs_hash(certificate, root.len())
+                    .map_or(false, |hash| hash.eq_ignore_ascii_case(root))
+            })
+        });
+        let publisher = signer.publisher.as_ref().map_or(true, |publisher| {
+            subject.publisher.as_ref() == Some(publisher)
+        });
+        let issuer = signer
+            .issuer
+            .as_ref()
+            .map_or(true, |issuer| subject.issuer.as_ref() == Some(issuer));
+        // EKU values are the encoded OID behind a 01 and its length
+        let eku = signer.ekus.is_empty()
+            || signer.ekus.iter().any(|id| {
+                self.ekus.get(id).map_or(false, |value| {
+                    subject.ekus.iter().any(|eku| {
+                        let oid = der::oid(eku);
+                        let encoded = [&[1, oid.len() as u8][..], &oid].concat();
+                        digest::hex(&encoded).eq_ignore_ascii_case(value)
+                    })
+                })
+            });
+        let file_attrib = signer.file_attribs.is_empty()
+            || signer.file_attribs.iter().any(|id| {
+                self.file_rules
+                    .iter()
+                    .any(|rule| &rule.id == id && rule.matches(subject))
+            });
+
+        root && publisher && issuer && eku && file_attrib
+    }
+
+    fn decide(&self, subject: &Subject, scenario: SigningScenario) -> Decision {
+        let value = match scenario {
+            SigningScenario::UserMode => SCENARIO_USER_MODE,
+            SigningScenario::KernelMode => SCENARIO_KERNEL_MODE,
+        };
+        let scenario = match self
+            .scenarios
+            .iter()
+            .find(|scenario| scenario.value == value)
+        {
+            Some(scenario) => scenario,
+            None => return Decision::NotAllowed,
+        };
+
+        let signer = |ids: &[String]| {
+            ids.iter()
+                .find(|id| {
+                    self.signers
+                        .iter()
+                        .any(|signer| &signer.id == *id && self.signer_matches(signer, subject))
+                })
+                .cloned()
+        };
+        let file_rule = |deny: bool| {
+            scenario
+                .file_rules
+                .iter()
+                .find(|id| {
+                    self.file_rules
+                        .iter()
+                        .any(|rule| &rule.id == *id && rule.deny == deny && rule.matches(subject))
+                })
+                .cloned()
+        };
+
+        if let Some(id) = signer(&scenario.denied_signers).or_else(|| file_rule(true)) {
+            return Decision::Denied(id);
+        }
+        match signer(&scenario.allowed_signers).or_else(|| file_rule(false)) {
+            Some(id) => Decision::Allowed(id),
+            None => Decision::NotAllowed,
+        }
+    }
+}
+
+impl Subject {
+    /// What's known about a file without its signature
+    fn for_file(path: Option<&Path>) -> Subject {
+        let path = match path {
+            Some(path) => path,
+            None => return Subject::default(),
+        };
+        let data = crate::digest::FileData::open(path).ok();
+        let version = crate::windows::version::version_strings(path);
+
+        Subject {
+            path: Some(path.to_string_lossy().replace('/', "\\")),
+            authenticode: data
+                .as_deref()
+                .and_then(crate::windows::image::PeFile::parse)
+                .and_then(|pe| pe.authenticode_sha256())
+                .map(|hash| digest::hex(&hash)),
+            original_filename: version.original_filename,
+            product_name: version.product_name,
+            version: version.file_version.as_deref().and_then(self::version),
+            ..Subject::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<SiPolicy xmlns="urn:schemas-microsoft-com:sipolicy" PolicyType="Base Policy">
+  <PolicyID>{A244370E-44C9-4C06-B551-F6016E563076}</PolicyID>
+  <Rules>
+    <Rule><Option>Enabled:Audit Mode</Option></Rule>
+  </Rules>
+  <EKUs>
+    <EKU ID="ID_EKU_CODESIGNING" Value="010A2B0601040182370A0306"/>
+  </EKUs>
+  <FileRules>
+    <Allow ID="ID_ALLOW_PATH" FilePath="%OSDRIVE%\Tools\*"/>
+    <Deny ID="ID_DENY_OLD" FileName="setup.exe" MaximumFileVersion="2.0.0.0"/>
+    <Allow ID="ID_ALLOW_SETUP" FileName="setup.exe" MinimumFileVersion="1.0.0.0"/>
+    <Allow ID="ID_ALLOW_SHA1" Hash="DA39A3EE5E6B4B0D3255BFEF95601890AFD80709"/>
+  </FileRules>
+  <Signers/>
+  <SigningScenarios>
+    <SigningScenario Value="12" ID="ID_SIGNINGSCENARIO_WINDOWS">
+      <ProductSigners>
+        <FileRulesRef>
+          <FileRuleRef RuleID="ID_ALLOW_PATH"/>
+          <FileRuleRef RuleID="ID_DENY_OLD"/>
+          <FileRuleRef RuleID="ID_ALLOW_SETUP"/>
+          <FileRuleRef RuleID="ID_ALLOW_SHA1"/>
+        </FileRulesRef>
+      </ProductSigners>
+    </SigningScenario>
+  </SigningScenarios>
+</SiPolicy>"#;
+
+    #[test]
+    fn test_evaluate() {
+        let policy = WdacPolicy::parse(POLICY).unwrap();
+        assert!(policy.is_audit_mode());
+        assert_eq!(
+            policy.policy_id(),
+            Some("{A244370E-44C9-4C06-B551-F6016E563076}")
+        );
+
+        let setup = |file_version| Subject {
+            original_filename: Some("SETUP.EXE".to_string()),
+            version: version(file_version),
+            ..Subject::default()
+        };
+        assert_eq!(
+            policy.decide(&setup("1.5.0.0"), SigningScenario::UserMode),
+            Decision::Denied("ID_DENY_OLD".to_string())
+        );
+        assert_eq!(
+            policy.decide(&setup("3.1.2 (release)"), SigningScenario::UserMode),
+            Decision::Allowed("ID_ALLOW_SETUP".to_string())
+        );
+        assert_eq!(
+            policy.decide(&setup("3.1.2"), SigningScenario::KernelMode),
+            Decision::NotAllowed
+        );
+        assert_eq!(
+            policy.decide(&Subject::default(), SigningScenario::UserMode),
+            Decision::NotAllowed
+        );
+
+        let tool = Subject {
+            path: Some(format!("{}\\tools\\bin\\tool.exe", expand("%OSDRIVE%"))),
+            ..Subject::default()
+        };
+        assert!(policy.decide(&tool, SigningScenario::UserMode).is_allowed());
+
+        assert!(wildcard("c:\\a\\*\\*.exe", "C:\\A\\b\\c\\D.EXE"));
+        assert!(!wildcard("c:\\a\\*.exe", "C:\\A\\b.dll"));
+        assert!(matches!(
+            WdacPolicy::parse("<assembly/>"),
+            Err(WdacError::NotAPolicy)
+        ));
+    }
+}
@@ -1,6 +1,26 @@
+use super::image::PeFile;
 use super::wintrust_sys::*;
-use crate::Name;
-use std::collections::HashMap;
+use super::{manifest, version};
+use crate::der;
+use crate::digest::{self, FileData};
+use crate::{
+    CatalogInfo, ChainStatus, DriverPolicy, DriverSigning, Error, LaunchConstraints, Name,
+    NestedCode, PlatformProperties, PropertyValue, ProvisioningProfile, RevocationSource,
+    RevocationState, RevocationStatus, RuntimeVersion, WindowsProperties,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const WHQL_EKU: &str = "1.3.6.1.4.1.311.10.3.5";
+const ATTESTATION_EKU: &str = "1.3.6.1.4.1.311.10.3.5.1";
+const STORE_EKU: &str = "1.3.6.1.4.1.311.76.3.1";
+/// Windows System Component Verification, only on the certificates Windows itself is signed with
+const SYSTEM_COMPONENT_EKU: &str = "1.3.6.1.4.1.311.10.3.6";
+const RFC3161_COUNTER_SIGN: &str = "1.3.6.1.4.1.311.3.3.1";
+
+/// 100ns intervals between the FILETIME epoch (1601-01-01) and the Unix epoch
+const FILETIME_UNIX_EPOCH: u64 = 116444736000000000;
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
@@ -11,7 +31,12 @@ struct CRYPT_PROVIDER_CERT_HDR {
 
 pub(crate) struct Context {
     data: HANDLE,
+    signer_ptr: *const CRYPT_PROVIDER_SGNR,
     leaf_cert_ptr: PCCERT_CONTEXT,
+    catalog: Option<CatalogInfo>,
+    path: Option<PathBuf>,
+    thumbprint: std::sync::OnceLock<[u8; 32]>, // Hashed on first use
+    cached_revocation: Option<Vec<RevocationStatus>>, // Settled by the revocation cache
 }
 
 impl Drop for Context {
@@ -20,6 +45,11 @@ impl Drop for Context {
     }
 }
 
+// The provider data is only read once verification completes, and releasing it isn't tied to
+// the thread that verified it, so a context can be handed to and shared between threads
+unsafe impl Send for Context {}
+unsafe impl Sync for Context {}
+
 fn close_data(handle: HANDLE) {
     // Initialize the WINTRUST_DATA structure
     let mut data: WINTRUST_DATA = unsafe { std::mem::zeroed() };
@@ -42,11 +72,121 @@ fn close_data(handle: HANDLE) {
     };
 }
 
+/// Read the trust status of the primary signer's certificate chain from provider state data
+pub fn chain_status(state_data: HANDLE) -> Option<ChainStatus> {
+    unsafe {
+        let crypt_prov_data = WTHelperProvDataFromStateData(state_data);
+        if crypt_prov_data.is_null() {
+            return None;
+        }
+
+        let signer = WTHelperGetProvSignerFromChain(crypt_prov_data, 0, 0, 0);
+        let signer = (signer as *const CRYPT_PROVIDER_SGNR).as_ref()?;
+        Some(chain_status_of(signer.pChainContext.as_ref()?))
+    }
+}
+
+pub fn chain_status_of(chain: &CERT_CHAIN_CONTEXT) -> ChainStatus {
+    ChainStatus {
+        error_status: chain.TrustStatus.dwErrorStatus,
+        info_status: chain.TrustStatus.dwInfoStatus,
+        details: Vec::new(),
+    }
+}
+
+/// A chain engine that only trusts a given set of roots
+struct AnchorEngine {
+    store: HCERTSTORE,
+    engine: HCERTCHAINENGINE,
+}
+
+impl AnchorEngine {
+    fn new(anchors: &[Vec<u8>]) -> Result<Self, Error> {
+        let mut ret = AnchorEngine {
+            store: std::ptr::null_mut(),
+            engine: 0,
+        };
+
+        unsafe {
+            ret.store = CertOpenStore(CERT_STORE_PROV_MEMORY, 0, 0, 0, std::ptr::null());
+            if ret.store.is_null() {
+                return Err(Error::os_error(GetLastError() as i32));
+            }
+
+            for der in anchors {
+                if CertAddEncodedCertificateToStore(
+                    ret.store,
+                    X509_ASN_ENCODING,
+                    der.as_ptr(),
+                    der.len() as u32,
+                    CERT_STORE_ADD_ALWAYS,
+                    std::ptr::null_mut(),
+                ) == 0
+                {
+                    return Err(Error::InvalidCertificate);
+                }
+            }
+
+            let mut config: CERT_CHAIN_ENGINE_CONFIG = std::mem::zeroed();
+            config.cbSize = std::mem::size_of::<CERT_CHAIN_ENGINE_CONFIG>() as u32;
+            config.hExclusiveRoot = ret.store;
+
+            if CertCreateCertificateChainEngine(&config, &mut ret.engine) == 0 {
+                return Err(Error::os_error(GetLastError() as i32));
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+// Chain engines can be used by several threads at once, and freed from any of them
+unsafe impl Send for AnchorEngine {}
+unsafe impl Sync for AnchorEngine {}
+
+/// The chain engine for a set of trust anchors, created on first use so verifications with
+/// the same anchors can share it
+#[derive(Default)]
+pub struct ChainEngine(std::sync::OnceLock<AnchorEngine>);
+
+impl ChainEngine {
+    /// The engine trusting only the given roots, the default engine when there are none
+    fn get(&self, anchors: &[Vec<u8>]) -> Result<Option<&AnchorEngine>, Error> {
+        if anchors.is_empty() {
+            return Ok(None);
+        }
+        if let Some(engine) = self.0.get() {
+            return Ok(Some(engine));
+        }
+
+        let engine = AnchorEngine::new(anchors)?;
+        Ok(Some(self.0.get_or_init(|| engine)))
+    }
+}
+
+impl Drop for AnchorEngine {
+    fn drop(&mut self) {
+        unsafe {
+            if self.engine != 0 {
+                CertFreeCertificateChainEngine(self.engine);
+            }
+            if !self.store.is_null() {
+                CertCloseStore(self.store, 0);
+            }
+        }
+    }
+}
+
 impl Context {
     pub fn new(state_data: HANDLE) -> Result<Self, WIN32_ERROR> {
         let mut ret = Context {
             data: state_data,
+            signer_ptr: std::ptr::null(),
             leaf_cert_ptr: std::ptr::null(),
+            catalog: None,
+            path: None,
+            thumbprint: std::sync::OnceLock::new(),
+            cached_revocation: None,
         };
 
         unsafe {
@@ -57,20 +197,159 @@ impl Context {
 
             let crypt_prov_sgnr = match WTHelperGetProvSignerFromChain(crypt_prov_data, 0, 0, 0) {
                 sgnr if sgnr.is_null() => return Err(TRUST_E_NO_SIGNER_CERT as u32),
-                sgnr => sgnr,
+                sgnr => sgnr as *const CRYPT_PROVIDER_SGNR,
             };
 
-            let crypt_prov_cert = match WTHelperGetProvCertFromChain(crypt_prov_sgnr, 0) {
+            let crypt_prov_cert = match WTHelperGetProvCertFromChain(crypt_prov_sgnr as _, 0) {
                 cert if cert.is_null() => return Err(TRUST_E_NO_SIGNER_CERT as u32),
                 cert => cert as *const CRYPT_PROVIDER_CERT_HDR,
             };
 
+            ret.signer_ptr = crypt_prov_sgnr;
             ret.leaf_cert_ptr = crypt_prov_cert.as_ref().unwrap().pCert as PCCERT_CONTEXT;
         }
 
         Ok(ret)
     }
 
+    pub fn chain_status(&self) -> ChainStatus {
+        let status = unsafe {
+            self.signer_ptr
+                .as_ref()
+                .and_then(|signer| signer.pChainContext.as_ref())
+                .map(chain_status_of)
+                .unwrap_or_default()
+        };
+        match self.cached_revocation {
+            Some(_) => crate::revocation::settled_chain_status(status),
+            None => status,
+        }
+    }
+
+    pub fn set_cached_revocation(&mut self, statuses: Vec<RevocationStatus>) {
+        self.cached_revocation = Some(statuses);
+    }
+
+    /// Build the leaf certificate's chain again, trusting only the given roots when there are
+    /// any, as of the given time rather than now, and bounding URL retrievals by the timeout.
+    /// The engine for the roots is taken from `engine`, the same anchors must be passed on
+    /// every use of it.
+    pub fn rebuild_chain(
+        &self,
+        engine: &ChainEngine,
+        anchors: &[Vec<u8>],
+        time: Option<SystemTime>,
+        timeout: Option<Duration>,
+        flags: u32,
+    ) -> Result<ChainStatus, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "chain_building",
+            anchors = anchors.len(),
+            ?time,
+            ?timeout,
+            flags
+        )
+        .entered();
+
+        let engine = engine.get(anchors)?;
+        let time = time.map(system_time_to_filetime);
+
+        let mut para: CERT_CHAIN_PARA = unsafe { std::mem::zeroed() };
+        para.cbSize = std::mem::size_of::<CERT_CHAIN_PARA>() as u32;
+        if let Some(timeout) = timeout {
+            para.dwUrlRetrievalTimeout = timeout.as_millis().clamp(1, u32::MAX as u128) as u32;
+        }
+        let mut chain: *mut CERT_CHAIN_CONTEXT = std::ptr::null_mut();
+
+        unsafe {
+            // The signature's own certificate store supplies the intermediates
+            let leaf = self.leaf_cert_ptr.as_ref().unwrap();
+            if CertGetCertificateChain(
+                engine.map_or(0, |engine| engine.engine), // The default engine otherwise
+                self.leaf_cert_ptr,
+                time.as_ref()
+                    .map_or(std::ptr::null(), |time| time as *const _),
+                leaf.hCertStore,
+                &para,
+                flags,
+                std::ptr::null(),
+                &mut chain,
+            ) == 0
+            {
+                return Err(Error::os_error(GetLastError() as i32));
+            }
+
+            let status = chain_status_of(&*chain);
+            CertFreeCertificateChain(chain);
+            Ok(status)
+        }
+    }
+
+    pub fn revocation_status(&self) -> Vec<RevocationStatus> {
+        if let Some(statuses) = &self.cached_revocation {
+            return statuses.clone();
+        }
+        let chain = unsafe {
+            match self
+                .signer_ptr
+                .as_ref()
+                .and_then(|s| s.pChainContext.as_ref())
+            {
+                Some(chain) if chain.cChain > 0 => &**chain.rgpChain,
+                _ => return Vec::new(),
+            }
+        };
+
+        (0..chain.cElement as usize)
+            .filter_map(|i| unsafe { (*chain.rgpElement.add(i)).as_ref() })
+            .map(element_revocation)
+            .collect()
+    }
+
+    /// The DER certificates of the signing chain, from the leaf up
+    pub fn certificate_chain(&self) -> Vec<Vec<u8>> {
+        let chain = unsafe {
+            match self
+                .signer_ptr
+                .as_ref()
+                .and_then(|s| s.pChainContext.as_ref())
+            {
+                Some(chain) if chain.cChain > 0 => &**chain.rgpChain,
+                _ => return vec![certificate_data(&*self.leaf_cert_ptr).to_vec()],
+            }
+        };
+
+        (0..chain.cElement as usize)
+            .filter_map(|i| unsafe { (*chain.rgpElement.add(i)).as_ref() })
+            .map(|element| certificate_data(unsafe { &*element.pCertContext }).to_vec())
+            .collect()
+    }
+
+    pub fn set_catalog(&mut self, catalog: CatalogInfo) {
+        self.catalog = Some(catalog);
+    }
+
+    pub fn catalog(&self) -> Option<&CatalogInfo> {
+        self.catalog.as_ref()
+    }
+
+    pub fn set_path(&mut self, path: Option<PathBuf>) {
+        self.path = path;
+    }
+
+    pub fn path(&self) -> Option<&std::path::Path> {
+        self.path.as_deref()
+    }
+
+    pub fn executable_path(&self) -> Option<PathBuf> {
+        self.path.clone()
+    }
+
+    pub fn bundle_path(&self) -> Option<PathBuf> {
+        None
+    }
+
     fn get_oid_name(&self, issuer: bool, oid: &str) -> Option<String> {
         use std::os::windows::ffi::OsStringExt;
         let key = std::ffi::CString::new(oid).unwrap();
@@ -112,7 +391,8 @@ impl Context {
         )
     }
 
-    pub fn serial(&self) -> Option<String> {
+    /// The serial number as DER encodes it, big endian
+    pub fn serial_bytes(&self) -> Option<Vec<u8>> {
         let serial_blob = unsafe {
             self.leaf_cert_ptr
                 .as_ref()
@@ -127,10 +407,17 @@ impl Context {
             unsafe { std::slice::from_raw_parts(serial_blob.pbData, serial_blob.cbData as usize) };
 
         // For some reason windows stores the serial number in reverse order
-        Some(
-            blob.iter()
-                .fold(String::new(), |v, s| format!("{:02x}{}", s, v)),
-        )
+        let mut serial = blob.to_vec();
+        serial.reverse();
+        Some(serial)
+    }
+
+    pub fn validity_period(&self) -> Option<(SystemTime, SystemTime)> {
+        let info = unsafe { self.leaf_cert_ptr.as_ref()?.pCertInfo.as_ref()? };
+        Some((
+            filetime_to_system_time(&info.NotBefore),
+            filetime_to_system_time(&info.NotAfter),
+        ))
     }
 
     pub fn subject_name(&self) -> Name {
@@ -152,20 +439,330 @@ impl Context {
     }
 
     pub fn sha256_thumbprint(&self) -> String {
-        let cert_ref = unsafe { self.leaf_cert_ptr.as_ref().unwrap() };
-        let cert_data = unsafe {
-            std::slice::from_raw_parts(cert_ref.pbCertEncoded, cert_ref.cbCertEncoded as _)
+        digest::hex(&self.sha256_thumbprint_bytes())
+    }
+
+    pub fn sha256_thumbprint_bytes(&self) -> [u8; 32] {
+        *self
+            .thumbprint
+            .get_or_init(|| thumbprint(unsafe { self.leaf_cert_ptr.as_ref().unwrap() }))
+    }
+
+    pub fn signing_time(&self) -> Option<SystemTime> {
+        // Authenticode signatures don't carry a signing time outside of the timestamp
+        None
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        // The time a counter signer verifies as of is the time it timestamped the signature
+        let signer = unsafe { self.signer_ptr.as_ref().unwrap() };
+        if signer.csCounterSigners == 0 {
+            return None;
+        }
+
+        let counter_signer = unsafe { signer.pasCounterSigners.as_ref()? };
+        Some(filetime_to_system_time(&counter_signer.sftVerifyAsOf))
+    }
+
+    /// Whether the signature is timestamped with an RFC 3161 timestamp, rather than a legacy
+    /// Authenticode counter signature
+    pub fn has_rfc3161_timestamp(&self) -> bool {
+        unsafe {
+            let signer = match self
+                .signer_ptr
+                .as_ref()
+                .and_then(|signer| signer.psSigner.as_ref())
+            {
+                Some(signer) => signer,
+                None => return false,
+            };
+
+            let attributes = &signer.UnauthAttrs;
+            (0..attributes.cAttr as usize)
+                .filter_map(|i| attributes.rgAttr.add(i).as_ref())
+                .any(|attribute| {
+                    !attribute.pszObjId.is_null()
+                        && std::ffi::CStr::from_ptr(attribute.pszObjId as _).to_bytes()
+                            == RFC3161_COUNTER_SIGN.as_bytes()
+                })
+        }
+    }
+
+    /// The enhanced key usage OIDs of the leaf certificate
+    pub fn enhanced_key_usages(&self) -> Vec<String> {
+        let mut len: u32 = 0;
+
+        unsafe {
+            if CertGetEnhancedKeyUsage(self.leaf_cert_ptr, 0, std::ptr::null_mut(), &mut len) == 0 {
+                return Vec::new();
+            }
+
+            // Allocate as u64 so the buffer is aligned for the CTL_USAGE header
+            let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+            let usage = buf.as_mut_ptr() as *mut CTL_USAGE;
+
+            if CertGetEnhancedKeyUsage(self.leaf_cert_ptr, 0, usage, &mut len) == 0 {
+                return Vec::new();
+            }
+
+            let usage = &*usage;
+            (0..usage.cUsageIdentifier as usize)
+                .map(|i| {
+                    std::ffi::CStr::from_ptr(*usage.rgpszUsageIdentifier.add(i) as _)
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        }
+    }
+
+    /// Whether the leaf certificate chains to a Microsoft product root
+    pub fn is_microsoft_root(&self) -> bool {
+        let mut para: CERT_CHAIN_POLICY_PARA = unsafe { std::mem::zeroed() };
+        para.cbSize = std::mem::size_of::<CERT_CHAIN_POLICY_PARA>() as u32;
+        let mut status: CERT_CHAIN_POLICY_STATUS = unsafe { std::mem::zeroed() };
+        status.cbSize = std::mem::size_of::<CERT_CHAIN_POLICY_STATUS>() as u32;
+
+        unsafe {
+            let chain = self.signer_ptr.as_ref().unwrap().pChainContext;
+            !chain.is_null()
+                && CertVerifyCertificateChainPolicy(
+                    CERT_CHAIN_POLICY_MICROSOFT_ROOT,
+                    chain,
+                    &para,
+                    &mut status,
+                ) != 0
+                && status.dwError == 0
+        }
+    }
+
+    /// Whether the leaf certificate is the Microsoft Store's
+    pub fn is_store_signed(&self) -> bool {
+        self.is_microsoft_root()
+            && self
+                .enhanced_key_usages()
+                .iter()
+                .any(|eku| eku == STORE_EKU)
+    }
+
+    /// Signed as part of Windows, or protected by Windows Resource Protection
+    pub fn is_platform_binary(&self) -> bool {
+        let system_component = self.is_microsoft_root()
+            && self
+                .enhanced_key_usages()
+                .iter()
+                .any(|eku| eku == SYSTEM_COMPONENT_EKU);
+        system_component || self.path.as_deref().map_or(false, is_file_protected)
+    }
+
+    pub fn driver_policy(&self) -> DriverPolicy {
+        let microsoft_root = self.is_microsoft_root();
+
+        let ekus = self.enhanced_key_usages();
+        let signing = if ekus.iter().any(|eku| eku == WHQL_EKU) {
+            Some(DriverSigning::Whql)
+        } else if ekus.iter().any(|eku| eku == ATTESTATION_EKU) {
+            Some(DriverSigning::Attestation)
+        } else if ekus.iter().any(|eku| eku == SYSTEM_COMPONENT_EKU) {
+            Some(DriverSigning::WindowsComponent)
+        } else {
+            None
         };
 
-        use sha2::Digest;
-        let hash = sha2::Sha256::digest(cert_data);
+        DriverPolicy {
+            microsoft_root,
+            signing,
+        }
+    }
 
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{:02x}", byte))
+    pub fn launch_constraints(&self) -> LaunchConstraints {
+        LaunchConstraints::default()
     }
 
-    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
+    pub fn runtime_version(&self) -> Option<RuntimeVersion> {
+        None
+    }
+
+    pub fn identifier(&self) -> Option<String> {
+        None
+    }
+
+    pub fn provisioning_profile(&self) -> Option<ProvisioningProfile> {
+        None
+    }
+
+    pub fn entitlements(&self) -> Option<BTreeMap<String, PropertyValue>> {
+        None
+    }
+
+    pub fn der_entitlements(&self) -> Option<BTreeMap<String, PropertyValue>> {
         None
     }
+
+    #[cfg(feature = "plist")]
+    pub fn info_plist_value(&self) -> Option<PropertyValue> {
+        None
+    }
+
+    pub fn nested_code(&self) -> &[NestedCode] {
+        &[]
+    }
+
+    fn windows_properties(&self) -> WindowsProperties {
+        let version = self
+            .path
+            .as_deref()
+            .map(version::version_strings)
+            .unwrap_or_default();
+        let image = self
+            .path
+            .as_deref()
+            .and_then(|path| FileData::open(path).ok());
+        let pe = image.as_deref().and_then(PeFile::parse);
+
+        WindowsProperties {
+            enhanced_key_usages: self.enhanced_key_usages(),
+            microsoft_root: self.is_microsoft_root(),
+            store_signed: self.is_store_signed(),
+            catalog: self.catalog.clone(),
+            product_name: version.product_name,
+            file_version: version.file_version,
+            company_name: version.company_name,
+            original_filename: version.original_filename,
+            manifest: pe.as_ref().and_then(manifest::manifest),
+            machine: pe.as_ref().map(PeFile::machine),
+        }
+    }
+
+    pub fn platform_properties(&self) -> PlatformProperties {
+        PlatformProperties::Windows(self.windows_properties())
+    }
+
+    pub fn additional_properties(&self) -> Option<HashMap<String, String>> {
+        let properties = self.windows_properties();
+        let mut ret = HashMap::new();
+        let mut insert = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                ret.insert(key.to_string(), value);
+            }
+        };
+
+        insert("product_name", properties.product_name);
+        insert("file_version", properties.file_version);
+        insert("company_name", properties.company_name);
+        insert("original_filename", properties.original_filename);
+        (!ret.is_empty()).then_some(ret)
+    }
+}
+
+/// The revocation outcome the chain engine recorded for a single certificate
+fn element_revocation(element: &CERT_CHAIN_ELEMENT) -> RevocationStatus {
+    let errors = element.TrustStatus.dwErrorStatus;
+    let info = unsafe { element.pRevocationInfo.as_ref() };
+    let crl = info.and_then(|info| unsafe { info.pCrlInfo.as_ref() });
+
+    let state = if errors & ChainStatus::IS_REVOKED != 0 {
+        RevocationState::Revoked
+    } else if errors & (ChainStatus::REVOCATION_STATUS_UNKNOWN | ChainStatus::IS_OFFLINE_REVOCATION)
+        != 0
+    {
+        RevocationState::Unknown
+    } else if info.map_or(false, |info| info.dwRevocationResult == 0) {
+        RevocationState::Good
+    } else {
+        RevocationState::NotChecked
+    };
+
+    let revocation_time = crl
+        .and_then(|crl| unsafe { crl.pCrlEntry.as_ref() })
+        .map(|entry| filetime_to_system_time(&entry.RevocationDate));
+
+    // OCSP responses are handed back as CRLs, which keep the original response as a property
+    let base = crl.and_then(|crl| unsafe { crl.pBaseCrlContext.as_ref() });
+    let ocsp_response = base.and_then(ocsp_response);
+    let source = base.map(|_| {
+        if ocsp_response.is_some() {
+            RevocationSource::Ocsp
+        } else {
+            RevocationSource::Crl
+        }
+    });
+    // For OCSP, the update times are those of the certificate's single response
+    let crl_info = base.and_then(|base| unsafe { base.pCrlInfo.as_ref() });
+    let next_update = crl_info
+        .map(|info| info.NextUpdate)
+        .filter(|time| time.dwLowDateTime != 0 || time.dwHighDateTime != 0)
+        .map(|time| filetime_to_system_time(&time));
+
+    RevocationStatus {
+        thumbprint: digest::hex(&thumbprint(unsafe { &*element.pCertContext })),
+        state,
+        revocation_time,
+        source,
+        this_update: crl_info.map(|info| filetime_to_system_time(&info.ThisUpdate)),
+        next_update,
+        produced_at: ocsp_response.and_then(|response| der::ocsp_produced_at(&response)),
+    }
+}
+
+/// The encoded OCSP response a CRL context was made from, None for actual CRLs
+fn ocsp_response(crl: &CRL_CONTEXT) -> Option<Vec<u8>> {
+    let mut len: u32 = 0;
+    unsafe {
+        if CertGetCRLContextProperty(
+            crl,
+            CERT_OCSP_RESPONSE_PROP_ID,
+            std::ptr::null_mut(),
+            &mut len,
+        ) == 0
+        {
+            return None;
+        }
+        let mut response = vec![0u8; len as usize];
+        if CertGetCRLContextProperty(
+            crl,
+            CERT_OCSP_RESPONSE_PROP_ID,
+            response.as_mut_ptr() as _,
+            &mut len,
+        ) == 0
+        {
+            return None;
+        }
+        response.truncate(len as usize);
+        Some(response)
+    }
+}
+
+/// Whether Windows Resource Protection guards the file against replacement
+fn is_file_protected(path: &std::path::Path) -> bool {
+    match super::wide_path(path) {
+        Ok(path) => unsafe { SfcIsFileProtected(0, path.as_ptr()) != 0 },
+        Err(_) => false,
+    }
+}
+
+pub fn certificate_data(cert: &CERT_CONTEXT) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(cert.pbCertEncoded, cert.cbCertEncoded as _) }
+}
+
+fn thumbprint(cert: &CERT_CONTEXT) -> [u8; 32] {
+    digest::sha256(certificate_data(cert))
+}
+
+fn system_time_to_filetime(time: SystemTime) -> FILETIME {
+    let since_unix = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let intervals = FILETIME_UNIX_EPOCH + (since_unix.as_nanos() / 100) as u64;
+
+    FILETIME {
+        dwLowDateTime: intervals as u32,
+        dwHighDateTime: (intervals >> 32) as u32,
+    }
+}
+
+fn filetime_to_system_time(ft: &FILETIME) -> SystemTime {
+    let intervals = ((ft.dwHighDateTime as u64) << 32) | ft.dwLowDateTime as u64;
+    let since_unix = intervals.saturating_sub(FILETIME_UNIX_EPOCH);
+    SystemTime::UNIX_EPOCH + Duration::from_nanos(since_unix * 100)
 }
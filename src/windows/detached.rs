@@ -0,0 +1,98 @@
+use super::context;
+use super::wintrust_sys::*;
+use crate::Error;
+
+const NTE_BAD_SIGNATURE: u32 = 0x80090006;
+const CRYPT_E_HASH_VALUE: u32 = 0x80091007;
+
+/// Verify a detached CMS signature over the content, building the first signer's chain against
+/// the system stores and checking it with the base chain policy. Returns the DER certificates
+/// of the chain, from the leaf up.
+pub fn verify(content: &[u8], signature: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut para: CRYPT_VERIFY_MESSAGE_PARA = unsafe { std::mem::zeroed() };
+    para.cbSize = std::mem::size_of::<CRYPT_VERIFY_MESSAGE_PARA>() as u32;
+    para.dwMsgAndCertEncodingType = X509_ASN_ENCODING | PKCS_7_ASN_ENCODING;
+
+    let mut signer: *mut CERT_CONTEXT = std::ptr::null_mut();
+    let content_ptr = content.as_ptr();
+    let content_len = content.len() as u32;
+
+    unsafe {
+        // The signer's certificate is looked up in the certificates the message carries
+        if CryptVerifyDetachedMessageSignature(
+            &para,
+            0,
+            signature.as_ptr(),
+            signature.len() as u32,
+            1,
+            &content_ptr,
+            &content_len,
+            &mut signer,
+        ) == 0
+        {
+            let code = GetLastError();
+            return Err(match code {
+                NTE_BAD_SIGNATURE | CRYPT_E_HASH_VALUE => Error::Tampered {
+                    code: code as i32,
+                    message: super::os_error_message(code as i32),
+                },
+                code => Error::os_error(code as i32),
+            });
+        }
+
+        let chain = chain(signer);
+        CertFreeCertificateContext(signer);
+        chain
+    }
+}
+
+/// Build and check the chain of a certificate, its own store supplying the intermediates
+unsafe fn chain(leaf: *const CERT_CONTEXT) -> Result<Vec<Vec<u8>>, Error> {
+    let mut para: CERT_CHAIN_PARA = std::mem::zeroed();
+    para.cbSize = std::mem::size_of::<CERT_CHAIN_PARA>() as u32;
+    let mut chain: *mut CERT_CHAIN_CONTEXT = std::ptr::null_mut();
+
+    if CertGetCertificateChain(
+        0, // The default engine, trusting the system roots
+        leaf,
+        std::ptr::null(),
+        (*leaf).hCertStore,
+        &para,
+        0,
+        std::ptr::null(),
+        &mut chain,
+    ) == 0
+    {
+        return Err(Error::os_error(GetLastError() as i32));
+    }
+
+    let mut policy_para: CERT_CHAIN_POLICY_PARA = std::mem::zeroed();
+    policy_para.cbSize = std::mem::size_of::<CERT_CHAIN_POLICY_PARA>() as u32;
+    let mut policy_status: CERT_CHAIN_POLICY_STATUS = std::mem::zeroed();
+    policy_status.cbSize = std::mem::size_of::<CERT_CHAIN_POLICY_STATUS>() as u32;
+
+    let result = if CertVerifyCertificateChainPolicy(
+        CERT_CHAIN_POLICY_BASE,
+        chain,
+        &policy_para,
+        &mut policy_status,
+    ) == 0
+    {
+        Err(Error::os_error(GetLastError() as i32))
+    } else if policy_status.dwError != 0 {
+        Err(Error::chain_failed(
+            policy_status.dwError as i32,
+            context::chain_status_of(&*chain),
+            None,
+        ))
+    } else {
+        let simple = &**(*chain).rgpChain;
+        Ok((0..simple.cElement as usize)
+            .filter_map(|i| (*simple.rgpElement.add(i)).as_ref())
+            .map(|element| context::certificate_data(&*element.pCertContext).to_vec())
+            .collect())
+    };
+
+    CertFreeCertificateChain(chain);
+    result
+}
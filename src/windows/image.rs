@@ -0,0 +1,485 @@
+use super::wintrust_sys::*;
+use super::OwnedHandle;
+use crate::digest::FileData;
+use crate::{Error, PeMachine};
+use sha2::Digest;
+use std::convert::TryInto;
+use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
+
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG: usize = 10;
+const IMAGE_DIRECTORY_ENTRY_IAT: usize = 12;
+/// Set in the offset of a resource directory entry that points to another directory
+const IMAGE_RESOURCE_DATA_IS_DIRECTORY: u32 = 0x8000_0000;
+/// The offset of `CHPEMetadataPointer` in the 64-bit load configuration
+const LOAD_CONFIG_CHPE_METADATA: usize = 0xc8;
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x01c4;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+struct Section {
+    rva: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+    characteristics: u32,
+}
+
+impl Section {
+    /// The part of the section backed by the file, the rest is zero filled when mapped
+    fn file_backed_len(&self) -> usize {
+        self.virtual_size.min(self.raw_size) as usize
+    }
+}
+
+/// The parts of a PE file needed to tell what its image looks like once mapped
+pub(crate) struct PeFile<'a> {
+    data: &'a [u8],
+    machine: u16,
+    pe64: bool,
+    checksum: usize, // The offset of the checksum in the optional header
+    image_base: u64,
+    directory_table: usize,       // The offset of the data directories
+    directories: Vec<(u32, u32)>, // RVA and size of each data directory
+    sections: Vec<Section>,
+}
+
+impl<'a> PeFile<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let pe = u32_at(data, 0x3c)? as usize;
+        if data.get(pe..pe + 4)? != b"PE\0\0" {
+            return None;
+        }
+
+        let coff = pe + 4;
+        let machine = u16_at(data, coff)?;
+        let section_count = u16_at(data, coff + 2)? as usize;
+        let optional_size = u16_at(data, coff + 16)? as usize;
+        let optional = coff + 20;
+
+        // The image base is 32 bits wide in PE32, and moves the fields after it in PE32+
+        let (pe64, image_base, directory_count, directories) = match u16_at(data, optional)? {
+            0x10b => (
+                false,
+                u32_at(data, optional + 28)? as u64,
+                optional + 92,
+                optional + 96,
+            ),
+            0x20b => (
+                true,
+                u64_at(data, optional + 24)?,
+                optional + 108,
+                optional + 112,
+            ),
+            _ => return None,
+        };
+        let directory_table = directories;
+        let directories = (0..u32_at(data, directory_count)?.min(16) as usize)
+            .map(|i| {
+                Some((
+                    u32_at(data, directories + i * 8)?,
+                    u32_at(data, directories + i * 8 + 4)?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let headers = optional + optional_size;
+        let sections = (0..section_count)
+            .map(|i| {
+                let header = headers + i * 40;
+                Some(Section {
+                    virtual_size: u32_at(data, header + 8)?,
+                    rva: u32_at(data, header + 12)?,
+                    raw_size: u32_at(data, header + 16)?,
+                    raw_offset: u32_at(data, header + 20)?,
+                    characteristics: u32_at(data, header + 36)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(PeFile {
+            data,
+            machine,
+            pe64,
+            checksum: optional + 64,
+            image_base,
+            directory_table,
+            directories,
+            sections,
+        })
+    }
+
+    fn directory(&self, index: usize) -> Option<(u32, u32)> {
+        self.directories
+            .get(index)
+            .copied()
+            .filter(|&(rva, size)| rva != 0 && size != 0)
+    }
+
+    /// The file bytes the given RVA is loaded from
+    fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        self.sections
+            .iter()
+            .find(|s| rva >= s.rva && rva - s.rva < s.file_backed_len() as u32)
+            .map(|s| (s.raw_offset + (rva - s.rva)) as usize)
+    }
+
+    /// Whether the load configuration points to hybrid metadata, which ARM64EC code needs to
+    /// interoperate with x64 code. The structure records its own size, older linkers emit
+    /// shorter ones without the field.
+    fn is_hybrid(&self) -> bool {
+        let hybrid = || {
+            let (rva, _) = self.directory(IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG)?;
+            let offset = self.rva_to_offset(rva)?;
+            if (u32_at(self.data, offset)? as usize) < LOAD_CONFIG_CHPE_METADATA + 8 {
+                return None;
+            }
+            Some(u64_at(self.data, offset + LOAD_CONFIG_CHPE_METADATA)? != 0)
+        };
+        self.pe64 && hybrid().unwrap_or(false)
+    }
+
+    /// The architecture of the code in the image. Hybrid images keep the machine of the
+    /// process they load into by default in the header, ARM64X images are ARM64 and ARM64EC
+    /// images are x64.
+    pub(crate) fn machine(&self) -> PeMachine {
+        match (self.machine, self.is_hybrid()) {
+            (IMAGE_FILE_MACHINE_ARM64, true) => PeMachine::Arm64X,
+            (IMAGE_FILE_MACHINE_AMD64, true) => PeMachine::Arm64EC,
+            (IMAGE_FILE_MACHINE_I386, _) => PeMachine::X86,
+            (IMAGE_FILE_MACHINE_AMD64, _) => PeMachine::X64,
+            (IMAGE_FILE_MACHINE_ARMNT, _) => PeMachine::Arm,
+            (IMAGE_FILE_MACHINE_ARM64, _) => PeMachine::Arm64,
+            (machine, _) => PeMachine::Other(machine),
+        }
+    }
+
+    /// The sha256 Authenticode hash of the file, which covers everything but the checksum, the
+    /// certificate table and its directory entry. Files the linker padded between the last
+    /// section and the table hash the same as with the spec's section by section layout.
+    pub(crate) fn authenticode_sha256(&self) -> Option<[u8; 32]> {
+        if self.directories.len() <= IMAGE_DIRECTORY_ENTRY_SECURITY {
+            return None;
+        }
+        let entry = self.directory_table + IMAGE_DIRECTORY_ENTRY_SECURITY * 8;
+        // The certificate table is located by file offset rather than RVA
+        let table = match self.directory(IMAGE_DIRECTORY_ENTRY_SECURITY) {
+            Some((offset, size)) => offset as usize..offset as usize + size as usize,
+            None => self.data.len()..self.data.len(),
+        };
+
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(self.data.get(..self.checksum)?);
+        hasher.update(self.data.get(self.checksum + 4..entry)?);
+        hasher.update(self.data.get(entry + 8..table.start)?);
+        hasher.update(self.data.get(table.end..)?);
+        Some(hasher.finalize().into())
+    }
+
+    /// The ID and offset of each entry of a resource directory, offsets relative to the root
+    fn resource_entries(&self, root: usize, directory: u32) -> Vec<(u32, u32)> {
+        let directory = root + directory as usize;
+        let count = match (
+            u16_at(self.data, directory + 12),
+            u16_at(self.data, directory + 14),
+        ) {
+            (Some(named), Some(ids)) => named as usize + ids as usize,
+            _ => return Vec::new(),
+        };
+
+        (0..count)
+            .map(|i| {
+                let entry = directory + 16 + i * 8;
+                Some((u32_at(self.data, entry)?, u32_at(self.data, entry + 4)?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default()
+    }
+
+    /// The first resource of the given type, whatever its name and language
+    pub(crate) fn resource(&self, kind: u32) -> Option<&'a [u8]> {
+        let (rva, _) = self.directory(IMAGE_DIRECTORY_ENTRY_RESOURCE)?;
+        let root = self.rva_to_offset(rva)?;
+
+        // Named entries have the high bit of their ID set, so they never match a type
+        let (_, mut offset) = self
+            .resource_entries(root, 0)
+            .into_iter()
+            .find(|&(id, _)| id == kind)?;
+        // Below the type are the name and the language
+        for _ in 0..2 {
+            if offset & IMAGE_RESOURCE_DATA_IS_DIRECTORY == 0 {
+                return None;
+            }
+            offset = self
+                .resource_entries(root, offset & !IMAGE_RESOURCE_DATA_IS_DIRECTORY)
+                .first()?
+                .1;
+        }
+        if offset & IMAGE_RESOURCE_DATA_IS_DIRECTORY != 0 {
+            return None;
+        }
+
+        let entry = root + offset as usize;
+        let start = self.rva_to_offset(u32_at(self.data, entry)?)?;
+        self.data
+            .get(start..start + u32_at(self.data, entry + 4)? as usize)
+    }
+
+    /// The RVA and type of every base relocation the loader applies
+    fn relocations(&self) -> Vec<(u32, u16)> {
+        let mut relocations = Vec::new();
+        let (rva, size) = match self.directory(IMAGE_DIRECTORY_ENTRY_BASERELOC) {
+            Some(directory) => directory,
+            None => return relocations,
+        };
+
+        let mut block = 0;
+        while block + 8 <= size {
+            let offset = match self.rva_to_offset(rva + block) {
+                Some(offset) => offset,
+                None => break,
+            };
+            let (page, block_size) =
+                match (u32_at(self.data, offset), u32_at(self.data, offset + 4)) {
+                    (Some(page), Some(block_size)) if block_size >= 8 => (page, block_size),
+                    _ => break,
+                };
+
+            for entry in (8..block_size as usize).step_by(2) {
+                if let Some(entry) = u16_at(self.data, offset + entry) {
+                    relocations.push((page + (entry & 0xfff) as u32, entry >> 12));
+                }
+            }
+            block += block_size;
+        }
+
+        relocations
+    }
+
+    /// The executable sections as the loader maps them at `base`, keyed by RVA
+    fn expected_code(&self, base: u64) -> Vec<(u32, Vec<u8>)> {
+        let delta = base.wrapping_sub(self.image_base);
+        let relocations = self.relocations();
+
+        self.sections
+            .iter()
+            .filter(|s| s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0)
+            .filter_map(|s| {
+                let start = s.raw_offset as usize;
+                let mut bytes = self.data.get(start..start + s.file_backed_len())?.to_vec();
+
+                for &(rva, kind) in &relocations {
+                    let at = match rva.checked_sub(s.rva) {
+                        Some(at) => at as usize,
+                        None => continue,
+                    };
+                    match kind {
+                        IMAGE_REL_BASED_HIGHLOW if at + 4 <= bytes.len() => {
+                            let value = u32_at(&bytes, at).unwrap().wrapping_add(delta as u32);
+                            bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+                        }
+                        IMAGE_REL_BASED_DIR64 if at + 8 <= bytes.len() => {
+                            let value = u64_at(&bytes, at).unwrap().wrapping_add(delta);
+                            bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+                        }
+                        _ => {}
+                    }
+                }
+
+                Some((s.rva, bytes))
+            })
+            .collect()
+    }
+}
+
+fn open_process(pid: u32) -> Result<OwnedHandle, Error> {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, FALSE, pid) };
+    if process == 0 {
+        return Err(Error::os_error(unsafe { GetLastError() } as i32));
+    }
+    Ok(OwnedHandle(process))
+}
+
+/// The modules loaded in the process, the main executable always comes first
+fn process_modules(process: &OwnedHandle) -> Result<Vec<HMODULE>, Error> {
+    let mut modules: Vec<HMODULE> = vec![0; 256];
+
+    loop {
+        let size = (modules.len() * std::mem::size_of::<HMODULE>()) as u32;
+        let mut needed = 0;
+        if unsafe {
+            K32EnumProcessModulesEx(
+                process.0,
+                modules.as_mut_ptr(),
+                size,
+                &mut needed,
+                LIST_MODULES_ALL,
+            )
+        } == 0
+        {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+
+        let count = needed as usize / std::mem::size_of::<HMODULE>();
+        if needed <= size {
+            modules.truncate(count);
+            return Ok(modules);
+        }
+        modules.resize(count, 0); // More modules got loaded, try again with room for them
+    }
+}
+
+fn module_path(process: &OwnedHandle, module: HMODULE) -> Result<PathBuf, Error> {
+    let mut path = [0u16; 2048];
+    let len = unsafe {
+        K32GetModuleFileNameExW(process.0, module, path.as_mut_ptr(), path.len() as u32) as usize
+    };
+    if len == 0 {
+        return Err(Error::InvalidPath);
+    }
+    Ok(std::ffi::OsString::from_wide(&path[..len]).into())
+}
+
+/// The files of all modules loaded in the process, starting with the main executable
+pub fn module_paths(pid: u32) -> Result<Vec<PathBuf>, Error> {
+    let process = open_process(pid)?;
+    process_modules(&process)?
+        .into_iter()
+        .map(|module| module_path(&process, module))
+        .collect()
+}
+
+/// Compare the executable sections of the main image mapped in the process with the file on disk
+pub fn compare_image(pid: u32) -> Result<(), Error> {
+    let process = open_process(pid)?;
+    let module = *process_modules(&process)?
+        .first()
+        .ok_or(Error::InvalidPath)?;
+
+    let data = FileData::open(&module_path(&process, module)?).map_err(Error::IoError)?;
+    // ARM64X images also carry dynamic relocations that switch them to ARM64EC when loaded
+    // into an x64 process, those only rewrite the headers and data directories, not code
+    let pe = PeFile::parse(&data).ok_or(Error::ImageMismatch)?;
+    let base = module as u64;
+    // The loader writes the import address table, which some linkers place in code sections
+    let iat = pe
+        .directory(IMAGE_DIRECTORY_ENTRY_IAT)
+        .map(|(rva, size)| rva..rva + size);
+
+    for (rva, expected) in pe.expected_code(base) {
+        let mut actual = vec![0u8; expected.len()];
+        let mut read = 0;
+        let result = unsafe {
+            ReadProcessMemory(
+                process.0,
+                (base + rva as u64) as *const _,
+                actual.as_mut_ptr() as _,
+                actual.len(),
+                &mut read,
+            )
+        };
+        if result == 0 || read != actual.len() {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+
+        let differs = expected
+            .iter()
+            .zip(&actual)
+            .enumerate()
+            .filter(|&(i, _)| {
+                !iat.as_ref()
+                    .map_or(false, |iat| iat.contains(&(rva + i as u32)))
+            })
+            .any(|(_, (expected, actual))| expected != actual);
+        if differs {
+            return Err(Error::ImageMismatch);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PE32+ image with a single section holding a 64-bit load configuration
+    fn image(machine: u16, chpe_metadata: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 0x400];
+        let mut put =
+            |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        put(0x3c, &0x40u32.to_le_bytes());
+        put(0x40, b"PE\0\0");
+        put(0x44, &machine.to_le_bytes());
+        put(0x46, &1u16.to_le_bytes()); // One section
+        put(0x54, &0xf0u16.to_le_bytes()); // The size of the optional header at 0x58
+        put(0x58, &0x20bu16.to_le_bytes());
+        put(0x58 + 24, &0x1_4000_0000u64.to_le_bytes());
+        put(0x58 + 108, &16u32.to_le_bytes());
+        let load_config = 0x58 + 112 + IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG * 8;
+        put(load_config, &0x1000u32.to_le_bytes());
+        put(load_config + 4, &0x140u32.to_le_bytes());
+
+        // The section at RVA 0x1000, read from offset 0x200
+        let section = 0x58 + 0xf0;
+        put(section + 8, &0x200u32.to_le_bytes());
+        put(section + 12, &0x1000u32.to_le_bytes());
+        put(section + 16, &0x200u32.to_le_bytes());
+        put(section + 20, &0x200u32.to_le_bytes());
+
+        put(0x200, &0x140u32.to_le_bytes());
+        put(
+            0x200 + LOAD_CONFIG_CHPE_METADATA,
+            &chpe_metadata.to_le_bytes(),
+        );
+        data
+    }
+
+    #[test]
+    fn test_machine() {
+        let machine = |machine, chpe_metadata| {
+            PeFile::parse(&image(machine, chpe_metadata))
+                .unwrap()
+                .machine()
+        };
+        assert_eq!(machine(IMAGE_FILE_MACHINE_AMD64, 0), PeMachine::X64);
+        assert_eq!(machine(IMAGE_FILE_MACHINE_ARM64, 0), PeMachine::Arm64);
+        assert_eq!(
+            machine(IMAGE_FILE_MACHINE_AMD64, 0x1_4000_1100),
+            PeMachine::Arm64EC
+        );
+        assert_eq!(
+            machine(IMAGE_FILE_MACHINE_ARM64, 0x1_4000_1100),
+            PeMachine::Arm64X
+        );
+        assert_eq!(machine(0x5064, 0), PeMachine::Other(0x5064));
+    }
+}
@@ -0,0 +1,99 @@
+//!
+//! The application manifest embedded in a PE file, as an `RT_MANIFEST` resource.
+//!
+//! Only the few elements that decide how the file is launched are read.
+//!
+
+use super::image::PeFile;
+use crate::xml::{self, Element};
+use crate::{ApplicationManifest, AssemblyIdentity, ExecutionLevel};
+
+const RT_MANIFEST: u32 = 24;
+
+fn attribute(element: &Element, name: &str) -> Option<String> {
+    element.attribute(name).map(str::to_string)
+}
+
+/// Read the launch settings from the manifest's XML
+pub(crate) fn parse(xml: &str) -> ApplicationManifest {
+    let assembly = match xml::parse(xml) {
+        Some(assembly) => assembly,
+        None => return ApplicationManifest::default(),
+    };
+
+    let level = assembly.find("requestedExecutionLevel");
+    // Dependencies have identities of their own, nested further down
+    let identity = assembly
+        .child("assemblyIdentity")
+        .map(|identity| AssemblyIdentity {
+            name: attribute(identity, "name"),
+            version: attribute(identity, "version"),
+            processor_architecture: attribute(identity, "processorArchitecture"),
+            kind: attribute(identity, "type"),
+        });
+
+    ApplicationManifest {
+        execution_level: level.and_then(|level| match level.attribute("level")? {
+            "asInvoker" => Some(ExecutionLevel::AsInvoker),
+            "highestAvailable" => Some(ExecutionLevel::HighestAvailable),
+            "requireAdministrator" => Some(ExecutionLevel::RequireAdministrator),
+            _ => None,
+        }),
+        ui_access: level
+            .and_then(|level| level.attribute("uiAccess"))
+            .map_or(false, |ui_access| ui_access.eq_ignore_ascii_case("true")),
+        assembly_identity: identity,
+    }
+}
+
+/// The manifest embedded in the image, None if it has none
+pub(crate) fn manifest(pe: &PeFile<'_>) -> Option<ApplicationManifest> {
+    let xml = pe.resource(RT_MANIFEST)?;
+    Some(parse(&String::from_utf8_lossy(xml)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::digest::FileData;
+    use std::path::Path;
+
+    #[test]
+    fn test_manifest() {
+        let manifest = parse(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0">
+  <assemblyIdentity type="win32" name="Contoso.Setup" version="1.2.0.0"
+                    processorArchitecture="amd64"/>
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity type="win32" name="Microsoft.Windows.Common-Controls"
+                        version="6.0.0.0" processorArchitecture="*"/>
+    </dependentAssembly>
+  </dependency>
+  <trustInfo xmlns="urn:schemas-microsoft-com:asm.v3">
+    <security>
+      <requestedPrivileges>
+        <!-- <requestedExecutionLevel level="asInvoker" uiAccess="false"/> -->
+        <ms_asmv3:requestedExecutionLevel level='requireAdministrator' uiAccess='TRUE'/>
+      </requestedPrivileges>
+    </security>
+  </trustInfo>
+</assembly>"#,
+        );
+        assert_eq!(
+            manifest.execution_level,
+            Some(ExecutionLevel::RequireAdministrator)
+        );
+        assert!(manifest.ui_access);
+        let identity = manifest.assembly_identity.unwrap();
+        assert_eq!(identity.name.as_deref(), Some("Contoso.Setup"));
+        assert_eq!(identity.processor_architecture.as_deref(), Some("amd64"));
+
+        assert_eq!(parse("<assembly/>"), ApplicationManifest::default());
+
+        let explorer = FileData::open(Path::new(r"C:\Windows\explorer.exe")).unwrap();
+        let manifest = super::manifest(&PeFile::parse(&explorer).unwrap()).unwrap();
+        assert_eq!(manifest.execution_level, Some(ExecutionLevel::AsInvoker));
+    }
+}
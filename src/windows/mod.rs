@@ -1,13 +1,41 @@
 #[allow(non_snake_case)]
 mod context;
+#[cfg(feature = "cms")]
+pub(crate) mod detached;
+pub(crate) mod image;
+mod manifest;
+pub(crate) mod version;
+pub(crate) mod watch;
 mod wintrust_sys;
 
-use super::Error;
+use super::{
+    CatalogInfo, ChainStatus, Error, Policy, RevocationMode, TimestampPolicy, VerificationOptions,
+};
+use std::cell::RefCell;
 use wintrust_sys::*;
 
-pub(crate) struct Verifier(Vec<u16>);
+pub(crate) struct Verifier {
+    path: Vec<u16>,
+    file: Option<OwnedHandle>, // Verify through this handle rather than opening the path again
+    held: Option<std::fs::File>, // The file held open since the verifier was created, if any
+}
+
+/// An owned handle, closed on drop
+struct OwnedHandle(HANDLE);
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
 pub(crate) use context::Context;
 
+/// State kept between verifications of the same file with the same options
+#[derive(Default)]
+pub(crate) struct Session {
+    engine: context::ChainEngine, // For the trust anchors in the options
+}
+
 struct CleanupContext {
     h_file: HANDLE,
     h_cat_admin: HANDLE,
@@ -33,21 +61,415 @@ impl Drop for CleanupContext {
         if self.h_cat_info != 0 {
             unsafe { CryptCATAdminReleaseCatalogContext(self.h_cat_admin, self.h_cat_info, 0) };
         }
+    }
+}
+
+/// The PID of the System process, which hosts the kernel
+const SYSTEM_PID: u32 = 4;
+
+/// The chain status bits set when revocation couldn't be checked
+const REVOCATION_UNKNOWN: u32 =
+    ChainStatus::REVOCATION_STATUS_UNKNOWN | ChainStatus::IS_OFFLINE_REVOCATION;
+
+/// Why WinVerifyTrust rejected a signature
+struct TrustFailure {
+    code: WIN32_ERROR,
+    chain: Option<ChainStatus>,
+    context: Option<Context>, // The signer the rejected signature claims, when it could be read
+}
+
+impl TrustFailure {
+    fn is_unsigned(&self) -> bool {
+        self.code == TRUST_E_NOSIGNATURE as u32
+    }
+
+    /// Whether the chain was only rejected for not ending at a root in the system store
+    fn is_untrusted_root(&self) -> bool {
+        self.code == CERT_E_UNTRUSTEDROOT as u32 || self.code == CERT_E_CHAINING as u32
+    }
+
+    /// Whether the chain was rejected for a certificate that isn't valid at the current time
+    fn is_expired(&self) -> bool {
+        self.code == CERT_E_EXPIRED as u32
+    }
+
+    /// Whether the chain was rejected for a revoked certificate
+    fn is_revoked(&self) -> bool {
+        self.code == CERT_E_REVOKED as u32 || self.code == CRYPT_E_REVOKED as u32
+    }
+
+    /// Whether the file no longer matches the hash its signature covers
+    fn is_tampered(&self) -> bool {
+        self.code == TRUST_E_BAD_DIGEST as u32
+    }
+
+    /// Whether the chain was only rejected because revocation couldn't be checked
+    fn is_revocation_unknown(&self) -> bool {
+        matches!(self.code, 0x80092012..=0x80092013) // CRYPT_E_NO_REVOCATION_CHECK, CRYPT_E_REVOCATION_OFFLINE
+            && self
+                .chain
+                .as_ref()
+                .map_or(true, |status| status.error_status & !REVOCATION_UNKNOWN == 0)
+    }
+
+    /// Whether the error code blames the certificate chain rather than the signature itself
+    fn is_chain_error(&self) -> bool {
+        matches!(self.code, 0x800B0100..=0x800B01FF) // CERT_E_*
+            || matches!(self.code, 0x80092010..=0x80092013) // CRYPT_E_*REVOCATION*
+            || self.code == TRUST_E_CERT_SIGNATURE as u32
+    }
+}
+
+impl From<WIN32_ERROR> for TrustFailure {
+    fn from(code: WIN32_ERROR) -> Self {
+        TrustFailure {
+            code,
+            chain: None,
+            context: None,
+        }
+    }
+}
 
-        if self.h_cat_admin != 0 {
-            unsafe { CryptCATAdminReleaseContext(self.h_cat_admin, 0) };
+impl From<TrustFailure> for Error {
+    fn from(failure: TrustFailure) -> Self {
+        let code = failure.code as i32;
+        // Without the chain status, the code alone tells which check failed
+        let chain_failed = |error_status| {
+            Error::chain_failed(code, ChainStatus::from_error_status(error_status), None)
+        };
+
+        match failure.chain {
+            Some(ref status) if status.has_errors() && failure.is_chain_error() => {
+                Error::chain_failed(code, failure.chain.unwrap(), None)
+            }
+            _ if failure.is_revoked() => chain_failed(ChainStatus::IS_REVOKED),
+            _ if failure.is_expired() => chain_failed(ChainStatus::IS_NOT_TIME_VALID),
+            _ if failure.is_untrusted_root() => chain_failed(ChainStatus::IS_UNTRUSTED_ROOT),
+            _ if failure.is_tampered() => Error::Tampered {
+                code,
+                message: os_error_message(code),
+            },
+            _ => Error::os_error(code),
         }
     }
 }
 
+/// A catalog admin context, acquiring one is expensive so it's kept for reuse
+struct CatAdmin(HANDLE);
+
+impl Drop for CatAdmin {
+    fn drop(&mut self) {
+        unsafe { CryptCATAdminReleaseContext(self.0, 0) };
+    }
+}
+
+thread_local! {
+    // Catalog admin contexts aren't documented as thread safe, so each thread caches its own
+    static CAT_ADMIN: RefCell<Option<CatAdmin>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with this thread's cached catalog admin context, acquiring it on first use
+fn with_cat_admin<T>(f: impl FnOnce(HANDLE) -> Result<T, Error>) -> Result<T, Error> {
+    let h_cat_admin = CAT_ADMIN.with(|cached| {
+        let mut cached = cached.borrow_mut();
+
+        if cached.is_none() {
+            let mut h_cat_admin: HANDLE = 0;
+            let result = unsafe {
+                CryptCATAdminAcquireContext2(
+                    &mut h_cat_admin,
+                    std::ptr::null(),
+                    BCRYPT_SHA256_ALGORITHM,
+                    std::ptr::null(),
+                    0,
+                )
+            };
+            if result == 0 {
+                let err = unsafe { GetLastError() } as i32;
+                return Err(Error::os_error(err));
+            }
+
+            *cached = Some(CatAdmin(h_cat_admin));
+        }
+
+        Ok(cached.as_ref().unwrap().0)
+    })?;
+
+    // Not borrowed while `f` runs, so it can verify other files on this thread in turn. The
+    // context is only released when the thread exits.
+    f(h_cat_admin)
+}
+
+/// The longest path the Win32 APIs take without the extended-length prefix, with its terminator
+const MAX_PATH: usize = 260;
+
+/// Encode `s` as UTF-16, for comparing against wide paths
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().collect()
+}
+
+/// Encode a path for the Win32 APIs, null terminated. Paths too long for them are made absolute
+/// and given the extended-length prefix, `\\?\` or `\\?\UNC\` for shares. The prefix also
+/// turns off the APIs' own normalization, so that's done first.
+pub(crate) fn wide_path(path: &std::path::Path) -> Result<Vec<u16>, Error> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut path: Vec<u16> = path.as_os_str().encode_wide().collect();
+    if path.contains(&0) {
+        return Err(Error::InvalidPath);
+    }
+    if path.len() >= MAX_PATH && !path.starts_with(&wide("\\\\?\\")) {
+        path = extended_path(&full_path(&path)?);
+    }
+    path.push(0); // Make sure path is null terminated
+    Ok(path)
+}
+
+/// Resolve a path as the Win32 APIs would: made absolute, with `/` turned into `\` and `.`
+/// and `..` components removed
+fn full_path(path: &[u16]) -> Result<Vec<u16>, Error> {
+    let path: Vec<u16> = path.iter().copied().chain(Some(0)).collect();
+    let mut buf = Vec::new();
+
+    // The first call reports the size needed, with the terminator
+    loop {
+        let len = unsafe {
+            GetFullPathNameW(
+                path.as_ptr(),
+                buf.len() as u32,
+                buf.as_mut_ptr(),
+                std::ptr::null_mut(),
+            )
+        } as usize;
+        match len {
+            0 => return Err(Error::os_error(unsafe { GetLastError() } as i32)),
+            len if len < buf.len() => {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            len => buf.resize(len, 0),
+        }
+    }
+}
+
+/// Give a full path the extended-length prefix
+fn extended_path(path: &[u16]) -> Vec<u16> {
+    if path.starts_with(&wide("\\\\?\\")) || path.starts_with(&wide("\\\\.\\")) {
+        return path.to_vec(); // Already verbatim, or a device
+    }
+    match path.strip_prefix(&wide("\\\\")[..]) {
+        Some(share) => [wide("\\\\?\\UNC\\"), share.to_vec()].concat(),
+        None => [wide("\\\\?\\"), path.to_vec()].concat(),
+    }
+}
+
+/// Remove the extended-length prefix from a path, as callers would write it
+fn plain_path(path: &[u16]) -> Vec<u16> {
+    if let Some(share) = path.strip_prefix(&wide("\\\\?\\UNC\\")[..]) {
+        return [wide("\\\\"), share.to_vec()].concat();
+    }
+    match path.strip_prefix(&wide("\\\\?\\")[..]) {
+        // Only drive paths, volume GUID paths have no other form
+        Some(rest) if rest.get(1) == Some(&(b':' as u16)) => rest.to_vec(),
+        _ => path.to_vec(),
+    }
+}
+
 impl Verifier {
     pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        let path_vec = wide_path(path.as_ref())?;
+
+        Ok(Self {
+            path: path_vec,
+            file: None,
+            held: None,
+        })
+    }
+
+    /// Open the file sharing only read access, so it can't be written, replaced or deleted
+    /// until the verifier is dropped, and verify it through that handle
+    pub fn for_held_file(path: &std::path::Path) -> Result<Self, Error> {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(FILE_SHARE_READ)
+            .open(path)
+            .map_err(Error::IoError)?;
+
+        let mut verifier = Verifier::for_file(path)?;
+        verifier.held = Some(file);
+        Ok(verifier)
+    }
+
+    pub fn held_file(&self) -> Option<&std::fs::File> {
+        self.held.as_ref()
+    }
+
+    /// The handle to verify through, if the verifier has one
+    fn handle(&self) -> Option<HANDLE> {
+        use std::os::windows::io::AsRawHandle;
+
+        match (&self.file, &self.held) {
+            (Some(file), _) => Some(file.0),
+            (None, Some(held)) => Some(held.as_raw_handle() as HANDLE),
+            (None, None) => None,
+        }
+    }
+
+    /// Copy the data to a delete-on-close temporary file, and verify it through that handle
+    pub fn for_reader(reader: &mut dyn std::io::Read) -> Result<Self, Error> {
         use std::os::windows::ffi::OsStrExt;
+        use std::os::windows::io::FromRawHandle;
 
-        let mut path_vec: Vec<u16> = path.as_ref().as_os_str().encode_wide().collect();
+        let mut path_vec: Vec<u16> = crate::temp_path().as_os_str().encode_wide().collect();
         path_vec.push(0); // Make sure path is null terminated
 
-        Ok(Self(path_vec))
+        let file = unsafe {
+            CreateFileW(
+                path_vec.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_SHARE_READ | FILE_SHARE_DELETE,
+                std::ptr::null_mut(),
+                CREATE_NEW,
+                FILE_ATTRIBUTE_TEMPORARY | FILE_FLAG_DELETE_ON_CLOSE,
+                0,
+            )
+        };
+        if file == INVALID_HANDLE_VALUE {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+        let file = OwnedHandle(file);
+
+        // Closing the handle deletes the file, so the writer mustn't close it when done
+        let mut writer =
+            std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_handle(file.0 as _) });
+        std::io::copy(reader, &mut *writer).map_err(Error::IoError)?;
+        if unsafe { SetFilePointerEx(file.0, 0, std::ptr::null_mut(), FILE_BEGIN) } == 0 {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+
+        Ok(Self {
+            path: path_vec,
+            file: Some(file),
+            held: None,
+        })
+    }
+
+    /// Verify an already open file, the handle is duplicated so the caller keeps ownership of it
+    pub fn for_handle(handle: HANDLE) -> Result<Self, Error> {
+        let mut file: HANDLE = 0;
+
+        unsafe {
+            let process = GetCurrentProcess();
+            if DuplicateHandle(
+                process,
+                handle,
+                process,
+                &mut file,
+                0,
+                FALSE,
+                DUPLICATE_SAME_ACCESS,
+            ) == 0
+            {
+                return Err(Error::os_error(GetLastError() as i32));
+            }
+        }
+        let file = OwnedHandle(file);
+
+        // WinVerifyTrust still wants a path, which is also the catalog member name. The first
+        // call reports the size needed, with the terminator.
+        let mut path_vec = Vec::new();
+        loop {
+            let len = unsafe {
+                GetFinalPathNameByHandleW(
+                    file.0,
+                    path_vec.as_mut_ptr(),
+                    path_vec.len() as u32,
+                    FILE_NAME_NORMALIZED,
+                )
+            } as usize;
+            match len {
+                0 => return Err(Error::InvalidPath),
+                len if len < path_vec.len() => {
+                    path_vec.truncate(len + 1); // Keep the null terminator
+                    break;
+                }
+                len => path_vec.resize(len, 0),
+            }
+        }
+
+        Ok(Self {
+            path: path_vec,
+            file: Some(file),
+            held: None,
+        })
+    }
+
+    /// Verify the executable of the client on the other end of a named pipe
+    pub fn for_pipe_client(pipe: HANDLE) -> Result<Self, Error> {
+        let mut pid = 0;
+        if unsafe { GetNamedPipeClientProcessId(pipe, &mut pid) } == 0 {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+        Self::for_process_image(pid)
+    }
+
+    /// Verify the executable of this process
+    pub fn for_current_process() -> Result<Self, Error> {
+        Self::for_process_image(unsafe { GetCurrentProcessId() })
+    }
+
+    /// Verify the executable of a process through a handle to its file that's kept open without
+    /// sharing write or delete access, so the file can't be swapped between resolving its path
+    /// and verifying it
+    fn for_process_image(pid: u32) -> Result<Self, Error> {
+        let path_vec = wide_path(std::path::Path::new(&get_process_path(pid)?))?;
+
+        let file = unsafe {
+            CreateFileW(
+                path_vec.as_ptr(),
+                GENERIC_READ,
+                FILE_SHARE_READ,
+                std::ptr::null_mut(),
+                OPEN_EXISTING,
+                0,
+                0,
+            )
+        };
+        if file == INVALID_HANDLE_VALUE {
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+
+        Ok(Self {
+            path: path_vec,
+            file: Some(OwnedHandle(file)),
+            held: None,
+        })
+    }
+
+    /// Compare the main image mapped in the process with its file on disk
+    pub fn compare_image(pid: i32) -> Result<(), Error> {
+        image::compare_image(pid as _)
+    }
+
+    /// The files of all modules loaded in the process
+    pub fn module_paths(pid: i32) -> Result<Vec<std::path::PathBuf>, Error> {
+        image::module_paths(pid as _)
+    }
+
+    /// The parent of the process, as long as it's still the process that started it
+    pub fn parent_pid(pid: i32) -> Result<Option<i32>, Error> {
+        let parent = match get_parent_pid(pid as u32)? {
+            0 | SYSTEM_PID => return Ok(None), // The kernel has no executable to verify
+            parent => parent,
+        };
+
+        // Process IDs are reused, a parent that exited may since have been replaced
+        match (get_creation_time(parent), get_creation_time(pid as u32)) {
+            (Some(parent_time), Some(time)) if parent_time > time => Ok(None),
+            _ => Ok(Some(parent as i32)),
+        }
     }
 
     // Extract the path of a pid, then call for file
@@ -56,56 +478,266 @@ impl Verifier {
         Self::for_file(path)
     }
 
-    pub fn verify(&self, _: &str) -> Result<Context, Error> {
-        unsafe {
-            let mut file_info: WINTRUST_FILE_INFO = std::mem::zeroed();
-            file_info.cbStruct = std::mem::size_of::<WINTRUST_FILE_INFO>() as u32;
-            file_info.pcwszFilePath = self.0.as_ptr();
+    /// The requirement, nested code and architecture options only apply to macOS
+    /// The path of the file being verified, the temporary file for data that wasn't in one
+    pub fn path(&self) -> Result<std::path::PathBuf, Error> {
+        use std::os::windows::ffi::OsStringExt;
+
+        let len = self.path.len().saturating_sub(1); // Without the null terminator
+        Ok(std::ffi::OsString::from_wide(&plain_path(&self.path[..len])).into())
+    }
 
-            match self.verify_internal(Some(&mut file_info), None) {
+    /// The path of the file being verified with links resolved, without the extended-length
+    /// prefix canonicalizing adds
+    fn canonical_path(&self) -> Option<std::path::PathBuf> {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+        let path = std::fs::canonicalize(self.path().ok()?).ok()?;
+        let path: Vec<u16> = path.as_os_str().encode_wide().collect();
+        Some(std::ffi::OsString::from_wide(&plain_path(&path)).into())
+    }
+
+    pub fn verify(&self, options: &VerificationOptions) -> Result<Context, Error> {
+        self.verify_in(&Session::default(), options)
+    }
+
+    /// Verify with the chain engine kept in the session, which must only ever be used with the
+    /// same options
+    pub fn verify_in(
+        &self,
+        session: &Session,
+        options: &VerificationOptions,
+    ) -> Result<Context, Error> {
+        check_anchors(&options.trust_anchors)?;
+
+        let mut context = unsafe {
+            let mut file_info = self.file_info();
+
+            match self.verify_internal(session, options, Some(&mut file_info), None, None) {
                 Ok(context) => Ok(context),
                 Err(err) => {
-                    if err == TRUST_E_NOSIGNATURE as u32 {
-                        self.verify_catalog_signed()
+                    if err.is_unsigned() {
+                        options.check_cancelled()?;
+                        self.verify_catalog_signed(session, options)
                     } else {
-                        Err(Error::OsError(err as i32))
+                        Err(err.into())
                     }
                 }
             }
+        }?;
+
+        context.set_path(self.canonical_path());
+        check_timestamp(&context, options)?;
+        check_requirement(context, options)
+    }
+
+    /// The primary signer an embedded signature claims, read whether or not it verifies.
+    /// Catalogs only vouch for files they validly sign, so there is none to read for them.
+    pub fn claimed_context(&self) -> Option<Context> {
+        let options = VerificationOptions::new()
+            .revocation(RevocationMode::None)
+            .allow_network(false);
+
+        let result = unsafe {
+            let mut file_info = self.file_info();
+            self.verify_internal(
+                &Session::default(),
+                &options,
+                Some(&mut file_info),
+                None,
+                None,
+            )
+        };
+        match result {
+            Ok(context) => Some(context),
+            Err(failure) => failure.context,
         }
     }
 
-    unsafe fn verify_catalog_signed(&self) -> Result<Context, Error> {
-        let h_file = CreateFileW(
-            self.0.as_ptr(),
-            GENERIC_READ,
-            FILE_SHARE_READ,
-            std::ptr::null_mut(),
-            OPEN_EXISTING,
-            0,
-            0,
-        );
+    /// The sha256 thumbprint of the certificate the embedded signature claims to be signed
+    /// with, read without verifying anything. None for files that aren't signed that way,
+    /// such as catalog signed ones.
+    pub fn claimed_thumbprint(&self) -> Option<String> {
+        unsafe {
+            let mut store: HCERTSTORE = std::mem::zeroed();
+            let mut msg: *mut std::ffi::c_void = std::ptr::null_mut();
+            if CryptQueryObject(
+                CERT_QUERY_OBJECT_FILE,
+                self.path.as_ptr() as _,
+                CERT_QUERY_CONTENT_FLAG_PKCS7_SIGNED_EMBED,
+                CERT_QUERY_FORMAT_FLAG_BINARY,
+                0,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut store,
+                &mut msg,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                return None;
+            }
 
-        if h_file == INVALID_HANDLE_VALUE {
-            let err = GetLastError() as i32;
-            return Err(Error::OsError(err));
+            // The signer is identified by the issuer and serial number of its certificate
+            let mut thumbprint = None;
+            let mut len: u32 = 0;
+            if CryptMsgGetParam(
+                msg,
+                CMSG_SIGNER_CERT_INFO_PARAM,
+                0,
+                std::ptr::null_mut(),
+                &mut len,
+            ) != 0
+            {
+                // Allocate as u64 so the buffer is aligned for the CERT_INFO
+                let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+                if CryptMsgGetParam(
+                    msg,
+                    CMSG_SIGNER_CERT_INFO_PARAM,
+                    0,
+                    buf.as_mut_ptr() as _,
+                    &mut len,
+                ) != 0
+                {
+                    let cert = CertFindCertificateInStore(
+                        store,
+                        X509_ASN_ENCODING | PKCS_7_ASN_ENCODING,
+                        0,
+                        CERT_FIND_SUBJECT_CERT,
+                        buf.as_ptr() as _,
+                        std::ptr::null(),
+                    );
+                    if let Some(cert) = cert.as_ref() {
+                        let data = context::certificate_data(cert);
+                        thumbprint = Some(crate::digest::hex(&crate::digest::sha256(data)));
+                        CertFreeCertificateContext(cert);
+                    }
+                }
+            }
+
+            CryptMsgClose(msg);
+            CertCloseStore(store, 0);
+            thumbprint
         }
+    }
 
-        let mut ctx = CleanupContext::new(h_file);
+    /// Count the primary signature along with any nested secondary signatures
+    pub fn signer_count(&self) -> Result<u32, Error> {
+        unsafe {
+            let mut file_info = self.file_info();
+            let mut settings =
+                signature_settings(WSS_VERIFY_SPECIFIC | WSS_GET_SECONDARY_SIG_COUNT, 0);
 
-        let mut h_cat_admin: HANDLE = 0;
-        let result = CryptCATAdminAcquireContext2(
-            &mut h_cat_admin,
-            std::ptr::null(),
-            BCRYPT_SHA256_ALGORITHM,
-            std::ptr::null(),
-            0,
-        );
-        if result == 0 {
-            let err = GetLastError() as i32;
-            return Err(Error::OsError(err));
+            let options = VerificationOptions::new();
+            let session = Session::default();
+
+            match self.verify_internal(
+                &session,
+                &options,
+                Some(&mut file_info),
+                None,
+                Some(&mut settings),
+            ) {
+                Ok(_) => Ok(settings.cSecondarySigs + 1),
+                // Catalogs only ever sign a file once
+                Err(err) if err.is_unsigned() => {
+                    self.verify_catalog_signed(&session, &options).map(|_| 1)
+                }
+                Err(err) => Err(err.into()),
+            }
         }
+    }
+
+    /// Verify the signature at the given index, where 0 is the primary signature
+    pub fn verify_signer(
+        &self,
+        index: u32,
+        options: &VerificationOptions,
+    ) -> Result<Context, Error> {
+        self.verify_signer_in(&Session::default(), index, options)
+    }
 
+    /// Verify the signature at the given index with the chain engine kept in the session
+    pub fn verify_signer_in(
+        &self,
+        session: &Session,
+        index: u32,
+        options: &VerificationOptions,
+    ) -> Result<Context, Error> {
+        if index == 0 {
+            return self.verify_in(session, options);
+        }
+        check_anchors(&options.trust_anchors)?;
+
+        let context = unsafe {
+            let mut file_info = self.file_info();
+            let mut settings = signature_settings(WSS_VERIFY_SPECIFIC, index);
+
+            match self.verify_internal(
+                session,
+                options,
+                Some(&mut file_info),
+                None,
+                Some(&mut settings),
+            ) {
+                Ok(context) => Ok(context),
+                Err(err) if err.is_unsigned() => Err(Error::SignerNotFound),
+                Err(err) => Err(err.into()),
+            }
+        }?;
+
+        check_requirement(context, options)
+    }
+
+    unsafe fn file_info(&self) -> WINTRUST_FILE_INFO {
+        let mut file_info: WINTRUST_FILE_INFO = std::mem::zeroed();
+        file_info.cbStruct = std::mem::size_of::<WINTRUST_FILE_INFO>() as u32;
+        file_info.pcwszFilePath = self.path.as_ptr();
+        if let Some(handle) = self.handle() {
+            file_info.hFile = handle;
+        }
+        file_info
+    }
+
+    unsafe fn verify_catalog_signed(
+        &self,
+        session: &Session,
+        options: &VerificationOptions,
+    ) -> Result<Context, Error> {
+        with_cat_admin(|h_cat_admin| self.verify_catalog_signed_with(session, options, h_cat_admin))
+    }
+
+    unsafe fn verify_catalog_signed_with(
+        &self,
+        session: &Session,
+        options: &VerificationOptions,
+        h_cat_admin: HANDLE,
+    ) -> Result<Context, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("catalog_lookup").entered();
+
+        let (h_file, owned) = match self.handle() {
+            Some(handle) => (handle, 0), // Still owned by the verifier
+            None => {
+                let h_file = CreateFileW(
+                    self.path.as_ptr(),
+                    GENERIC_READ,
+                    FILE_SHARE_READ,
+                    std::ptr::null_mut(),
+                    OPEN_EXISTING,
+                    0,
+                    0,
+                );
+
+                if h_file == INVALID_HANDLE_VALUE {
+                    let err = GetLastError() as i32;
+                    return Err(Error::os_error(err));
+                }
+                (h_file, h_file)
+            }
+        };
+
+        let mut ctx = CleanupContext::new(owned);
         ctx.h_cat_admin = h_cat_admin;
 
         let mut hash_size: DWORD = 32;
@@ -119,7 +751,7 @@ impl Verifier {
         );
         if result == 0 {
             let err = GetLastError() as i32;
-            return Err(Error::OsError(err));
+            return Err(Error::os_error(err));
         }
 
         let h_cat_info = CryptCATAdminEnumCatalogFromHash(
@@ -130,6 +762,8 @@ impl Verifier {
             std::ptr::null_mut(),
         );
         if h_cat_info == 0 {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("no catalog contains the file hash");
             return Err(Error::Unsigned);
         }
 
@@ -141,58 +775,89 @@ impl Verifier {
         let result = CryptCATCatalogInfoFromContext(h_cat_info, &mut ci, 0);
         if result == 0 {
             let err = GetLastError() as i32;
-            return Err(Error::OsError(err));
+            return Err(Error::os_error(err));
         }
 
-        let hash_str = hash_buffer
-            .iter()
-            .map(|&val| format!("{:02x}", val))
-            .collect::<Vec<String>>()
-            .join("");
+        let hash_str = crate::digest::hex(&hash_buffer[..hash_size as usize]);
         let mut hash: Vec<u16> = hash_str.encode_utf16().collect();
         hash.push(0); // Make sure hash is null terminated
 
         let mut wci: WINTRUST_CATALOG_INFO = std::mem::zeroed();
         wci.cbStruct = std::mem::size_of::<WINTRUST_CATALOG_INFO>() as u32;
         wci.pcwszCatalogFilePath = ci.wszCatalogFile.as_ptr();
-        wci.pcwszMemberFilePath = self.0.as_ptr();
+        wci.pcwszMemberFilePath = self.path.as_ptr();
+        wci.hMemberFile = h_file;
         wci.pcwszMemberTag = hash.as_ptr();
 
-        match self.verify_internal(None, Some(&mut wci)) {
-            Ok(context) => Ok(context),
-            Err(err) => Err(Error::OsError(err as i32)),
+        match self.verify_internal(session, options, None, Some(&mut wci), None) {
+            Ok(mut context) => {
+                use std::os::windows::ffi::OsStringExt;
+
+                let len = ci.wszCatalogFile.iter().position(|&c| c == 0);
+                let path = &ci.wszCatalogFile[..len.unwrap_or(ci.wszCatalogFile.len())];
+
+                context.set_catalog(CatalogInfo {
+                    path: std::ffi::OsString::from_wide(path).into(),
+                    member_tag: hash_str,
+                });
+                Ok(context)
+            }
+            Err(err) => Err(err.into()),
         }
     }
 
     unsafe fn verify_internal(
         &self,
+        session: &Session,
+        options: &VerificationOptions,
         file_info: Option<*mut WINTRUST_FILE_INFO>,
         catalog_info: Option<*mut WINTRUST_CATALOG_INFO>,
-    ) -> Result<Context, WIN32_ERROR> {
+        settings: Option<*mut WINTRUST_SIGNATURE_SETTINGS>,
+    ) -> Result<Context, TrustFailure> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "win_verify_trust",
+            catalog = catalog_info.is_some(),
+            revocation = ?options.revocation
+        )
+        .entered();
+
         // Initialize the WINTRUST_DATA structure
         let mut data: WINTRUST_DATA = std::mem::zeroed();
         data.cbStruct = std::mem::size_of::<WINTRUST_DATA>() as u32;
         data.dwUIChoice = WTD_UI_NONE;
-        data.fdwRevocationChecks = WTD_REVOKE_NONE;
+        data.fdwRevocationChecks = match options.revocation {
+            Some(RevocationMode::WholeChain) => WTD_REVOKE_WHOLECHAIN,
+            _ => WTD_REVOKE_NONE,
+        };
         data.dwStateAction = WTD_STATEACTION_VERIFY;
         data.dwUIContext = WTD_UICONTEXT_EXECUTE;
+        data.pSignatureSettings = settings.unwrap_or(std::ptr::null_mut());
 
         if let Some(fi) = file_info {
             data.dwUnionChoice = WTD_CHOICE_FILE;
             data.Anonymous.pFile = fi;
-            data.dwProvFlags =
-                WTD_DISABLE_MD2_MD4 | WTD_REVOCATION_CHECK_END_CERT | WTD_NO_IE4_CHAIN_FLAG;
+            data.dwProvFlags = WTD_DISABLE_MD2_MD4
+                | WTD_NO_IE4_CHAIN_FLAG
+                | revocation_flags(options).unwrap_or(WTD_REVOCATION_CHECK_END_CERT)
+                | provider_flags(options);
         } else if let Some(ci) = catalog_info {
             data.dwUnionChoice = WTD_CHOICE_CATALOG;
             data.Anonymous.pCatalog = ci;
-            data.dwProvFlags = WTD_CACHE_ONLY_URL_RETRIEVAL | WTD_USE_DEFAULT_OSVER_CHECK;
+            data.dwProvFlags = WTD_CACHE_ONLY_URL_RETRIEVAL
+                | WTD_USE_DEFAULT_OSVER_CHECK
+                | revocation_flags(options).unwrap_or(0)
+                | provider_flags(options);
         } else {
-            return Err(ERROR_INVALID_PARAMETER);
+            return Err(ERROR_INVALID_PARAMETER.into());
         }
 
         let mut guid = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+        // Taken once, so the cached-only verification and the rebuild agree on whether to use it
+        let network_budget = options.network_budget();
 
         // Verify that the signature is actually valid
+        let mut system_trusted = true;
         match WinVerifyTrust(
             INVALID_HANDLE_VALUE as _,
             &mut guid,
@@ -200,30 +865,281 @@ impl Verifier {
         ) {
             0 => {}
             _ => {
-                let _ = Context::new(data.hWVTStateData); // So close gets called on the data
-                return Err(GetLastError());
+                let code = GetLastError();
+                let mut failure = TrustFailure {
+                    code,
+                    chain: context::chain_status(data.hWVTStateData),
+                    context: None,
+                };
+
+                // A chain ending at a root the system doesn't know can still end at a pinned anchor,
+                // one that expired can still be valid as of the requested time, and offline
+                // verification reports unknown revocation in the chain status instead
+                let tolerated = (!options.trust_anchors.is_empty() && failure.is_untrusted_root())
+                    || (options.verify_time.is_some() && failure.is_expired())
+                    || ((options.offline || network_budget.is_some())
+                        && failure.is_revocation_unknown());
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    code = failure.code,
+                    tolerated,
+                    revocation_unknown = failure.is_revocation_unknown(),
+                    "WinVerifyTrust failed"
+                );
+                if !tolerated {
+                    // Kept for the claimed signer, dropping it closes the data
+                    failure.context = Context::new(data.hWVTStateData).ok();
+                    return Err(failure);
+                }
+                system_trusted = !failure.is_untrusted_root();
+            }
+        }
+
+        let context = Context::new(data.hWVTStateData).map_err(TrustFailure::from)?;
+
+        // When the system roots are kept, the anchors only matter for chains the system rejects
+        let anchors: &[Vec<u8>] = if options.trust_system_roots && system_trusted {
+            &[]
+        } else {
+            &options.trust_anchors
+        };
+
+        // With a timeout WinVerifyTrust only used cached data, the network is left to the chain rebuild
+        if !anchors.is_empty() || options.verify_time.is_some() || network_budget.is_some() {
+            let status = context
+                .rebuild_chain(
+                    &session.engine,
+                    anchors,
+                    options.verify_time,
+                    network_budget,
+                    chain_flags(options, !anchors.is_empty()),
+                )
+                .map_err(|_| TrustFailure::from(CERT_E_UNTRUSTEDROOT as u32))?;
+
+            // Offline, revocation that can't be checked is only reported
+            let ignored = if options.offline {
+                REVOCATION_UNKNOWN
+            } else {
+                0
+            };
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                error_status = status.error_status,
+                revocation_unknown = status.error_status & REVOCATION_UNKNOWN != 0,
+                "chain rebuilt"
+            );
+            if status.error_status & !ignored != 0 {
+                let code = if status.is_expired() {
+                    CERT_E_EXPIRED
+                } else if status.error_status & !REVOCATION_UNKNOWN == 0 {
+                    CRYPT_E_REVOCATION_OFFLINE
+                } else {
+                    CERT_E_UNTRUSTEDROOT
+                };
+                return Err(TrustFailure {
+                    code: code as u32,
+                    chain: Some(status),
+                    context: Some(context),
+                });
             }
         }
 
-        Context::new(data.hWVTStateData)
+        Ok(context)
+    }
+}
+
+/// The provider flags for an explicitly requested revocation mode
+fn revocation_flags(options: &VerificationOptions) -> Option<u32> {
+    options.revocation.map(|mode| match mode {
+        RevocationMode::None => WTD_REVOCATION_CHECK_NONE,
+        RevocationMode::EndCertificate => WTD_REVOCATION_CHECK_END_CERT,
+        RevocationMode::WholeChain => WTD_REVOCATION_CHECK_CHAIN,
+    })
+}
+
+/// Check the policy, and the certificate conditions of the requirement, the only ones with a Windows equivalent
+fn check_requirement(context: Context, options: &VerificationOptions) -> Result<Context, Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("requirement_evaluation", policy = ?options.policy).entered();
+
+    let satisfied = match options.policy {
+        None | Some(Policy::AnySystemTrusted) => true, // WinVerifyTrust already requires a trusted root
+        Some(Policy::MicrosoftRootsOnly) => context.is_microsoft_root(),
+        Some(Policy::StoreSigned) => context.is_store_signed(),
+        Some(Policy::AppleAnchored) => return Err(Error::Unsupported),
+    };
+
+    if !satisfied {
+        return Err(Error::RequirementFailed);
+    }
+
+    match &options.requirement {
+        Some(requirement) if !requirement.matches_subject(&context.subject_name()) => {
+            Err(Error::RequirementFailed)
+        }
+        _ => Ok(context),
+    }
+}
+
+/// WinVerifyTrust already evaluates the chain as of a counter signer's time, short-lived
+/// certificates only need the signature to have an RFC 3161 one
+fn check_timestamp(context: &Context, options: &VerificationOptions) -> Result<(), Error> {
+    if options.timestamp_policy == TimestampPolicy::ShortLived
+        && (context.timestamp().is_none() || !context.has_rfc3161_timestamp())
+    {
+        return Err(Error::TimestampRequired);
+    }
+    Ok(())
+}
+
+/// Reject malformed trust anchors up front, rather than reporting them as a chain failure
+fn check_anchors(anchors: &[Vec<u8>]) -> Result<(), Error> {
+    for der in anchors {
+        let cert = unsafe {
+            CertCreateCertificateContext(X509_ASN_ENCODING, der.as_ptr(), der.len() as _)
+        };
+        if cert.is_null() {
+            return Err(Error::InvalidCertificate);
+        }
+        unsafe { CertFreeCertificateContext(cert) };
+    }
+    Ok(())
+}
+
+/// The chain building flags matching the revocation options, chains to a pinned anchor
+/// are only checked for revocation when asked to
+fn chain_flags(options: &VerificationOptions, pinned: bool) -> u32 {
+    let mut flags = match options.revocation {
+        Some(RevocationMode::None) => 0,
+        None if pinned => 0,
+        Some(RevocationMode::WholeChain) => CERT_CHAIN_REVOCATION_CHECK_CHAIN,
+        _ => CERT_CHAIN_REVOCATION_CHECK_END_CERT,
+    };
+
+    if !options.allow_network || options.offline {
+        flags |= CERT_CHAIN_REVOCATION_CHECK_CACHE_ONLY;
+    } else if options.network_budget().is_some() {
+        // The timeout covers all retrievals for the chain, rather than each one
+        flags |= CERT_CHAIN_REVOCATION_ACCUMULATIVE_TIMEOUT;
+    }
+
+    flags
+}
+
+/// Extra provider flags for the options, on top of the defaults
+fn provider_flags(options: &VerificationOptions) -> u32 {
+    let mut flags = options.windows_provider_flags;
+
+    if options.timestamp_policy == TimestampPolicy::LifetimeSigning {
+        flags |= WTD_LIFETIME_SIGNING_FLAG;
     }
+    // Only use revocation data already in the URL cache, never go to the network for it.
+    // With a timeout the network is only used by the chain rebuild, which can be bounded
+    if !options.allow_network || options.offline || options.network_budget().is_some() {
+        flags |= WTD_CACHE_ONLY_URL_RETRIEVAL;
+    }
+
+    flags
+}
+
+fn signature_settings(flags: u32, index: u32) -> WINTRUST_SIGNATURE_SETTINGS {
+    let mut settings: WINTRUST_SIGNATURE_SETTINGS = unsafe { std::mem::zeroed() };
+    settings.cbStruct = std::mem::size_of::<WINTRUST_SIGNATURE_SETTINGS>() as u32;
+    settings.dwFlags = flags;
+    settings.dwIndex = index;
+    settings
 }
 
 /// Attempts to get the full system path for a given proccess id
-fn get_process_path(proc_id: u32) -> Result<String, Error> {
-    let mut buf = [0u16; 2048];
+/// The system's message for a Win32 error or HRESULT, when there is one
+pub fn os_error_message(code: i32) -> Option<String> {
+    let mut buf = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code as u32,
+            0,
+            buf.as_mut_ptr(),
+            buf.len() as u32,
+            std::ptr::null(),
+        )
+    } as usize;
+
+    match String::from_utf16_lossy(&buf[..len]).trim_end() {
+        "" => None,
+        message => Some(message.to_string()),
+    }
+}
+
+/// Describe a Win32 error or HRESULT, with its message when there is one
+pub fn describe_os_error(code: i32, message: Option<&str>) -> String {
+    match message {
+        Some(message) => format!("{} ({:#010x})", message, code as u32),
+        None => format!("{:#010x}", code as u32),
+    }
+}
+
+/// Find the process in a snapshot of all processes, which records the PID of its parent
+fn get_parent_pid(proc_id: u32) -> Result<u32, Error> {
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(Error::os_error(GetLastError() as i32));
+        }
+        let snapshot = OwnedHandle(snapshot);
+
+        let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+
+        let mut found = Process32FirstW(snapshot.0, &mut entry);
+        while found != 0 {
+            if entry.th32ProcessID == proc_id {
+                return Ok(entry.th32ParentProcessID);
+            }
+            found = Process32NextW(snapshot.0, &mut entry);
+        }
+    }
+
+    Err(Error::os_error(ERROR_INVALID_PARAMETER as i32)) // No such process
+}
+
+/// When the process started, as a FILETIME tick count
+fn get_creation_time(proc_id: u32) -> Option<u64> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, proc_id);
+        if process == 0 {
+            return None;
+        }
+        let process = OwnedHandle(process);
+
+        let mut creation: FILETIME = std::mem::zeroed();
+        let (mut exit, mut kernel, mut user) = (creation, creation, creation);
+        if GetProcessTimes(process.0, &mut creation, &mut exit, &mut kernel, &mut user) == 0 {
+            return None;
+        }
+        Some((creation.dwHighDateTime as u64) << 32 | creation.dwLowDateTime as u64)
+    }
+}
+
+fn get_process_path(proc_id: u32) -> Result<std::ffi::OsString, Error> {
+    use std::os::windows::ffi::OsStringExt;
+
+    // Long enough for an extended-length path
+    let mut buf = vec![0u16; 32768];
 
     unsafe {
         let proc_handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, proc_id) {
-            handle if handle == 0 => return Err(Error::OsError(GetLastError() as i32)),
-            handle => handle,
+            handle if handle == 0 => return Err(Error::os_error(GetLastError() as i32)),
+            handle => OwnedHandle(handle),
         };
 
         let mut path_len = buf.len() as _;
 
-        match QueryFullProcessImageNameW(proc_handle, 0, buf.as_mut_ptr(), &mut path_len) {
-            0 => Err(Error::OsError(GetLastError() as i32)),
-            _ => Ok(String::from_utf16_lossy(&buf[..path_len as usize])),
+        match QueryFullProcessImageNameW(proc_handle.0, 0, buf.as_mut_ptr(), &mut path_len) {
+            0 => Err(Error::os_error(GetLastError() as i32)),
+            // Kept as UTF-16, file names needn't be valid Unicode
+            _ => Ok(std::ffi::OsString::from_wide(&buf[..path_len as usize])),
         }
     }
 }
@@ -237,7 +1153,7 @@ mod tests {
     fn verify_file(process_path: &str, expected_issuer: &str) {
         match Verifier::for_file(process_path) {
             Ok(signature_verifier) => {
-                match signature_verifier.verify() {
+                match signature_verifier.verify(&VerificationOptions::new()) {
                     Ok(context) => {
                         assert_eq!(context.issuer_name().organization.unwrap(), expected_issuer);
                     }
@@ -264,4 +1180,386 @@ mod tests {
     fn test_catalog_signed_file() {
         verify_file("c:\\windows\\system32\\cmd.exe", "Microsoft Corporation");
     }
+
+    #[test]
+    fn test_catalog_attribution() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\cmd.exe").unwrap();
+        let context = verifier.verify(&VerificationOptions::new()).unwrap();
+        let catalog = context.catalog().unwrap();
+        assert!(catalog
+            .path
+            .extension()
+            .unwrap()
+            .eq_ignore_ascii_case("cat"));
+        assert_eq!(catalog.member_tag.len(), 64);
+
+        // The cached catalog admin context is reused for the second lookup
+        assert!(verifier
+            .verify(&VerificationOptions::new())
+            .unwrap()
+            .catalog()
+            .is_some());
+    }
+
+    #[test]
+    fn test_for_handle() {
+        use std::os::windows::io::AsRawHandle;
+
+        // Both an embedded and a catalog signature
+        for path in &[
+            "c:\\windows\\system32\\svchost.exe",
+            "c:\\windows\\system32\\cmd.exe",
+        ] {
+            let file = std::fs::File::open(path).unwrap();
+            let verifier = Verifier::for_handle(file.as_raw_handle() as _).unwrap();
+            assert!(verifier.verify(&VerificationOptions::new()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_long_path() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let root = crate::temp_path();
+        let mut dir = root.clone();
+        for _ in 0..5 {
+            dir.push("d".repeat(60));
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // An unpaired surrogate isn't valid Unicode, but is a valid file name
+        let name = std::ffi::OsString::from_wide(&[
+            b's' as u16,
+            0xd800,
+            b'.' as u16,
+            b'e' as u16,
+            b'x' as u16,
+            b'e' as u16,
+        ]);
+        let path = dir.join(name);
+        assert!(path.as_os_str().len() > MAX_PATH);
+        std::fs::copy("c:\\windows\\system32\\svchost.exe", &path).unwrap();
+
+        let verifier = Verifier::for_file(&path).unwrap();
+        let res = verifier.verify(&VerificationOptions::new());
+        let reported = verifier.path().unwrap();
+        drop(verifier);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(res.is_ok());
+        assert_eq!(reported, path);
+    }
+
+    #[test]
+    fn test_extended_path() {
+        let convert = |path: &str| String::from_utf16(&extended_path(&wide(path))).unwrap();
+        assert_eq!(convert("c:\\dir\\a.exe"), "\\\\?\\c:\\dir\\a.exe");
+        assert_eq!(
+            convert("\\\\server\\share\\a.exe"),
+            "\\\\?\\UNC\\server\\share\\a.exe"
+        );
+        assert_eq!(convert("\\\\?\\c:\\a.exe"), "\\\\?\\c:\\a.exe");
+
+        let plain = |path: &str| String::from_utf16(&plain_path(&wide(path))).unwrap();
+        assert_eq!(
+            plain("\\\\?\\UNC\\server\\share\\a.exe"),
+            "\\\\server\\share\\a.exe"
+        );
+        assert_eq!(plain("\\\\?\\c:\\a.exe"), "c:\\a.exe");
+        assert_eq!(plain("\\\\?\\Volume{0}\\a.exe"), "\\\\?\\Volume{0}\\a.exe");
+    }
+
+    #[test]
+    fn test_pipe_client() {
+        use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_DUPLEX;
+        use windows_sys::Win32::System::Pipes::CreateNamedPipeW;
+
+        let name = format!("\\\\.\\pipe\\codesign-verify-{}", std::process::id());
+        let wide: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+        let server = unsafe {
+            CreateNamedPipeW(
+                wide.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                0,
+                1,
+                0,
+                0,
+                0,
+                std::ptr::null(),
+            )
+        };
+        assert_ne!(server, INVALID_HANDLE_VALUE);
+        let server = OwnedHandle(server);
+        let _client = std::fs::File::open(&name).unwrap();
+
+        // The client is the test executable itself, which isn't signed
+        let res = Verifier::for_pipe_client(server.0)
+            .unwrap()
+            .verify(&VerificationOptions::new());
+        assert!(matches!(res, Err(Error::Unsigned)));
+    }
+
+    #[test]
+    fn test_current_process() {
+        // The test executable isn't signed
+        let res = Verifier::for_current_process()
+            .unwrap()
+            .verify(&VerificationOptions::new());
+        assert!(matches!(res, Err(Error::Unsigned)));
+    }
+
+    #[test]
+    fn test_compare_image() {
+        assert!(Verifier::compare_image(std::process::id() as i32).is_ok());
+    }
+
+    #[test]
+    fn test_signer_count() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let count = verifier.signer_count().unwrap();
+        assert!(count >= 1);
+
+        for index in 0..count {
+            assert!(verifier
+                .verify_signer(index, &VerificationOptions::new())
+                .is_ok());
+        }
+        assert!(matches!(
+            verifier.verify_signer(count, &VerificationOptions::new()),
+            Err(Error::SignerNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_offline() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let options = VerificationOptions::new()
+            .revocation(RevocationMode::WholeChain)
+            .offline(true);
+        assert!(verifier.verify(&options).is_ok());
+    }
+
+    #[test]
+    fn test_network_timeout() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let options =
+            VerificationOptions::new().network_timeout(std::time::Duration::from_secs(30));
+        assert!(verifier.verify(&options).is_ok());
+    }
+
+    #[test]
+    fn test_revocation_status() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let context = verifier.verify(&VerificationOptions::new()).unwrap();
+        let status = context.revocation_status();
+
+        // The leaf certificate comes first
+        assert_eq!(status[0].thumbprint, context.sha256_thumbprint());
+        assert!(status
+            .iter()
+            .all(|cert| cert.state != crate::RevocationState::Revoked));
+        // Checked certificates report the CRL or OCSP response they were checked with
+        for cert in status
+            .iter()
+            .filter(|cert| cert.state == crate::RevocationState::Good)
+        {
+            assert!(cert.source.is_some());
+            assert!(cert.is_current_at(std::time::SystemTime::now()));
+        }
+    }
+
+    #[test]
+    fn test_requirement() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+
+        let options = VerificationOptions::new().requirement(
+            crate::Requirement::anchor_trusted().organization("Microsoft Corporation"),
+        );
+        assert!(verifier.verify(&options).is_ok());
+
+        let options = VerificationOptions::new()
+            .requirement(crate::Requirement::anchor_trusted().organization("Someone Else"));
+        assert!(matches!(
+            verifier.verify(&options),
+            Err(Error::RequirementFailed)
+        ));
+    }
+
+    #[test]
+    fn test_verify_time() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let context = verifier.verify(&VerificationOptions::new()).unwrap();
+
+        // Long before the certificate was issued
+        let options = VerificationOptions::new().verify_time(std::time::SystemTime::UNIX_EPOCH);
+        assert!(matches!(
+            verifier.verify(&options),
+            Err(Error::Expired { ref status, .. }) if status.is_expired()
+        ));
+
+        if let Some(timestamp) = context.timestamp() {
+            let options = VerificationOptions::new().verify_time(timestamp);
+            assert!(verifier.verify(&options).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_cat_admin_reentrant() {
+        let nested = with_cat_admin(|outer| {
+            with_cat_admin(|inner| {
+                assert_eq!(outer, inner);
+                Ok(())
+            })
+        });
+        assert!(nested.is_ok());
+    }
+
+    #[test]
+    fn test_driver_policy() {
+        let windir = std::env::var("windir").unwrap();
+        let path = format!("{}\\System32\\drivers\\ntfs.sys", windir);
+        let context = Verifier::for_file(&path)
+            .unwrap()
+            .verify(&VerificationOptions::new())
+            .unwrap();
+
+        let policy = context.driver_policy();
+        assert!(policy.microsoft_root);
+        assert_eq!(policy.signing, Some(crate::DriverSigning::WindowsComponent));
+        assert!(policy.would_load());
+
+        // Without a Microsoft root no driver signature is enough
+        let cross_signed = crate::DriverPolicy {
+            microsoft_root: false,
+            signing: Some(crate::DriverSigning::Whql),
+        };
+        assert!(!cross_signed.would_load());
+        assert!(crate::CodeSignVerifier::for_file(&path)
+            .unwrap()
+            .verify_driver()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_short_lived() {
+        let verifier = Verifier::for_file("c:\\windows\\explorer.exe").unwrap();
+        let context = verifier.verify(&VerificationOptions::new()).unwrap();
+        let timestamped = context.timestamp().is_some() && context.has_rfc3161_timestamp();
+
+        let options = VerificationOptions::new().timestamp_policy(TimestampPolicy::ShortLived);
+        match verifier.verify(&options) {
+            Ok(_) => assert!(timestamped),
+            Err(err) => assert!(!timestamped && matches!(err, Error::TimestampRequired)),
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+
+        let options = VerificationOptions::new().policy(Policy::MicrosoftRootsOnly);
+        assert!(verifier.verify(&options).is_ok());
+
+        let options = VerificationOptions::new().policy(Policy::AppleAnchored);
+        assert!(matches!(verifier.verify(&options), Err(Error::Unsupported)));
+    }
+
+    /// A root of the system store, other than the given one
+    fn other_root(root: &[u8]) -> Vec<u8> {
+        unsafe {
+            let store = CertOpenSystemStoreW(0, wide("ROOT\0").as_ptr());
+            assert!(!store.is_null());
+
+            let mut other = None;
+            let mut cert = CertEnumCertificatesInStore(store, std::ptr::null());
+            while let Some(c) = cert.as_ref() {
+                let der = context::certificate_data(c);
+                if der != root {
+                    other = Some(der.to_vec());
+                    CertFreeCertificateContext(cert);
+                    break;
+                }
+                cert = CertEnumCertificatesInStore(store, cert);
+            }
+            CertCloseStore(store, 0);
+            other.unwrap()
+        }
+    }
+
+    #[test]
+    fn test_trust_anchors() {
+        let verifier = Verifier::for_file("c:\\windows\\explorer.exe").unwrap();
+        let chain = verifier
+            .verify(&VerificationOptions::new())
+            .unwrap()
+            .certificate_chain();
+        let root = chain.last().unwrap();
+
+        // Only the pinned root is trusted, the chain of the system binary ends at it
+        let options = VerificationOptions::new().trust_anchor(root);
+        assert!(verifier.verify(&options).is_ok());
+
+        let options = VerificationOptions::new().trust_anchor(&other_root(root));
+        let err = verifier.verify(&options).unwrap_err();
+        assert!(err.chain_status().is_some(), "{:?}", err);
+    }
+
+    #[test]
+    fn test_lifetime_signing_flag() {
+        let options = VerificationOptions::new();
+        assert_eq!(provider_flags(&options) & WTD_LIFETIME_SIGNING_FLAG, 0);
+
+        let options = options.timestamp_policy(TimestampPolicy::LifetimeSigning);
+        assert_ne!(provider_flags(&options) & WTD_LIFETIME_SIGNING_FLAG, 0);
+    }
+
+    #[test]
+    fn test_revocation_flags() {
+        let options = VerificationOptions::new();
+        assert_eq!(revocation_flags(&options), None);
+        assert_eq!(
+            chain_flags(&options, false),
+            CERT_CHAIN_REVOCATION_CHECK_END_CERT
+        );
+        assert_eq!(chain_flags(&options, true), 0);
+        assert_eq!(provider_flags(&options) & WTD_CACHE_ONLY_URL_RETRIEVAL, 0);
+
+        let options = VerificationOptions::new().revocation(RevocationMode::None);
+        assert_eq!(revocation_flags(&options), Some(WTD_REVOCATION_CHECK_NONE));
+        assert_eq!(chain_flags(&options, false), 0);
+
+        let options = VerificationOptions::new().revocation(RevocationMode::EndCertificate);
+        assert_eq!(
+            revocation_flags(&options),
+            Some(WTD_REVOCATION_CHECK_END_CERT)
+        );
+        assert_eq!(
+            chain_flags(&options, true),
+            CERT_CHAIN_REVOCATION_CHECK_END_CERT
+        );
+
+        let options = VerificationOptions::new().revocation(RevocationMode::WholeChain);
+        assert_eq!(revocation_flags(&options), Some(WTD_REVOCATION_CHECK_CHAIN));
+        assert_eq!(
+            chain_flags(&options, false),
+            CERT_CHAIN_REVOCATION_CHECK_CHAIN
+        );
+
+        // Cache-only retrieval, for both WinVerifyTrust and the chain rebuild
+        let options = options.allow_network(false);
+        assert_eq!(
+            chain_flags(&options, false),
+            CERT_CHAIN_REVOCATION_CHECK_CHAIN | CERT_CHAIN_REVOCATION_CHECK_CACHE_ONLY
+        );
+        assert_ne!(provider_flags(&options) & WTD_CACHE_ONLY_URL_RETRIEVAL, 0);
+    }
+
+    #[test]
+    fn test_invalid_trust_anchor() {
+        let verifier = Verifier::for_file("c:\\windows\\system32\\svchost.exe").unwrap();
+        let options = VerificationOptions::new().trust_anchor(b"not a certificate");
+        assert!(matches!(
+            verifier.verify(&options),
+            Err(Error::InvalidCertificate)
+        ));
+    }
 }
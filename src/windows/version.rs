@@ -0,0 +1,105 @@
+//!
+//! The `VS_VERSIONINFO` resource of a PE file, read with the version API.
+//!
+
+use super::wintrust_sys::*;
+use std::path::Path;
+
+/// The string table read when the file lists no translation, US English in Unicode
+const DEFAULT_TRANSLATION: &str = "040904b0";
+
+/// The strings of the version resource that identify the product
+#[derive(Default)]
+pub(crate) struct VersionStrings {
+    pub product_name: Option<String>,
+    pub file_version: Option<String>,
+    pub company_name: Option<String>,
+    pub original_filename: Option<String>,
+}
+
+/// The address and length of a value in the resource, which points into `data`
+fn query(data: &[u8], sub_block: &str) -> Option<(*const u8, usize)> {
+    let mut sub_block = super::wide(sub_block);
+    sub_block.push(0);
+    let mut value: *mut std::ffi::c_void = std::ptr::null_mut();
+    let mut len: u32 = 0;
+
+    match unsafe { VerQueryValueW(data.as_ptr() as _, sub_block.as_ptr(), &mut value, &mut len) } {
+        0 => None,
+        _ if value.is_null() => None,
+        _ => Some((value as *const u8, len as usize)),
+    }
+}
+
+/// The language and code page of the first string table the file lists
+fn translation(data: &[u8]) -> Option<String> {
+    let (value, len) = query(data, "\\VarFileInfo\\Translation")?;
+    if len < 4 {
+        return None;
+    }
+    let pair = unsafe { std::slice::from_raw_parts(value as *const u16, 2) };
+    Some(format!("{:04x}{:04x}", pair[0], pair[1]))
+}
+
+/// A string from the table, None when it's missing or blank
+fn string(data: &[u8], translation: &str, name: &str) -> Option<String> {
+    let (value, len) = query(
+        data,
+        &format!("\\StringFileInfo\\{}\\{}", translation, name),
+    )?;
+    // The length of a string value is in characters, including the terminator
+    let chars = unsafe { std::slice::from_raw_parts(value as *const u16, len) };
+    let end = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+    let value = String::from_utf16_lossy(&chars[..end]);
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Read the version strings of the file, none if it has no version resource
+pub(crate) fn version_strings(path: &Path) -> VersionStrings {
+    let path = match super::wide_path(path) {
+        Ok(path) => path,
+        Err(_) => return VersionStrings::default(),
+    };
+
+    let mut handle = 0;
+    let len = unsafe { GetFileVersionInfoSizeW(path.as_ptr(), &mut handle) };
+    if len == 0 {
+        return VersionStrings::default();
+    }
+    let mut data = vec![0u8; len as usize];
+    if unsafe { GetFileVersionInfoW(path.as_ptr(), 0, len, data.as_mut_ptr() as _) } == 0 {
+        return VersionStrings::default();
+    }
+
+    let translation = translation(&data).unwrap_or_else(|| DEFAULT_TRANSLATION.to_string());
+    VersionStrings {
+        product_name: string(&data, &translation, "ProductName"),
+        file_version: string(&data, &translation, "FileVersion"),
+        company_name: string(&data, &translation, "CompanyName"),
+        original_filename: string(&data, &translation, "OriginalFilename"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_strings() {
+        let strings = version_strings(Path::new("c:\\windows\\explorer.exe"));
+        assert_eq!(
+            strings.company_name.as_deref(),
+            Some("Microsoft Corporation")
+        );
+        assert!(strings.product_name.is_some() && strings.file_version.is_some());
+
+        // No version resource
+        let path = crate::temp_path();
+        std::fs::write(&path, b"not a PE file").unwrap();
+        let strings = version_strings(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(strings.company_name.is_none() && strings.product_name.is_none());
+        assert!(strings.file_version.is_none() && strings.original_filename.is_none());
+    }
+}
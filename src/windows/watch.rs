@@ -0,0 +1,139 @@
+//!
+//! ReadDirectoryChangesW watches reporting file changes for the watch module.
+//!
+
+use super::wintrust_sys::*;
+use crate::Error;
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The size of the buffer each directory's changes are read into
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A thread reading the changes of one directory
+struct DirectoryThread {
+    handle: HANDLE,
+    thread: JoinHandle<()>,
+}
+
+/// The threads watching each directory, stopped when dropped
+pub struct DirectoryWatch {
+    threads: Vec<DirectoryThread>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for DirectoryWatch {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+
+        for DirectoryThread { handle, thread } in self.threads.drain(..) {
+            // A thread between two reads isn't waiting on anything to cancel yet, keep
+            // cancelling until it sees the flag
+            while !thread.is_finished() {
+                unsafe { CancelIoEx(handle, std::ptr::null()) };
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let _ = thread.join();
+            unsafe { CloseHandle(handle) };
+        }
+    }
+}
+
+/// Send the full path of each file added, modified or renamed into place in the buffer
+fn send_changes(directory: &Path, buffer: &[u32], len: usize, events: &Sender<PathBuf>) {
+    let base = buffer.as_ptr() as *const u8;
+    let mut offset = 0;
+
+    while offset + std::mem::size_of::<FILE_NOTIFY_INFORMATION>() <= len {
+        let info = unsafe { &*(base.add(offset) as *const FILE_NOTIFY_INFORMATION) };
+        let name = unsafe {
+            std::slice::from_raw_parts(
+                info.FileName.as_ptr(),
+                info.FileNameLength as usize / std::mem::size_of::<u16>(),
+            )
+        };
+
+        match info.Action {
+            FILE_ACTION_ADDED | FILE_ACTION_MODIFIED | FILE_ACTION_RENAMED_NEW_NAME => {
+                let _ = events.send(directory.join(OsString::from_wide(name)));
+            }
+            _ => {}
+        }
+
+        match info.NextEntryOffset {
+            0 => break,
+            next => offset += next as usize,
+        }
+    }
+}
+
+fn read_changes(
+    directory: PathBuf,
+    handle: HANDLE,
+    events: Sender<PathBuf>,
+    stopped: Arc<AtomicBool>,
+) {
+    // FILE_NOTIFY_INFORMATION entries are DWORD aligned
+    let mut buffer = vec![0u32; BUFFER_SIZE / std::mem::size_of::<u32>()];
+
+    while !stopped.load(Ordering::Relaxed) {
+        let mut len = 0;
+        let read = unsafe {
+            ReadDirectoryChangesW(
+                handle,
+                buffer.as_mut_ptr() as _,
+                BUFFER_SIZE as u32,
+                1, // The whole subtree
+                FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_LAST_WRITE,
+                &mut len,
+                std::ptr::null_mut(),
+                None,
+            )
+        };
+        if read == 0 {
+            return; // Cancelled, or the directory went away
+        }
+        // A length of 0 means the buffer overflowed and the changes were lost
+        send_changes(&directory, &buffer, len as usize, &events);
+    }
+}
+
+/// Send the path of every file created or modified under the directories, recursively
+pub fn watch(directories: &[PathBuf], events: Sender<PathBuf>) -> Result<DirectoryWatch, Error> {
+    let mut watch = DirectoryWatch {
+        threads: Vec::new(),
+        stopped: Arc::new(AtomicBool::new(false)),
+    };
+
+    for directory in directories {
+        let path = super::wide_path(directory)?;
+
+        let handle = unsafe {
+            CreateFileW(
+                path.as_ptr(),
+                FILE_LIST_DIRECTORY,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null_mut(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            // Dropping the watch stops the directories already being read
+            return Err(Error::os_error(unsafe { GetLastError() } as i32));
+        }
+
+        let (directory, events, stopped) =
+            (directory.clone(), events.clone(), watch.stopped.clone());
+        let thread = std::thread::spawn(move || read_changes(directory, handle, events, stopped));
+        watch.threads.push(DirectoryThread { handle, thread });
+    }
+    Ok(watch)
+}
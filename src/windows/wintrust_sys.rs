@@ -1,18 +1,40 @@
 use std::ffi::{c_int, c_uchar, c_ulong};
 
 pub use windows_sys::Win32::Foundation::{
-    CloseHandle, GetLastError, ERROR_INVALID_PARAMETER, FALSE, GENERIC_READ, HANDLE,
-    INVALID_HANDLE_VALUE, TRUST_E_NOSIGNATURE, TRUST_E_NO_SIGNER_CERT, WIN32_ERROR,
+    CloseHandle, DuplicateHandle, GetLastError, CERT_E_CHAINING, CERT_E_EXPIRED, CERT_E_REVOKED,
+    CERT_E_UNTRUSTEDROOT, CRYPT_E_REVOCATION_OFFLINE, CRYPT_E_REVOKED, DUPLICATE_SAME_ACCESS,
+    ERROR_INVALID_PARAMETER, FALSE, FILETIME, GENERIC_READ, GENERIC_WRITE, HANDLE,
+    INVALID_HANDLE_VALUE, TRUST_E_BAD_DIGEST, TRUST_E_CERT_SIGNATURE, TRUST_E_NOSIGNATURE,
+    TRUST_E_NO_SIGNER_CERT, WIN32_ERROR,
 };
 pub use windows_sys::Win32::Security::Cryptography::Catalog::*;
 pub use windows_sys::Win32::Security::Cryptography::*;
 pub use windows_sys::Win32::Security::WinTrust::*;
 pub use windows_sys::Win32::Storage::FileSystem::{
-    CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_READ, OPEN_EXISTING,
+    CreateFileW, GetFileVersionInfoSizeW, GetFileVersionInfoW, GetFinalPathNameByHandleW,
+    GetFullPathNameW, ReadDirectoryChangesW, SetFilePointerEx, SfcIsFileProtected, VerQueryValueW,
+    CREATE_NEW, FILE_ACTION_ADDED, FILE_ACTION_MODIFIED, FILE_ACTION_RENAMED_NEW_NAME,
+    FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_TEMPORARY, FILE_BEGIN, FILE_FLAG_BACKUP_SEMANTICS,
+    FILE_FLAG_DELETE_ON_CLOSE, FILE_LIST_DIRECTORY, FILE_NAME_NORMALIZED,
+    FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_INFORMATION,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+pub use windows_sys::Win32::System::Diagnostics::Debug::{
+    FormatMessageW, ReadProcessMemory, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+pub use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+};
+pub use windows_sys::Win32::System::Pipes::GetNamedPipeClientProcessId;
+pub use windows_sys::Win32::System::ProcessStatus::{
+    K32EnumProcessModulesEx, K32GetModuleFileNameExW, LIST_MODULES_ALL,
 };
 pub use windows_sys::Win32::System::Threading::{
-    OpenProcess, QueryFullProcessImageNameW, PROCESS_QUERY_LIMITED_INFORMATION,
+    GetCurrentProcess, GetCurrentProcessId, GetProcessTimes, OpenProcess,
+    QueryFullProcessImageNameW, PROCESS_QUERY_INFORMATION, PROCESS_QUERY_LIMITED_INFORMATION,
+    PROCESS_VM_READ,
 };
+pub use windows_sys::Win32::System::IO::CancelIoEx;
 
 #[allow(non_camel_case_types)]
 pub type PCCERT_CONTEXT = *const CERT_CONTEXT;
@@ -0,0 +1,199 @@
+//!
+//! Just enough XML to read the documents Windows embeds in and applies to files.
+//!
+//! Application manifests and App Control policies are small, machine written and don't rely on
+//! DTDs, CDATA or processing instructions, so elements are read by scanning the tags rather
+//! than with a full parser. Namespace prefixes are dropped from names, as Windows ignores them.
+//!
+
+/// An element, with its attributes, child elements and text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String, // The text directly inside the element, trimmed
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The first child element with the name
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The child elements with the name
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The first element with the name anywhere below this one, depth first
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|child| {
+            if child.name == name {
+                Some(child)
+            } else {
+                child.find(name)
+            }
+        })
+    }
+}
+
+/// The name without its namespace prefix
+fn local(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Replace the predefined entities and character references
+fn unescape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => match reference.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => reference.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+            }
+            .and_then(std::char::from_u32),
+        };
+        match decoded {
+            Some(decoded) => {
+                ret.push(decoded);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                ret.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// The document with its comments removed, so commented out elements aren't picked up
+fn strip_comments(xml: &str) -> String {
+    let mut ret = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find("<!--") {
+        ret.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// The attributes of a start tag
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        attributes.push((local(name).to_string(), unescape(&value[1..end])));
+        rest = &value[end + 1..];
+    }
+    attributes
+}
+
+/// Parse the document's root element, None if the tags don't nest
+pub(crate) fn parse(xml: &str) -> Option<Element> {
+    let xml = strip_comments(xml.trim_start_matches('\u{feff}'));
+    // The root is kept as the bottom of the stack, elements are added to their parent when closed
+    let mut stack = vec![Element::default()];
+
+    for segment in xml.split('<').skip(1) {
+        let (tag, text) = match segment.find('>') {
+            Some(end) => (&segment[..end], &segment[end + 1..]),
+            None => return None,
+        };
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().filter(|_| !stack.is_empty())?;
+            if element.name != local(name.trim()) {
+                return None;
+            }
+            stack.last_mut()?.children.push(element);
+        } else if !tag.starts_with(|c: char| c == '?' || c == '!') {
+            let end = tag
+                .find(|c: char| c.is_whitespace() || c == '/')
+                .unwrap_or(tag.len());
+            let element = Element {
+                name: local(&tag[..end]).to_string(),
+                attributes: attributes(tag[end..].trim_end_matches('/')),
+                ..Element::default()
+            };
+            if tag.ends_with('/') {
+                stack.last_mut()?.children.push(element);
+            } else {
+                stack.push(element);
+            }
+        }
+
+        let text = text.trim();
+        if !text.is_empty() {
+            stack.last_mut()?.text.push_str(&unescape(text));
+        }
+    }
+
+    match stack.pop() {
+        Some(document) if stack.is_empty() => document.children.into_iter().next(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let root = parse(
+            r#"<?xml version="1.0"?>
+<!-- <ignored/> -->
+<root xmlns:a="urn:example">
+  <a:item key="one" other='x &amp; y'/>
+  <item key="two">text &lt;here&gt;</item>
+  <group><item key="three"/></group>
+</root>"#,
+        )
+        .unwrap();
+        assert_eq!(root.name, "root");
+        assert_eq!(root.children("item").count(), 2);
+        let first = root.child("item").unwrap();
+        assert_eq!(first.attribute("other"), Some("x & y"));
+        assert_eq!(root.children[1].text, "text <here>");
+        assert_eq!(root.find("group").unwrap().children.len(), 1);
+        assert!(root.find("ignored").is_none());
+
+        assert!(parse("<root><open></root>").is_none());
+    }
+}